@@ -0,0 +1,268 @@
+use crate::export::serialize_seconds;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The factor by which the mean latency of a window of requests must exceed
+/// the lowest window mean seen so far for the window to be treated as a sign
+/// of congestion
+const LATENCY_FACTOR: u32 = 2;
+
+/// The range of numbers of workers within which `--adaptive-workers` adjusts
+/// the concurrency of a traversal
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct AdaptiveRange {
+    pub(crate) min: usize,
+    pub(crate) max: usize,
+}
+
+impl FromStr for AdaptiveRange {
+    type Err = ParseAdaptiveRangeError;
+
+    /// Parse a range of the form `MIN..MAX`
+    fn from_str(s: &str) -> Result<AdaptiveRange, ParseAdaptiveRangeError> {
+        let (min, max) = s.split_once("..").ok_or(ParseAdaptiveRangeError)?;
+        let min = min
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| ParseAdaptiveRangeError)?;
+        let max = max
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| ParseAdaptiveRangeError)?;
+        if min == 0 || min > max {
+            return Err(ParseAdaptiveRangeError);
+        }
+        Ok(AdaptiveRange { min, max })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid worker range; expected MIN..MAX with 0 < MIN <= MAX")]
+pub(crate) struct ParseAdaptiveRangeError;
+
+/// Adjusts the number of workers of a traversal by additive increase,
+/// multiplicative decrease: after each window of as many completed requests
+/// as there are workers, the number of workers is halved if any request in
+/// the window failed or was retried or if the window's mean latency was more
+/// than `LATENCY_FACTOR` times the lowest window mean seen so far, and it is
+/// increased by one otherwise
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct AimdController {
+    range: AdaptiveRange,
+    workers: usize,
+    /// Number of requests completed in the current window
+    window_requests: usize,
+    /// Number of requests completed successfully in the current window
+    window_successes: u32,
+    /// Total latency of the successful requests in the current window
+    window_latency: Duration,
+    /// Whether a failure or retry has occurred in the current window
+    window_congested: bool,
+    /// The lowest mean latency of any window so far
+    baseline: Option<Duration>,
+    increases: usize,
+    decreases: usize,
+    trajectory: Vec<ConcurrencyChange>,
+}
+
+/// A change in the number of workers made by `--adaptive-workers`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ConcurrencyChange {
+    /// The time since the start of the traversal at which the change was
+    /// made
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) elapsed: Duration,
+    pub(crate) workers: usize,
+}
+
+impl AimdController {
+    /// Create a controller that starts at `initial` workers, clamped to
+    /// `range`
+    pub(crate) fn new(range: AdaptiveRange, initial: usize) -> AimdController {
+        let workers = initial.clamp(range.min, range.max);
+        AimdController {
+            range,
+            workers,
+            window_requests: 0,
+            window_successes: 0,
+            window_latency: Duration::ZERO,
+            window_congested: false,
+            baseline: None,
+            increases: 0,
+            decreases: 0,
+            trajectory: vec![ConcurrencyChange {
+                elapsed: Duration::ZERO,
+                workers,
+            }],
+        }
+    }
+
+    /// The current number of workers
+    pub(crate) fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Record the completion of a request `elapsed` into the traversal,
+    /// given its latency (`None` if it failed) and whether any retries were
+    /// made since the last completion.  Returns the new number of workers if
+    /// it has changed.
+    pub(crate) fn record(
+        &mut self,
+        latency: Option<Duration>,
+        retried: bool,
+        elapsed: Duration,
+    ) -> Option<usize> {
+        self.window_requests = self.window_requests.saturating_add(1);
+        match latency {
+            Some(latency) => {
+                self.window_successes = self.window_successes.saturating_add(1);
+                self.window_latency = self.window_latency.saturating_add(latency);
+            }
+            None => self.window_congested = true,
+        }
+        if retried {
+            self.window_congested = true;
+        }
+        if self.window_requests < self.workers {
+            return None;
+        }
+        let mean = (self.window_successes > 0).then(|| self.window_latency / self.window_successes);
+        let slow = match (mean, self.baseline) {
+            (Some(mean), Some(baseline)) => mean > baseline.saturating_mul(LATENCY_FACTOR),
+            _ => false,
+        };
+        if let Some(mean) = mean {
+            self.baseline = Some(self.baseline.map_or(mean, |b| b.min(mean)));
+        }
+        let new_workers = if self.window_congested || slow {
+            (self.workers / 2).max(self.range.min)
+        } else {
+            self.workers.saturating_add(1).min(self.range.max)
+        };
+        self.window_requests = 0;
+        self.window_successes = 0;
+        self.window_latency = Duration::ZERO;
+        self.window_congested = false;
+        if new_workers == self.workers {
+            return None;
+        }
+        if new_workers > self.workers {
+            self.increases = self.increases.saturating_add(1);
+        } else {
+            self.decreases = self.decreases.saturating_add(1);
+        }
+        self.workers = new_workers;
+        self.trajectory.push(ConcurrencyChange {
+            elapsed,
+            workers: new_workers,
+        });
+        Some(new_workers)
+    }
+
+    /// Summarize the adjustments made over a traversal lasting `elapsed`
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn finish(self, elapsed: Duration) -> AdaptiveStats {
+        let mut weighted = 0.0;
+        for (i, change) in self.trajectory.iter().enumerate() {
+            let end = self
+                .trajectory
+                .get(i.saturating_add(1))
+                .map_or(elapsed, |next| next.elapsed);
+            weighted += end.saturating_sub(change.elapsed).as_secs_f64() * change.workers as f64;
+        }
+        let mean_workers = (!elapsed.is_zero()).then(|| weighted / elapsed.as_secs_f64());
+        AdaptiveStats {
+            min_workers: self.range.min,
+            max_workers: self.range.max,
+            final_workers: self.workers,
+            mean_workers,
+            increases: self.increases,
+            decreases: self.decreases,
+            trajectory: self.trajectory,
+        }
+    }
+}
+
+/// The adjustments made to the number of workers of a traversal by
+/// `--adaptive-workers`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct AdaptiveStats {
+    pub(crate) min_workers: usize,
+    pub(crate) max_workers: usize,
+    /// The number of workers at the end of the traversal
+    pub(crate) final_workers: usize,
+    /// The time-weighted mean number of workers over the traversal
+    pub(crate) mean_workers: Option<f64>,
+    pub(crate) increases: usize,
+    pub(crate) decreases: usize,
+    /// The initial number of workers followed by each change made
+    pub(crate) trajectory: Vec<ConcurrencyChange>,
+}
+
+impl fmt::Display for AdaptiveStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Adaptive concurrency: {}..{} workers; ended at {} after {} increases & {} decreases",
+            self.min_workers, self.max_workers, self.final_workers, self.increases, self.decreases
+        )?;
+        if let Some(mean) = self.mean_workers {
+            write!(f, "; time-weighted mean {mean:.2} workers")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("1..64", Some((1, 64)))]
+    #[case(" 4 .. 4 ", Some((4, 4)))]
+    #[case("0..8", None)]
+    #[case("8..4", None)]
+    #[case("8", None)]
+    #[case("a..b", None)]
+    fn test_parse_adaptive_range(#[case] s: &str, #[case] expected: Option<(usize, usize)>) {
+        assert_eq!(
+            s.parse::<AdaptiveRange>().ok(),
+            expected.map(|(min, max)| AdaptiveRange { min, max })
+        );
+    }
+
+    #[test]
+    fn test_aimd_controller() {
+        let ms = Duration::from_millis;
+        let secs = Duration::from_secs;
+        let mut aimd = AimdController::new(AdaptiveRange { min: 2, max: 5 }, 1);
+        assert_eq!(aimd.workers(), 2);
+        // Healthy windows increase the workers by one each, up to the max
+        assert_eq!(aimd.record(Some(ms(10)), false, secs(1)), None);
+        assert_eq!(aimd.record(Some(ms(10)), false, secs(1)), Some(3));
+        for _ in 0..2 {
+            assert_eq!(aimd.record(Some(ms(12)), false, secs(2)), None);
+        }
+        assert_eq!(aimd.record(Some(ms(12)), false, secs(2)), Some(4));
+        // A retry halves the workers at the end of the window
+        assert_eq!(aimd.record(Some(ms(10)), true, secs(3)), None);
+        for _ in 0..2 {
+            assert_eq!(aimd.record(Some(ms(10)), false, secs(3)), None);
+        }
+        assert_eq!(aimd.record(Some(ms(10)), false, secs(3)), Some(2));
+        // So does a window whose latency exceeds twice the best mean
+        assert_eq!(aimd.record(Some(ms(30)), false, secs(4)), None);
+        assert_eq!(aimd.record(Some(ms(30)), false, secs(4)), None);
+        assert_eq!(aimd.workers(), 2);
+        let stats = aimd.finish(secs(5));
+        assert_eq!((stats.increases, stats.decreases), (2, 1));
+        assert_eq!(stats.final_workers, 2);
+        // 1s at 2, 1s at 3, 1s at 4, 2s at 2
+        assert!((stats.mean_workers.unwrap() - 2.6).abs() < 1e-9);
+        assert_eq!(stats.trajectory.len(), 4);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::btn::{BoundedTreeNursery, Spawner};
+use crate::client::{AutoindexResponse, Client, RequestError};
+use crate::stats::LatencySummary;
+use crate::types::{normalize_url, DirectoryListing, Entry};
+use crate::verify::{compare, relative_path, walk_remote, EntryKind, Listing};
+use futures_util::{future::BoxFuture, FutureExt, TryStreamExt};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Extract the children of the collection at `page_url` from the HTML index
+/// page that the server generated for it.  Links that do not point to an
+/// immediate child of the collection (such as parent directory links, sort
+/// links, and absolute links elsewhere) are ignored.  Links ending in a
+/// slash are taken to be collections.
+pub(crate) fn parse_autoindex(html: &str, page_url: &Url) -> DirectoryListing<Url> {
+    let mut directories = Vec::new();
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for href in anchor_hrefs(html) {
+        let Ok(mut url) = page_url.join(&href) else {
+            continue;
+        };
+        if url.query().is_some() {
+            // Apache's column sorting links
+            continue;
+        }
+        url.set_fragment(None);
+        if url.origin() != page_url.origin() {
+            continue;
+        }
+        let Some(name) = url.path().strip_prefix(page_url.path()) else {
+            continue;
+        };
+        let is_dir = name.ends_with('/');
+        let name = name.strip_suffix('/').unwrap_or(name);
+        if name.is_empty() || name.contains('/') {
+            continue;
+        }
+        let url = normalize_url(url, is_dir);
+        if !seen.insert(url.clone()) {
+            continue;
+        }
+        if is_dir {
+            directories.push(Entry::new(url));
+        } else {
+            files.push(Entry::new(url));
+        }
+    }
+    DirectoryListing { directories, files }
+}
+
+/// Return the values of the `href` attributes of all `<a>` tags in `html`,
+/// with character references for `&`, `<`, `>`, `"`, and `'` decoded
+fn anchor_hrefs(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut hrefs = Vec::new();
+    let mut pos = 0;
+    while let Some(i) = lower[pos..].find("<a") {
+        let start = pos.saturating_add(i).saturating_add(2);
+        let end = lower[start..]
+            .find('>')
+            .map_or(lower.len(), |j| start.saturating_add(j));
+        pos = end;
+        if !lower[start..].starts_with(|c: char| c.is_ascii_whitespace()) {
+            // Some other tag, like <abbr>
+            continue;
+        }
+        if let Some(value) = attribute(&html[start..end], &lower[start..end], "href") {
+            hrefs.push(decode_entities(value));
+        }
+    }
+    hrefs
+}
+
+/// Find the value of the attribute `name` in the text `tag` of an opening
+/// tag, where `lower` is `tag` converted to lowercase
+fn attribute<'a>(tag: &'a str, lower: &str, name: &str) -> Option<&'a str> {
+    let mut pos = 0;
+    while let Some(i) = lower[pos..].find(name) {
+        let at = pos.saturating_add(i);
+        pos = at.saturating_add(name.len());
+        let preceded_by_space = lower[..at].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[pos..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = lower.len().saturating_sub(rest.len()).saturating_add(1);
+        let value = tag[value_start..].trim_start();
+        return Some(match value.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let value = &value[1..];
+                value.find(q).map_or(value, |j| &value[..j])
+            }
+            _ => value
+                .find(|c: char| c.is_ascii_whitespace())
+                .map_or(value, |j| &value[..j]),
+        });
+    }
+    None
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Walk the hierarchy at `base_url` both via `PROPFIND` and via the HTML
+/// index pages returned for `GET` requests on collections, and compare the
+/// results
+pub(crate) async fn compare_discovery(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+) -> Result<DiscoveryReport, RequestError> {
+    let start = Instant::now();
+    let (propfind, propfind_times) = walk_remote(client.clone(), base_url.clone(), workers).await?;
+    let propfind_time = start.elapsed();
+    let start = Instant::now();
+    let (autoindex, autoindex_times) = walk_autoindex(client, base_url, workers).await?;
+    let autoindex_time = start.elapsed();
+    // Autoindex pages don't reliably report sizes, so the autoindex listing
+    // is passed as the side whose sizes are ignored
+    let diff = compare(&autoindex, &propfind);
+    Ok(DiscoveryReport {
+        propfind: DiscoveryStats::new(&propfind, &propfind_times, propfind_time),
+        autoindex: DiscoveryStats::new(&autoindex, &autoindex_times, autoindex_time),
+        only_autoindex: diff.missing,
+        only_propfind: diff.extra,
+        mismatched: diff.mismatched.into_iter().map(|m| m.path).collect(),
+    })
+}
+
+async fn walk_autoindex(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+) -> Result<(Listing, Vec<Duration>), RequestError> {
+    let root = base_url.clone();
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        [move |spawner| list_autoindex_dir(spawner, client, root)],
+    );
+    let mut listing = Listing::new();
+    let mut times = Vec::new();
+    while let Some((elapsed, entries)) = stream.try_next().await? {
+        times.push(elapsed);
+        for (url, kind) in entries {
+            listing.insert(relative_path(&base_url, &url), kind);
+        }
+    }
+    Ok((listing, times))
+}
+
+type AutoindexEntries = (Duration, Vec<(Url, EntryKind)>);
+
+fn list_autoindex_dir(
+    spawner: Spawner<Result<AutoindexEntries, RequestError>>,
+    client: Client,
+    url: Url,
+) -> BoxFuture<'static, Result<AutoindexEntries, RequestError>> {
+    async move {
+        let AutoindexResponse { listing, elapsed } = client.get_autoindex(url).await?;
+        let mut entries = Vec::new();
+        for d in listing.directories {
+            let cl2 = client.clone();
+            let url = d.href.clone();
+            spawner.spawn(move |spawner| list_autoindex_dir(spawner, cl2, url));
+            entries.push((d.href, EntryKind::Directory));
+        }
+        for f in listing.files {
+            entries.push((f.href, EntryKind::File { size: None }));
+        }
+        Ok((elapsed, entries))
+    }
+    .boxed()
+}
+
+/// Statistics on walking a hierarchy with one discovery method
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct DiscoveryStats {
+    pub(crate) directories: usize,
+    pub(crate) files: usize,
+    pub(crate) latency: Option<LatencySummary>,
+    pub(crate) overall_time: Duration,
+}
+
+impl DiscoveryStats {
+    fn new(listing: &Listing, times: &[Duration], overall_time: Duration) -> DiscoveryStats {
+        let directories = listing
+            .values()
+            .filter(|&&k| k == EntryKind::Directory)
+            .count();
+        DiscoveryStats {
+            directories,
+            files: listing.len().saturating_sub(directories),
+            latency: LatencySummary::new(times),
+            overall_time,
+        }
+    }
+}
+
+impl fmt::Display for DiscoveryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} directories and {} files in {:?}",
+            self.directories, self.files, self.overall_time
+        )?;
+        if let Some(latency) = self.latency {
+            write!(f, "; {latency}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The results of comparing `PROPFIND` discovery with autoindex discovery
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct DiscoveryReport {
+    pub(crate) propfind: DiscoveryStats,
+    pub(crate) autoindex: DiscoveryStats,
+    /// Paths found only via autoindex pages
+    pub(crate) only_autoindex: Vec<String>,
+    /// Paths found only via `PROPFIND`
+    pub(crate) only_propfind: Vec<String>,
+    /// Paths that are collections under one method but not the other, or
+    /// whose sizes differ
+    pub(crate) mismatched: Vec<String>,
+}
+
+impl fmt::Display for DiscoveryReport {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PROPFIND: {}", self.propfind)?;
+        writeln!(f, "Autoindex: {}", self.autoindex)?;
+        if let Some((p, a)) = self.propfind.latency.zip(self.autoindex.latency) {
+            writeln!(
+                f,
+                "Autoindex/PROPFIND mean latency ratio: {:.2}",
+                a.mean.as_secs_f64() / p.mean.as_secs_f64()
+            )?;
+        }
+        for path in &self.only_propfind {
+            writeln!(f, "ONLY PROPFIND: {path}")?;
+        }
+        for path in &self.only_autoindex {
+            writeln!(f, "ONLY AUTOINDEX: {path}")?;
+        }
+        for path in &self.mismatched {
+            writeln!(f, "TYPE MISMATCH: {path}")?;
+        }
+        writeln!(
+            f,
+            "{} only via PROPFIND, {} only via autoindex, {} mismatched",
+            self.only_propfind.len(),
+            self.only_autoindex.len(),
+            self.mismatched.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_parse_autoindex_apache() {
+        let html = indoc! {r#"
+            <!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">
+            <html>
+             <head>
+              <title>Index of /dav/foo</title>
+             </head>
+             <body>
+            <h1>Index of /dav/foo</h1>
+              <table>
+               <tr><th><a href="?C=N;O=D">Name</a></th><th><a href="?C=M;O=A">Last modified</a></th></tr>
+               <tr><td><a href="/dav/">Parent Directory</a></td></tr>
+               <tr><td><a href="bar/">bar/</a></td></tr>
+               <tr><td><A HREF='baz%20quux.txt'>baz quux.txt</A></td></tr>
+               <tr><td><a class="x" href="a&amp;b.txt">a&amp;b.txt</a></td></tr>
+               <tr><td><abbr title="href=nope">x</abbr><a href="https://elsewhere.org/dav/foo/x.txt">x</a></td></tr>
+               <tr><td><a href="bar/">bar/</a> <a href="bar/inner.txt">inner</a></td></tr>
+              </table>
+            </body></html>
+        "#};
+        let page = Url::parse("https://example.com/dav/foo/").unwrap();
+        let listing = parse_autoindex(html, &page);
+        let hrefs = |entries: &[Entry<Url>]| {
+            entries
+                .iter()
+                .map(|e| e.href.as_str().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hrefs(&listing.directories),
+            ["https://example.com/dav/foo/bar/"]
+        );
+        assert_eq!(
+            hrefs(&listing.files),
+            [
+                "https://example.com/dav/foo/baz%20quux.txt",
+                "https://example.com/dav/foo/a&b.txt",
+            ]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use crate::client::{Client, DirectoryResponse, FileResponse, RequestError};
+use futures_util::{future::BoxFuture, FutureExt};
+use std::collections::VecDeque;
+use url::Url;
+
+/// A source of directory listings & file probes for a traversal.  The
+/// traversal scheduler only interacts with the hierarchy being traversed
+/// through this trait, so that alternative protocols and mock
+/// implementations can be swapped in for the WebDAV `Client`.
+pub(crate) trait TraversalBackend: Send + Sync {
+    /// List the children of the collection at `url`
+    fn list_children(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>>;
+
+    /// List the collection at `url` and all of its descendants.  The default
+    /// implementation lists each collection in turn and merges the results;
+    /// backends that can list an entire subtree with a single request should
+    /// override it.
+    fn list_tree(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        async move {
+            let mut resp = self.list_children(url).await?;
+            let mut queue = resp
+                .listing
+                .directories
+                .iter()
+                .map(|d| d.href.clone())
+                .collect::<VecDeque<_>>();
+            while let Some(url) = queue.pop_front() {
+                let sub = self.list_children(url).await?;
+                queue.extend(sub.listing.directories.iter().map(|d| d.href.clone()));
+                resp.listing.directories.extend(sub.listing.directories);
+                resp.listing.files.extend(sub.listing.files);
+                resp.duplicates = resp.duplicates.saturating_add(sub.duplicates);
+                resp.descriptions.extend(sub.descriptions);
+                resp.warnings.extend(sub.warnings);
+                resp.propstat_statuses.extend(sub.propstat_statuses);
+                resp.body_size = resp.body_size.saturating_add(sub.body_size);
+                resp.elapsed = resp.elapsed.saturating_add(sub.elapsed);
+            }
+            Ok(resp)
+        }
+        .boxed()
+    }
+
+    /// Probe the non-collection resource at `url`.  If `follow_redirects` is
+    /// set and the resource redirects, also follow the chain of redirects for
+    /// up to that many further requests.
+    fn probe_file(
+        &self,
+        url: Url,
+        follow_redirects: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileResponse, RequestError>>;
+}
+
+impl TraversalBackend for Client {
+    fn list_children(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        self.list_directory(url).boxed()
+    }
+
+    fn list_tree(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        Client::list_tree(self, url).boxed()
+    }
+
+    fn probe_file(
+        &self,
+        url: Url,
+        follow_redirects: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileResponse, RequestError>> {
+        match follow_redirects {
+            Some(max_hops) => self.follow_file_redirects(url, max_hops).boxed(),
+            None => self.get_file_redirect(url).boxed(),
+        }
+    }
+}
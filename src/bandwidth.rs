@@ -0,0 +1,201 @@
+use crate::client::{Client, DownloadResponse, RequestError};
+use crate::export::serialize_seconds;
+use crate::stats::mib_per_sec;
+use futures_util::StreamExt;
+use rand::Rng;
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Maximum number of redirects to follow when resolving a sampled file
+const MAX_REDIRECTS: usize = 10;
+
+/// A uniform random sample of at most `capacity` items drawn from a stream of
+/// unknown length
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Reservoir<T> {
+    capacity: usize,
+    seen: usize,
+    items: Vec<T>,
+}
+
+impl<T> Reservoir<T> {
+    pub(crate) fn new(capacity: usize) -> Reservoir<T> {
+        Reservoir {
+            capacity,
+            seen: 0,
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn offer<R: Rng>(&mut self, item: T, rng: &mut R) {
+        self.seen = self.seen.saturating_add(1);
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else {
+            let i = rng.gen_range(0..self.seen);
+            if let Some(slot) = self.items.get_mut(i) {
+                *slot = item;
+            }
+        }
+    }
+
+    /// The number of items offered to the reservoir
+    pub(crate) fn seen(&self) -> usize {
+        self.seen
+    }
+
+    pub(crate) fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+/// Fully download each of `urls` (after following any redirects), discarding
+/// the bytes received, with up to `concurrency` downloads in progress at once
+pub(crate) async fn measure_bandwidth(
+    client: &Client,
+    mut urls: Vec<Url>,
+    files_found: usize,
+    concurrency: usize,
+) -> BandwidthStats {
+    urls.sort();
+    let start = Instant::now();
+    let mut stream = futures_util::stream::iter(urls.into_iter().map(|url| async move {
+        let r = download(client, &url).await;
+        (url, r)
+    }))
+    .buffered(concurrency.max(1));
+    let mut files = Vec::new();
+    let mut failures = Vec::new();
+    while let Some((url, r)) = stream.next().await {
+        match r {
+            Ok(DownloadResponse { received, elapsed }) => files.push(FileBandwidth {
+                url,
+                bytes: received,
+                elapsed,
+                mib_per_sec: mib_per_sec(received, elapsed),
+            }),
+            Err(e) => failures.push(BandwidthFailure {
+                url,
+                message: format!("{:#}", anyhow::Error::from(e)),
+            }),
+        }
+    }
+    BandwidthStats::new(files_found, files, failures, start.elapsed())
+}
+
+async fn download(client: &Client, url: &Url) -> Result<DownloadResponse, RequestError> {
+    let target = client.resolve_redirects(url.clone(), MAX_REDIRECTS).await?;
+    client.download(&target).await
+}
+
+/// The download rate of a single sampled file
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct FileBandwidth {
+    pub(crate) url: Url,
+    /// Number of bytes received
+    pub(crate) bytes: u64,
+    /// Time taken to download the file, not including resolving redirects
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) elapsed: Duration,
+    pub(crate) mib_per_sec: f64,
+}
+
+/// A sampled file that could not be downloaded
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct BandwidthFailure {
+    pub(crate) url: Url,
+    pub(crate) message: String,
+}
+
+/// Download rates for a random sample of the files found during a traversal
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BandwidthStats {
+    /// Number of files from which the sample was drawn
+    pub(crate) files_found: usize,
+    /// The successfully downloaded files, in URL order
+    pub(crate) files: Vec<FileBandwidth>,
+    pub(crate) failures: Vec<BandwidthFailure>,
+    /// Total number of bytes received
+    pub(crate) total_bytes: u64,
+    /// Wall-clock time taken to download all of the sampled files
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) elapsed: Duration,
+    /// Aggregate download rate over all sampled files
+    pub(crate) mib_per_sec: f64,
+}
+
+impl BandwidthStats {
+    fn new(
+        files_found: usize,
+        files: Vec<FileBandwidth>,
+        failures: Vec<BandwidthFailure>,
+        elapsed: Duration,
+    ) -> BandwidthStats {
+        let total_bytes = files
+            .iter()
+            .fold(0u64, |acc, fb| acc.saturating_add(fb.bytes));
+        BandwidthStats {
+            files_found,
+            files,
+            failures,
+            total_bytes,
+            elapsed,
+            mib_per_sec: mib_per_sec(total_bytes, elapsed),
+        }
+    }
+}
+
+impl fmt::Display for BandwidthStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Bandwidth: downloaded {} bytes from {} of {} sampled files ({} found) in {:?} ({:.2} MiB/s aggregate)",
+            self.total_bytes,
+            self.files.len(),
+            self.files.len().saturating_add(self.failures.len()),
+            self.files_found,
+            self.elapsed,
+            self.mib_per_sec,
+        )?;
+        for fb in &self.files {
+            writeln!(
+                f,
+                "  {}: {} bytes in {:?} ({:.2} MiB/s)",
+                fb.url, fb.bytes, fb.elapsed, fb.mib_per_sec
+            )?;
+        }
+        for fail in &self.failures {
+            writeln!(f, "  FAILED {}: {}", fail.url, fail.message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_reservoir() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut reservoir = Reservoir::new(5);
+        for i in 0..3 {
+            reservoir.offer(i, &mut rng);
+        }
+        assert_eq!(reservoir.clone().into_items(), [0, 1, 2]);
+        for i in 3..1000 {
+            reservoir.offer(i, &mut rng);
+        }
+        assert_eq!(reservoir.seen(), 1000);
+        let mut items = reservoir.into_items();
+        assert_eq!(items.len(), 5);
+        items.sort_unstable();
+        items.dedup();
+        assert_eq!(items.len(), 5);
+        assert!(items.iter().all(|&i| i < 1000));
+    }
+}
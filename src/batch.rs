@@ -0,0 +1,1228 @@
+use crate::client::take_userinfo;
+use crate::compress::{Compression, InFile, OutFile};
+use crate::export::csv_field;
+use crate::stats::{
+    CsvFields, Sample, ServerFingerprints, WorkerRow, WorkerSamples, WorkerSummary,
+    SIGNIFICANCE_LEVEL,
+};
+use crate::table::{Align, Table};
+use crate::traverse::TraversalReport;
+use crate::types::{parse_batch_target, parse_target_list, BatchTarget};
+use anyhow::Context;
+use clap::ValueEnum;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{
+    ser::{Error as _, SerializeSeq},
+    Deserialize, Serialize, Serializer,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::io::AsyncBufReadExt;
+use url::Url;
+
+#[derive(Debug)]
+pub(crate) enum StatManager {
+    JsonFile {
+        outfile: PathBuf,
+        data: Box<StatReport>,
+        /// How much of each traversal report to record
+        detail: JsonDetail,
+    },
+    PerTraversal {
+        /// The columns to output
+        fields: CsvFields<TraversalReport>,
+        out: Output,
+        /// The label of the current target, if the lines are labelled by
+        /// target
+        label: Option<String>,
+    },
+    PerWorkers {
+        format: SummaryFormat,
+        /// The columns to output in CSV format
+        fields: CsvFields<WorkerRow>,
+        worker_samples: WorkerSamples,
+        out: Output,
+        /// Whether to test the differences between adjacent numbers of
+        /// workers for significance
+        significance: bool,
+        /// The label of the current target, if the summary is written
+        /// separately for each target
+        label: Option<String>,
+        /// Whether a summary has been written for an earlier target
+        summarized: bool,
+    },
+}
+
+/// The destination for CSV & table output
+#[derive(Debug)]
+pub(crate) enum Output {
+    Stdout(Stdout),
+    File(BufWriter<File>),
+}
+
+impl Output {
+    /// Write to the file at `path` if given, or to standard output otherwise
+    pub(crate) fn open(path: Option<&Path>) -> anyhow::Result<Output> {
+        match path {
+            Some(p) => {
+                let fp =
+                    File::create(p).with_context(|| format!("failed to create {}", p.display()))?;
+                Ok(Output::File(BufWriter::new(fp)))
+            }
+            None => Ok(Output::Stdout(std::io::stdout())),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Stdout(fp) => fp.write(buf),
+            Output::File(fp) => fp.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Stdout(fp) => fp.flush(),
+            Output::File(fp) => fp.flush(),
+        }
+    }
+}
+
+/// How many traversals to perform for each number of workers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum SamplingPlan {
+    /// Perform exactly this many traversals
+    Fixed(usize),
+    /// Perform at least `min` and at most `max` traversals, stopping once the
+    /// 95% confidence interval for the mean traversal time is within
+    /// `percent` percent of the mean
+    UntilCi {
+        min: usize,
+        max: usize,
+        percent: f64,
+    },
+}
+
+impl SamplingPlan {
+    /// The least number of traversals that may be performed
+    pub(crate) fn min_samples(&self) -> usize {
+        match *self {
+            SamplingPlan::Fixed(n) => n,
+            SamplingPlan::UntilCi { min, .. } => min,
+        }
+    }
+
+    /// The greatest number of traversals that may be performed
+    pub(crate) fn max_samples(&self) -> usize {
+        match *self {
+            SamplingPlan::Fixed(n) => n,
+            SamplingPlan::UntilCi { max, .. } => max,
+        }
+    }
+
+    /// Return whether another traversal should be performed, given the
+    /// number performed so far and the current relative confidence interval
+    /// half-width (see `WorkerSamples::time_ci_percent()`)
+    pub(crate) fn needs_more(&self, taken: usize, ci_percent: Option<f64>) -> bool {
+        match *self {
+            SamplingPlan::Fixed(n) => taken < n,
+            SamplingPlan::UntilCi { min, max, percent } => {
+                taken < min
+                    || (taken < max && ci_percent.map_or(true, |ci| ci.is_nan() || ci > percent))
+            }
+        }
+    }
+}
+
+/// The base URLs traversed by a batch
+#[derive(Debug)]
+pub(crate) enum Targets {
+    /// The single base URL given on the command line, until it is taken
+    Single(Option<Url>),
+    /// The remaining targets read from a `--targets` file
+    List(std::vec::IntoIter<BatchTarget>),
+    /// Targets read from standard input as they arrive
+    Stdin {
+        lines: tokio::io::Lines<tokio::io::BufReader<tokio::io::Stdin>>,
+        lineno: usize,
+    },
+}
+
+impl Targets {
+    /// Read targets from the file at `path`, or from standard input if
+    /// `path` is `-`
+    pub(crate) fn open(path: &Path) -> anyhow::Result<Targets> {
+        if path == Path::new("-") {
+            return Ok(Targets::Stdin {
+                lines: tokio::io::BufReader::new(tokio::io::stdin()).lines(),
+                lineno: 0,
+            });
+        }
+        let s = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let targets =
+            parse_target_list(&s).with_context(|| format!("failed to parse {}", path.display()))?;
+        Ok(Targets::List(targets.into_iter()))
+    }
+
+    /// Return the next base URL to traverse along with, when reading from a
+    /// `--targets` list, its label.  When reading from standard input, this
+    /// waits for the next line to arrive.
+    pub(crate) async fn next(&mut self) -> anyhow::Result<Option<(Url, Option<String>)>> {
+        match self {
+            Targets::Single(url) => Ok(url.take().map(|u| (u, None))),
+            Targets::List(iter) => Ok(iter.next().map(|t| (t.url, Some(t.label)))),
+            Targets::Stdin { lines, lineno } => {
+                while let Some(line) = lines
+                    .next_line()
+                    .await
+                    .context("failed to read targets from standard input")?
+                {
+                    *lineno = lineno.saturating_add(1);
+                    if let Some(t) = parse_batch_target(&line).with_context(|| {
+                        format!("invalid URL on line {lineno} of standard input")
+                    })? {
+                        return Ok(Some((t.url, Some(t.label))));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Chooser of a random collection at which to start each traversal in a batch.
+/// The root chosen for a given run number is the same for every number of
+/// workers, so that comparisons between worker quantities are made over the
+/// same sequence of subtrees.
+#[derive(Clone, Debug)]
+pub(crate) struct SubtreePicker {
+    base_url: Url,
+    /// The candidate roots, including `base_url`
+    collections: Vec<Url>,
+    rng: StdRng,
+    /// The roots chosen so far, indexed by run number
+    chosen: Vec<Url>,
+}
+
+impl SubtreePicker {
+    pub(crate) fn new(base_url: Url, mut collections: Vec<Url>, seed: u64) -> Self {
+        collections.push(base_url.clone());
+        collections.sort_unstable();
+        collections.dedup();
+        SubtreePicker {
+            base_url,
+            collections,
+            rng: StdRng::seed_from_u64(seed),
+            chosen: Vec::new(),
+        }
+    }
+
+    /// The number of candidate roots
+    pub(crate) fn candidates(&self) -> usize {
+        self.collections.len()
+    }
+
+    /// Return the root for the zero-based run number `run`
+    pub(crate) fn root(&mut self, run: usize) -> Url {
+        while self.chosen.len() <= run {
+            let url = self
+                .collections
+                .choose(&mut self.rng)
+                .unwrap_or(&self.base_url)
+                .clone();
+            self.chosen.push(url);
+        }
+        self.chosen[run].clone()
+    }
+}
+
+/// How to treat an existing JSON outfile
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum JsonMode {
+    /// Replace the file
+    Overwrite,
+    /// Append new traversals to the report in the file, if it exists
+    Append,
+    /// Append new traversals to the report in the file, which must exist
+    Resume,
+}
+
+/// How much detail on each traversal to record in a JSON outfile
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum JsonDetail {
+    /// Only the per-traversal aggregates
+    Summary,
+    /// The aggregates plus the individual request latencies
+    #[default]
+    Timings,
+    /// The aggregates, the individual request latencies, and a complete
+    /// record of each request
+    Full,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub(crate) enum SummaryFormat {
+    /// CSV with one line per number of workers
+    Csv,
+    /// A human-readable table
+    Table,
+}
+
+impl StatManager {
+    /// Write traversal reports to the JSON file `outfile`.  Unless `mode` is
+    /// `JsonMode::Overwrite`, the new reports are appended to those already
+    /// in the file.  If `detail` is `JsonDetail::Summary`, the individual
+    /// request latencies are omitted from the reports.  If `low_memory` is
+    /// true, the reports are spooled to a temporary file rather than kept in
+    /// memory.
+    pub(crate) fn json_file(
+        outfile: PathBuf,
+        base_url: Url,
+        labels: BTreeMap<String, String>,
+        mode: JsonMode,
+        detail: JsonDetail,
+        low_memory: bool,
+    ) -> anyhow::Result<Self> {
+        let mut data = match mode {
+            JsonMode::Append | JsonMode::Resume if outfile.exists() => {
+                let mut data = StatReport::load(&outfile, &base_url)?;
+                data.restrict_labels(&labels);
+                data
+            }
+            JsonMode::Resume => {
+                anyhow::bail!("cannot resume: {} does not exist", outfile.display())
+            }
+            _ => StatReport::new(base_url, labels),
+        };
+        if low_memory {
+            let mut spoolname = outfile.file_name().unwrap_or_default().to_os_string();
+            spoolname.push(".spool");
+            data.traversals.spool(outfile.with_file_name(spoolname))?;
+        }
+        Ok(StatManager::JsonFile {
+            outfile,
+            data: Box::new(data),
+            detail,
+        })
+    }
+
+    /// Write a CSV line for each traversal consisting of the given `fields`
+    /// or, if none are given, the default fields.  If `labelled` is true,
+    /// each line starts with the label of the target traversed.
+    pub(crate) fn per_traversal(
+        sizes: bool,
+        fields: &[String],
+        out: Output,
+        labelled: bool,
+    ) -> anyhow::Result<Self> {
+        let fields = if fields.is_empty() {
+            let mut defaults = vec!["workers", "requests", "elapsed"];
+            if sizes {
+                defaults.extend(["directories", "files", "bytes"]);
+            }
+            defaults.push("utilization");
+            CsvFields::select(&defaults)?
+        } else {
+            CsvFields::select(fields)?
+        };
+        Ok(StatManager::PerTraversal {
+            fields,
+            out,
+            label: labelled.then(String::new),
+        })
+    }
+
+    /// Write a summary of the traversals for each number of workers.  In CSV
+    /// format, the summary consists of the given `fields` or, if none are
+    /// given, the default fields; selecting `p_value` or `significant`
+    /// implies `significance`.  If `labelled` is true, a separate summary is
+    /// written for each target.
+    pub(crate) fn per_workers(
+        format: SummaryFormat,
+        fields: &[String],
+        out: Output,
+        significance: bool,
+        labelled: bool,
+    ) -> anyhow::Result<Self> {
+        let (fields, significance) = worker_fields(
+            fields,
+            vec!["workers", "time_mean", "time_stddev"],
+            significance,
+            &["utilization_mean"],
+        )?;
+        Ok(StatManager::PerWorkers {
+            format,
+            fields,
+            worker_samples: WorkerSamples::new(),
+            out,
+            significance,
+            label: labelled.then(String::new),
+            summarized: false,
+        })
+    }
+
+    /// Return the samples from the traversals already recorded in the JSON
+    /// outfile, if any
+    pub(crate) fn recorded_samples(&self) -> anyhow::Result<WorkerSamples> {
+        match self {
+            StatManager::JsonFile { data, .. } => data.samples(),
+            _ => Ok(WorkerSamples::new()),
+        }
+    }
+
+    pub(crate) fn start(&mut self) -> anyhow::Result<()> {
+        match self {
+            StatManager::JsonFile { data, .. } => {
+                // When appending, keep the start time of the original batch
+                data.start_time.get_or_insert_with(OffsetDateTime::now_utc);
+            }
+            StatManager::PerTraversal { fields, out, label } => {
+                if label.is_some() {
+                    write!(out, "target,").context("failed to write CSV")?;
+                }
+                writeln!(out, "{}", fields.header()).context("failed to write CSV")?;
+            }
+            StatManager::PerWorkers { .. } => (),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn process(&mut self, mut report: TraversalReport) -> anyhow::Result<()> {
+        match self {
+            StatManager::JsonFile {
+                outfile,
+                data,
+                detail,
+            } => {
+                if *detail == JsonDetail::Summary {
+                    report.clear_request_times();
+                }
+                data.servers.merge(&report.servers);
+                data.traversals.push(
+                    serde_json::to_value(report).context("failed to serialize traversal report")?,
+                )?;
+                // Save the results so far so that they aren't lost if the
+                // batch is interrupted.  Spooled reports are already on disk,
+                // and rewriting the whole report after every traversal would
+                // take time quadratic in the size of the batch, so in that
+                // case the report is only written at the end.
+                if !data.traversals.is_spooled() {
+                    dump_json(outfile, data)?;
+                }
+            }
+            StatManager::PerTraversal { fields, out, label } => {
+                if let Some(label) = label {
+                    write!(out, "{},", csv_field(label)).context("failed to write CSV")?;
+                }
+                writeln!(out, "{}", fields.row(&report)).context("failed to write CSV")?;
+                // Flush after each line so that the results so far aren't lost
+                // if the batch is interrupted
+                out.flush().context("failed to flush CSV")?;
+            }
+            StatManager::PerWorkers { worker_samples, .. } => {
+                worker_samples.push(report.workers, Sample::from(&report));
+            }
+        }
+        Ok(())
+    }
+
+    /// Begin recording the traversals of the batch target labelled `target`
+    pub(crate) fn start_target(&mut self, target: &str) {
+        match self {
+            StatManager::PerTraversal {
+                label: Some(label), ..
+            }
+            | StatManager::PerWorkers {
+                label: Some(label), ..
+            } => target.clone_into(label),
+            _ => (),
+        }
+    }
+
+    /// Finish recording the traversals of the current batch target.  If the
+    /// per-workers summary is written separately for each target, this
+    /// writes the current target's summary.
+    pub(crate) fn end_target(&mut self) -> anyhow::Result<()> {
+        if let StatManager::PerWorkers {
+            format,
+            fields,
+            worker_samples,
+            out,
+            significance,
+            label: Some(label),
+            summarized,
+        } = self
+        {
+            write_worker_summary(
+                out,
+                *format,
+                fields,
+                worker_samples,
+                *significance,
+                false,
+                Some((label, *summarized)),
+            )?;
+            out.flush().context("failed to flush output")?;
+            *worker_samples = WorkerSamples::new();
+            *summarized = true;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn end(self) -> anyhow::Result<()> {
+        match self {
+            StatManager::JsonFile {
+                outfile, mut data, ..
+            } => {
+                data.end_time = Some(OffsetDateTime::now_utc());
+                dump_json(&outfile, &data)?;
+                data.traversals.remove_spool()?;
+            }
+            StatManager::PerTraversal { mut out, .. } => {
+                out.flush().context("failed to flush CSV")?;
+            }
+            StatManager::PerWorkers {
+                format,
+                fields,
+                worker_samples,
+                mut out,
+                significance,
+                label,
+                ..
+            } => {
+                // When labelling by target, each target's summary has already
+                // been written by `end_target()`
+                if label.is_none() {
+                    write_worker_summary(
+                        &mut out,
+                        format,
+                        &fields,
+                        &worker_samples,
+                        significance,
+                        false,
+                        None,
+                    )?;
+                }
+                out.flush().context("failed to flush output")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the samples from the traversals recorded in the `batch` JSON
+/// report at `path`, which must contain at least one traversal
+pub(crate) fn load_samples(path: &Path) -> anyhow::Result<WorkerSamples> {
+    let report = StatReport::read(path)?;
+    if report.schema_version != SCHEMA_VERSION {
+        anyhow::bail!(
+            "{}: report has schema version {}, expected {}",
+            path.display(),
+            report.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+    let samples = report.samples()?;
+    if samples.counts().is_empty() {
+        anyhow::bail!("{} does not contain any traversals", path.display());
+    }
+    Ok(samples)
+}
+
+/// Combine the traversals recorded in the `batch` JSON reports at `paths`,
+/// which must all be for traversals of the same base URL, into a single
+/// report written to `outfile`.  The metadata other than the start & end
+/// times is taken from the first report.  A report that is given more than
+/// once — either as the same file or as a copy of an earlier report, as
+/// identified by its hostname & start time — is an error.  Returns the total
+/// number of traversals.
+pub(crate) fn merge_reports(paths: &[PathBuf], outfile: &Path) -> anyhow::Result<usize> {
+    let mut merged: Option<StatReport> = None;
+    let mut seen_paths = BTreeMap::new();
+    let mut seen_runs = BTreeMap::new();
+    for path in paths {
+        let canonical = std::fs::canonicalize(path)
+            .with_context(|| format!("failed to resolve path {}", path.display()))?;
+        if let Some(prev) = seen_paths.insert(canonical, path) {
+            anyhow::bail!(
+                "cannot merge {}: same file as {}",
+                path.display(),
+                prev.display()
+            );
+        }
+        let report = StatReport::read(path)?;
+        if let Some(start) = report.start_time {
+            if let Some(prev) = seen_runs.insert((report.hostname.clone(), start), path) {
+                anyhow::bail!(
+                    "cannot merge {}: report is a copy of {}",
+                    path.display(),
+                    prev.display()
+                );
+            }
+        }
+        if report.schema_version != SCHEMA_VERSION {
+            anyhow::bail!(
+                "cannot merge {}: report has schema version {}, expected {}",
+                path.display(),
+                report.schema_version,
+                SCHEMA_VERSION
+            );
+        }
+        match merged.as_mut() {
+            None => merged = Some(report),
+            Some(m) if m.base_url != report.base_url => {
+                anyhow::bail!(
+                    "cannot merge {}: report is for traversals of {}, not {}",
+                    path.display(),
+                    report.base_url,
+                    m.base_url
+                );
+            }
+            Some(m) => m.merge(&report)?,
+        }
+    }
+    let Some(merged) = merged else {
+        anyhow::bail!("no reports to merge");
+    };
+    let mut traversals = 0usize;
+    merged.traversals.try_for_each(|_| {
+        traversals = traversals.saturating_add(1);
+        Ok(())
+    })?;
+    dump_json(outfile, &merged)?;
+    Ok(traversals)
+}
+
+/// Select the per-workers CSV columns named in `fields` or, if none are
+/// given, the columns in `defaults`, then the significance columns if
+/// `significance` is true, and then the columns in `trailing`.  Returns the
+/// columns along with whether significance testing is needed for them.
+fn worker_fields(
+    fields: &[String],
+    mut defaults: Vec<&'static str>,
+    significance: bool,
+    trailing: &[&'static str],
+) -> anyhow::Result<(CsvFields<WorkerRow>, bool)> {
+    let fields = if fields.is_empty() {
+        if significance {
+            defaults.extend(["p_value", "significant"]);
+        }
+        defaults.extend(trailing);
+        CsvFields::select(&defaults)?
+    } else {
+        CsvFields::select(fields)?
+    };
+    let significance = significance || fields.contains("p_value") || fields.contains("significant");
+    Ok((fields, significance))
+}
+
+/// Summarize the traversal `samples` loaded from a `batch` JSON report for
+/// each number of workers, writing the given `fields` (or, if none are
+/// given, a default set of fields) to `out`
+pub(crate) fn analyze(
+    samples: &WorkerSamples,
+    format: SummaryFormat,
+    fields: &[String],
+    significance: bool,
+    mut out: Output,
+) -> anyhow::Result<()> {
+    let (fields, significance) = worker_fields(
+        fields,
+        vec![
+            "workers",
+            "samples",
+            "time_mean",
+            "time_ci",
+            "time_median",
+            "time_p95",
+            "throughput_mean",
+            "efficiency",
+            "utilization_mean",
+        ],
+        significance,
+        &[],
+    )?;
+    write_worker_summary(&mut out, format, &fields, samples, significance, true, None)?;
+    out.flush().context("failed to flush output")?;
+    Ok(())
+}
+
+/// Write a per-workers summary of `samples` to `out`.  If `detailed` is
+/// true, the table format includes further statistics beyond the traversal
+/// times' mean & standard deviation.  If `target` is non-`None`, the summary
+/// is for a single target of a multi-target batch, given as the label of the
+/// target and whether a summary for an earlier target has already been
+/// written.
+fn write_worker_summary(
+    out: &mut Output,
+    format: SummaryFormat,
+    fields: &CsvFields<WorkerRow>,
+    samples: &WorkerSamples,
+    significance: bool,
+    detailed: bool,
+    target: Option<(&str, bool)>,
+) -> anyhow::Result<()> {
+    let mut table = Table::new()
+        .column("Workers", Align::Right)
+        .column("Samples", Align::Right)
+        .column("Mean Time", Align::Right);
+    if detailed {
+        table = table
+            .column("95% CI", Align::Right)
+            .column("Median", Align::Right)
+            .column("P95", Align::Right)
+            .column("Throughput", Align::Right)
+            .column("Efficiency", Align::Right);
+    } else {
+        table = table.column("Std Dev", Align::Right);
+    }
+    table = table.column("Utilization", Align::Right);
+    if significance {
+        table = table.column("p vs. Prev", Align::Left);
+    }
+    match (format, target) {
+        // The CSV header is only written before the first target
+        (SummaryFormat::Csv, Some((_, true))) => (),
+        (SummaryFormat::Csv, _) => {
+            if target.is_some() {
+                write!(out, "target,").context("failed to write CSV")?;
+            }
+            writeln!(out, "{}", fields.header()).context("failed to write CSV")?;
+        }
+        (SummaryFormat::Table, Some((label, later))) => {
+            if later {
+                writeln!(out).context("failed to write table")?;
+            }
+            writeln!(out, "Target: {label}").context("failed to write table")?;
+        }
+        (SummaryFormat::Table, None) => (),
+    }
+    let p_values = if significance {
+        samples.adjacent_time_tests()
+    } else {
+        BTreeMap::new()
+    };
+    for ws in samples.summarize() {
+        let WorkerSummary {
+            workers,
+            samples,
+            time_mean: mean,
+            time_stddev: stddev,
+            time_median: median,
+            time_p95: p95,
+            time_ci,
+            throughput_mean,
+            utilization_mean,
+            efficiency,
+            ..
+        } = ws;
+        let p_value = p_values.get(&workers).copied();
+        match format {
+            SummaryFormat::Csv => {
+                if let Some((label, _)) = target {
+                    write!(out, "{},", csv_field(label)).context("failed to write CSV")?;
+                }
+                let row = WorkerRow {
+                    summary: ws,
+                    p_value,
+                };
+                writeln!(out, "{}", fields.row(&row)).context("failed to write CSV")?;
+            }
+            SummaryFormat::Table => {
+                let percent = |v: Option<f64>| {
+                    v.map_or_else(|| String::from("-"), |v| format!("{:.1}%", v * 100.0))
+                };
+                let mut row = vec![
+                    workers.to_string(),
+                    samples.to_string(),
+                    format!("{mean:.3} s"),
+                ];
+                if detailed {
+                    row.extend([
+                        time_ci.map_or_else(|| String::from("-"), |ci| format!("±{ci:.3} s")),
+                        format!("{median:.3} s"),
+                        format!("{p95:.3} s"),
+                        format!("{throughput_mean:.1}/s"),
+                        percent(efficiency),
+                    ]);
+                } else {
+                    row.push(format!("{stddev:.3} s"));
+                }
+                row.push(percent(utilization_mean));
+                if significance {
+                    row.push(match p_value {
+                        Some(p) if p < SIGNIFICANCE_LEVEL => {
+                            format!("{p:.3} (significant)")
+                        }
+                        Some(p) => format!("{p:.3}"),
+                        None => String::from("-"),
+                    });
+                }
+                table.push_row(row);
+            }
+        }
+    }
+    if format == SummaryFormat::Table {
+        write!(out, "{table}").context("failed to write table")?;
+    }
+    Ok(())
+}
+
+/// The serialized `TraversalReport`s in a `StatReport`.  These are stored as
+/// JSON values so that reports loaded from a file for appending are passed
+/// through unchanged.  In low-memory mode, they are kept in a spool file
+/// instead and streamed from there whenever the report is written.
+#[derive(Debug, Deserialize)]
+#[serde(from = "Vec<Value>")]
+enum Traversals {
+    InMemory(Vec<Value>),
+    Spooled(Spool),
+}
+
+impl From<Vec<Value>> for Traversals {
+    fn from(values: Vec<Value>) -> Traversals {
+        Traversals::InMemory(values)
+    }
+}
+
+impl Traversals {
+    fn push(&mut self, value: Value) -> anyhow::Result<()> {
+        match self {
+            Traversals::InMemory(values) => {
+                values.push(value);
+                Ok(())
+            }
+            Traversals::Spooled(spool) => spool.push(&value),
+        }
+    }
+
+    /// Move any reports held in memory to a new spool file at `path`, and
+    /// store all further reports there
+    fn spool(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        if let Traversals::InMemory(values) = self {
+            let mut spool = Spool::create(path)?;
+            for value in values.drain(..) {
+                spool.push(&value)?;
+            }
+            *self = Traversals::Spooled(spool);
+        }
+        Ok(())
+    }
+
+    fn is_spooled(&self) -> bool {
+        matches!(self, Traversals::Spooled(_))
+    }
+
+    /// Delete the spool file, if any, once its contents have been written
+    /// out
+    fn remove_spool(self) -> anyhow::Result<()> {
+        if let Traversals::Spooled(spool) = self {
+            std::fs::remove_file(&spool.path)
+                .with_context(|| format!("failed to remove spool file {}", spool.path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn try_for_each<F>(&self, mut func: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&Value) -> anyhow::Result<()>,
+    {
+        match self {
+            Traversals::InMemory(values) => values.iter().try_for_each(func),
+            Traversals::Spooled(spool) => {
+                for value in spool.values()? {
+                    func(&value?)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for Traversals {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Traversals::InMemory(values) => values.serialize(serializer),
+            Traversals::Spooled(spool) => {
+                let mut seq = serializer.serialize_seq(None)?;
+                for value in spool.values().map_err(S::Error::custom)? {
+                    seq.serialize_element(&value.map_err(S::Error::custom)?)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+/// A temporary file of JSON values, one per line.  The file is left in place
+/// if the batch is interrupted so that the reports in it can be recovered.
+#[derive(Debug)]
+struct Spool {
+    path: PathBuf,
+    fp: BufWriter<File>,
+}
+
+impl Spool {
+    fn create(path: PathBuf) -> anyhow::Result<Spool> {
+        let fp = File::create(&path)
+            .with_context(|| format!("failed to create spool file {}", path.display()))?;
+        Ok(Spool {
+            path,
+            fp: BufWriter::new(fp),
+        })
+    }
+
+    fn push(&mut self, value: &Value) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.fp, value).context("failed to write to spool file")?;
+        writeln!(self.fp).context("failed to write to spool file")?;
+        self.fp.flush().context("failed to flush spool file")?;
+        Ok(())
+    }
+
+    /// Read back the values written to the spool so far
+    fn values(&self) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Value>>> {
+        let fp = File::open(&self.path)
+            .with_context(|| format!("failed to open spool file {}", self.path.display()))?;
+        Ok(BufReader::new(fp).lines().map(|line| {
+            let line = line.context("failed to read spool file")?;
+            serde_json::from_str(&line).context("failed to parse spool file")
+        }))
+    }
+}
+
+/// If `arg` is a URL containing a username or password, return it with them
+/// removed so that credentials don't end up in reports
+fn redact_url_credentials(arg: String) -> String {
+    if let Ok(mut url) = Url::parse(&arg) {
+        if take_userinfo(&mut url).is_some() {
+            return url.into();
+        }
+    }
+    arg
+}
+
+/// Write `data` as JSON to `outfile`.  The document is first written to a
+/// temporary file in the same directory which is then renamed over `outfile`,
+/// so that an interruption while writing cannot leave a truncated report.
+fn dump_json(outfile: &Path, data: &StatReport) -> anyhow::Result<()> {
+    let mut tmpname = outfile.file_name().unwrap_or_default().to_os_string();
+    tmpname.push(".partial");
+    let tmppath = outfile.with_file_name(tmpname);
+    let mut fp = OutFile::with_compression(&tmppath, Compression::for_path(outfile))
+        .context("failed to open JSON outfile")?;
+    serde_json::to_writer_pretty(&mut fp, data).context("failed to dump JSON to file")?;
+    fp.write_all(b"\n")
+        .context("failed to write final newline to JSON outfile")?;
+    fp.finish().context("failed to flush JSON outfile")?;
+    std::fs::rename(&tmppath, outfile).context("failed to move JSON outfile into place")?;
+    Ok(())
+}
+
+/// The fields of a serialized `TraversalReport` needed to reconstruct its
+/// `Sample`
+#[derive(Deserialize)]
+struct RecordedTraversal {
+    workers: usize,
+    directories: usize,
+    files: usize,
+    overall_time: Duration,
+    /// Absent from reports written before the concurrency timeline was
+    /// recorded
+    concurrency: Option<RecordedConcurrency>,
+    /// Absent from reports written before inventories were verified
+    #[serde(default)]
+    inventory_mismatch: bool,
+}
+
+/// The part of a recorded traversal's concurrency timeline needed for
+/// summarizing a batch
+#[derive(Deserialize)]
+struct RecordedConcurrency {
+    utilization: Option<f64>,
+}
+
+/// The version of the format of `StatReport` JSON documents.  This must be
+/// incremented (and the change documented in the README) whenever a field is
+/// removed, renamed, or changes meaning; adding a field does not require a new
+/// version.
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct StatReport {
+    schema_version: u32,
+    batchdav_version: String,
+    /// The command-line arguments with which batchdav was invoked
+    command_line: Vec<String>,
+    /// The name of the host on which batchdav was run
+    hostname: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    start_time: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    end_time: Option<OffsetDateTime>,
+    base_url: Url,
+    /// The user-defined labels given with `--label` that apply to every
+    /// traversal in the report
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    /// The distinct values of the server-identifying headers seen across all
+    /// traversals
+    #[serde(default)]
+    servers: ServerFingerprints,
+    traversals: Traversals,
+}
+
+impl StatReport {
+    fn new(base_url: Url, labels: BTreeMap<String, String>) -> Self {
+        StatReport {
+            schema_version: SCHEMA_VERSION,
+            batchdav_version: env!("CARGO_PKG_VERSION").to_owned(),
+            command_line: std::env::args_os()
+                .map(|s| redact_url_credentials(s.to_string_lossy().into_owned()))
+                .collect(),
+            hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+            start_time: None,
+            end_time: None,
+            base_url,
+            labels,
+            servers: ServerFingerprints::default(),
+            traversals: Traversals::InMemory(Vec::new()),
+        }
+    }
+
+    /// Add the traversals recorded in `other` to this report, extending the
+    /// start & end times to cover both
+    fn merge(&mut self, other: &StatReport) -> anyhow::Result<()> {
+        self.servers.merge(&other.servers);
+        self.restrict_labels(&other.labels);
+        other
+            .traversals
+            .try_for_each(|value| self.traversals.push(value.clone()))?;
+        self.start_time = match (self.start_time, other.start_time) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.end_time = match (self.end_time, other.end_time) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        Ok(())
+    }
+
+    /// Discard the labels that are not also present with the same values in
+    /// `labels`, so that only the labels applying to all traversals remain
+    fn restrict_labels(&mut self, labels: &BTreeMap<String, String>) {
+        self.labels.retain(|k, v| labels.get(k) == Some(v));
+    }
+
+    /// Return the samples from the traversals recorded in the report, leaving
+    /// out those that found a different inventory than the first traversal
+    fn samples(&self) -> anyhow::Result<WorkerSamples> {
+        let mut samples = WorkerSamples::new();
+        self.traversals.try_for_each(|value| {
+            let RecordedTraversal {
+                workers,
+                directories,
+                files,
+                overall_time,
+                concurrency,
+                inventory_mismatch,
+            } = RecordedTraversal::deserialize(value)
+                .context("failed to parse traversal report in JSON outfile")?;
+            if inventory_mismatch {
+                return Ok(());
+            }
+            let utilization = concurrency.and_then(|c| c.utilization);
+            samples.push(
+                workers,
+                Sample {
+                    requests: directories.saturating_add(files),
+                    elapsed: overall_time,
+                    utilization,
+                },
+            );
+            Ok(())
+        })?;
+        Ok(samples)
+    }
+
+    /// Read a report previously written to `path`
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let fp = InFile::open(path)
+            .with_context(|| format!("failed to open JSON outfile {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(fp))
+            .with_context(|| format!("failed to read JSON outfile {}", path.display()))
+    }
+
+    /// Load a report previously written to `path` for appending further
+    /// traversals to it, checking that it's for a traversal of `base_url`
+    fn load(path: &Path, base_url: &Url) -> anyhow::Result<Self> {
+        let data = StatReport::read(path)?;
+        if data.schema_version != SCHEMA_VERSION {
+            anyhow::bail!(
+                "cannot append to {}: report has schema version {}, expected {}",
+                path.display(),
+                data.schema_version,
+                SCHEMA_VERSION
+            );
+        }
+        if &data.base_url != base_url {
+            anyhow::bail!(
+                "cannot append to {}: report is for traversals of {}, not {}",
+                path.display(),
+                data.base_url,
+                base_url
+            );
+        }
+        Ok(StatReport {
+            end_time: None,
+            ..data
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use time::macros::datetime;
+
+    fn write_report(
+        path: &Path,
+        base_url: &str,
+        start: OffsetDateTime,
+        labels: &[(&str, &str)],
+        workers: &[usize],
+    ) {
+        let labels = labels
+            .iter()
+            .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        let mut report = StatReport::new(Url::parse(base_url).unwrap(), labels);
+        report.start_time = Some(start);
+        report.end_time = Some(start + Duration::from_secs(60));
+        report.traversals = Traversals::InMemory(
+            workers
+                .iter()
+                .map(|&w| {
+                    json!({
+                        "workers": w,
+                        "directories": 2,
+                        "files": 8,
+                        "overall_time": {"secs": 1, "nanos": 0},
+                    })
+                })
+                .collect(),
+        );
+        dump_json(path, &report).unwrap();
+    }
+
+    #[test]
+    fn test_samples_skip_inventory_mismatches() {
+        let mut report =
+            StatReport::new(Url::parse("https://example.com/").unwrap(), BTreeMap::new());
+        report.traversals = Traversals::InMemory(vec![
+            json!({
+                "workers": 1,
+                "directories": 2,
+                "files": 8,
+                "overall_time": {"secs": 1, "nanos": 0},
+            }),
+            json!({
+                "workers": 1,
+                "directories": 2,
+                "files": 7,
+                "overall_time": {"secs": 1, "nanos": 0},
+                "inventory_mismatch": true,
+            }),
+            json!({
+                "workers": 2,
+                "directories": 2,
+                "files": 8,
+                "overall_time": {"secs": 1, "nanos": 0},
+                "inventory_mismatch": false,
+            }),
+        ]);
+        assert_eq!(
+            report.samples().unwrap().counts(),
+            BTreeMap::from([(1, 1), (2, 1)]),
+            "traversals with inventory mismatches should be left out"
+        );
+    }
+
+    #[test]
+    fn test_merge_reports() {
+        let dir = std::env::temp_dir().join(format!("batchdav-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.json");
+        let b = dir.join("b.json.gz");
+        let out = dir.join("out.json");
+        write_report(
+            &a,
+            "https://example.com/",
+            datetime!(2024-06-02 12:00 UTC),
+            &[("site", "x"), ("run", "1")],
+            &[1, 2],
+        );
+        write_report(
+            &b,
+            "https://example.com/",
+            datetime!(2024-06-01 12:00 UTC),
+            &[("site", "x"), ("run", "2")],
+            &[4],
+        );
+        assert_eq!(merge_reports(&[a, b], &out).unwrap(), 3);
+        let merged = StatReport::read(&out).unwrap();
+        assert_eq!(merged.start_time, Some(datetime!(2024-06-01 12:00 UTC)));
+        assert_eq!(merged.end_time, Some(datetime!(2024-06-02 12:01 UTC)));
+        assert_eq!(
+            merged.labels,
+            BTreeMap::from([(String::from("site"), String::from("x"))])
+        );
+        let samples = merged.samples().unwrap();
+        assert_eq!(samples.counts(), BTreeMap::from([(1, 1), (2, 1), (4, 1)]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_reports_rejects() {
+        let dir =
+            std::env::temp_dir().join(format!("batchdav-merge-rejects-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.json");
+        let copy = dir.join("copy.json");
+        let other = dir.join("other.json");
+        let out = dir.join("out.json");
+        write_report(
+            &a,
+            "https://example.com/",
+            datetime!(2024-06-01 12:00 UTC),
+            &[],
+            &[1],
+        );
+        std::fs::copy(&a, &copy).unwrap();
+        write_report(
+            &other,
+            "https://example.org/",
+            datetime!(2024-06-02 12:00 UTC),
+            &[],
+            &[1],
+        );
+        let same = dir.join(".").join("a.json");
+        let e = merge_reports(&[a.clone(), same], &out).unwrap_err();
+        assert!(e.to_string().contains("same file as"), "{e:#}");
+        let e = merge_reports(&[a.clone(), copy], &out).unwrap_err();
+        assert!(e.to_string().contains("is a copy of"), "{e:#}");
+        let e = merge_reports(&[a, other], &out).unwrap_err();
+        assert!(e.to_string().contains("not https://example.com/"), "{e:#}");
+        assert!(!out.exists(), "output should not be written on error");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
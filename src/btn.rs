@@ -1,14 +1,19 @@
+use clap::ValueEnum;
 use futures_util::{
     future::{select, Either},
     FutureExt, Stream,
 };
+use rand::Rng;
+use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::{pin, Pin};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
 use tokio::sync::{
     mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-    Semaphore,
+    oneshot,
 };
 use tokio_util::sync::{CancellationToken, DropGuard};
 
@@ -25,34 +30,138 @@ type UnwindResult<T> = Result<T, Box<dyn std::any::Any + Send>>;
 ///   (which must all be `T`).
 ///
 /// - Dropping `BoundedTreeNursery` causes all tasks to be aborted.
+///
+/// - Each active task occupies one of `limit` numbered worker slots, and the
+///   number of tasks run & the time spent running them is tracked per slot.
+///
+/// - Tasks waiting for a free slot are started in the order given by a
+///   `DispatchOrder`.
+///
+/// - The worker slots can be divided into multiple numbered pools, each with
+///   its own limit & queue of waiting tasks, with each task spawned in a
+///   given pool.
+///
+/// - A panic in a task is handled according to a `PanicPolicy`.
 #[derive(Debug)]
 pub(crate) struct BoundedTreeNursery<T> {
     receiver: UnboundedReceiver<UnwindResult<T>>,
+    pools: Arc<Pools>,
+    on_panic: PanicPolicy,
+    /// The messages of the panics caught under `PanicPolicy::Error` or
+    /// `PanicPolicy::Skip`
+    panics: Vec<String>,
     _on_drop: DropGuard,
 }
 
 impl<T: Send + 'static> BoundedTreeNursery<T> {
     /// Create a `BoundedTreeNursery` that limits the number of active tasks to
-    /// at most `limit` and with `root` spawned as the initial task
-    pub(crate) fn new<F, Fut>(limit: usize, root: F) -> Self
+    /// at most `limit` and with each of `roots` spawned as initial tasks.  If
+    /// `roots` is empty, the stream will be empty.
+    pub(crate) fn new<I, F, Fut>(limit: usize, roots: I) -> Self
     where
+        I: IntoIterator<Item = F>,
         F: FnOnce(Spawner<T>) -> Fut + Send + 'static,
         Fut: Future<Output = T> + Send + 'static,
     {
-        let semaphore = Arc::new(Semaphore::new(limit));
+        Self::with_order(limit, DispatchOrder::default(), roots)
+    }
+
+    /// Like `new()`, but with tasks waiting for a free slot started in the
+    /// given order
+    pub(crate) fn with_order<I, F, Fut>(limit: usize, order: DispatchOrder, roots: I) -> Self
+    where
+        I: IntoIterator<Item = F>,
+        F: FnOnce(Spawner<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        Self::with_pools(vec![limit], order, roots.into_iter().map(|root| (0, root)))
+    }
+
+    /// Like `with_order()`, but with the worker slots divided into pools, the
+    /// `i`-th of which limits the number of active tasks spawned in it to
+    /// `limits[i]`, and with each of `roots` paired with the pool to spawn it
+    /// in
+    pub(crate) fn with_pools<I, F, Fut>(limits: Vec<usize>, order: DispatchOrder, roots: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, F)>,
+        F: FnOnce(Spawner<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let pools = Arc::new(Pools::new(limits, order));
         let token = CancellationToken::new();
         let (sender, receiver) = unbounded_channel();
         let spawner = Spawner {
-            semaphore,
+            pools: pools.clone(),
             sender,
             token: token.child_token(),
+            queue_wait: Duration::ZERO,
         };
-        spawner.spawn_with_self(root);
+        for (pool, root) in roots {
+            spawner.spawn_in(pool, root);
+        }
         BoundedTreeNursery {
             receiver,
+            pools,
+            on_panic: PanicPolicy::default(),
+            panics: Vec::new(),
             _on_drop: token.drop_guard(),
         }
     }
+
+    /// Return the number of tasks run by each worker slot of every pool so
+    /// far and the total time spent running them
+    pub(crate) fn worker_loads(&self) -> Vec<WorkerLoad> {
+        self.pools
+            .slots
+            .iter()
+            .flat_map(WorkerSlots::loads)
+            .collect()
+    }
+
+    /// Return the greatest number of tasks that have been active at once so
+    /// far
+    pub(crate) fn peak_active(&self) -> usize {
+        self.pools.occupancy.peak.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of tasks that are currently active (i.e., running
+    /// rather than waiting for a slot)
+    pub(crate) fn active(&self) -> usize {
+        self.pools.occupancy.active.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of tasks run by each worker slot of the given pool
+    /// so far and the total time spent running them
+    pub(crate) fn pool_loads(&self, pool: usize) -> Vec<WorkerLoad> {
+        self.pools.get(pool).loads()
+    }
+
+    /// Return the greatest number of tasks that have been active at once in
+    /// the given pool so far
+    pub(crate) fn pool_peak_active(&self, pool: usize) -> usize {
+        self.pools.get(pool).peak()
+    }
+
+    /// Change the number of tasks that may be active at once in the given
+    /// pool to `limit` (capped at the pool's initial limit).  If the new
+    /// limit is lower than the number of active tasks, no further tasks are
+    /// started in the pool until enough of them have finished.
+    pub(crate) fn set_pool_limit(&self, pool: usize, limit: usize) {
+        self.pools
+            .get(pool)
+            .set_allowed(limit, &self.pools.occupancy);
+    }
+
+    /// Set how panics in tasks are handled from now on
+    pub(crate) fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.on_panic = policy;
+    }
+
+    /// Return the messages of the panics in tasks that have been caught so
+    /// far under `PanicPolicy::Error` or `PanicPolicy::Skip`
+    pub(crate) fn panics(&self) -> &[String] {
+        &self.panics
+    }
 }
 
 impl<T: 'static> Stream for BoundedTreeNursery<T> {
@@ -63,22 +172,67 @@ impl<T: 'static> Stream for BoundedTreeNursery<T> {
     ///
     /// # Panics
     ///
-    /// If a task panics, this method resumes unwinding the panic.
+    /// If a task panics and the panic policy is `PanicPolicy::Propagate`,
+    /// this method resumes unwinding the panic.  Under `PanicPolicy::Error`,
+    /// the panic is recorded and the stream ends instead, while under
+    /// `PanicPolicy::Skip`, the panic is recorded and the task's result is
+    /// omitted from the stream.
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
-        match ready!(self.receiver.poll_recv(cx)) {
-            Some(Ok(r)) => Some(r).into(),
-            Some(Err(e)) => std::panic::resume_unwind(e),
-            None => None.into(),
+        if self.on_panic == PanicPolicy::Error && !self.panics.is_empty() {
+            return None.into();
+        }
+        loop {
+            match ready!(self.receiver.poll_recv(cx)) {
+                Some(Ok(r)) => return Some(r).into(),
+                Some(Err(e)) => {
+                    if self.on_panic == PanicPolicy::Propagate {
+                        std::panic::resume_unwind(e);
+                    }
+                    self.panics.push(panic_message(&*e));
+                    if self.on_panic == PanicPolicy::Error {
+                        return None.into();
+                    }
+                }
+                None => return None.into(),
+            }
         }
     }
 }
 
+/// Extract the message from a panic payload
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+/// What a `BoundedTreeNursery` does when one of its tasks panics
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum PanicPolicy {
+    /// Resume unwinding the panic in the task polling the nursery
+    #[default]
+    Propagate,
+    /// Record the panic and end the nursery's stream early, cancelling the
+    /// remaining tasks, so that the caller can report it as an error
+    Error,
+    /// Record the panic, discard the task's result, and carry on with the
+    /// remaining tasks
+    Skip,
+}
+
 /// A handle for spawning tasks in a `BoundedTreeNursery<T>`
 #[derive(Debug)]
 pub(crate) struct Spawner<T> {
-    semaphore: Arc<Semaphore>,
+    pools: Arc<Pools>,
     sender: UnboundedSender<UnwindResult<T>>,
     token: CancellationToken,
+    /// How long the task that this `Spawner` was passed to spent waiting for
+    /// a permit before it started
+    queue_wait: Duration,
 }
 
 // Clone can't be derived, as that would erroneously add `T: Clone` bounds to
@@ -86,9 +240,10 @@ pub(crate) struct Spawner<T> {
 impl<T> Clone for Spawner<T> {
     fn clone(&self) -> Spawner<T> {
         Spawner {
-            semaphore: self.semaphore.clone(),
+            pools: self.pools.clone(),
             sender: self.sender.clone(),
             token: self.token.clone(),
+            queue_wait: self.queue_wait,
         }
     }
 }
@@ -100,24 +255,49 @@ impl<T: Send + 'static> Spawner<T> {
         F: FnOnce(Spawner<T>) -> Fut + Send + 'static,
         Fut: Future<Output = T> + Send + 'static,
     {
-        self.clone().spawn_with_self(func);
+        self.spawn_in(0, func);
     }
 
-    /// Spawn the given task in the task group, passing it this `Spawner`
-    fn spawn_with_self<F, Fut>(self, func: F)
+    /// Spawn the given task in the given pool of the task group, passing it
+    /// a new `Spawner`
+    pub(crate) fn spawn_in<F, Fut>(&self, pool: usize, func: F)
+    where
+        F: FnOnce(Spawner<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        self.clone().spawn_with_self(pool, func);
+    }
+
+    /// Return the time that the task to which this `Spawner` was passed spent
+    /// waiting for a permit between being spawned and starting.  This is zero
+    /// for the `Spawner` used to spawn the root tasks.
+    pub(crate) fn queue_wait(&self) -> Duration {
+        self.queue_wait
+    }
+
+    /// Spawn the given task in the given pool of the task group, passing it
+    /// this `Spawner`
+    fn spawn_with_self<F, Fut>(mut self, pool: usize, func: F)
     where
         F: FnOnce(Spawner<T>) -> Fut + Send + 'static,
         Fut: Future<Output = T> + Send + 'static,
     {
         let Spawner {
-            semaphore,
+            pools,
             sender,
             token,
+            ..
         } = self.clone();
+        let scheduled = Instant::now();
+        // Join the queue for a slot now rather than when the task is first
+        // polled so that the dispatch order follows the order of spawning
+        let ticket = pools.get(pool).request(&pools.occupancy);
         let fut = async move {
-            let Ok(_permit) = semaphore.acquire().await else {
-                unreachable!("Semaphore should not be closed");
+            let Ok(index) = ticket.await else {
+                unreachable!("Worker slots should not be dropped while a task is waiting");
             };
+            self.queue_wait = scheduled.elapsed();
+            let _slot = SlotGuard::new(pools, pool, index);
             func(self).await
         };
         tokio::spawn(async move {
@@ -132,3 +312,609 @@ impl<T: Send + 'static> Spawner<T> {
         });
     }
 }
+
+/// The order in which the tasks of a `BoundedTreeNursery` that are waiting for
+/// a free worker slot are started
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum DispatchOrder {
+    /// Start the task that has been waiting longest, so that a hierarchy is
+    /// traversed breadth-first
+    #[default]
+    Bfs,
+    /// Start the task spawned most recently, so that the contents of each
+    /// collection are traversed before those of the collections listed
+    /// before it
+    Dfs,
+    /// Start a waiting task chosen uniformly at random
+    Random,
+}
+
+/// The work done by a single worker slot of a `BoundedTreeNursery`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct WorkerLoad {
+    /// Number of tasks run
+    pub(crate) tasks: usize,
+    /// Total time spent running tasks
+    pub(crate) busy: Duration,
+}
+
+/// The pools of worker slots of a `BoundedTreeNursery`
+#[derive(Debug)]
+struct Pools {
+    slots: Vec<WorkerSlots>,
+    occupancy: Occupancy,
+}
+
+impl Pools {
+    fn new(mut limits: Vec<usize>, order: DispatchOrder) -> Pools {
+        if limits.is_empty() {
+            limits.push(0);
+        }
+        Pools {
+            slots: limits
+                .into_iter()
+                .map(|limit| WorkerSlots::new(limit, order))
+                .collect(),
+            occupancy: Occupancy::default(),
+        }
+    }
+
+    /// Return the pool with the given index, treating out-of-range indices as
+    /// referring to the last pool
+    fn get(&self, pool: usize) -> &WorkerSlots {
+        let last = self.slots.len().saturating_sub(1);
+        &self.slots[pool.min(last)]
+    }
+}
+
+/// The number of worker slots occupied across all pools of a
+/// `BoundedTreeNursery`
+#[derive(Debug, Default)]
+struct Occupancy {
+    active: AtomicUsize,
+    /// Greatest number of slots occupied at once
+    peak: AtomicUsize,
+}
+
+impl Occupancy {
+    fn occupy(&self) {
+        let active = self
+            .active
+            .fetch_add(1, Ordering::Relaxed)
+            .saturating_add(1);
+        self.peak.fetch_max(active, Ordering::Relaxed);
+    }
+
+    fn vacate(&self) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A pool of worker slots of a `BoundedTreeNursery`.  Each task is given the
+/// idle slot that has spent the least time busy so far, so that the work is
+/// spread evenly across the slots whenever more than one is idle.
+#[derive(Debug)]
+struct WorkerSlots {
+    order: DispatchOrder,
+    state: Mutex<SlotState>,
+}
+
+#[derive(Debug)]
+struct SlotState {
+    idle: VecDeque<usize>,
+    /// Tasks waiting for a free slot, in the order in which they were spawned
+    waiting: VecDeque<oneshot::Sender<usize>>,
+    loads: Vec<WorkerLoad>,
+    /// Greatest number of slots occupied at once
+    peak: usize,
+    /// The number of slots that may currently be occupied at once, which is
+    /// at most the total number of slots
+    allowed: usize,
+}
+
+impl SlotState {
+    fn occupied(&self) -> usize {
+        self.loads.len().saturating_sub(self.idle.len())
+    }
+
+    /// Remove & return the idle slot that has spent the least time busy,
+    /// preferring the one that has been idle longest in case of a tie
+    fn take_idle(&mut self) -> Option<usize> {
+        let (pos, _) = self
+            .idle
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, &index)| self.loads.get(index).map(|load| load.busy))?;
+        self.idle.remove(pos)
+    }
+}
+
+impl WorkerSlots {
+    fn new(limit: usize, order: DispatchOrder) -> WorkerSlots {
+        WorkerSlots {
+            order,
+            state: Mutex::new(SlotState {
+                idle: (0..limit).collect(),
+                waiting: VecDeque::new(),
+                loads: vec![WorkerLoad::default(); limit],
+                peak: 0,
+                allowed: limit,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SlotState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Request a slot, returning a receiver that will be sent the index of
+    /// the slot once one is free
+    fn request(&self, occupancy: &Occupancy) -> oneshot::Receiver<usize> {
+        let (sender, receiver) = oneshot::channel();
+        let mut state = self.lock();
+        state.waiting.push_back(sender);
+        self.dispatch(&mut state, occupancy);
+        receiver
+    }
+
+    /// Set the number of slots that may be occupied at once, starting
+    /// waiting tasks in any idle slots that this frees up
+    fn set_allowed(&self, allowed: usize, occupancy: &Occupancy) {
+        let mut state = self.lock();
+        state.allowed = allowed.min(state.loads.len());
+        self.dispatch(&mut state, occupancy);
+    }
+
+    /// Start waiting tasks in idle slots for as long as there are both and
+    /// the number of occupied slots is below the number allowed
+    fn dispatch(&self, state: &mut SlotState, occupancy: &Occupancy) {
+        while state.occupied() < state.allowed && !state.waiting.is_empty() {
+            // Once taken, the slot counts as occupied
+            let Some(index) = state.take_idle() else {
+                break;
+            };
+            if !self.hand_off(state, index) {
+                state.idle.push_front(index);
+                break;
+            }
+            let occupied = state.occupied();
+            state.peak = state.peak.max(occupied);
+            occupancy.occupy();
+        }
+    }
+
+    /// Give the slot at `index` to the next waiting task, if any.  Returns
+    /// `true` if a task received it.
+    fn hand_off(&self, state: &mut SlotState, index: usize) -> bool {
+        while let Some(waiter) = self.next_waiter(&mut state.waiting) {
+            // Sending fails if the waiting task was cancelled, in which case
+            // the slot goes to the next one
+            if waiter.send(index).is_ok() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Credit the slot at `index` with a task that ran for `busy`, mark it
+    /// idle, and start the next waiting task, if any, in the least busy idle
+    /// slot
+    fn release(&self, index: usize, busy: Duration, occupancy: &Occupancy) {
+        let mut state = self.lock();
+        if let Some(load) = state.loads.get_mut(index) {
+            load.tasks = load.tasks.saturating_add(1);
+            load.busy = load.busy.saturating_add(busy);
+        }
+        state.idle.push_back(index);
+        occupancy.vacate();
+        self.dispatch(&mut state, occupancy);
+    }
+
+    fn next_waiter(
+        &self,
+        waiting: &mut VecDeque<oneshot::Sender<usize>>,
+    ) -> Option<oneshot::Sender<usize>> {
+        match self.order {
+            DispatchOrder::Bfs => waiting.pop_front(),
+            DispatchOrder::Dfs => waiting.pop_back(),
+            DispatchOrder::Random => {
+                if waiting.is_empty() {
+                    None
+                } else {
+                    let i = rand::thread_rng().gen_range(0..waiting.len());
+                    waiting.swap_remove_back(i)
+                }
+            }
+        }
+    }
+
+    fn loads(&self) -> Vec<WorkerLoad> {
+        self.lock().loads.clone()
+    }
+
+    fn peak(&self) -> usize {
+        self.lock().peak
+    }
+}
+
+/// An occupied worker slot, which is credited with a task & the time since
+/// it was taken when dropped
+#[derive(Debug)]
+struct SlotGuard {
+    pools: Arc<Pools>,
+    pool: usize,
+    index: usize,
+    start: Instant,
+}
+
+impl SlotGuard {
+    fn new(pools: Arc<Pools>, pool: usize, index: usize) -> SlotGuard {
+        SlotGuard {
+            pools,
+            pool,
+            index,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        self.pools
+            .get(self.pool)
+            .release(self.index, self.start.elapsed(), &self.pools.occupancy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    /// Return the index of the slot that `ticket` has been given, if any
+    fn slot(ticket: &mut oneshot::Receiver<usize>) -> Option<usize> {
+        ticket.try_recv().ok()
+    }
+
+    /// Run `nursery` to completion and return the results of its tasks in
+    /// the order in which they finished
+    async fn results<T: 'static>(nursery: &mut BoundedTreeNursery<T>) -> Vec<T> {
+        let mut results = Vec::new();
+        while let Some(r) = nursery.next().await {
+            results.push(r);
+        }
+        results
+    }
+
+    /// Run a nursery with a single worker whose root task spawns tasks
+    /// returning 1 through 4, and return the order in which the tasks
+    /// finished
+    async fn dispatch_order(order: DispatchOrder) -> Vec<usize> {
+        let mut nursery = BoundedTreeNursery::with_order(
+            1,
+            order,
+            [|spawner: Spawner<usize>| async move {
+                for i in 1..=4 {
+                    spawner.spawn(move |_| async move { i });
+                }
+                0
+            }],
+        );
+        results(&mut nursery).await
+    }
+
+    #[test]
+    fn test_request_least_busy_slot() {
+        let slots = WorkerSlots::new(3, DispatchOrder::Bfs);
+        let occupancy = Occupancy::default();
+        let mut a = slots.request(&occupancy);
+        let mut b = slots.request(&occupancy);
+        let mut c = slots.request(&occupancy);
+        assert_eq!(
+            slot(&mut a),
+            Some(0),
+            "first task should get the first slot"
+        );
+        assert_eq!(
+            slot(&mut b),
+            Some(1),
+            "second task should get the second slot"
+        );
+        assert_eq!(
+            slot(&mut c),
+            Some(2),
+            "third task should get the third slot"
+        );
+        slots.release(0, secs(5), &occupancy);
+        slots.release(2, secs(3), &occupancy);
+        slots.release(1, secs(1), &occupancy);
+        // Slot 0 has been idle longest, but slot 1 has been busy the least
+        let mut d = slots.request(&occupancy);
+        assert_eq!(slot(&mut d), Some(1), "task should get the least busy slot");
+        let mut e = slots.request(&occupancy);
+        assert_eq!(slot(&mut e), Some(2), "task should get the least busy slot");
+        assert_eq!(
+            occupancy.active.load(Ordering::Relaxed),
+            2,
+            "two slots should be occupied"
+        );
+    }
+
+    #[test]
+    fn test_release_rotates_slots() {
+        // With only one slot allowed at a time, freeing it for a waiting task
+        // should still rotate the work through all of the slots rather than
+        // handing the freed slot straight back out
+        let slots = WorkerSlots::new(3, DispatchOrder::Bfs);
+        let occupancy = Occupancy::default();
+        slots.set_allowed(1, &occupancy);
+        let mut tickets = std::iter::repeat_with(|| slots.request(&occupancy))
+            .take(6)
+            .collect::<Vec<_>>();
+        let mut order = Vec::new();
+        for ticket in &mut tickets {
+            let index = slot(ticket).expect("task should have been given a slot");
+            order.push(index);
+            slots.release(index, secs(1), &occupancy);
+        }
+        assert_eq!(order, [0, 1, 2, 0, 1, 2], "slots should take turns");
+        assert_eq!(
+            slots.loads(),
+            [WorkerLoad {
+                tasks: 2,
+                busy: secs(2)
+            }; 3],
+            "work should be spread evenly"
+        );
+        assert_eq!(
+            slots.peak(),
+            1,
+            "only one slot should have been occupied at once"
+        );
+        assert_eq!(
+            occupancy.active.load(Ordering::Relaxed),
+            0,
+            "no slots should be occupied"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_pool_limit() {
+        // The root task holds the only allowed slot, so its children wait
+        // until it finishes and then run one at a time
+        let mut nursery = BoundedTreeNursery::new(
+            3,
+            [|spawner: Spawner<usize>| async move {
+                for i in 1..=6 {
+                    spawner.spawn(move |_| async move {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        i
+                    });
+                }
+                0
+            }],
+        );
+        nursery.set_pool_limit(0, 1);
+        let mut done = Vec::new();
+        for _ in 0..3 {
+            done.extend(nursery.next().await);
+        }
+        assert_eq!(done, [0, 1, 2], "tasks should have run one at a time");
+        assert_eq!(
+            nursery.peak_active(),
+            1,
+            "only one task should have run at once"
+        );
+        // Raising the limit (past the initial limit, which caps it) starts
+        // waiting tasks straight away
+        nursery.set_pool_limit(0, 10);
+        assert_eq!(
+            nursery.active(),
+            3,
+            "all three slots should be occupied after raising the limit"
+        );
+        done.extend(results(&mut nursery).await);
+        done.sort_unstable();
+        assert_eq!(done, [0, 1, 2, 3, 4, 5, 6], "every task should run once");
+        assert_eq!(
+            nursery.peak_active(),
+            3,
+            "the limit should be capped at three"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hand_off_skips_cancelled() {
+        let slots = WorkerSlots::new(1, DispatchOrder::Bfs);
+        let occupancy = Occupancy::default();
+        let first = slots.request(&occupancy);
+        let second = slots.request(&occupancy);
+        let third = slots.request(&occupancy);
+        assert_eq!(first.await, Ok(0), "first task should get the slot");
+        // A waiting task that is cancelled drops its ticket, and the slot
+        // goes to the next waiting task instead
+        drop(second);
+        slots.release(0, secs(1), &occupancy);
+        assert_eq!(third.await, Ok(0), "slot should skip the cancelled task");
+        // If every waiting task has been cancelled, the slot is left idle
+        // for the next request
+        let fourth = slots.request(&occupancy);
+        drop(fourth);
+        slots.release(0, secs(1), &occupancy);
+        assert_eq!(slots.lock().idle, [0], "slot should be idle");
+        assert_eq!(
+            occupancy.active.load(Ordering::Relaxed),
+            0,
+            "no slots should be occupied"
+        );
+        let fifth = slots.request(&occupancy);
+        assert_eq!(fifth.await, Ok(0), "idle slot should be handed out again");
+        assert_eq!(
+            slots.loads(),
+            [WorkerLoad {
+                tasks: 2,
+                busy: secs(2)
+            }],
+            "only completed tasks should be credited"
+        );
+    }
+
+    /// Create a nursery with a single worker whose root task spawns a task
+    /// that panics followed by tasks returning 1 & 2
+    fn panicking_nursery(policy: PanicPolicy) -> BoundedTreeNursery<usize> {
+        let mut nursery = BoundedTreeNursery::new(
+            1,
+            [|spawner: Spawner<usize>| async move {
+                spawner.spawn(|_| async { panic!("task failed") });
+                for i in 1..=2 {
+                    spawner.spawn(move |_| async move { i });
+                }
+                0
+            }],
+        );
+        nursery.set_panic_policy(policy);
+        nursery
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "task failed")]
+    async fn test_panic_propagate() {
+        let mut nursery = panicking_nursery(PanicPolicy::Propagate);
+        results(&mut nursery).await;
+    }
+
+    #[tokio::test]
+    async fn test_panic_error() {
+        let mut nursery = panicking_nursery(PanicPolicy::Error);
+        assert_eq!(
+            results(&mut nursery).await,
+            [0],
+            "stream should end at the panic"
+        );
+        assert_eq!(
+            nursery.panics(),
+            ["task failed"],
+            "panic should be recorded"
+        );
+        assert_eq!(
+            nursery.next().await,
+            None,
+            "stream should stay ended after the panic"
+        );
+        // The slot is released while the panic unwinds, before the panic is
+        // sent to the stream
+        assert!(
+            nursery.worker_loads()[0].tasks >= 2,
+            "panicking task should free its slot"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_panic_skip() {
+        let mut nursery = panicking_nursery(PanicPolicy::Skip);
+        assert_eq!(
+            results(&mut nursery).await,
+            [0, 1, 2],
+            "other tasks should still run"
+        );
+        assert_eq!(
+            nursery.panics(),
+            ["task failed"],
+            "panic should be recorded"
+        );
+        assert_eq!(
+            nursery.pool_loads(0),
+            [WorkerLoad {
+                tasks: 4,
+                ..nursery.pool_loads(0)[0]
+            }],
+            "panicking task should be credited to its slot"
+        );
+    }
+
+    #[test]
+    fn test_panic_message() {
+        assert_eq!(panic_message(&"static"), "static");
+        assert_eq!(panic_message(&String::from("owned")), "owned");
+        assert_eq!(panic_message(&42), "Box<dyn Any>");
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_order() {
+        assert_eq!(
+            dispatch_order(DispatchOrder::Bfs).await,
+            [0, 1, 2, 3, 4],
+            "BFS should start the longest-waiting task first"
+        );
+        assert_eq!(
+            dispatch_order(DispatchOrder::Dfs).await,
+            [0, 4, 3, 2, 1],
+            "DFS should start the most recently spawned task first"
+        );
+        let mut random = dispatch_order(DispatchOrder::Random).await;
+        assert_eq!(random.first(), Some(&0), "root task should finish first");
+        random.sort_unstable();
+        assert_eq!(random, [0, 1, 2, 3, 4], "every task should run once");
+    }
+
+    #[tokio::test]
+    async fn test_pool_limits() {
+        // Pool 0 runs one task at a time and pool 1 runs two, with
+        // out-of-range pools treated as pool 1
+        let mut nursery = BoundedTreeNursery::with_pools(
+            vec![1, 2],
+            DispatchOrder::Bfs,
+            (0..9).map(|i| {
+                (i % 3, move |_| async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    i
+                })
+            }),
+        );
+        let mut done = results(&mut nursery).await;
+        done.sort_unstable();
+        assert_eq!(
+            done,
+            (0..9).collect::<Vec<_>>(),
+            "every task should run once"
+        );
+        assert_eq!(
+            nursery.pool_peak_active(0),
+            1,
+            "pool 0 should be limited to one task"
+        );
+        assert_eq!(
+            nursery.pool_peak_active(1),
+            2,
+            "pool 1 should be limited to two tasks"
+        );
+        assert_eq!(
+            nursery.peak_active(),
+            3,
+            "both pools should have run at once"
+        );
+        let tasks = |pool| {
+            nursery
+                .pool_loads(pool)
+                .iter()
+                .map(|load| load.tasks)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tasks(0), [3], "pool 0 should have run a third of the tasks");
+        assert_eq!(tasks(1).len(), 2, "pool 1 should have two slots");
+        assert_eq!(
+            tasks(1).iter().sum::<usize>(),
+            6,
+            "pool 1 should have run the rest"
+        );
+        assert_eq!(
+            nursery.worker_loads().len(),
+            3,
+            "there should be three slots in all"
+        );
+    }
+}
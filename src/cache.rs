@@ -0,0 +1,246 @@
+use crate::client::ServerHeaders;
+use crate::sigv4::hex;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use url::Url;
+
+/// Where & for how long to cache PROPFIND responses
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CacheOptions {
+    /// The directory in which to store cached responses
+    pub(crate) dir: PathBuf,
+    /// How long a cached response remains usable after it was stored
+    pub(crate) ttl: Duration,
+}
+
+/// An on-disk cache of successful PROPFIND responses, keyed by request URL,
+/// `Depth`, and body.  Each response is stored as a `{key}.json` file of
+/// metadata alongside a `{key}.body` file containing the raw multistatus
+/// body.
+#[derive(Debug)]
+pub(crate) struct PropfindCache {
+    dir: PathBuf,
+    ttl: Duration,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// A PROPFIND response as stored in the cache
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CachedResponse {
+    pub(crate) status: u16,
+    pub(crate) content_type: Option<String>,
+    pub(crate) charset: Option<String>,
+    pub(crate) server_headers: ServerHeaders,
+    pub(crate) body: Bytes,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct CacheMeta {
+    url: Url,
+    depth: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    charset: Option<String>,
+    #[serde(default)]
+    server_headers: ServerHeaders,
+    /// When the response was stored, in seconds since the Unix epoch
+    stored: u64,
+}
+
+impl PropfindCache {
+    pub(crate) fn new(opts: CacheOptions) -> PropfindCache {
+        PropfindCache {
+            dir: opts.dir,
+            ttl: opts.ttl,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of lookups answered from the cache
+    pub(crate) fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups that found no usable cached response
+    pub(crate) fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Return the cached response to a PROPFIND request with the given URL,
+    /// depth, and body, if one was stored less than the TTL ago.  Unreadable
+    /// or corrupt entries are treated as misses.
+    pub(crate) async fn get(&self, url: &Url, depth: &str, body: &[u8]) -> Option<CachedResponse> {
+        let r = self.lookup(url, depth, body, SystemTime::now()).await;
+        let counter = if r.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        r
+    }
+
+    async fn lookup(
+        &self,
+        url: &Url,
+        depth: &str,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Option<CachedResponse> {
+        let key = cache_key(url, depth, body);
+        let meta = tokio::fs::read(self.dir.join(format!("{key}.json")))
+            .await
+            .ok()?;
+        let meta = serde_json::from_slice::<CacheMeta>(&meta).ok()?;
+        if &meta.url != url || meta.depth != depth {
+            return None;
+        }
+        let age = unix_secs(now).saturating_sub(meta.stored);
+        if Duration::from_secs(age) >= self.ttl {
+            return None;
+        }
+        let body = tokio::fs::read(self.dir.join(format!("{key}.body")))
+            .await
+            .ok()?;
+        Some(CachedResponse {
+            status: meta.status,
+            content_type: meta.content_type,
+            charset: meta.charset,
+            server_headers: meta.server_headers,
+            body: Bytes::from(body),
+        })
+    }
+
+    /// Store the response to a PROPFIND request with the given URL, depth,
+    /// and body
+    pub(crate) async fn put(
+        &self,
+        url: &Url,
+        depth: &str,
+        body: &[u8],
+        resp: &CachedResponse,
+    ) -> Result<(), CacheError> {
+        let key = cache_key(url, depth, body);
+        let meta = CacheMeta {
+            url: url.clone(),
+            depth: depth.to_owned(),
+            status: resp.status,
+            content_type: resp.content_type.clone(),
+            charset: resp.charset.clone(),
+            server_headers: resp.server_headers.clone(),
+            stored: unix_secs(SystemTime::now()),
+        };
+        let meta = serde_json::to_vec(&meta).map_err(CacheError::Serialize)?;
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|source| CacheError::Mkdir {
+                path: self.dir.clone(),
+                source,
+            })?;
+        // Write the body before the metadata so that a reader never sees
+        // fresh metadata alongside a stale or partial body
+        self.write_atomic(format!("{key}.body"), resp.body.clone())
+            .await?;
+        self.write_atomic(format!("{key}.json"), Bytes::from(meta))
+            .await?;
+        Ok(())
+    }
+
+    /// Write `data` to the file `name` in the cache directory by way of a
+    /// uniquely-named temporary file, so that concurrent writers of the same
+    /// entry never see each other's partial writes
+    async fn write_atomic(&self, name: String, data: Bytes) -> Result<(), CacheError> {
+        let dir = self.dir.clone();
+        let path = self.dir.join(name);
+        let dest = path.clone();
+        tokio::task::spawn_blocking(move || {
+            // The temporary file is deleted on drop if it is not persisted
+            let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+            tmp.write_all(&data)?;
+            tmp.persist(dest)?;
+            Ok(())
+        })
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+        .map_err(|source| CacheError::Write { path, source })
+    }
+}
+
+/// Returns the hex SHA-256 digest identifying a PROPFIND request
+fn cache_key(url: &Url, depth: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_str());
+    hasher.update(b"\n");
+    hasher.update(depth);
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex(&hasher.finalize())
+}
+
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum CacheError {
+    #[error("failed to create PROPFIND cache directory {}", .path.display())]
+    Mkdir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to serialize PROPFIND cache entry")]
+    Serialize(#[source] serde_json::Error),
+    #[error("failed to write PROPFIND cache entry {}", .path.display())]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_propfind_cache() {
+        let dir = std::env::temp_dir().join(format!("batchdav-cache-{}", std::process::id()));
+        let cache = PropfindCache::new(CacheOptions {
+            dir: dir.clone(),
+            ttl: Duration::from_secs(60),
+        });
+        let url = Url::parse("https://example.com/foo/").unwrap();
+        let resp = CachedResponse {
+            status: 207,
+            content_type: Some(String::from("application/xml; charset=utf-8")),
+            charset: Some(String::from("utf-8")),
+            server_headers: vec![(String::from("server"), String::from("test"))],
+            body: Bytes::from_static(b"<multistatus/>"),
+        };
+        assert_eq!(cache.get(&url, "1", b"<propfind/>").await, None);
+        cache.put(&url, "1", b"<propfind/>", &resp).await.unwrap();
+        assert_eq!(cache.get(&url, "1", b"<propfind/>").await, Some(resp));
+        assert_eq!(cache.get(&url, "infinity", b"<propfind/>").await, None);
+        assert_eq!(
+            cache
+                .get(&url, "1", b"<propfind><allprop/></propfind>")
+                .await,
+            None
+        );
+        assert_eq!((cache.hits(), cache.misses()), (1, 3));
+        let later = SystemTime::now() + Duration::from_secs(61);
+        assert_eq!(cache.lookup(&url, "1", b"<propfind/>", later).await, None);
+        // Only the body & metadata files are left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use crate::traverse::Task;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// How often to save the checkpoint file while traversing
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The on-disk state of an in-progress traversal
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct CheckpointData {
+    base_url: Url,
+    /// URLs that have been successfully requested but whose parent requests
+    /// have not yet completed
+    completed: BTreeSet<Url>,
+    /// Requests that have been scheduled but not yet successfully completed
+    pending: Vec<Task>,
+}
+
+/// Tracker for the completed & pending requests of a traversal that
+/// periodically persists them to a file so that an interrupted traversal can
+/// be resumed
+#[derive(Clone, Debug)]
+pub(crate) struct Checkpointer {
+    path: PathBuf,
+    base_url: Url,
+    /// URLs whose requests completed successfully before those of their
+    /// parents did.  Once a parent completes, its children's entries are
+    /// pruned, so only the completed edge of the traversal is kept.
+    completed: BTreeSet<Url>,
+    pending: BTreeMap<Url, Task>,
+    last_saved: Instant,
+}
+
+impl Checkpointer {
+    /// Load the checkpoint at `path` if it exists; otherwise, start a new
+    /// checkpoint for a traversal of `base_url`
+    pub(crate) fn open(path: &Path, base_url: &Url) -> anyhow::Result<Checkpointer> {
+        let (completed, pending) = match File::open(path) {
+            Ok(fp) => {
+                let data: CheckpointData = serde_json::from_reader(BufReader::new(fp))
+                    .with_context(|| format!("failed to read checkpoint {}", path.display()))?;
+                if &data.base_url != base_url {
+                    anyhow::bail!(
+                        "checkpoint {} is for a traversal of {}, not {}",
+                        path.display(),
+                        data.base_url,
+                        base_url
+                    );
+                }
+                let pending = data
+                    .pending
+                    .into_iter()
+                    .map(|t| (t.url().clone(), t))
+                    .collect();
+                (data.completed, pending)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let root = Task::Dir {
+                    url: base_url.clone(),
+                    display_name: None,
+                };
+                (BTreeSet::new(), BTreeMap::from([(base_url.clone(), root)]))
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to open checkpoint {}", path.display()))
+            }
+        };
+        Ok(Checkpointer {
+            path: path.to_owned(),
+            base_url: base_url.clone(),
+            completed,
+            pending,
+            last_saved: Instant::now(),
+        })
+    }
+
+    /// Returns the tasks with which to start or resume the traversal
+    pub(crate) fn pending(&self) -> Vec<Task> {
+        self.pending.values().cloned().collect()
+    }
+
+    /// Record that the request for `url` completed successfully and spawned
+    /// the tasks `children`
+    pub(crate) fn complete<I: IntoIterator<Item = Task>>(&mut self, url: &Url, children: I) {
+        if self.pending.remove(url).is_none() {
+            // The task was spawned by a parent that hasn't been recorded as
+            // complete yet; remember it so that the parent doesn't mark it as
+            // pending again.
+            self.completed.insert(url.clone());
+        }
+        for t in children {
+            if !self.completed.remove(t.url()) {
+                self.pending.insert(t.url().clone(), t);
+            }
+        }
+    }
+
+    /// Save the checkpoint if it hasn't been saved recently
+    pub(crate) fn tick(&mut self) -> anyhow::Result<()> {
+        if self.last_saved.elapsed() >= SAVE_INTERVAL {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn save(&mut self) -> anyhow::Result<()> {
+        let data = CheckpointData {
+            base_url: self.base_url.clone(),
+            completed: self.completed.clone(),
+            pending: self.pending.values().cloned().collect(),
+        };
+        let mut tmpname = self.path.file_name().unwrap_or_default().to_os_string();
+        tmpname.push(".partial");
+        let tmppath = self.path.with_file_name(tmpname);
+        let mut fp = BufWriter::new(File::create(&tmppath).context("failed to open checkpoint")?);
+        serde_json::to_writer(&mut fp, &data).context("failed to write checkpoint")?;
+        fp.write_all(b"\n").context("failed to write checkpoint")?;
+        fp.flush().context("failed to flush checkpoint")?;
+        drop(fp);
+        std::fs::rename(&tmppath, &self.path).context("failed to move checkpoint into place")?;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+
+    /// Called at the end of the traversal.  If any requests are still
+    /// pending (i.e., failed), the checkpoint is saved so they can be retried;
+    /// otherwise, it is deleted.
+    pub(crate) fn finish(mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            match std::fs::remove_file(&self.path) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e).context("failed to remove checkpoint"),
+            }
+        } else {
+            self.save()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(url: &Url) -> Task {
+        Task::Dir {
+            url: url.clone(),
+            display_name: None,
+        }
+    }
+
+    fn checkpointer(base_url: &Url) -> Checkpointer {
+        let path = std::env::temp_dir().join(format!(
+            "batchdav-checkpoint-{}-nonexistent.json",
+            std::process::id()
+        ));
+        Checkpointer::open(&path, base_url).unwrap()
+    }
+
+    #[test]
+    fn child_before_parent() {
+        let base_url = Url::parse("https://dav.example.com/").unwrap();
+        let foo = base_url.join("foo/").unwrap();
+        let bar = base_url.join("bar/").unwrap();
+        let baz = foo.join("baz/").unwrap();
+        let mut c = checkpointer(&base_url);
+        assert_eq!(c.pending(), vec![dir(&base_url)]);
+        // `foo/` finishes (discovering `foo/baz/`) before its parent's
+        // completion is recorded
+        c.complete(&foo, [dir(&baz)]);
+        assert_eq!(c.completed, BTreeSet::from([foo.clone()]));
+        c.complete(&base_url, [dir(&foo), dir(&bar)]);
+        assert_eq!(c.pending(), vec![dir(&bar), dir(&baz)]);
+        assert!(c.completed.is_empty());
+        c.complete(&bar, []);
+        c.complete(&baz, []);
+        assert!(c.pending().is_empty());
+        assert!(c.completed.is_empty());
+    }
+}
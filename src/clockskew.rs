@@ -0,0 +1,211 @@
+use reqwest::header::{HeaderMap, DATE};
+use serde::Serialize;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+/// Number of seconds in an hour, the unit in which drift is reported
+const SECONDS_PER_HOUR: f64 = 3600.0;
+
+/// The minimum number of seconds over which responses must have been received
+/// for drift to be estimated.  Over shorter spans, the one-second resolution
+/// of `Date` headers swamps any real drift.
+const MIN_DRIFT_SPAN: f64 = 60.0;
+
+/// Estimates the offset of the server's clock from the local clock by
+/// comparing the `Date` headers of responses against the local time at which
+/// they were received.
+///
+/// A `Date` header is truncated to whole seconds and is generated at some
+/// point while the request is in flight, so each response is taken to show
+/// the server's clock reading half a second past its `Date` at the midpoint
+/// between sending the request and receiving the response.  The drift of the
+/// server's clock is the slope of the least-squares line through these
+/// estimates over local time.
+#[derive(Debug, Default)]
+pub(crate) struct ClockTracker(Mutex<SkewAccumulator>);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct SkewAccumulator {
+    /// The local time of the first estimate, from which the local times of
+    /// later estimates are measured in order to preserve precision
+    origin: Option<SystemTime>,
+    samples: u64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_xy: f64,
+    min: f64,
+    max: f64,
+    /// Local time in seconds since `origin` of the latest estimate
+    last_x: f64,
+}
+
+impl ClockTracker {
+    /// Record the `Date` header, if any, of a response to a request sent at
+    /// `sent` and answered at `received`
+    pub(crate) fn record(&self, sent: SystemTime, received: SystemTime, headers: &HeaderMap) {
+        let Some(date) = headers
+            .get(DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| OffsetDateTime::parse(s, &Rfc2822).ok())
+        else {
+            return;
+        };
+        let midpoint = match received.duration_since(sent) {
+            Ok(d) => sent + d / 2,
+            Err(_) => received,
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let (server, local) = (
+            date.unix_timestamp() as f64 + 0.5,
+            OffsetDateTime::from(midpoint).unix_timestamp_nanos() as f64 / 1e9,
+        );
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .add(midpoint, server - local);
+    }
+
+    /// Summarize the estimates recorded so far, if any
+    pub(crate) fn report(&self) -> Option<ClockSkew> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .report()
+    }
+}
+
+impl SkewAccumulator {
+    /// Add an estimate of `skew` seconds at local time `at`
+    fn add(&mut self, at: SystemTime, skew: f64) {
+        let origin = *self.origin.get_or_insert(at);
+        let x = match at.duration_since(origin) {
+            Ok(d) => d.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        };
+        if self.samples == 0 {
+            self.min = skew;
+            self.max = skew;
+        } else {
+            self.min = self.min.min(skew);
+            self.max = self.max.max(skew);
+        }
+        self.samples = self.samples.saturating_add(1);
+        self.sum_x += x;
+        self.sum_y += skew;
+        self.sum_xx += x * x;
+        self.sum_xy += x * skew;
+        self.last_x = self.last_x.max(x);
+    }
+
+    fn report(&self) -> Option<ClockSkew> {
+        if self.samples == 0 {
+            return None;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let n = self.samples as f64;
+        let mean_x = self.sum_x / n;
+        let var_x = mean_x.mul_add(-mean_x, self.sum_xx / n);
+        let cov_xy = mean_x.mul_add(-self.sum_y / n, self.sum_xy / n);
+        let drift = (self.last_x >= MIN_DRIFT_SPAN && var_x > f64::EPSILON)
+            .then(|| cov_xy / var_x * SECONDS_PER_HOUR);
+        Some(ClockSkew {
+            samples: self.samples,
+            skew: self.sum_y / n,
+            min_skew: self.min,
+            max_skew: self.max,
+            drift,
+            span: self.last_x,
+        })
+    }
+}
+
+/// The estimated offset of the server's clock from the local clock over a
+/// traversal, in seconds.  Positive values mean that the server's clock is
+/// ahead.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct ClockSkew {
+    /// Number of responses with valid `Date` headers
+    pub(crate) samples: u64,
+    /// Mean estimated skew
+    pub(crate) skew: f64,
+    pub(crate) min_skew: f64,
+    pub(crate) max_skew: f64,
+    /// Rate at which the skew changed, in seconds per hour, if the responses
+    /// were received over at least a minute
+    pub(crate) drift: Option<f64>,
+    /// Seconds of local time between the first & last responses
+    pub(crate) span: f64,
+}
+
+impl ClockSkew {
+    /// Whether the skew, or the change in skew over the traversal due to
+    /// drift, is large enough to be worth reporting given the one-second
+    /// resolution of `Date` headers
+    pub(crate) fn is_notable(&self) -> bool {
+        self.skew.abs() >= 1.0
+            || self
+                .drift
+                .is_some_and(|d| (d * self.span / SECONDS_PER_HOUR).abs() >= 1.0)
+    }
+}
+
+impl fmt::Display for ClockSkew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Server clock: {:.1} s {} local clock (range {:+.1} to {:+.1} s over {} responses)",
+            self.skew.abs(),
+            if self.skew < 0.0 {
+                "behind"
+            } else {
+                "ahead of"
+            },
+            self.min_skew,
+            self.max_skew,
+            self.samples,
+        )?;
+        if let Some(drift) = self.drift {
+            write!(f, "; drift {drift:+.2} s/hour over {:.0} s", self.span)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use std::time::Duration;
+
+    #[test]
+    fn test_clock_tracker() {
+        let tracker = ClockTracker::default();
+        assert_eq!(tracker.report(), None);
+        // 2024-06-01T12:00:00Z
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_717_243_200);
+        // The server's clock starts 10 seconds ahead and gains 2 seconds over
+        // the course of an hour
+        for (offset, date) in [
+            (0, "Sat, 01 Jun 2024 12:00:10 GMT"),
+            (1800, "Sat, 01 Jun 2024 12:30:11 GMT"),
+            (3600, "Sat, 01 Jun 2024 13:00:12 GMT"),
+        ] {
+            let sent = t0 + Duration::from_secs(offset);
+            let mut headers = HeaderMap::new();
+            headers.insert(DATE, HeaderValue::from_static(date));
+            tracker.record(sent, sent + Duration::from_secs(1), &headers);
+        }
+        tracker.record(t0, t0, &HeaderMap::new());
+        let skew = tracker.report().unwrap();
+        assert_eq!(skew.samples, 3);
+        assert!((skew.skew - 11.0).abs() < 1e-6);
+        assert!((skew.min_skew - 10.0).abs() < 1e-6);
+        assert!((skew.max_skew - 12.0).abs() < 1e-6);
+        assert!((skew.drift.unwrap() - 2.0).abs() < 1e-6);
+        assert!((skew.span - 3600.0).abs() < 1e-6);
+        assert!(skew.is_notable());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::traverse::{RequestKind, RequestRecord};
+use anyhow::Context;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::column::writer::ColumnWriter;
+use parquet::data_type::ByteArray;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use url::Url;
+
+/// The schema of the rows written by `RequestParquet`, one per request
+const SCHEMA: &str = "
+    message request {
+        REQUIRED BYTE_ARRAY root (UTF8);
+        REQUIRED INT64 workers;
+        REQUIRED INT64 run;
+        REQUIRED BYTE_ARRAY url (UTF8);
+        REQUIRED BYTE_ARRAY type (UTF8);
+        REQUIRED INT32 status;
+        REQUIRED DOUBLE start;
+        REQUIRED DOUBLE end;
+        REQUIRED INT64 start_time (TIMESTAMP(MICROS, true));
+        REQUIRED INT64 end_time (TIMESTAMP(MICROS, true));
+        REQUIRED DOUBLE queue_wait;
+        REQUIRED DOUBLE service_time;
+        REQUIRED DOUBLE sojourn_time;
+        OPTIONAL BYTE_ARRAY request_id (UTF8);
+        OPTIONAL BYTE_ARRAY traversal_id (UTF8);
+        OPTIONAL BYTE_ARRAY traceparent (UTF8);
+        REQUIRED BYTE_ARRAY method (UTF8);
+    }
+";
+
+/// A Parquet file of per-request records, to which the requests of each
+/// traversal are written as a separate row group.  If the value is dropped
+/// without being closed (e.g., because the batch failed partway through),
+/// the file is finished on a best-effort basis so that the row groups
+/// written so far remain readable.
+pub(crate) struct RequestParquet {
+    writer: SerializedFileWriter<File>,
+    finished: bool,
+}
+
+// `SerializedFileWriter` does not implement `Debug`
+impl std::fmt::Debug for RequestParquet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RequestParquet(..)")
+    }
+}
+
+impl RequestParquet {
+    pub(crate) fn create(path: &Path) -> anyhow::Result<RequestParquet> {
+        let schema = Arc::new(parse_message_type(SCHEMA).context("invalid Parquet schema")?);
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let fp = File::create(path)
+            .with_context(|| format!("failed to create Parquet file {}", path.display()))?;
+        let writer = SerializedFileWriter::new(fp, schema, Arc::new(props))
+            .context("failed to initialize Parquet file")?;
+        Ok(RequestParquet {
+            writer,
+            finished: false,
+        })
+    }
+
+    /// Write the requests made by the `run`-th traversal of `root` with
+    /// `workers` workers as a row group
+    pub(crate) fn write_traversal(
+        &mut self,
+        root: &Url,
+        workers: usize,
+        run: usize,
+        records: &[RequestRecord],
+    ) -> anyhow::Result<()> {
+        let mut columns = columns(root, workers, run, records).into_iter();
+        let mut rg = self
+            .writer
+            .next_row_group()
+            .context("failed to start Parquet row group")?;
+        while let Some(mut col) = rg.next_column().context("failed to start Parquet column")? {
+            let data = columns
+                .next()
+                .context("Parquet schema has more columns than expected")?;
+            data.write(col.untyped())
+                .context("failed to write Parquet column")?;
+            col.close().context("failed to finish Parquet column")?;
+        }
+        rg.close().context("failed to finish Parquet row group")?;
+        Ok(())
+    }
+
+    pub(crate) fn close(mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        self.writer
+            .finish()
+            .context("failed to finish Parquet file")?;
+        Ok(())
+    }
+}
+
+impl Drop for RequestParquet {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Errors cannot be reported from here; if a row group was left
+            // half-written, the file cannot be finished anyway.
+            let _ = self.writer.finish();
+        }
+    }
+}
+
+/// The values of a single column of a row group, in schema order
+#[derive(Clone, Debug, PartialEq)]
+enum ColumnData {
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+    Str(Vec<ByteArray>),
+    OptStr(Vec<Option<ByteArray>>),
+}
+
+impl ColumnData {
+    fn write(self, col: &mut ColumnWriter<'_>) -> parquet::errors::Result<()> {
+        match (self, col) {
+            (ColumnData::Int32(values), ColumnWriter::Int32ColumnWriter(w)) => {
+                w.write_batch(&values, None, None)?;
+            }
+            (ColumnData::Int64(values), ColumnWriter::Int64ColumnWriter(w)) => {
+                w.write_batch(&values, None, None)?;
+            }
+            (ColumnData::Double(values), ColumnWriter::DoubleColumnWriter(w)) => {
+                w.write_batch(&values, None, None)?;
+            }
+            (ColumnData::Str(values), ColumnWriter::ByteArrayColumnWriter(w)) => {
+                w.write_batch(&values, None, None)?;
+            }
+            (ColumnData::OptStr(values), ColumnWriter::ByteArrayColumnWriter(w)) => {
+                let def_levels = values
+                    .iter()
+                    .map(|v| i16::from(v.is_some()))
+                    .collect::<Vec<_>>();
+                let present = values.into_iter().flatten().collect::<Vec<_>>();
+                w.write_batch(&present, Some(&def_levels), None)?;
+            }
+            (data, _) => {
+                return Err(parquet::errors::ParquetError::General(format!(
+                    "column type mismatch for {data:?}"
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert the records of a traversal to column data in the order of
+/// `SCHEMA`
+fn columns(root: &Url, workers: usize, run: usize, records: &[RequestRecord]) -> Vec<ColumnData> {
+    let n = records.len();
+    let int = |i: usize| i64::try_from(i).unwrap_or(i64::MAX);
+    let secs = |f: fn(&RequestRecord) -> std::time::Duration| {
+        ColumnData::Double(records.iter().map(|r| f(r).as_secs_f64()).collect())
+    };
+    let micros = |f: fn(&RequestRecord) -> time::OffsetDateTime| {
+        ColumnData::Int64(
+            records
+                .iter()
+                .map(|r| i64::try_from(f(r).unix_timestamp_nanos() / 1000).unwrap_or(i64::MAX))
+                .collect(),
+        )
+    };
+    let opt_str = |f: fn(&RequestRecord) -> Option<&str>| {
+        ColumnData::OptStr(records.iter().map(|r| f(r).map(ByteArray::from)).collect())
+    };
+    vec![
+        ColumnData::Str(vec![ByteArray::from(root.as_str()); n]),
+        ColumnData::Int64(vec![int(workers); n]),
+        ColumnData::Int64(vec![int(run); n]),
+        ColumnData::Str(
+            records
+                .iter()
+                .map(|r| ByteArray::from(r.url.as_str()))
+                .collect(),
+        ),
+        ColumnData::Str(
+            records
+                .iter()
+                .map(|r| ByteArray::from(kind_name(r.kind)))
+                .collect(),
+        ),
+        ColumnData::Int32(records.iter().map(|r| i32::from(r.status)).collect()),
+        secs(|r| r.start),
+        secs(|r| r.end),
+        micros(|r| r.start_time),
+        micros(|r| r.end_time),
+        secs(|r| r.queue_wait),
+        secs(|r| r.service_time),
+        secs(|r| r.sojourn_time),
+        opt_str(|r| r.request_id.as_deref()),
+        opt_str(|r| r.traversal_id.as_deref()),
+        opt_str(|r| r.traceparent.as_deref()),
+        ColumnData::Str(
+            records
+                .iter()
+                .map(|r| ByteArray::from(r.method.as_str()))
+                .collect(),
+        ),
+    ]
+}
+
+fn kind_name(kind: RequestKind) -> &'static str {
+    match kind {
+        RequestKind::Dir => "dir",
+        RequestKind::File => "file",
+        RequestKind::Zarr => "zarr",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+    use std::time::Duration;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_request_parquet() {
+        let path = std::env::temp_dir().join(format!("batchdav-{}.parquet", std::process::id()));
+        let root = Url::parse("https://example.com/").unwrap();
+        let record = RequestRecord {
+            url: Url::parse("https://example.com/foo").unwrap(),
+            kind: RequestKind::File,
+            method: String::from("HEAD"),
+            status: 200,
+            start: Duration::from_millis(10),
+            end: Duration::from_millis(25),
+            start_time: datetime!(2024-06-01 12:00:00.010 UTC),
+            end_time: datetime!(2024-06-01 12:00:00.025 UTC),
+            queue_wait: Duration::from_millis(5),
+            service_time: Duration::from_millis(15),
+            sojourn_time: Duration::from_millis(20),
+            request_id: Some(String::from("abc")),
+            traversal_id: None,
+            traceparent: None,
+            retries: Vec::new(),
+        };
+        let mut pq = RequestParquet::create(&path).unwrap();
+        pq.write_traversal(&root, 2, 1, &[record.clone(), record.clone()])
+            .unwrap();
+        pq.write_traversal(&root, 4, 1, &[record]).unwrap();
+        pq.close().unwrap();
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        let meta = reader.metadata();
+        assert_eq!(meta.num_row_groups(), 2);
+        assert_eq!(meta.file_metadata().num_rows(), 3);
+        assert_eq!(meta.file_metadata().schema_descr().num_columns(), 17);
+        let row = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            row.to_string(),
+            "{root: \"https://example.com/\", workers: 2, run: 1, url: \"https://example.com/foo\", type: \"file\", status: 200, start: 0.01, end: 0.025, start_time: 2024-06-01 12:00:00 +00:00, end_time: 2024-06-01 12:00:00 +00:00, queue_wait: 0.005, service_time: 0.015, sojourn_time: 0.02, request_id: \"abc\", traversal_id: null, traceparent: null, method: \"HEAD\"}"
+        );
+        assert_eq!(row.get_timestamp_micros(8).unwrap(), 1_717_243_200_010_000);
+        assert_eq!(row.get_timestamp_micros(9).unwrap(), 1_717_243_200_025_000);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_request_parquet_dropped() {
+        let path =
+            std::env::temp_dir().join(format!("batchdav-dropped-{}.parquet", std::process::id()));
+        let root = Url::parse("https://example.com/").unwrap();
+        let record = RequestRecord {
+            url: Url::parse("https://example.com/foo").unwrap(),
+            kind: RequestKind::Dir,
+            method: String::from("PROPFIND"),
+            status: 207,
+            start: Duration::from_millis(10),
+            end: Duration::from_millis(25),
+            start_time: datetime!(2024-06-01 12:00:00.010 UTC),
+            end_time: datetime!(2024-06-01 12:00:00.025 UTC),
+            queue_wait: Duration::ZERO,
+            service_time: Duration::from_millis(15),
+            sojourn_time: Duration::from_millis(15),
+            request_id: None,
+            traversal_id: None,
+            traceparent: None,
+            retries: Vec::new(),
+        };
+        let mut pq = RequestParquet::create(&path).unwrap();
+        pq.write_traversal(&root, 2, 1, &[record]).unwrap();
+        drop(pq);
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 1);
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// The compression applied to an output file, as determined by its file
+/// extension
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Compression {
+    #[default]
+    None,
+    /// Compress with gzip; used for paths ending in `.gz`
+    Gzip,
+    /// Compress with Zstandard; used for paths ending in `.zst`
+    Zstd,
+}
+
+impl Compression {
+    pub(crate) fn for_path(path: &Path) -> Compression {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// A file being written to, compressed according to its extension.
+/// `finish()` must be called once all data has been written in order to
+/// complete the compressed stream.
+pub(crate) enum OutFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl OutFile {
+    pub(crate) fn create(path: &Path) -> std::io::Result<OutFile> {
+        OutFile::with_compression(path, Compression::for_path(path))
+    }
+
+    /// Create a file at `path` compressed with `compression` regardless of
+    /// the path's extension
+    pub(crate) fn with_compression(
+        path: &Path,
+        compression: Compression,
+    ) -> std::io::Result<OutFile> {
+        let fp = BufWriter::new(File::create(path)?);
+        Ok(match compression {
+            Compression::None => OutFile::Plain(fp),
+            Compression::Gzip => OutFile::Gzip(GzEncoder::new(fp, flate2::Compression::default())),
+            Compression::Zstd => OutFile::Zstd(zstd::Encoder::new(fp, 0)?),
+        })
+    }
+
+    /// Complete the compressed stream (if any) and flush all data to the
+    /// file
+    pub(crate) fn finish(self) -> std::io::Result<()> {
+        let mut fp = match self {
+            OutFile::Plain(fp) => fp,
+            OutFile::Gzip(enc) => enc.finish()?,
+            OutFile::Zstd(enc) => enc.finish()?,
+        };
+        fp.flush()
+    }
+}
+
+impl Write for OutFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutFile::Plain(fp) => fp.write(buf),
+            OutFile::Gzip(fp) => fp.write(buf),
+            OutFile::Zstd(fp) => fp.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutFile::Plain(fp) => fp.flush(),
+            OutFile::Gzip(fp) => fp.flush(),
+            OutFile::Zstd(fp) => fp.flush(),
+        }
+    }
+}
+
+// The zstd types do not implement `Debug`
+impl fmt::Debug for OutFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutFile::Plain(fp) => f.debug_tuple("Plain").field(fp).finish(),
+            OutFile::Gzip(fp) => f.debug_tuple("Gzip").field(fp).finish(),
+            OutFile::Zstd(_) => f.write_str("Zstd(..)"),
+        }
+    }
+}
+
+/// A file being read from, decompressed according to its extension
+pub(crate) enum InFile {
+    Plain(BufReader<File>),
+    Gzip(MultiGzDecoder<BufReader<File>>),
+    Zstd(zstd::Decoder<'static, BufReader<File>>),
+}
+
+impl InFile {
+    pub(crate) fn open(path: &Path) -> std::io::Result<InFile> {
+        let fp = BufReader::new(File::open(path)?);
+        Ok(match Compression::for_path(path) {
+            Compression::None => InFile::Plain(fp),
+            Compression::Gzip => InFile::Gzip(MultiGzDecoder::new(fp)),
+            Compression::Zstd => InFile::Zstd(zstd::Decoder::with_buffer(fp)?),
+        })
+    }
+}
+
+impl fmt::Debug for InFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InFile::Plain(fp) => f.debug_tuple("Plain").field(fp).finish(),
+            InFile::Gzip(fp) => f.debug_tuple("Gzip").field(fp).finish(),
+            InFile::Zstd(_) => f.write_str("Zstd(..)"),
+        }
+    }
+}
+
+impl Read for InFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            InFile::Plain(fp) => fp.read(buf),
+            InFile::Gzip(fp) => fp.read(buf),
+            InFile::Zstd(fp) => fp.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("report.json", Compression::None)]
+    #[case("report.json.gz", Compression::Gzip)]
+    #[case("requests.ndjson.zst", Compression::Zstd)]
+    #[case("gz", Compression::None)]
+    #[case("report.tgz", Compression::None)]
+    fn test_for_path(#[case] path: &str, #[case] compression: Compression) {
+        assert_eq!(Compression::for_path(Path::new(path)), compression);
+    }
+
+    #[rstest]
+    #[case("roundtrip.json")]
+    #[case("roundtrip.json.gz")]
+    #[case("roundtrip.ndjson.zst")]
+    fn test_roundtrip(#[case] name: &str) {
+        let dir = std::env::temp_dir().join(format!("batchdav-compress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let data = "{\"url\": \"https://example.com/\"}\n".repeat(100);
+        let mut fp = OutFile::create(&path).unwrap();
+        fp.write_all(data.as_bytes()).unwrap();
+        fp.finish().unwrap();
+        let mut s = String::new();
+        InFile::open(&path).unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(s, data);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
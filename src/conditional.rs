@@ -0,0 +1,194 @@
+use crate::client::{Client, ConditionalResponse, RequestError};
+use crate::stats::LatencySummary;
+use futures_util::StreamExt;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use url::Url;
+
+/// For each `(url, etag)` pair, make a `GET` request for `url` with an
+/// `If-None-Match: {etag}` header followed by an unconditional `GET` request,
+/// with up to `concurrency` files being checked at once, and verify that the
+/// server responded to the conditional request with 304 Not Modified
+pub(crate) async fn check_conditional(
+    client: &Client,
+    mut files: Vec<(Url, String)>,
+    without_etags: usize,
+    concurrency: usize,
+) -> ConditionalStats {
+    files.sort();
+    let mut stream = futures_util::stream::iter(files.into_iter().map(|(url, etag)| async move {
+        let r = check_file(client, &url, &etag).await;
+        (url, etag, r)
+    }))
+    .buffered(concurrency.max(1));
+    let mut stats = ConditionalStats {
+        without_etags,
+        ..ConditionalStats::default()
+    };
+    let mut conditional_ttfb = Vec::new();
+    let mut unconditional_ttfb = Vec::new();
+    while let Some((url, etag, r)) = stream.next().await {
+        stats.checked = stats.checked.saturating_add(1);
+        match r {
+            Ok((cond, uncond)) => {
+                conditional_ttfb.push(cond.ttfb);
+                unconditional_ttfb.push(uncond);
+                match classify(&cond) {
+                    Outcome::NotModified => {
+                        stats.not_modified = stats.not_modified.saturating_add(1);
+                    }
+                    Outcome::Redirected => {
+                        stats.redirected = stats.redirected.saturating_add(1);
+                    }
+                    Outcome::Mismatch => stats.mismatches.push(ConditionalMismatch {
+                        url,
+                        etag,
+                        status: cond.status,
+                        response_etag: cond.etag,
+                    }),
+                }
+            }
+            Err(e) => stats.failures.push(ConditionalFailure {
+                url,
+                message: format!("{:#}", anyhow::Error::from(e)),
+            }),
+        }
+    }
+    stats.conditional_ttfb = LatencySummary::new(&conditional_ttfb);
+    stats.unconditional_ttfb = LatencySummary::new(&unconditional_ttfb);
+    stats
+}
+
+/// Make a conditional and then an unconditional `GET` request for `url`,
+/// returning the conditional response and the time to first byte of the
+/// unconditional one
+async fn check_file(
+    client: &Client,
+    url: &Url,
+    etag: &str,
+) -> Result<(ConditionalResponse, Duration), RequestError> {
+    let cond = client.get_conditional(url, Some(etag)).await?;
+    let uncond = client.get_conditional(url, None).await?;
+    Ok((cond, uncond.ttfb))
+}
+
+/// The result of a conditional request whose `If-None-Match` header
+/// contained the resource's current ETag
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    /// The server correctly responded with 304 Not Modified
+    NotModified,
+    /// The server redirected the request, so the validator was never
+    /// evaluated against the resource itself
+    Redirected,
+    /// The server sent a full response
+    Mismatch,
+}
+
+fn classify(resp: &ConditionalResponse) -> Outcome {
+    match resp.status {
+        304 => Outcome::NotModified,
+        300..=399 => Outcome::Redirected,
+        _ => Outcome::Mismatch,
+    }
+}
+
+/// A file for which the server did not respond to a conditional request
+/// with 304 Not Modified
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ConditionalMismatch {
+    pub(crate) url: Url,
+    /// The ETag sent in the `If-None-Match` header, as reported in the
+    /// collection listing
+    pub(crate) etag: String,
+    /// The HTTP status code of the response to the conditional request
+    pub(crate) status: u16,
+    /// The ETag in the response to the conditional request, if any
+    pub(crate) response_etag: Option<String>,
+}
+
+/// A file for which the conditional or unconditional request failed
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ConditionalFailure {
+    pub(crate) url: Url,
+    pub(crate) message: String,
+}
+
+/// The results of checking the server's handling of `If-None-Match`
+/// requests for the files found during a traversal
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct ConditionalStats {
+    /// Number of files checked
+    pub(crate) checked: usize,
+    /// Number of files skipped because the listing gave them no ETag
+    pub(crate) without_etags: usize,
+    /// Number of conditional requests answered with 304 Not Modified
+    pub(crate) not_modified: usize,
+    /// Number of conditional requests answered with a redirect
+    pub(crate) redirected: usize,
+    /// Files whose conditional requests were answered with a full response
+    pub(crate) mismatches: Vec<ConditionalMismatch>,
+    pub(crate) failures: Vec<ConditionalFailure>,
+    /// Times to first byte of the conditional requests
+    pub(crate) conditional_ttfb: Option<LatencySummary>,
+    /// Times to first byte of the unconditional requests
+    pub(crate) unconditional_ttfb: Option<LatencySummary>,
+}
+
+impl fmt::Display for ConditionalStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Conditional requests: {} files checked ({} without ETags skipped): {} not modified, {} redirected, {} mismatches, {} failed",
+            self.checked,
+            self.without_etags,
+            self.not_modified,
+            self.redirected,
+            self.mismatches.len(),
+            self.failures.len(),
+        )?;
+        if let Some(lat) = self.conditional_ttfb {
+            writeln!(f, "  Conditional TTFB: {lat}")?;
+        }
+        if let Some(lat) = self.unconditional_ttfb {
+            writeln!(f, "  Unconditional TTFB: {lat}")?;
+        }
+        for m in &self.mismatches {
+            write!(
+                f,
+                "  MISMATCH {}: sent If-None-Match: {}, got {}",
+                m.url, m.etag, m.status
+            )?;
+            if let Some(ref etag) = m.response_etag {
+                write!(f, " with ETag {etag}")?;
+            }
+            writeln!(f)?;
+        }
+        for fail in &self.failures {
+            writeln!(f, "  FAILED {}: {}", fail.url, fail.message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(304, Outcome::NotModified)]
+    #[case(302, Outcome::Redirected)]
+    #[case(307, Outcome::Redirected)]
+    #[case(200, Outcome::Mismatch)]
+    #[case(206, Outcome::Mismatch)]
+    fn test_classify(#[case] status: u16, #[case] outcome: Outcome) {
+        let resp = ConditionalResponse {
+            status,
+            etag: None,
+            ttfb: Duration::ZERO,
+        };
+        assert_eq!(classify(&resp), outcome);
+    }
+}
@@ -0,0 +1,455 @@
+use crate::client::{Client, UncheckedResponse};
+use crate::table::{Align, Table};
+use crate::types::is_self_href;
+use crate::writebench::join_url;
+use crate::xml::{parse_propnames, parse_response_summaries, XmlOptions};
+use bytes::Bytes;
+use indoc::indoc;
+use std::fmt;
+use url::Url;
+
+static PROP_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <propfind xmlns="DAV:">
+        <prop>
+            <resourcetype/>
+        </prop>
+    </propfind>
+"#};
+
+static PROPNAME_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <propfind xmlns="DAV:">
+        <propname/>
+    </propfind>
+"#};
+
+static ALLPROP_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <propfind xmlns="DAV:">
+        <allprop/>
+    </propfind>
+"#};
+
+static UNKNOWN_PROP_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <propfind xmlns="DAV:" xmlns:B="urn:x-batchdav:conformance">
+        <prop>
+            <resourcetype/>
+            <B:no-such-property/>
+        </prop>
+    </propfind>
+"#};
+
+static EXTENSION_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <D:propfind xmlns:D="DAV:" xmlns:B="urn:x-batchdav:conformance">
+        <B:extension>ignore me</B:extension>
+        <D:prop>
+            <D:resourcetype/>
+        </D:prop>
+    </D:propfind>
+"#};
+
+static MALFORMED_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <propfind xmlns="DAV:">
+        <prop>
+            <resourcetype/>
+"#};
+
+static WRONG_ROOT_BODY: &str = indoc! {r#"
+    <?xml version="1.0" encoding="utf-8"?>
+    <propertyupdate xmlns="DAV:">
+        <set><prop><displayname>x</displayname></prop></set>
+    </propertyupdate>
+"#};
+
+/// Name of a member of the test collection that is assumed not to exist
+static MISSING_NAME: &str = "batchdav-conformance-no-such-resource/";
+
+/// A single request to make against the test collection along with the
+/// expected response
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Check {
+    name: &'static str,
+    /// Whether to make the request to a nonexistent member of the test
+    /// collection rather than the collection itself
+    missing: bool,
+    depth: Option<&'static str>,
+    body: Option<&'static str>,
+    expect: Expect,
+}
+
+static CHECKS: &[Check] = &[
+    Check {
+        name: "depth-0",
+        missing: false,
+        depth: Some("0"),
+        body: Some(PROP_BODY),
+        expect: Expect::SelfOnly,
+    },
+    Check {
+        name: "depth-1",
+        missing: false,
+        depth: Some("1"),
+        body: Some(PROP_BODY),
+        expect: Expect::Listing,
+    },
+    Check {
+        name: "depth-infinity",
+        missing: false,
+        depth: Some("infinity"),
+        body: Some(PROP_BODY),
+        expect: Expect::ListingOrForbidden,
+    },
+    Check {
+        name: "no-depth",
+        missing: false,
+        depth: None,
+        body: Some(PROP_BODY),
+        expect: Expect::ListingOrForbidden,
+    },
+    Check {
+        name: "invalid-depth",
+        missing: false,
+        depth: Some("2"),
+        body: Some(PROP_BODY),
+        expect: Expect::Status(&[400]),
+    },
+    Check {
+        name: "propname",
+        missing: false,
+        depth: Some("1"),
+        body: Some(PROPNAME_BODY),
+        expect: Expect::Propnames,
+    },
+    Check {
+        name: "allprop",
+        missing: false,
+        depth: Some("0"),
+        body: Some(ALLPROP_BODY),
+        expect: Expect::SelfOnly,
+    },
+    Check {
+        name: "empty-body",
+        missing: false,
+        depth: Some("0"),
+        body: None,
+        expect: Expect::SelfOnly,
+    },
+    Check {
+        name: "unknown-property",
+        missing: false,
+        depth: Some("0"),
+        body: Some(UNKNOWN_PROP_BODY),
+        expect: Expect::MissingProp,
+    },
+    Check {
+        name: "extension-element",
+        missing: false,
+        depth: Some("0"),
+        body: Some(EXTENSION_BODY),
+        expect: Expect::SelfOnly,
+    },
+    Check {
+        name: "malformed-body",
+        missing: false,
+        depth: Some("0"),
+        body: Some(MALFORMED_BODY),
+        expect: Expect::Status(&[400]),
+    },
+    Check {
+        name: "wrong-root-element",
+        missing: false,
+        depth: Some("0"),
+        body: Some(WRONG_ROOT_BODY),
+        expect: Expect::Status(&[400, 422]),
+    },
+    Check {
+        name: "missing-resource",
+        missing: true,
+        depth: Some("0"),
+        body: Some(PROP_BODY),
+        expect: Expect::Status(&[404]),
+    },
+];
+
+/// The response expected for a check
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Expect {
+    /// A multistatus response listing only the collection itself
+    SelfOnly,
+    /// A multistatus response listing the collection (and any members)
+    Listing,
+    /// A multistatus response listing the collection, or a 403 refusal (as
+    /// servers may decline `Depth: infinity` requests)
+    ListingOrForbidden,
+    /// A `<propname/>` multistatus response in which the collection defines
+    /// `resourcetype`
+    Propnames,
+    /// A multistatus response listing the collection with a 404 propstat
+    MissingProp,
+    /// A response with one of the given statuses
+    Status(&'static [u16]),
+}
+
+impl fmt::Display for Expect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expect::SelfOnly => write!(f, "207, self only"),
+            Expect::Listing => write!(f, "207"),
+            Expect::ListingOrForbidden => write!(f, "207 or 403"),
+            Expect::Propnames => write!(f, "207 with names"),
+            Expect::MissingProp => write!(f, "207 with 404 propstat"),
+            Expect::Status(statuses) => {
+                for (i, s) in statuses.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " or ")?;
+                    }
+                    write!(f, "{s}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The result of a single check
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Outcome {
+    pub(crate) passed: bool,
+    /// A description of the response received
+    pub(crate) detail: String,
+}
+
+impl Outcome {
+    fn pass<S: Into<String>>(detail: S) -> Outcome {
+        Outcome {
+            passed: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn fail<S: Into<String>>(detail: S) -> Outcome {
+        Outcome {
+            passed: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Make each conformance check's request against the collection at `url`
+/// and compare the responses against what RFC 4918 prescribes
+pub(crate) async fn check_conformance(client: Client, url: Url) -> ConformanceReport {
+    let xml_options = client.options().xml_options;
+    let missing_url = join_url(&url, MISSING_NAME);
+    let mut results = Vec::with_capacity(CHECKS.len());
+    for check in CHECKS {
+        let target = if check.missing { &missing_url } else { &url };
+        let outcome = match client
+            .propfind_unchecked(
+                target,
+                check.depth,
+                check.body.map(|s| Bytes::from_static(s.as_bytes())),
+            )
+            .await
+        {
+            Ok(resp) => evaluate(check.expect, &url, resp, xml_options),
+            Err(e) => Outcome::fail(format!("request failed: {e}")),
+        };
+        results.push(CheckResult {
+            name: check.name,
+            expected: check.expect.to_string(),
+            outcome,
+        });
+    }
+    ConformanceReport { url, results }
+}
+
+/// Determine whether `resp` is the response to a request for the collection
+/// at `url` that `expect` calls for
+fn evaluate(expect: Expect, url: &Url, resp: UncheckedResponse, options: XmlOptions) -> Outcome {
+    let UncheckedResponse {
+        status,
+        charset,
+        body,
+    } = resp;
+    if let Expect::Status(statuses) = expect {
+        return if statuses.contains(&status) {
+            Outcome::pass(status.to_string())
+        } else {
+            Outcome::fail(format!("got {status}"))
+        };
+    }
+    if expect == Expect::ListingOrForbidden && status == 403 {
+        return Outcome::pass("403 (refused)");
+    }
+    if status != 207 {
+        return Outcome::fail(format!("got {status}"));
+    }
+    let is_self = |href: &str| is_self_href(url, href);
+    if expect == Expect::Propnames {
+        return match parse_propnames(body, charset, options) {
+            Ok(entries) => {
+                let defines_resourcetype = entries.iter().any(|(href, names)| {
+                    is_self(href)
+                        && names
+                            .iter()
+                            .any(|n| n.namespace == "DAV:" && n.name == "resourcetype")
+                });
+                if defines_resourcetype {
+                    Outcome::pass(format!("207 with {} responses", entries.len()))
+                } else {
+                    Outcome::fail("collection's resourcetype not listed")
+                }
+            }
+            Err(e) => Outcome::fail(format!("invalid multistatus: {e}")),
+        };
+    }
+    let summaries = match parse_response_summaries(body, charset, options) {
+        Ok(summaries) => summaries,
+        Err(e) => return Outcome::fail(format!("invalid multistatus: {e}")),
+    };
+    let Some(this) = summaries.iter().find(|r| is_self(&r.href)) else {
+        return Outcome::fail("collection not listed");
+    };
+    match expect {
+        Expect::SelfOnly if summaries.len() > 1 => {
+            Outcome::fail(format!("207 with {} responses", summaries.len()))
+        }
+        Expect::MissingProp if !this.propstat_statuses.contains(&404) => {
+            Outcome::fail("no 404 propstat")
+        }
+        _ => Outcome::pass(format!("207 with {} responses", summaries.len())),
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CheckResult {
+    pub(crate) name: &'static str,
+    /// A description of the expected response
+    pub(crate) expected: String,
+    pub(crate) outcome: Outcome,
+}
+
+/// The results of running the conformance checks against a collection
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ConformanceReport {
+    pub(crate) url: Url,
+    pub(crate) results: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    /// The number of checks that failed
+    pub(crate) fn failures(&self) -> usize {
+        self.results.iter().filter(|r| !r.outcome.passed).count()
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Conformance checks against {}:", self.url)?;
+        let mut table = Table::new()
+            .column("Check", Align::Left)
+            .column("Expected", Align::Left)
+            .column("Result", Align::Left)
+            .column("Details", Align::Left);
+        for r in &self.results {
+            table.push_row(vec![
+                r.name.to_owned(),
+                r.expected.clone(),
+                String::from(if r.outcome.passed { "PASS" } else { "FAIL" }),
+                r.outcome.detail.clone(),
+            ]);
+        }
+        write!(f, "{table}")?;
+        writeln!(
+            f,
+            "{} of {} checks passed",
+            self.results.len().saturating_sub(self.failures()),
+            self.results.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn response(status: u16, body: &'static str) -> UncheckedResponse {
+        UncheckedResponse {
+            status,
+            charset: None,
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    static SELF_ONLY: &str = indoc! {r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <multistatus xmlns="DAV:">
+            <response>
+                <href>/dav/test/</href>
+                <propstat>
+                    <prop><resourcetype><collection/></resourcetype></prop>
+                    <status>HTTP/1.1 200 OK</status>
+                </propstat>
+                <propstat>
+                    <prop><no-such-property xmlns="urn:x-batchdav:conformance"/></prop>
+                    <status>HTTP/1.1 404 Not Found</status>
+                </propstat>
+            </response>
+        </multistatus>
+    "#};
+
+    static WITH_MEMBER: &str = indoc! {r#"
+        <?xml version="1.0" encoding="utf-8"?>
+        <multistatus xmlns="DAV:">
+            <response>
+                <href>/dav/test</href>
+                <propstat>
+                    <prop><resourcetype><collection/></resourcetype></prop>
+                    <status>HTTP/1.1 200 OK</status>
+                </propstat>
+            </response>
+            <response>
+                <href>/dav/test/file.txt</href>
+                <propstat>
+                    <prop><resourcetype/></prop>
+                    <status>HTTP/1.1 200 OK</status>
+                </propstat>
+            </response>
+        </multistatus>
+    "#};
+
+    #[rstest]
+    #[case(Expect::SelfOnly, 207, SELF_ONLY, true)]
+    #[case(Expect::SelfOnly, 207, WITH_MEMBER, false)]
+    #[case(Expect::Listing, 207, WITH_MEMBER, true)]
+    #[case(Expect::Listing, 200, WITH_MEMBER, false)]
+    #[case(Expect::ListingOrForbidden, 403, "", true)]
+    #[case(Expect::ListingOrForbidden, 207, "<html>", false)]
+    #[case(Expect::MissingProp, 207, SELF_ONLY, true)]
+    #[case(Expect::MissingProp, 207, WITH_MEMBER, false)]
+    #[case(Expect::Propnames, 207, SELF_ONLY, true)]
+    #[case(Expect::Propnames, 207, "<multistatus xmlns=\"DAV:\"/>", false)]
+    #[case(Expect::Status(&[400, 422]), 422, "", true)]
+    #[case(Expect::Status(&[400]), 207, SELF_ONLY, false)]
+    fn test_evaluate(
+        #[case] expect: Expect,
+        #[case] status: u16,
+        #[case] body: &'static str,
+        #[case] passed: bool,
+    ) {
+        let url = Url::parse("https://example.com/dav/test/").unwrap();
+        let outcome = evaluate(expect, &url, response(status, body), XmlOptions::default());
+        assert_eq!(outcome.passed, passed, "{outcome:?}");
+    }
+
+    #[test]
+    fn test_expect_display() {
+        assert_eq!(Expect::Status(&[400, 422]).to_string(), "400 or 422");
+        assert_eq!(Expect::ListingOrForbidden.to_string(), "207 or 403");
+    }
+}
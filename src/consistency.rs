@@ -0,0 +1,336 @@
+use crate::traverse::TraversalReport;
+use crate::types::path_order_key;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use url::Url;
+
+/// Tracks which resources were found by each of a series of traversals of
+/// the same hierarchy in order to detect resources that appear in some
+/// traversals but not others
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ConsistencyTracker {
+    traversals: usize,
+    sightings: BTreeMap<Url, Sightings>,
+    /// The number of resources found by the first traversal
+    first_resources: usize,
+}
+
+/// How often a resource was found by the traversals recorded by a
+/// `ConsistencyTracker`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct Sightings {
+    /// The number of traversals in which the resource was found
+    traversals: usize,
+    /// Whether the resource was found by the first traversal
+    first: bool,
+}
+
+impl ConsistencyTracker {
+    pub(crate) fn new() -> ConsistencyTracker {
+        ConsistencyTracker::default()
+    }
+
+    /// Record the set of resources found by a traversal, returning whether
+    /// it is the same as the set found by the first traversal
+    pub(crate) fn record(&mut self, urls: &BTreeSet<Url>) -> bool {
+        let first = self.traversals == 0;
+        self.traversals = self.traversals.saturating_add(1);
+        if first {
+            self.first_resources = urls.len();
+        }
+        let mut shared = 0usize;
+        for url in urls {
+            let s = self.sightings.entry(url.clone()).or_insert(Sightings {
+                traversals: 0,
+                first,
+            });
+            s.traversals = s.traversals.saturating_add(1);
+            if s.first {
+                shared = shared.saturating_add(1);
+            }
+        }
+        shared == self.first_resources && urls.len() == self.first_resources
+    }
+
+    pub(crate) fn report(&self) -> ConsistencyReport {
+        let mut flapping = self
+            .sightings
+            .iter()
+            .filter(|&(_, s)| s.traversals < self.traversals)
+            .map(|(url, s)| (url.clone(), s.traversals))
+            .collect::<Vec<_>>();
+        flapping.sort_by_cached_key(|(url, _)| path_order_key(url));
+        ConsistencyReport {
+            traversals: self.traversals,
+            resources: self.sightings.len(),
+            flapping,
+        }
+    }
+}
+
+/// A summary of the differences between the sets of resources found by a
+/// series of traversals
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ConsistencyReport {
+    pub(crate) traversals: usize,
+    /// The number of distinct resources found across all traversals
+    pub(crate) resources: usize,
+    /// The resources that were not found by every traversal, in canonical
+    /// path order, each paired with the number of traversals that found it
+    pub(crate) flapping: Vec<(Url, usize)>,
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Consistency across {} traversals: {} distinct resources, {} flapping",
+            self.traversals,
+            self.resources,
+            self.flapping.len()
+        )?;
+        for (url, n) in &self.flapping {
+            writeln!(f, "  {url}: found in {n} of {} traversals", self.traversals)?;
+        }
+        Ok(())
+    }
+}
+
+/// The amount of work done by a traversal: the numbers of collections &
+/// files it found
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct InventoryCounts {
+    pub(crate) directories: usize,
+    pub(crate) files: usize,
+}
+
+impl InventoryCounts {
+    pub(crate) fn new(report: &TraversalReport) -> InventoryCounts {
+        InventoryCounts {
+            directories: report.directories,
+            files: report.files,
+        }
+    }
+}
+
+impl fmt::Display for InventoryCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} and {} {}",
+            self.directories,
+            plural(self.directories, "directory", "directories"),
+            self.files,
+            plural(self.files, "file", "files"),
+        )
+    }
+}
+
+/// Return `one` if `n` is 1 and `many` otherwise
+fn plural(n: usize, one: &'static str, many: &'static str) -> &'static str {
+    if n == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// Checks that every traversal in a batch found the same inventory as the
+/// first one, so that timings of traversals that did different amounts of
+/// work are not compared unawares
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct InventoryCheck {
+    /// The inventory found by the first traversal
+    reference: Option<InventoryCounts>,
+    /// Tracks the sets of URLs found by the traversals, if they are also to
+    /// be compared
+    urls: Option<ConsistencyTracker>,
+    traversals: usize,
+    mismatches: Vec<InventoryMismatch>,
+}
+
+impl InventoryCheck {
+    /// Create a new check, which also compares the sets of URLs found by the
+    /// traversals if `compare_urls` is true
+    pub(crate) fn new(compare_urls: bool) -> InventoryCheck {
+        InventoryCheck {
+            urls: compare_urls.then(ConsistencyTracker::new),
+            ..InventoryCheck::default()
+        }
+    }
+
+    /// Record the inventory found by the `run`-th traversal with `workers`
+    /// workers, including the set of URLs it found if they were collected,
+    /// returning the mismatch if it differs from the first traversal's
+    pub(crate) fn record(
+        &mut self,
+        workers: usize,
+        run: usize,
+        counts: InventoryCounts,
+        urls: Option<&BTreeSet<Url>>,
+    ) -> Option<&InventoryMismatch> {
+        self.traversals = self.traversals.saturating_add(1);
+        let reference = *self.reference.get_or_insert(counts);
+        let same_urls = match (self.urls.as_mut(), urls) {
+            (Some(tracker), Some(urls)) => tracker.record(urls),
+            _ => true,
+        };
+        if counts == reference && same_urls {
+            return None;
+        }
+        self.mismatches.push(InventoryMismatch {
+            workers,
+            run,
+            found: counts,
+            expected: reference,
+        });
+        self.mismatches.last()
+    }
+
+    pub(crate) fn mismatches(&self) -> &[InventoryMismatch] {
+        &self.mismatches
+    }
+}
+
+impl fmt::Display for InventoryCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(reference) = self.reference else {
+            return Ok(());
+        };
+        writeln!(
+            f,
+            "Inventory check: {} of {} {} differed from the first traversal's {reference}",
+            self.mismatches.len(),
+            self.traversals,
+            plural(self.traversals, "traversal", "traversals"),
+        )?;
+        for m in &self.mismatches {
+            writeln!(f, "  {m}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A traversal that found a different inventory than the first traversal in
+/// its batch
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct InventoryMismatch {
+    pub(crate) workers: usize,
+    pub(crate) run: usize,
+    pub(crate) found: InventoryCounts,
+    pub(crate) expected: InventoryCounts,
+}
+
+impl fmt::Display for InventoryMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "workers = {}, run {}: found {}",
+            self.workers, self.run, self.found
+        )?;
+        if self.found.directories == self.expected.directories
+            && self.found.files == self.expected.files
+        {
+            write!(f, " but a different set of URLs")?;
+        } else {
+            write!(f, " instead of {}", self.expected)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(paths: &[&str]) -> BTreeSet<Url> {
+        paths
+            .iter()
+            .map(|p| Url::parse("https://example.com/").unwrap().join(p).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_consistency_tracker() {
+        let mut tracker = ConsistencyTracker::new();
+        tracker.record(&urls(&["/", "/a/", "/a/x.txt", "/b.txt"]));
+        tracker.record(&urls(&["/", "/a/", "/b.txt", "/c.txt"]));
+        tracker.record(&urls(&["/", "/a/", "/a/x.txt", "/b.txt"]));
+        let report = tracker.report();
+        assert_eq!(report.traversals, 3);
+        assert_eq!(report.resources, 5);
+        assert_eq!(
+            report.flapping,
+            [
+                (Url::parse("https://example.com/a/x.txt").unwrap(), 2),
+                (Url::parse("https://example.com/c.txt").unwrap(), 1),
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            concat!(
+                "Consistency across 3 traversals: 5 distinct resources, 2 flapping\n",
+                "  https://example.com/a/x.txt: found in 2 of 3 traversals\n",
+                "  https://example.com/c.txt: found in 1 of 3 traversals\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_inventory_check() {
+        let counts = |directories, files| InventoryCounts { directories, files };
+        let mut check = InventoryCheck::new(true);
+        let mut record = |workers, run, directories, files, paths: &[&str]| {
+            check
+                .record(workers, run, counts(directories, files), Some(&urls(paths)))
+                .map(ToString::to_string)
+        };
+        assert_eq!(record(1, 1, 2, 2, &["/", "/a/", "/a/x", "/y"]), None);
+        assert_eq!(record(1, 2, 2, 2, &["/", "/a/", "/a/x", "/y"]), None);
+        assert_eq!(
+            record(2, 1, 2, 1, &["/", "/a/", "/y"]).as_deref(),
+            Some("workers = 2, run 1: found 2 directories and 1 file instead of 2 directories and 2 files")
+        );
+        assert_eq!(
+            record(2, 2, 2, 2, &["/", "/a/", "/a/z", "/y"]).as_deref(),
+            Some("workers = 2, run 2: found 2 directories and 2 files but a different set of URLs")
+        );
+        assert_eq!(
+            record(2, 3, 2, 2, &["/", "/a/", "/a/x", "/y"]),
+            None,
+            "an earlier mismatch should not affect later traversals"
+        );
+        assert_eq!(check.mismatches().len(), 2);
+        assert!(check
+            .to_string()
+            .starts_with("Inventory check: 2 of 5 traversals differed from the first traversal's 2 directories and 2 files\n"));
+    }
+
+    #[test]
+    fn test_inventory_counts_display() {
+        assert_eq!(
+            InventoryCounts {
+                directories: 1,
+                files: 1
+            }
+            .to_string(),
+            "1 directory and 1 file"
+        );
+    }
+
+    #[test]
+    fn test_consistent() {
+        let mut tracker = ConsistencyTracker::new();
+        assert!(
+            tracker.record(&urls(&["/", "/a.txt"])),
+            "first traversal should match itself"
+        );
+        assert!(
+            tracker.record(&urls(&["/", "/a.txt"])),
+            "identical traversal should match the first"
+        );
+        let report = tracker.report();
+        assert!(report.flapping.is_empty());
+        assert_eq!(report.resources, 2);
+    }
+}
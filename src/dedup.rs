@@ -0,0 +1,148 @@
+use crate::backend::TraversalBackend;
+use crate::client::{DirectoryResponse, FileResponse, RequestError};
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use url::Url;
+
+type SharedResult<T> = Shared<BoxFuture<'static, Result<T, Arc<RequestError>>>>;
+
+/// The requests in flight for a given type of operation, keyed by the
+/// operation's arguments
+type InFlight<K, T> = Mutex<HashMap<K, SharedResult<T>>>;
+
+/// A `TraversalBackend` wrapper that coalesces concurrent identical requests,
+/// so that a request for a URL that is already being requested with the same
+/// parameters waits for & shares the result of the request in flight rather
+/// than making another
+pub(crate) struct Deduplicator {
+    inner: Arc<dyn TraversalBackend>,
+    listings: InFlight<Url, DirectoryResponse>,
+    trees: InFlight<Url, DirectoryResponse>,
+    probes: InFlight<(Url, Option<usize>), FileResponse>,
+    /// Number of requests that shared the result of a request in flight
+    coalesced: AtomicUsize,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(inner: Arc<dyn TraversalBackend>) -> Deduplicator {
+        Deduplicator {
+            inner,
+            listings: Mutex::new(HashMap::new()),
+            trees: Mutex::new(HashMap::new()),
+            probes: Mutex::new(HashMap::new()),
+            coalesced: AtomicUsize::new(0),
+        }
+    }
+
+    /// Return the number of requests so far that shared the result of an
+    /// identical request in flight instead of being made themselves
+    pub(crate) fn coalesced(&self) -> usize {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Run the operation for `key` produced by `make`, or wait for the same
+    /// operation if one is already in flight
+    async fn run<K, T, F, Fut>(
+        &self,
+        table: &InFlight<K, T>,
+        key: K,
+        make: F,
+    ) -> Result<T, RequestError>
+    where
+        K: Clone + Eq + Hash,
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce(Arc<dyn TraversalBackend>) -> Fut,
+        Fut: Future<Output = Result<T, RequestError>> + Send + 'static,
+    {
+        let fut = {
+            let mut table = table.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(fut) = table.get(&key) {
+                self.coalesced.fetch_add(1, Ordering::Relaxed);
+                fut.clone()
+            } else {
+                let fut = make(Arc::clone(&self.inner))
+                    .map(|r| r.map_err(Arc::new))
+                    .boxed()
+                    .shared();
+                table.insert(key.clone(), fut.clone());
+                fut
+            }
+        };
+        let r = fut.clone().await;
+        {
+            // Whichever sharer finishes first removes the entry, but only if
+            // it hasn't already been replaced by a later request
+            let mut table = table.lock().unwrap_or_else(PoisonError::into_inner);
+            if table.get(&key).is_some_and(|f| f.ptr_eq(&fut)) {
+                table.remove(&key);
+            }
+        }
+        drop(fut);
+        r.map_err(|e| Arc::try_unwrap(e).unwrap_or_else(RequestError::Shared))
+    }
+}
+
+impl TraversalBackend for Deduplicator {
+    fn list_children(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        let key = url.clone();
+        self.run(&self.listings, key, |inner| async move {
+            inner.list_children(url).await
+        })
+        .boxed()
+    }
+
+    fn list_tree(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        let key = url.clone();
+        self.run(&self.trees, key, |inner| async move {
+            inner.list_tree(url).await
+        })
+        .boxed()
+    }
+
+    fn probe_file(
+        &self,
+        url: Url,
+        follow_redirects: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileResponse, RequestError>> {
+        let key = (url.clone(), follow_redirects);
+        self.run(&self.probes, key, move |inner| async move {
+            inner.probe_file(url, follow_redirects).await
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulate::{Simulation, SimulationSpec};
+
+    #[tokio::test]
+    async fn test_coalesce_concurrent_requests() {
+        let base_url = Url::parse("http://sim.example/").unwrap();
+        let spec = "fanout=2,depth=1,files=2,latency=20ms"
+            .parse::<SimulationSpec>()
+            .unwrap();
+        let dedup = Deduplicator::new(Arc::new(Simulation::new(spec, base_url.clone(), false)));
+        let (a, b) = tokio::join!(
+            dedup.list_children(base_url.clone()),
+            dedup.list_children(base_url.clone()),
+        );
+        assert_eq!(a.unwrap(), b.unwrap());
+        assert_eq!(dedup.coalesced(), 1);
+        // Once the request has completed, it is made anew
+        dedup.list_children(base_url.clone()).await.unwrap();
+        assert_eq!(dedup.coalesced(), 1);
+        let file = base_url.join("f0.dat").unwrap();
+        let (a, b) = tokio::join!(
+            dedup.probe_file(file.clone(), None),
+            dedup.probe_file(file.clone(), Some(5)),
+        );
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(dedup.coalesced(), 1);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::stats::{mann_whitney_u, WorkerSamples, SIGNIFICANCE_LEVEL};
+use crate::table::{Align, Table};
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// A comparison of the traversal times recorded in two `batch` reports for
+/// each number of workers present in both
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ReportDiff {
+    pub(crate) workers: Vec<WorkerDiff>,
+    /// Numbers of workers present only in the old report
+    pub(crate) old_only: Vec<usize>,
+    /// Numbers of workers present only in the new report
+    pub(crate) new_only: Vec<usize>,
+}
+
+/// The change in traversal times for a given number of workers.  Times are in
+/// seconds.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct WorkerDiff {
+    pub(crate) workers: usize,
+    pub(crate) old_samples: usize,
+    pub(crate) new_samples: usize,
+    pub(crate) old_mean: f64,
+    pub(crate) new_mean: f64,
+    /// The new mean minus the old mean
+    pub(crate) delta: f64,
+    /// `delta` as a percentage of the old mean
+    pub(crate) percent_change: f64,
+    /// The two-sided p-value of a Mann-Whitney U test comparing the old &
+    /// new traversal times
+    pub(crate) p_value: Option<f64>,
+    pub(crate) significant: Option<bool>,
+}
+
+impl ReportDiff {
+    pub(crate) fn new(old: &WorkerSamples, new: &WorkerSamples) -> ReportDiff {
+        let old_counts = old.counts().into_keys().collect::<BTreeSet<_>>();
+        let new_counts = new.counts().into_keys().collect::<BTreeSet<_>>();
+        let new_summaries = new.summarize();
+        let workers = old
+            .summarize()
+            .into_iter()
+            .filter_map(|o| {
+                let n = new_summaries.iter().find(|n| n.workers == o.workers)?;
+                let delta = n.time_mean - o.time_mean;
+                let p_value = mann_whitney_u(&old.times(o.workers), &new.times(o.workers));
+                Some(WorkerDiff {
+                    workers: o.workers,
+                    old_samples: o.samples,
+                    new_samples: n.samples,
+                    old_mean: o.time_mean,
+                    new_mean: n.time_mean,
+                    delta,
+                    percent_change: delta / o.time_mean * 100.0,
+                    p_value,
+                    significant: p_value.map(|p| p < SIGNIFICANCE_LEVEL),
+                })
+            })
+            .collect();
+        ReportDiff {
+            workers,
+            old_only: old_counts.difference(&new_counts).copied().collect(),
+            new_only: new_counts.difference(&old_counts).copied().collect(),
+        }
+    }
+
+    /// The numbers of workers whose traversals became significantly slower
+    /// by more than `threshold` percent
+    pub(crate) fn regressions(&self, threshold: f64) -> Vec<WorkerDiff> {
+        self.workers
+            .iter()
+            .filter(|d| d.significant == Some(true) && d.percent_change > threshold)
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        let mut fp = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create diff file {}", path.display()))?,
+        );
+        serde_json::to_writer_pretty(&mut fp, self).context("failed to write diff file")?;
+        fp.write_all(b"\n").context("failed to write diff file")?;
+        fp.flush().context("failed to flush diff file")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReportDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Table::new()
+            .column("Workers", Align::Right)
+            .column("Old Mean", Align::Right)
+            .column("New Mean", Align::Right)
+            .column("Delta", Align::Right)
+            .column("Change", Align::Right)
+            .column("p", Align::Left);
+        for d in &self.workers {
+            table.push_row(vec![
+                d.workers.to_string(),
+                format!("{:.3} s", d.old_mean),
+                format!("{:.3} s", d.new_mean),
+                format!("{:+.3} s", d.delta),
+                format!("{:+.1}%", d.percent_change),
+                match (d.p_value, d.significant) {
+                    (Some(p), Some(true)) => format!("{p:.3} (significant)"),
+                    (Some(p), _) => format!("{p:.3}"),
+                    (None, _) => String::from("-"),
+                },
+            ]);
+        }
+        write!(f, "{table}")?;
+        for (workers, which) in [(&self.old_only, "old"), (&self.new_only, "new")] {
+            if !workers.is_empty() {
+                writeln!(
+                    f,
+                    "Only in {which} report: workers = {}",
+                    workers
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Sample;
+    use std::time::Duration;
+
+    fn samples(data: &[(usize, &[u64])]) -> WorkerSamples {
+        let mut samples = WorkerSamples::new();
+        for &(workers, times) in data {
+            for &ms in times {
+                samples.push(
+                    workers,
+                    Sample {
+                        requests: 100,
+                        elapsed: Duration::from_millis(ms),
+                        utilization: None,
+                    },
+                );
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn test_report_diff() {
+        let old = samples(&[(1, &[1000, 1100, 1200, 1050, 1150]), (2, &[500, 550])]);
+        let new = samples(&[(1, &[800, 850, 900, 820, 880]), (4, &[300])]);
+        let diff = ReportDiff::new(&old, &new);
+        assert_eq!(diff.old_only, [2]);
+        assert_eq!(diff.new_only, [4]);
+        assert_eq!(diff.workers.len(), 1);
+        let d = &diff.workers[0];
+        assert_eq!((d.workers, d.old_samples, d.new_samples), (1, 5, 5));
+        assert!((d.delta - -0.25).abs() < 1e-9);
+        assert!((d.percent_change - -22.727).abs() < 1e-3);
+        assert!((d.p_value.unwrap() - 0.0122).abs() < 1e-3);
+        assert_eq!(d.significant, Some(true));
+    }
+
+    #[test]
+    fn test_regressions() {
+        let old = samples(&[
+            (1, &[800, 850, 900, 820, 880]),
+            (2, &[500, 510, 490, 505, 495]),
+        ]);
+        let new = samples(&[
+            (1, &[1000, 1100, 1200, 1050, 1150]),
+            (2, &[520, 530, 510, 525, 515]),
+        ]);
+        let diff = ReportDiff::new(&old, &new);
+        let regressions = diff.regressions(10.0);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].workers, 1);
+        assert!(diff.regressions(50.0).is_empty());
+        // Speedups are not regressions
+        assert!(ReportDiff::new(&new, &old).regressions(0.0).is_empty());
+    }
+}
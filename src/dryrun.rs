@@ -0,0 +1,162 @@
+use crate::show_duration::show_hms;
+use crate::table::{Align, Table};
+use std::fmt;
+use std::time::Duration;
+
+/// The traversals that a `batch` would perform, along with estimates of how
+/// long they would take based on a probe traversal
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BatchPlan {
+    pub(crate) workers: Vec<usize>,
+    /// The minimum & maximum number of traversals to perform for each number
+    /// of workers
+    pub(crate) traversals: (usize, usize),
+    /// The number of requests made by a single traversal
+    pub(crate) requests: usize,
+    /// The probe traversal, if the number of requests was measured by
+    /// traversing the hierarchy rather than read from a tree file
+    pub(crate) probe: Option<Probe>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Probe {
+    pub(crate) workers: usize,
+    pub(crate) elapsed: Duration,
+}
+
+impl BatchPlan {
+    /// Bounds on the time taken by a single traversal with `workers`
+    /// workers: between the probe's time (if changing the number of workers
+    /// makes no difference) and the probe's time scaled by the ratio of the
+    /// numbers of workers (if throughput scales linearly)
+    #[allow(clippy::cast_precision_loss)]
+    fn traversal_time(&self, workers: usize) -> Option<(f64, f64)> {
+        let probe = self.probe?;
+        let probed = probe.elapsed.as_secs_f64();
+        let scaled = probed * (probe.workers.max(1) as f64) / (workers.max(1) as f64);
+        Some((probed.min(scaled), probed.max(scaled)))
+    }
+
+    /// Bounds on the total time taken by the traversals for `workers`
+    /// workers
+    #[allow(clippy::cast_precision_loss)]
+    fn workers_time(&self, workers: usize) -> Option<(f64, f64)> {
+        let (lo, hi) = self.traversal_time(workers)?;
+        let (min, max) = self.traversals;
+        Some((lo * (min as f64), hi * (max as f64)))
+    }
+
+    fn total_traversals(&self) -> (usize, usize) {
+        let n = self.workers.len();
+        let (min, max) = self.traversals;
+        (min.saturating_mul(n), max.saturating_mul(n))
+    }
+
+    fn total_time(&self) -> Option<(f64, f64)> {
+        self.workers
+            .iter()
+            .map(|&w| self.workers_time(w))
+            .try_fold((0.0, 0.0), |(lo, hi), t| {
+                t.map(|(tlo, thi)| (lo + tlo, hi + thi))
+            })
+    }
+}
+
+impl fmt::Display for BatchPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.probe {
+            Some(probe) => writeln!(
+                f,
+                "Probe traversal with {} workers made {} requests in {:.3} s",
+                probe.workers,
+                self.requests,
+                probe.elapsed.as_secs_f64()
+            )?,
+            None => writeln!(f, "Tree file lists {} requests", self.requests)?,
+        }
+        let mut table = Table::new()
+            .column("Workers", Align::Right)
+            .column("Traversals", Align::Right)
+            .column("Requests", Align::Right)
+            .column("Estimated Time", Align::Right);
+        for &w in &self.workers {
+            table.push_row(vec![
+                w.to_string(),
+                show_range(self.traversals),
+                show_range((
+                    self.traversals.0.saturating_mul(self.requests),
+                    self.traversals.1.saturating_mul(self.requests),
+                )),
+                self.workers_time(w)
+                    .map_or_else(|| String::from("-"), show_time_range),
+            ]);
+        }
+        write!(f, "{table}")?;
+        let traversals = self.total_traversals();
+        write!(
+            f,
+            "Total: {} traversals, {} requests",
+            show_range(traversals),
+            show_range((
+                traversals.0.saturating_mul(self.requests),
+                traversals.1.saturating_mul(self.requests),
+            ))
+        )?;
+        match self.total_time() {
+            Some(t) => writeln!(f, ", estimated time {}", show_time_range(t)),
+            None => writeln!(f),
+        }
+    }
+}
+
+fn show_range((min, max): (usize, usize)) -> String {
+    if min == max {
+        min.to_string()
+    } else {
+        format!("{min}–{max}")
+    }
+}
+
+fn show_time_range((lo, hi): (f64, f64)) -> String {
+    let (lo, hi) = (show_hms(lo), show_hms(hi));
+    if lo == hi {
+        lo
+    } else {
+        format!("{lo} – {hi}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_plan() {
+        let plan = BatchPlan {
+            workers: vec![1, 2, 4],
+            traversals: (5, 10),
+            requests: 100,
+            probe: Some(Probe {
+                workers: 4,
+                elapsed: Duration::from_secs(2),
+            }),
+        };
+        assert_eq!(plan.traversal_time(4), Some((2.0, 2.0)));
+        assert_eq!(plan.traversal_time(1), Some((2.0, 8.0)));
+        assert_eq!(plan.workers_time(2), Some((10.0, 40.0)));
+        assert_eq!(plan.total_traversals(), (15, 30));
+        assert_eq!(plan.total_time(), Some((30.0, 140.0)));
+        assert!(plan.to_string().ends_with(
+            "Total: 15–30 traversals, 1500–3000 requests, estimated time 30.0s – 2m20s\n"
+        ));
+        let plan = BatchPlan {
+            traversals: (10, 10),
+            probe: None,
+            ..plan
+        };
+        assert_eq!(plan.total_time(), None);
+        assert!(plan
+            .to_string()
+            .ends_with("Total: 30 traversals, 3000 requests\n"));
+    }
+}
@@ -0,0 +1,112 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// The greatest percentage of failed requests tolerated among the most
+/// recent `window` requests of a traversal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ErrorRateLimit {
+    pub(crate) percent: f64,
+    pub(crate) window: usize,
+}
+
+/// Tracks the outcomes of the most recent requests of a traversal in order
+/// to detect when too many of them are failing
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ErrorRateWindow {
+    limit: ErrorRateLimit,
+    /// Whether each of the most recent requests failed, oldest first
+    outcomes: VecDeque<bool>,
+    /// Number of `true` values in `outcomes`
+    errors: usize,
+}
+
+impl ErrorRateWindow {
+    pub(crate) fn new(limit: ErrorRateLimit) -> ErrorRateWindow {
+        ErrorRateWindow {
+            limit,
+            outcomes: VecDeque::with_capacity(limit.window),
+            errors: 0,
+        }
+    }
+
+    /// Record the outcome of a request.  Returns a description of the
+    /// excessive error rate if the window is full and the percentage of
+    /// failed requests in it exceeds the limit.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn record(&mut self, failed: bool) -> Option<ErrorRateAbort> {
+        if self.limit.window == 0 {
+            return None;
+        }
+        if self.outcomes.len() == self.limit.window && self.outcomes.pop_front() == Some(true) {
+            self.errors = self.errors.saturating_sub(1);
+        }
+        self.outcomes.push_back(failed);
+        if failed {
+            self.errors = self.errors.saturating_add(1);
+        }
+        let rate = (self.errors as f64) * 100.0 / (self.limit.window as f64);
+        (self.outcomes.len() == self.limit.window && rate > self.limit.percent).then_some(
+            ErrorRateAbort {
+                max_error_rate: self.limit.percent,
+                window: self.limit.window,
+                errors: self.errors,
+                error_rate: rate,
+            },
+        )
+    }
+}
+
+/// The error rate that caused a traversal to be aborted under
+/// `--max-error-rate`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct ErrorRateAbort {
+    /// The configured limit, as a percentage
+    pub(crate) max_error_rate: f64,
+    /// The number of most recent requests over which the rate was measured
+    pub(crate) window: usize,
+    /// The number of those requests that failed
+    pub(crate) errors: usize,
+    /// `errors` as a percentage of `window`
+    pub(crate) error_rate: f64,
+}
+
+impl fmt::Display for ErrorRateAbort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of the last {} requests failed ({:.1}%), exceeding the maximum error rate of {}%",
+            self.errors, self.window, self.error_rate, self.max_error_rate
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_rate_window() {
+        let mut window = ErrorRateWindow::new(ErrorRateLimit {
+            percent: 50.0,
+            window: 4,
+        });
+        // Not judged until the window is full
+        assert_eq!(window.record(true), None);
+        assert_eq!(window.record(true), None);
+        assert_eq!(window.record(false), None);
+        // 2 of 4 is not more than 50%
+        assert_eq!(window.record(false), None);
+        // The oldest failure drops out of the window
+        assert_eq!(window.record(true), None);
+        assert_eq!(window.record(false), None);
+        assert_eq!(window.record(true), None);
+        let abort = window.record(true).unwrap();
+        assert_eq!((abort.errors, abort.window), (3, 4));
+        assert!((abort.error_rate - 75.0).abs() < 1e-9);
+        assert_eq!(
+            abort.to_string(),
+            "3 of the last 4 requests failed (75.0%), exceeding the maximum error rate of 50%"
+        );
+    }
+}
@@ -0,0 +1,455 @@
+use crate::show_duration::show_duration_as_seconds;
+use crate::stats::{WorkerSamples, WorkerSummary};
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// A single traversal sample in "tidy" (long) format
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct TidyRow {
+    pub(crate) workers: usize,
+    pub(crate) run: usize,
+    pub(crate) requests: usize,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) elapsed: Duration,
+}
+
+pub(crate) fn tidy_rows(samples: &WorkerSamples) -> Vec<TidyRow> {
+    samples
+        .iter()
+        .map(|(workers, run, s)| TidyRow {
+            workers,
+            run,
+            requests: s.requests,
+            elapsed: s.elapsed,
+        })
+        .collect()
+}
+
+/// Write tidy rows to `path` as either CSV or a JSON array, depending on
+/// whether the file extension is `.csv`
+pub(crate) fn write_tidy_file(path: &Path, rows: &[TidyRow]) -> anyhow::Result<()> {
+    let mut fp = BufWriter::new(File::create(path).context("failed to open tidy data outfile")?);
+    if path.extension().is_some_and(|ext| ext == "csv") {
+        writeln!(fp, "workers,run,requests,elapsed")?;
+        for r in rows {
+            writeln!(
+                fp,
+                "{},{},{},{}",
+                r.workers,
+                r.run,
+                r.requests,
+                show_duration_as_seconds(r.elapsed)
+            )?;
+        }
+    } else {
+        serde_json::to_writer_pretty(&mut fp, rows)?;
+        fp.write_all(b"\n")?;
+    }
+    fp.flush().context("failed to flush tidy data outfile")?;
+    Ok(())
+}
+
+/// Write a self-contained Vega-Lite specification plotting the elapsed time
+/// of each sample versus number of workers, overlaid with the per-workers
+/// mean
+pub(crate) fn write_vega_lite(path: &Path, rows: &[TidyRow]) -> anyhow::Result<()> {
+    let spec = vega_lite_spec(rows)?;
+    let mut fp = BufWriter::new(File::create(path).context("failed to open Vega-Lite outfile")?);
+    serde_json::to_writer_pretty(&mut fp, &spec)?;
+    fp.write_all(b"\n")?;
+    fp.flush().context("failed to flush Vega-Lite outfile")?;
+    Ok(())
+}
+
+/// A single result in the format consumed by github-action-benchmark's
+/// "customSmallerIsBetter" tool and similar continuous benchmarking services
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BenchmarkEntry {
+    pub(crate) name: String,
+    pub(crate) unit: &'static str,
+    pub(crate) value: f64,
+    /// The standard deviation of the value, formatted as "± N"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) range: Option<String>,
+}
+
+/// Return an entry giving the mean traversal time for each number of workers
+pub(crate) fn benchmark_entries(summaries: &[WorkerSummary]) -> Vec<BenchmarkEntry> {
+    summaries
+        .iter()
+        .map(|s| BenchmarkEntry {
+            name: format!("traversal time with {} workers", s.workers),
+            unit: "s",
+            value: s.time_mean,
+            range: s
+                .time_stddev
+                .is_finite()
+                .then(|| format!("± {}", s.time_stddev)),
+        })
+        .collect()
+}
+
+pub(crate) fn write_benchmark_json(path: &Path, entries: &[BenchmarkEntry]) -> anyhow::Result<()> {
+    let mut fp = BufWriter::new(File::create(path).context("failed to open benchmark outfile")?);
+    serde_json::to_writer_pretty(&mut fp, entries)?;
+    fp.write_all(b"\n")?;
+    fp.flush().context("failed to flush benchmark outfile")?;
+    Ok(())
+}
+
+fn vega_lite_spec(rows: &[TidyRow]) -> serde_json::Result<Value> {
+    let x = json!({"field": "workers", "type": "quantitative", "title": "Workers"});
+    Ok(json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "description": "batchdav traversal times by number of workers",
+        "data": {"values": serde_json::to_value(rows)?},
+        "layer": [
+            {
+                "mark": "point",
+                "encoding": {
+                    "x": x,
+                    "y": {"field": "elapsed", "type": "quantitative", "title": "Time (s)"},
+                },
+            },
+            {
+                "mark": "line",
+                "encoding": {
+                    "x": x,
+                    "y": {"aggregate": "mean", "field": "elapsed", "type": "quantitative"},
+                },
+            },
+        ],
+    }))
+}
+
+/// Number of percentile levels reported by `write_hgrm()` per halving of the
+/// distance to the 100th percentile, matching HdrHistogram's default
+const HGRM_TICKS_PER_HALF_DISTANCE: f64 = 5.0;
+
+/// Write the distribution of the request latencies `times` to `path` in the
+/// `.hgrm` percentile distribution format produced by HdrHistogram's
+/// `outputPercentileDistribution()`, with values in milliseconds
+pub(crate) fn write_hgrm(path: &Path, times: &[Duration]) -> anyhow::Result<()> {
+    let mut fp = BufWriter::new(File::create(path).context("failed to open .hgrm outfile")?);
+    write_hgrm_distribution(&mut fp, times).context("failed to write .hgrm outfile")?;
+    fp.flush().context("failed to flush .hgrm outfile")?;
+    Ok(())
+}
+
+/// Write the `.hgrm` percentile distribution of `times` to `fp`.  Unlike
+/// HdrHistogram, the reported values are exact rather than bucketed.
+#[allow(clippy::cast_precision_loss)]
+fn write_hgrm_distribution<W: Write>(fp: &mut W, times: &[Duration]) -> std::io::Result<()> {
+    let mut values = times
+        .iter()
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .collect::<Vec<_>>();
+    values.sort_by(f64::total_cmp);
+    let total = values.len();
+    writeln!(
+        fp,
+        "{:>12} {:>14} {:>10} {:>14}\n",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    )?;
+    let mut level = 0.0f64;
+    let mut i = 0;
+    while i < total {
+        let value = values[i];
+        // Advance past the last occurrence of `value`
+        let mut count = i.saturating_add(1);
+        while values
+            .get(count)
+            .is_some_and(|&v| v.total_cmp(&value).is_eq())
+        {
+            count = count.saturating_add(1);
+        }
+        let percentile = 100.0 * (count as f64) / (total as f64);
+        loop {
+            if percentile < level {
+                break;
+            }
+            writeln!(
+                fp,
+                "{value:12.3} {:2.12} {count:10} {:14.2}",
+                level / 100.0,
+                1.0 / (1.0 - level / 100.0)
+            )?;
+            let halvings = (100.0 / (100.0 - level)).log2().floor();
+            level += 100.0 / (HGRM_TICKS_PER_HALF_DISTANCE * (halvings + 1.0).exp2());
+            // Like HdrHistogram, the greatest value is reported only once
+            // before the final 100th percentile line
+            if count == total {
+                break;
+            }
+        }
+        if count == total {
+            writeln!(fp, "{value:12.3} {:2.12} {count:10}", 1.0)?;
+        }
+        i = count;
+    }
+    if let Some(&max) = values.last() {
+        let n = total as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let stddev = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+        writeln!(
+            fp,
+            "#[Mean    = {mean:12.3}, StdDeviation   = {stddev:12.3}]"
+        )?;
+        writeln!(fp, "#[Max     = {max:12.3}, Total count    = {total:12}]")?;
+        let max_micros = times
+            .iter()
+            .map(Duration::as_micros)
+            .max()
+            .unwrap_or_default();
+        writeln!(
+            fp,
+            "#[Buckets = {:12}, SubBuckets     = {:12}]",
+            hgrm_buckets(max_micros),
+            HGRM_SUB_BUCKETS
+        )?;
+    }
+    Ok(())
+}
+
+/// Number of sub-buckets per bucket in an HdrHistogram with three
+/// significant digits
+const HGRM_SUB_BUCKETS: u128 = 2048;
+
+/// Return the number of buckets that an HdrHistogram with three significant
+/// digits needs in order to record `max` (in microseconds), for the
+/// `#[Buckets ...]` footer line of a `.hgrm` file
+fn hgrm_buckets(max: u128) -> u32 {
+    let mut buckets = 1;
+    let mut untrackable = HGRM_SUB_BUCKETS;
+    while untrackable <= max {
+        untrackable = untrackable.saturating_mul(2);
+        buckets += 1;
+    }
+    buckets
+}
+
+/// Quote `s` for use as a CSV field if it contains any commas, double quotes,
+/// or line breaks
+pub(crate) fn csv_field(s: &str) -> Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", s.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+pub(crate) fn serialize_seconds<S: serde::Serializer>(
+    d: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(d.as_secs_f64())
+}
+
+pub(crate) fn serialize_optional_seconds<S: serde::Serializer>(
+    d: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match d {
+        Some(d) => serializer.serialize_f64(d.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Sample;
+    use regex::Regex;
+    use rstest::rstest;
+
+    #[test]
+    fn test_benchmark_entries() {
+        let mut samples = WorkerSamples::new();
+        for (workers, secs) in [(1, 4), (1, 6), (8, 1)] {
+            samples.push(
+                workers,
+                Sample {
+                    requests: 10,
+                    elapsed: Duration::from_secs(secs),
+                    utilization: None,
+                },
+            );
+        }
+        let entries = benchmark_entries(&samples.summarize());
+        assert_eq!(
+            serde_json::to_value(entries).unwrap(),
+            json!([
+                {
+                    "name": "traversal time with 1 workers",
+                    "unit": "s",
+                    "value": 5.0,
+                    "range": "± 1.4142135623730951",
+                },
+                {
+                    "name": "traversal time with 8 workers",
+                    "unit": "s",
+                    "value": 1.0,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("dataset A"), "dataset A");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_hgrm_distribution() {
+        let times = [4, 2, 1, 3].map(Duration::from_millis);
+        let mut buf = Vec::new();
+        write_hgrm_distribution(&mut buf, &times).unwrap();
+        // The percentile levels & layout match those of HdrHistogram's
+        // `outputPercentileDistribution(out, 5, 1000.0)` for the same values
+        // recorded in microseconds, except that HdrHistogram would report
+        // 3.001 & 4.001, the highest values equivalent to 3 ms & 4 ms in its
+        // buckets
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                "       Value     Percentile TotalCount 1/(1-Percentile)\n",
+                "\n",
+                "       1.000 0.000000000000          1           1.00\n",
+                "       1.000 0.100000000000          1           1.11\n",
+                "       1.000 0.200000000000          1           1.25\n",
+                "       2.000 0.300000000000          2           1.43\n",
+                "       2.000 0.400000000000          2           1.67\n",
+                "       2.000 0.500000000000          2           2.00\n",
+                "       3.000 0.550000000000          3           2.22\n",
+                "       3.000 0.600000000000          3           2.50\n",
+                "       3.000 0.650000000000          3           2.86\n",
+                "       3.000 0.700000000000          3           3.33\n",
+                "       3.000 0.750000000000          3           4.00\n",
+                "       4.000 0.775000000000          4           4.44\n",
+                "       4.000 1.000000000000          4\n",
+                "#[Mean    =        2.500, StdDeviation   =        1.118]\n",
+                "#[Max     =        4.000, Total count    =            4]\n",
+                "#[Buckets =            2, SubBuckets     =         2048]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_hgrm_format() {
+        // Check every line against the formats used by HdrHistogram's
+        // `outputPercentileDistribution()`: "%12.3f %2.12f %10d %14.2f" for
+        // each percentile level, "%12.3f %2.12f %10d" for the final 100th
+        // percentile line, and fixed-width footer lines
+        let times = (1..=1000u64)
+            .map(|i| Duration::from_micros(i * i % 7919 * 37))
+            .collect::<Vec<_>>();
+        let mut buf = Vec::new();
+        write_hgrm_distribution(&mut buf, &times).unwrap();
+        let hgrm = String::from_utf8(buf).unwrap();
+        let mut lines = hgrm.lines();
+        assert_eq!(
+            lines.next(),
+            Some("       Value     Percentile TotalCount 1/(1-Percentile)")
+        );
+        assert_eq!(lines.next(), Some(""));
+        let level = Regex::new(r"^ *\d+\.\d{3} [01]\.\d{12} +\d+ +\d+\.\d{2}$").unwrap();
+        let last = Regex::new(r"^ *\d+\.\d{3} 1\.0{12} +1000$").unwrap();
+        let footer = Regex::new(concat!(
+            r"^#\[Mean    = +\d+\.\d{3}, StdDeviation   = +\d+\.\d{3}\]\n",
+            r"#\[Max     = +\d+\.\d{3}, Total count    = +1000\]\n",
+            r"#\[Buckets = +\d+, SubBuckets     = +2048\]$",
+        ))
+        .unwrap();
+        let mut prev = (0.0, 0.0, 0u64);
+        let mut levels = 0;
+        for ln in lines.by_ref() {
+            if last.is_match(ln) {
+                break;
+            }
+            assert!(level.is_match(ln), "Bad percentile line: {ln:?}");
+            assert_eq!(
+                ln.len(),
+                12 + 1 + 14 + 1 + 10 + 1 + 14,
+                "Bad widths: {ln:?}"
+            );
+            let fields = ln.split_whitespace().collect::<Vec<_>>();
+            let cur: (f64, f64, u64) = (
+                fields[0].parse().unwrap(),
+                fields[1].parse().unwrap(),
+                fields[2].parse().unwrap(),
+            );
+            assert!(
+                cur.0 >= prev.0 && cur.1 >= prev.1 && cur.2 >= prev.2,
+                "Values, percentiles, & counts should not decrease: {ln:?}"
+            );
+            prev = cur;
+            levels += 1;
+        }
+        assert!(levels > 50, "Expected many percentile levels, got {levels}");
+        let rest = lines.collect::<Vec<_>>().join("\n");
+        assert!(footer.is_match(&rest), "Bad footer: {rest:?}");
+    }
+
+    #[rstest]
+    #[case(0, 1)]
+    #[case(2047, 1)]
+    #[case(2048, 2)]
+    #[case(4095, 2)]
+    #[case(4096, 3)]
+    #[case(60_000_000, 16)]
+    fn test_hgrm_buckets(#[case] max: u128, #[case] buckets: u32) {
+        assert_eq!(hgrm_buckets(max), buckets);
+    }
+
+    #[test]
+    fn test_hgrm_distribution_skips_levels() {
+        let times = [1, 1, 1, 10].map(Duration::from_millis);
+        let mut buf = Vec::new();
+        write_hgrm_distribution(&mut buf, &times).unwrap();
+        let values = String::from_utf8(buf)
+            .unwrap()
+            .lines()
+            .skip(2)
+            .filter(|ln| !ln.starts_with('#'))
+            .map(|ln| ln.split_whitespace().next().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        // Levels 0% through 75% are reached at 1 ms; the 10 ms value is
+        // reported once at 77.5% and once at 100%
+        assert_eq!(values.iter().filter(|v| *v == "1.000").count(), 11);
+        assert_eq!(values.iter().filter(|v| *v == "10.000").count(), 2);
+    }
+
+    #[test]
+    fn test_tidy_rows() {
+        let mut samples = WorkerSamples::new();
+        for (workers, secs) in [(5, 2), (1, 9), (5, 3)] {
+            samples.push(
+                workers,
+                Sample {
+                    requests: 42,
+                    elapsed: Duration::from_secs(secs),
+                    utilization: None,
+                },
+            );
+        }
+        let rows = tidy_rows(&samples);
+        assert_eq!(
+            rows.iter()
+                .map(|r| (r.workers, r.run, r.elapsed.as_secs()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1, 9), (5, 1, 2), (5, 2, 3)]
+        );
+        assert_eq!(
+            serde_json::to_value(rows[0]).unwrap(),
+            json!({"workers": 1, "run": 1, "requests": 42, "elapsed": 9.0})
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use crate::types::DirectoryListing;
+use clap::ValueEnum;
+use percent_encoding::percent_decode_str;
+use rand::seq::index::sample;
+use regex::Regex;
+use std::str::FromStr;
+use url::Url;
+
+/// Criteria for excluding entries found in collection listings from a
+/// traversal
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct EntryFilter {
+    /// Exclude collections & files whose names start with a period
+    pub(crate) skip_hidden: bool,
+    /// If nonempty, exclude files whose names do not end in one of these
+    /// extensions
+    pub(crate) only_ext: Vec<String>,
+    /// Exclude files whose names end in one of these extensions
+    pub(crate) skip_ext: Vec<String>,
+    /// If set, exclude files whose decoded paths do not match this pattern
+    pub(crate) filter_regex: Option<PathPattern>,
+    /// Exclude collections & files whose decoded paths match this pattern
+    pub(crate) prune_regex: Option<PathPattern>,
+    /// If set, exclude all but this many of the remaining files in each
+    /// listing
+    pub(crate) max_files_per_dir: Option<usize>,
+    /// How to choose the files to keep under `max_files_per_dir`
+    pub(crate) file_sample: FileSample,
+}
+
+/// How to choose which files in a listing to keep when limiting the number of
+/// files per collection
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum FileSample {
+    /// The first files in the order in which the server listed them
+    #[default]
+    First,
+    /// A random selection of files, kept in the order in which the server
+    /// listed them
+    Random,
+}
+
+impl EntryFilter {
+    /// Remove the excluded entries from `listing` and return the number
+    /// removed
+    pub(crate) fn apply(&self, listing: &mut DirectoryListing<Url>) -> usize {
+        let before = listing
+            .directories
+            .len()
+            .saturating_add(listing.files.len());
+        listing
+            .directories
+            .retain(|e| self.allows_collection(&e.href));
+        listing.files.retain(|e| self.allows_file(&e.href));
+        if let Some(max) = self.max_files_per_dir {
+            if listing.files.len() > max {
+                match self.file_sample {
+                    FileSample::First => listing.files.truncate(max),
+                    FileSample::Random => {
+                        let mut keep = vec![false; listing.files.len()];
+                        for i in sample(&mut rand::thread_rng(), keep.len(), max) {
+                            keep[i] = true;
+                        }
+                        let mut kept = keep.into_iter();
+                        listing.files.retain(|_| kept.next().unwrap_or_default());
+                    }
+                }
+            }
+        }
+        before.saturating_sub(
+            listing
+                .directories
+                .len()
+                .saturating_add(listing.files.len()),
+        )
+    }
+
+    fn allows_collection(&self, url: &Url) -> bool {
+        !((self.skip_hidden && entry_name(url).starts_with('.')) || self.is_pruned(url))
+    }
+
+    fn allows_file(&self, url: &Url) -> bool {
+        let name = entry_name(url);
+        if (self.skip_hidden && name.starts_with('.')) || self.is_pruned(url) {
+            return false;
+        }
+        if let Some(ref pat) = self.filter_regex {
+            if !pat.0.is_match(&decoded_path(url)) {
+                return false;
+            }
+        }
+        let name = name.to_lowercase();
+        let has_ext = |ext: &String| name.len() > ext.len() && name.ends_with(ext.as_str());
+        (self.only_ext.is_empty() || self.only_ext.iter().any(has_ext))
+            && !self.skip_ext.iter().any(has_ext)
+    }
+
+    fn is_pruned(&self, url: &Url) -> bool {
+        self.prune_regex
+            .as_ref()
+            .is_some_and(|pat| pat.0.is_match(&decoded_path(url)))
+    }
+}
+
+/// A regular expression to match against the decoded paths of resources
+#[derive(Clone, Debug)]
+pub(crate) struct PathPattern(Regex);
+
+impl FromStr for PathPattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<PathPattern, regex::Error> {
+        Regex::new(s).map(PathPattern)
+    }
+}
+
+impl PartialEq for PathPattern {
+    fn eq(&self, other: &PathPattern) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for PathPattern {}
+
+/// Normalize a file extension given on the command line to lowercase with a
+/// leading period
+pub(crate) fn normalize_extension(s: &str) -> String {
+    let ext = s.trim().to_lowercase();
+    if ext.starts_with('.') {
+        ext
+    } else {
+        format!(".{ext}")
+    }
+}
+
+/// Return the percent-decoded path of `url`
+fn decoded_path(url: &Url) -> String {
+    percent_decode_str(url.path())
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// Return the percent-decoded final path segment of `url`
+fn entry_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segs| segs.rfind(|s| !s.is_empty()))
+        .map(|s| percent_decode_str(s).decode_utf8_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Entry;
+    use rstest::rstest;
+
+    fn listing(directories: &[&str], files: &[&str]) -> DirectoryListing<Url> {
+        let entry = |s: &&str| Entry::new(Url::parse(s).unwrap());
+        DirectoryListing {
+            directories: directories.iter().map(entry).collect(),
+            files: files.iter().map(entry).collect(),
+        }
+    }
+
+    #[test]
+    fn test_apply_skip_hidden() {
+        let filter = EntryFilter {
+            skip_hidden: true,
+            ..EntryFilter::default()
+        };
+        let mut dl = listing(
+            &["https://example.com/.git/", "https://example.com/src/"],
+            &[
+                "https://example.com/.gitignore",
+                "https://example.com/%2Eenv",
+                "https://example.com/README.md",
+            ],
+        );
+        assert_eq!(filter.apply(&mut dl), 3);
+        assert_eq!(
+            dl,
+            listing(
+                &["https://example.com/src/"],
+                &["https://example.com/README.md"]
+            )
+        );
+    }
+
+    #[rstest]
+    #[case("https://example.com/data/a.nc", true)]
+    #[case("https://example.com/data/B.NC", true)]
+    #[case("https://example.com/data/c.tar.gz", true)]
+    #[case("https://example.com/data/d.txt", false)]
+    #[case("https://example.com/data/nc", false)]
+    #[case("https://example.com/data/.nc", false)]
+    fn test_only_ext(#[case] url: Url, #[case] allowed: bool) {
+        let filter = EntryFilter {
+            only_ext: vec![normalize_extension("nc"), normalize_extension(".tar.gz")],
+            ..EntryFilter::default()
+        };
+        assert_eq!(filter.allows_file(&url), allowed);
+    }
+
+    #[test]
+    fn test_only_and_skip_ext() {
+        let filter = EntryFilter {
+            only_ext: vec![".gz".into()],
+            skip_ext: vec![".tar.gz".into()],
+            ..EntryFilter::default()
+        };
+        let mut dl = listing(
+            &["https://example.com/sub.tar.gz/"],
+            &[
+                "https://example.com/a.gz",
+                "https://example.com/b.tar.gz",
+                "https://example.com/c.zip",
+            ],
+        );
+        assert_eq!(filter.apply(&mut dl), 2);
+        assert_eq!(
+            dl,
+            listing(
+                &["https://example.com/sub.tar.gz/"],
+                &["https://example.com/a.gz"]
+            )
+        );
+    }
+
+    #[rstest]
+    #[case(FileSample::First)]
+    #[case(FileSample::Random)]
+    fn test_max_files_per_dir(#[case] file_sample: FileSample) {
+        let filter = EntryFilter {
+            skip_ext: vec![".tmp".into()],
+            max_files_per_dir: Some(3),
+            file_sample,
+            ..EntryFilter::default()
+        };
+        let files = (0..10)
+            .map(|i| format!("https://example.com/f{i}.dat"))
+            .chain(std::iter::once(String::from("https://example.com/x.tmp")))
+            .collect::<Vec<_>>();
+        let files = files.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut dl = listing(&["https://example.com/sub/"], &files);
+        assert_eq!(filter.apply(&mut dl), 8);
+        assert_eq!(dl.directories.len(), 1);
+        assert_eq!(dl.files.len(), 3);
+        let positions = dl
+            .files
+            .iter()
+            .map(|e| files.iter().position(|&f| f == e.href.as_str()).unwrap())
+            .collect::<Vec<_>>();
+        let mut sorted = positions.clone();
+        sorted.sort_unstable();
+        assert_eq!(positions, sorted);
+        assert!(!positions.contains(&10));
+        if file_sample == FileSample::First {
+            assert_eq!(positions, [0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_filter_and_prune_regex() {
+        let filter = EntryFilter {
+            filter_regex: Some("/raw/".parse().unwrap()),
+            prune_regex: Some("/(tmp|cache) dir/".parse().unwrap()),
+            ..EntryFilter::default()
+        };
+        let mut dl = listing(
+            &[
+                "https://example.com/data/raw/",
+                "https://example.com/data/tmp%20dir/",
+                "https://example.com/data/cache%20dirs/",
+            ],
+            &[
+                "https://example.com/data/raw/a.bin",
+                "https://example.com/data/b.bin",
+                "https://example.com/data/tmp%20dir/raw/c.bin",
+            ],
+        );
+        assert_eq!(filter.apply(&mut dl), 3);
+        assert_eq!(
+            dl,
+            listing(
+                &[
+                    "https://example.com/data/raw/",
+                    "https://example.com/data/cache%20dirs/"
+                ],
+                &["https://example.com/data/raw/a.bin"]
+            )
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::traverse::TraversalReport;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+use url::Url;
+
+/// A shell command to run after each traversal and after each batch of
+/// traversals, with details about what completed passed in environment
+/// variables
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CompletionHook {
+    command: String,
+    base_url: Url,
+    /// The file to which results are being written, if any
+    report_path: Option<PathBuf>,
+}
+
+impl CompletionHook {
+    pub(crate) fn new(command: String, base_url: Url, report_path: Option<PathBuf>) -> Self {
+        CompletionHook {
+            command,
+            base_url,
+            report_path,
+        }
+    }
+
+    /// Run the command after the completion of a traversal
+    pub(crate) async fn traversal_complete(&self, report: &TraversalReport) {
+        self.run(traversal_env(report)).await;
+    }
+
+    /// Run the command after a traversal was aborted by an error
+    pub(crate) async fn traversal_failed(&self, workers: usize, elapsed: Duration, error: &str) {
+        self.run(vec![
+            ("BATCHDAV_EVENT", String::from("traversal")),
+            ("BATCHDAV_SUCCESS", String::from("0")),
+            ("BATCHDAV_ERROR", error.to_owned()),
+            ("BATCHDAV_WORKERS", workers.to_string()),
+            ("BATCHDAV_ELAPSED", elapsed.as_secs_f64().to_string()),
+        ])
+        .await;
+    }
+
+    /// Run the command after the completion of a batch of `traversals`
+    /// traversals
+    pub(crate) async fn batch_complete(&self, traversals: usize) {
+        self.run(vec![
+            ("BATCHDAV_EVENT", String::from("batch")),
+            ("BATCHDAV_SUCCESS", String::from("1")),
+            ("BATCHDAV_TRAVERSALS", traversals.to_string()),
+        ])
+        .await;
+    }
+
+    /// Run the command with the given environment variables set in addition
+    /// to `BATCHDAV_BASE_URL` and `BATCHDAV_REPORT`.  Failure to run the
+    /// command or a nonzero exit status is reported as a warning.
+    async fn run(&self, env: Vec<(&'static str, String)>) {
+        match self.output(env).await {
+            Ok(output) => {
+                // The command's standard output is passed on to standard
+                // error so that it isn't mixed in with any data that we're
+                // writing to standard output
+                let _ = io::stderr().write_all(&output.stdout);
+                if !output.status.success() {
+                    eprintln!(
+                        "Warning: --on-complete command exited with {}",
+                        output.status
+                    );
+                }
+            }
+            Err(e) => eprintln!("Warning: failed to run --on-complete command: {e}"),
+        }
+    }
+
+    /// Run the command with the given environment variables and return its
+    /// exit status & captured standard output
+    async fn output(&self, env: Vec<(&'static str, String)>) -> io::Result<Output> {
+        let mut cmd = tokio::process::Command::from(shell_command(&self.command));
+        cmd.env("BATCHDAV_BASE_URL", self.base_url.as_str())
+            .env_remove("BATCHDAV_REPORT")
+            .envs(env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        if let Some(ref path) = self.report_path {
+            cmd.env("BATCHDAV_REPORT", path);
+        }
+        cmd.output().await
+    }
+}
+
+/// Return the environment variables describing a completed traversal
+fn traversal_env(report: &TraversalReport) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("BATCHDAV_EVENT", String::from("traversal")),
+        ("BATCHDAV_SUCCESS", String::from("1")),
+        ("BATCHDAV_ROOT", report.root.to_string()),
+        ("BATCHDAV_WORKERS", report.workers.to_string()),
+        ("BATCHDAV_DIRECTORIES", report.directories.to_string()),
+        ("BATCHDAV_FILES", report.files.to_string()),
+        ("BATCHDAV_ERRORS", report.errors.total().to_string()),
+        (
+            "BATCHDAV_ELAPSED",
+            report.overall_time.as_secs_f64().to_string(),
+        ),
+    ];
+    if let Some(bytes) = report.total_bytes {
+        env.push(("BATCHDAV_TOTAL_BYTES", bytes.to_string()));
+    }
+    env
+}
+
+#[cfg(unix)]
+pub(crate) fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+pub(crate) fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str) -> CompletionHook {
+        CompletionHook::new(
+            command.to_owned(),
+            Url::parse("https://example.com/dav/").unwrap(),
+            Some(PathBuf::from("report.json")),
+        )
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_env() {
+        let hook = hook(
+            r#"echo "$BATCHDAV_EVENT $BATCHDAV_TRAVERSALS $BATCHDAV_BASE_URL $BATCHDAV_REPORT""#,
+        );
+        let output = hook
+            .output(vec![
+                ("BATCHDAV_EVENT", String::from("batch")),
+                ("BATCHDAV_TRAVERSALS", String::from("3")),
+            ])
+            .await
+            .unwrap();
+        assert!(output.status.success(), "hook failed: {}", output.status);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "batch 3 https://example.com/dav/ report.json\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_failure() {
+        let output = hook("exit 3").output(Vec::new()).await.unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert!(output.stdout.is_empty(), "unexpected output");
+    }
+}
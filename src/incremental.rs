@@ -0,0 +1,206 @@
+use crate::types::Entry;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use url::Url;
+
+/// The collections found by a traversal along with their ETags, saved so
+/// that the next traversal of the same hierarchy can skip listing
+/// collections that have not changed
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct EtagState {
+    collections: BTreeMap<Url, CollectionState>,
+    /// The ETags given to collections in their parents' listings, kept until
+    /// the collections' own listings complete
+    #[serde(skip)]
+    pending: BTreeMap<Url, Option<String>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+struct CollectionState {
+    /// The collection's ETag as given in its parent's listing, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    /// The number of non-collection resources in the collection's listing
+    files: usize,
+}
+
+impl EtagState {
+    /// Load the state saved at `path`, or return an empty state if the file
+    /// does not exist
+    pub(crate) fn load(path: &Path) -> anyhow::Result<EtagState> {
+        match File::open(path) {
+            Ok(fp) => serde_json::from_reader(BufReader::new(fp))
+                .with_context(|| format!("failed to read ETag state {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(EtagState::default()),
+            Err(e) => {
+                Err(e).with_context(|| format!("failed to open ETag state {}", path.display()))
+            }
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut fp = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create ETag state {}", path.display()))?,
+        );
+        serde_json::to_writer(&mut fp, self).context("failed to write ETag state")?;
+        fp.write_all(b"\n").context("failed to write ETag state")?;
+        fp.flush().context("failed to flush ETag state")?;
+        Ok(())
+    }
+
+    /// Returns true if the state records the collection listed as `entry`
+    /// with the same ETag as the listing gives it
+    pub(crate) fn is_unchanged(&self, entry: &Entry<Url>) -> bool {
+        entry.etag.as_ref().is_some_and(|etag| {
+            self.collections
+                .get(&entry.href)
+                .and_then(|c| c.etag.as_ref())
+                == Some(etag)
+        })
+    }
+
+    /// Record that the collection at `url` was listed and found to contain
+    /// `files` non-collection resources and the collections `children`.  A
+    /// collection is only recorded (along with the ETag given to it in its
+    /// parent's listing) once its own listing has completed, so that
+    /// collections whose listings failed or were never made are not skipped
+    /// by the next traversal.
+    pub(crate) fn record_listing<'a, I>(&mut self, url: &Url, files: usize, children: I)
+    where
+        I: IntoIterator<Item = &'a Entry<Url>>,
+    {
+        let etag = self.pending.remove(url).flatten();
+        self.collections
+            .insert(url.clone(), CollectionState { etag, files });
+        for child in children {
+            // The child's own listing may have been recorded first
+            match self.collections.get_mut(&child.href) {
+                Some(c) => c.etag.clone_from(&child.etag),
+                None => {
+                    self.pending.insert(child.href.clone(), child.etag.clone());
+                }
+            }
+        }
+    }
+
+    /// Copy the entries for the collection at `url` and all collections
+    /// beneath it from `old` into this state
+    pub(crate) fn carry_over(&mut self, old: &EtagState, url: &Url) {
+        for (u, c) in old.subtree(url) {
+            self.collections.insert(u.clone(), c.clone());
+        }
+    }
+
+    /// Returns the number of collections (including the collection itself)
+    /// and non-collection resources recorded at or beneath `url`
+    fn subtree_size(&self, url: &Url) -> (usize, usize) {
+        self.subtree(url).fold((0, 0), |(dirs, files), (_, c)| {
+            (dirs.saturating_add(1), files.saturating_add(c.files))
+        })
+    }
+
+    fn subtree<'a>(&'a self, url: &'a Url) -> impl Iterator<Item = (&'a Url, &'a CollectionState)> {
+        self.collections
+            .range(url.clone()..)
+            .take_while(move |(u, _)| u.as_str().starts_with(url.as_str()))
+    }
+}
+
+/// Statistics on the collections skipped by an incremental traversal
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct IncrementalStats {
+    /// Number of collections that were not listed because their ETags were
+    /// unchanged
+    pub(crate) unchanged: usize,
+    /// Number of collections at or beneath the unchanged collections, as
+    /// recorded by the previous traversal
+    pub(crate) skipped_collections: usize,
+    /// Number of non-collection resources beneath the unchanged collections,
+    /// as recorded by the previous traversal
+    pub(crate) skipped_files: usize,
+}
+
+impl IncrementalStats {
+    /// Summarize the collections at `unchanged` that were skipped based on
+    /// the previous traversal's state `old`
+    pub(crate) fn new(old: &EtagState, unchanged: &[Url]) -> IncrementalStats {
+        let mut stats = IncrementalStats {
+            unchanged: unchanged.len(),
+            ..IncrementalStats::default()
+        };
+        for url in unchanged {
+            let (dirs, files) = old.subtree_size(url);
+            stats.skipped_collections = stats.skipped_collections.saturating_add(dirs);
+            stats.skipped_files = stats.skipped_files.saturating_add(files);
+        }
+        stats
+    }
+}
+
+impl fmt::Display for IncrementalStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Incremental: skipped {} unchanged collections ({} collections and {} files as of the previous traversal)",
+            self.unchanged, self.skipped_collections, self.skipped_files,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, etag: Option<&str>) -> Entry<Url> {
+        Entry {
+            etag: etag.map(String::from),
+            ..Entry::new(Url::parse(url).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_etag_state() {
+        let base = Url::parse("https://example.com/").unwrap();
+        let a = Url::parse("https://example.com/a/").unwrap();
+        let mut old = EtagState::default();
+        old.record_listing(
+            &base,
+            2,
+            &[
+                entry("https://example.com/a/", Some("\"1\"")),
+                entry("https://example.com/ab/", Some("\"2\"")),
+                entry("https://example.com/c/", Some("\"4\"")),
+            ],
+        );
+        // `/a/b/`'s listing is recorded before that of its parent
+        old.record_listing(&Url::parse("https://example.com/a/b/").unwrap(), 7, &[]);
+        old.record_listing(&a, 5, &[entry("https://example.com/a/b/", Some("\"3\""))]);
+        old.record_listing(&Url::parse("https://example.com/ab/").unwrap(), 11, &[]);
+        assert!(old.is_unchanged(&entry("https://example.com/a/", Some("\"1\""))));
+        assert!(!old.is_unchanged(&entry("https://example.com/a/", Some("\"9\""))));
+        assert!(!old.is_unchanged(&entry("https://example.com/a/", None)));
+        assert!(old.is_unchanged(&entry("https://example.com/a/b/", Some("\"3\""))));
+        // `/c/` was never listed itself
+        assert!(!old.is_unchanged(&entry("https://example.com/c/", Some("\"4\""))));
+        assert!(!old.is_unchanged(&entry("https://example.com/d/", Some("\"1\""))));
+        // `/ab/` is not beneath `/a/`
+        assert_eq!(
+            IncrementalStats::new(&old, std::slice::from_ref(&a)),
+            IncrementalStats {
+                unchanged: 1,
+                skipped_collections: 2,
+                skipped_files: 12,
+            }
+        );
+        let mut new = EtagState::default();
+        new.carry_over(&old, &a);
+        assert_eq!(new.collections.len(), 2);
+        assert_eq!(new.subtree_size(&a), (2, 12));
+    }
+}
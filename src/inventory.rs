@@ -0,0 +1,37 @@
+use crate::traverse::Task;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use url::Url;
+
+/// A record of the resources found by a traversal, saved so that the same
+/// requests can later be replayed without rediscovering the hierarchy
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct Inventory {
+    pub(crate) base_url: Url,
+    /// The successfully completed requests of the traversal, in the order
+    /// they completed
+    pub(crate) tasks: Vec<Task>,
+}
+
+impl Inventory {
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Inventory> {
+        let fp = File::open(path)
+            .with_context(|| format!("failed to open tree file {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(fp))
+            .with_context(|| format!("failed to read tree file {}", path.display()))
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut fp = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create tree file {}", path.display()))?,
+        );
+        serde_json::to_writer(&mut fp, self).context("failed to write tree file")?;
+        fp.write_all(b"\n").context("failed to write tree file")?;
+        fp.flush().context("failed to flush tree file")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use crate::client::Client;
+use crate::traverse::{traverse, ErrorPolicy, TraversalOptions};
+use anyhow::Context;
+use futures_util::StreamExt;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use statrs::statistics::{Data, Distribution};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+/// Number of per-URL results listed by the human-readable output for each
+/// number of workers
+const NOISIEST: usize = 5;
+
+/// Settings for a latency jitter measurement
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct JitterOptions {
+    /// Number of files to sample from the hierarchy
+    pub(crate) files: usize,
+    /// Number of times to probe each sampled file for each number of workers
+    pub(crate) rounds: usize,
+}
+
+/// Traverse the hierarchy at `base_url`, choose a random sample of the files
+/// found, and then, for each number of workers in `workers_list`, probe every
+/// sampled file `opts.rounds` times.  Each round probes the whole sample in a
+/// fresh random order so that a file's latencies do not depend on its
+/// position in the hierarchy or on which files it is probed alongside.
+pub(crate) async fn jitter(
+    client: Client,
+    base_url: Url,
+    workers_list: &[usize],
+    opts: JitterOptions,
+) -> anyhow::Result<JitterReport> {
+    let options = TraversalOptions {
+        quiet: true,
+        on_error: ErrorPolicy::Skip,
+        collect_urls: true,
+        ..TraversalOptions::default()
+    };
+    let discovery_workers = workers_list.iter().copied().max().unwrap_or(1);
+    let found = traverse(
+        client.clone(),
+        base_url.clone(),
+        discovery_workers,
+        &options,
+    )
+    .await?;
+    let files = found
+        .urls
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|u| !u.path().ends_with('/'))
+        .collect::<Vec<_>>();
+    let mut sample = files
+        .choose_multiple(&mut rand::thread_rng(), opts.files)
+        .cloned()
+        .collect::<Vec<_>>();
+    sample.sort();
+    let mut per_workers = Vec::with_capacity(workers_list.len());
+    for &workers in workers_list {
+        let mut latencies = vec![Vec::with_capacity(opts.rounds); sample.len()];
+        let mut failures = vec![0usize; sample.len()];
+        for _ in 0..opts.rounds {
+            let mut order = (0..sample.len()).collect::<Vec<_>>();
+            order.shuffle(&mut rand::thread_rng());
+            let mut stream = futures_util::stream::iter(order.into_iter().map(|i| {
+                let client = &client;
+                let url = sample[i].clone();
+                async move { (i, client.get_file_redirect(url).await) }
+            }))
+            .buffer_unordered(workers.max(1));
+            while let Some((i, r)) = stream.next().await {
+                match r {
+                    Ok(resp) => latencies[i].push(resp.elapsed),
+                    Err(_) => failures[i] = failures[i].saturating_add(1),
+                }
+            }
+        }
+        let urls = sample
+            .iter()
+            .zip(latencies)
+            .zip(failures)
+            .map(|((url, times), failures)| UrlJitter::new(url.clone(), &times, failures))
+            .collect::<Vec<_>>();
+        per_workers.push(WorkerJitter::new(workers, urls));
+    }
+    Ok(JitterReport {
+        base_url,
+        files_found: files.len(),
+        rounds: opts.rounds,
+        workers: per_workers,
+    })
+}
+
+/// The variability of the latencies of repeated probes of a single URL
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct UrlJitter {
+    pub(crate) url: Url,
+    /// Number of successful probes
+    pub(crate) requests: usize,
+    /// Number of failed probes
+    pub(crate) failures: usize,
+    /// Mean latency in seconds
+    pub(crate) mean: Option<f64>,
+    /// Sample variance of the latencies in seconds squared
+    pub(crate) variance: Option<f64>,
+    /// Standard deviation of the latencies in seconds
+    pub(crate) stddev: Option<f64>,
+    /// `stddev` as a fraction of `mean`
+    pub(crate) cv: Option<f64>,
+    /// Mean absolute difference in seconds between the latencies of
+    /// consecutive probes (in order of completion)
+    pub(crate) successive_difference: Option<f64>,
+    /// The latencies of the probes in seconds, in order of completion
+    pub(crate) latencies: Vec<f64>,
+}
+
+impl UrlJitter {
+    fn new(url: Url, times: &[Duration], failures: usize) -> UrlJitter {
+        let latencies = times.iter().map(Duration::as_secs_f64).collect::<Vec<_>>();
+        let data = Data::new(latencies.clone());
+        let mean = data.mean();
+        let variance = data.variance().filter(|v| v.is_finite());
+        let stddev = variance.map(f64::sqrt);
+        #[allow(clippy::cast_precision_loss)]
+        let successive_difference = (latencies.len() > 1).then(|| {
+            latencies
+                .windows(2)
+                .map(|w| (w[1] - w[0]).abs())
+                .sum::<f64>()
+                / (latencies.len() - 1) as f64
+        });
+        UrlJitter {
+            url,
+            requests: latencies.len(),
+            failures,
+            mean,
+            variance,
+            stddev,
+            cv: mean
+                .zip(stddev)
+                .and_then(|(m, s)| (m > 0.0).then_some(s / m)),
+            successive_difference,
+            latencies,
+        }
+    }
+}
+
+/// Jitter statistics for the sampled URLs when probed with a given number of
+/// workers.  The variance of all of the latencies is split into the variance
+/// within each URL's repeated probes (the server's inherent latency noise)
+/// and the variance between the URLs' mean latencies (consistent differences
+/// between the resources themselves).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct WorkerJitter {
+    pub(crate) workers: usize,
+    /// Total number of successful probes
+    pub(crate) requests: usize,
+    /// Total number of failed probes
+    pub(crate) failures: usize,
+    /// Mean latency of all probes in seconds
+    pub(crate) mean: Option<f64>,
+    /// Standard deviation of all latencies in seconds
+    pub(crate) stddev: Option<f64>,
+    /// Pooled standard deviation of each URL's latencies around its own mean,
+    /// in seconds
+    pub(crate) within_url: Option<f64>,
+    /// Standard deviation of the URLs' mean latencies in seconds
+    pub(crate) between_url: Option<f64>,
+    /// Fraction of the total latency variance accounted for by variance
+    /// within URLs
+    pub(crate) noise_share: Option<f64>,
+    /// Mean of the URLs' mean absolute successive differences, in seconds
+    pub(crate) successive_difference: Option<f64>,
+    /// Statistics for each sampled URL, in URL order
+    pub(crate) urls: Vec<UrlJitter>,
+}
+
+impl WorkerJitter {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(workers: usize, urls: Vec<UrlJitter>) -> WorkerJitter {
+        let all = Data::new(
+            urls.iter()
+                .flat_map(|u| u.latencies.iter().copied())
+                .collect::<Vec<_>>(),
+        );
+        let requests = urls.iter().map(|u| u.requests).sum::<usize>();
+        // Pooled variance: the sum of each URL's squared deviations from its
+        // own mean over the total degrees of freedom
+        let (sum_squares, dof) = urls
+            .iter()
+            .filter_map(|u| Some((u.variance? * (u.requests - 1) as f64, u.requests - 1)))
+            .fold((0.0, 0usize), |(ss, n), (s, d)| {
+                (ss + s, n.saturating_add(d))
+            });
+        let within_variance = (dof > 0).then(|| sum_squares / dof as f64);
+        let means = Data::new(urls.iter().filter_map(|u| u.mean).collect::<Vec<_>>());
+        let between_url = means.std_dev().filter(|s| s.is_finite());
+        let total_variance = all.variance().filter(|v| v.is_finite());
+        let diffs = urls
+            .iter()
+            .filter_map(|u| u.successive_difference)
+            .collect::<Vec<_>>();
+        WorkerJitter {
+            workers,
+            requests,
+            failures: urls.iter().map(|u| u.failures).sum(),
+            mean: all.mean(),
+            stddev: total_variance.map(f64::sqrt),
+            within_url: within_variance.map(f64::sqrt),
+            between_url,
+            noise_share: within_variance
+                .zip(total_variance)
+                .and_then(|(w, t)| (t > 0.0).then(|| (w / t).min(1.0))),
+            successive_difference: (!diffs.is_empty())
+                .then(|| diffs.iter().sum::<f64>() / diffs.len() as f64),
+            urls,
+        }
+    }
+}
+
+/// The results of a latency jitter measurement
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct JitterReport {
+    pub(crate) base_url: Url,
+    /// Number of files found in the hierarchy
+    pub(crate) files_found: usize,
+    /// Number of times each sampled file was probed per number of workers
+    pub(crate) rounds: usize,
+    pub(crate) workers: Vec<WorkerJitter>,
+}
+
+impl JitterReport {
+    /// Total number of failed probes
+    pub(crate) fn failures(&self) -> usize {
+        self.workers.iter().map(|w| w.failures).sum()
+    }
+
+    pub(crate) fn write_json(&self, path: &Path) -> anyhow::Result<()> {
+        let mut fp = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("failed to create jitter file {}", path.display()))?,
+        );
+        serde_json::to_writer_pretty(&mut fp, self).context("failed to write jitter file")?;
+        fp.write_all(b"\n").context("failed to write jitter file")?;
+        fp.flush().context("failed to flush jitter file")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for JitterReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sampled = self.workers.first().map_or(0, |w| w.urls.len());
+        writeln!(
+            f,
+            "Probed {sampled} of {} files {} times each per number of workers",
+            self.files_found, self.rounds
+        )?;
+        for w in &self.workers {
+            write!(
+                f,
+                "workers = {}: {} requests, {} failed",
+                w.workers, w.requests, w.failures
+            )?;
+            if let (Some(mean), Some(stddev)) = (w.mean, w.stddev) {
+                write!(f, "; mean {}, stddev {}", secs(mean), secs(stddev))?;
+            }
+            writeln!(f)?;
+            if let Some(within) = w.within_url {
+                write!(f, "  Within-URL jitter: {}", secs(within))?;
+                if let Some(share) = w.noise_share {
+                    write!(f, " ({:.1}% of variance)", share * 100.0)?;
+                }
+                writeln!(f)?;
+            }
+            if let Some(between) = w.between_url {
+                writeln!(f, "  Between-URL spread: {}", secs(between))?;
+            }
+            if let Some(diff) = w.successive_difference {
+                writeln!(f, "  Mean successive difference: {}", secs(diff))?;
+            }
+            let mut noisiest = w
+                .urls
+                .iter()
+                .filter_map(|u| Some((u, u.stddev?)))
+                .collect::<Vec<_>>();
+            noisiest.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            if !noisiest.is_empty() {
+                writeln!(f, "  Noisiest URLs:")?;
+            }
+            for (u, stddev) in noisiest.into_iter().take(NOISIEST) {
+                write!(f, "    {}: stddev {}", u.url, secs(stddev))?;
+                if let Some(mean) = u.mean {
+                    write!(f, ", mean {}", secs(mean))?;
+                }
+                if let Some(cv) = u.cv {
+                    write!(f, " (CV {:.1}%)", cv * 100.0)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format a number of seconds as a `Duration`
+fn secs(s: f64) -> String {
+    format!("{:?}", Duration::try_from_secs_f64(s).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url_jitter(path: &str, millis: &[u64]) -> UrlJitter {
+        let url = Url::parse("https://dav.example.com/")
+            .unwrap()
+            .join(path)
+            .unwrap();
+        let times = millis
+            .iter()
+            .map(|&ms| Duration::from_millis(ms))
+            .collect::<Vec<_>>();
+        UrlJitter::new(url, &times, 0)
+    }
+
+    #[test]
+    fn test_url_jitter() {
+        let u = url_jitter("a", &[10, 14, 10, 14]);
+        assert_eq!(u.requests, 4);
+        assert!((u.mean.unwrap() - 0.012).abs() < 1e-9);
+        // Sample variance of ±2ms deviations over 3 degrees of freedom
+        assert!((u.variance.unwrap() - 16e-6 / 3.0).abs() < 1e-12);
+        assert!((u.successive_difference.unwrap() - 0.004).abs() < 1e-9);
+        let single = url_jitter("b", &[10]);
+        assert_eq!(single.successive_difference, None);
+    }
+
+    #[test]
+    fn test_worker_jitter_separates_noise() {
+        // Two URLs with identical noise but different typical latencies
+        let w = WorkerJitter::new(
+            4,
+            vec![
+                url_jitter("a", &[10, 12, 10, 12]),
+                url_jitter("b", &[50, 52, 50, 52]),
+            ],
+        );
+        assert_eq!(w.requests, 8);
+        let within = w.within_url.unwrap();
+        assert!((within - (4e-6f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert!((w.between_url.unwrap() - 0.04 / 2f64.sqrt()).abs() < 1e-9);
+        assert!(w.noise_share.unwrap() < 0.01);
+        assert!((w.successive_difference.unwrap() - 0.002).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::btn::{BoundedTreeNursery, Spawner};
+use crate::client::Client;
+use crate::types::{normalize_url, path_order_key, url_plus_path};
+use crate::xml::{ResponseSummary, XmlWarning};
+use futures_util::{future::BoxFuture, FutureExt, StreamExt};
+use std::collections::HashMap;
+use std::fmt;
+use url::Url;
+
+/// Traverse the remote hierarchy at `base_url` and check each multistatus
+/// response for WebDAV hygiene issues
+pub(crate) async fn lint(client: Client, base_url: Url, workers: usize) -> LintReport {
+    let root = base_url.clone();
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        [move |spawner| lint_dir(spawner, client, base_url, root, true)],
+    );
+    let mut report = LintReport::default();
+    while let Some(checked) = stream.next().await {
+        report.collections = report.collections.saturating_add(1);
+        report.responses = report.responses.saturating_add(checked.responses);
+        report.findings.extend(checked.findings);
+    }
+    report
+        .findings
+        .sort_by_cached_key(|f| path_order_key(&f.collection));
+    report
+}
+
+/// The results of checking a single collection's listing
+struct CheckedListing {
+    responses: usize,
+    findings: Vec<Finding>,
+}
+
+fn lint_dir(
+    spawner: Spawner<CheckedListing>,
+    client: Client,
+    base_url: Url,
+    url: Url,
+    is_root: bool,
+) -> BoxFuture<'static, CheckedListing> {
+    async move {
+        match client.summarize_directory(url.clone()).await {
+            Ok(summaries) => {
+                let (children, findings) = check_responses(&base_url, &url, &summaries, is_root);
+                for child in children {
+                    let cl2 = client.clone();
+                    let base2 = base_url.clone();
+                    spawner.spawn(move |spawner| lint_dir(spawner, cl2, base2, child, false));
+                }
+                CheckedListing {
+                    responses: summaries.len(),
+                    findings,
+                }
+            }
+            Err(e) => CheckedListing {
+                responses: 0,
+                findings: vec![Finding {
+                    collection: url,
+                    href: None,
+                    issue: Issue::RequestFailed(e.to_string()),
+                }],
+            },
+        }
+    }
+    .boxed()
+}
+
+/// Check the responses in the multistatus listing of the collection at `url`
+/// and return the subcollections to descend into along with any issues
+/// found.  The response for the collection itself is only checked if
+/// `is_root` is true, as it is otherwise already checked as part of its
+/// parent's listing.
+fn check_responses(
+    base_url: &Url,
+    url: &Url,
+    summaries: &[ResponseSummary],
+    is_root: bool,
+) -> (Vec<Url>, Vec<Finding>) {
+    let self_key = url.as_str().trim_end_matches('/').to_owned();
+    let mut children = Vec::new();
+    let mut findings = Vec::new();
+    let mut seen = HashMap::<String, (usize, usize)>::new();
+    let finding = |href: &str, issue| Finding {
+        collection: url.clone(),
+        href: Some(href.to_owned()),
+        issue,
+    };
+    for (i, r) in summaries.iter().enumerate() {
+        let resolved = match url_plus_path(base_url, &r.href) {
+            Ok(u) => normalize_url(u, r.is_collection),
+            Err(e) => {
+                findings.push(finding(&r.href, Issue::InvalidHref(e.to_string())));
+                continue;
+            }
+        };
+        let key = resolved.as_str().trim_end_matches('/').to_owned();
+        let is_self = key == self_key;
+        let (_, count) = seen.entry(key).or_insert((i, 0));
+        *count = count.saturating_add(1);
+        if *count > 1 || (is_self && !is_root) {
+            continue;
+        }
+        if r.is_collection && !r.href.ends_with('/') {
+            findings.push(finding(&r.href, Issue::CollectionWithoutSlash));
+        } else if !r.is_collection && r.href.ends_with('/') {
+            findings.push(finding(&r.href, Issue::FileWithSlash));
+        }
+        for &status in &r.propstat_statuses {
+            if status != 200 {
+                findings.push(finding(&r.href, Issue::PropstatStatus(status)));
+            }
+        }
+        for line in &r.invalid_status_lines {
+            findings.push(finding(&r.href, Issue::InvalidStatusLine(line.clone())));
+        }
+        for w in &r.warnings {
+            if let XmlWarning::StatusLine { line, .. } = w {
+                findings.push(finding(&r.href, Issue::InvalidStatusLine(line.clone())));
+            }
+        }
+        if r.is_collection && !is_self && resolved.path().starts_with(url.path()) {
+            children.push(resolved);
+        }
+    }
+    let mut duplicates = seen
+        .into_values()
+        .filter(|&(_, count)| count > 1)
+        .collect::<Vec<_>>();
+    duplicates.sort_unstable();
+    for (i, count) in duplicates {
+        if let Some(r) = summaries.get(i) {
+            findings.push(finding(&r.href, Issue::DuplicateHref(count)));
+        }
+    }
+    (children, findings)
+}
+
+/// A hygiene issue found in a collection's listing
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Finding {
+    /// The collection whose listing exhibited the issue
+    pub(crate) collection: Url,
+    /// The href of the offending `<response>`, if any
+    pub(crate) href: Option<String>,
+    pub(crate) issue: Issue,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.href {
+            Some(ref href) => write!(f, "{}: {href:?}: {}", self.collection, self.issue),
+            None => write!(f, "{}: {}", self.collection, self.issue),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Issue {
+    /// A collection's href lacks a trailing slash
+    CollectionWithoutSlash,
+    /// A non-collection's href has a trailing slash
+    FileWithSlash,
+    /// The same resource is listed the given number of times
+    DuplicateHref(usize),
+    /// A `<propstat>` has a status other than 200
+    PropstatStatus(u16),
+    /// A `<propstat>` status line is not of the form `HTTP/x.y NNN ...`
+    InvalidStatusLine(String),
+    /// An href could not be resolved to a URL
+    InvalidHref(String),
+    /// The collection could not be listed
+    RequestFailed(String),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::CollectionWithoutSlash => write!(f, "collection href lacks trailing slash"),
+            Issue::FileWithSlash => write!(f, "non-collection href has trailing slash"),
+            Issue::DuplicateHref(count) => write!(f, "href listed {count} times"),
+            Issue::PropstatStatus(status) => write!(f, "propstat has status {status}"),
+            Issue::InvalidStatusLine(line) => write!(f, "invalid status line {line:?}"),
+            Issue::InvalidHref(msg) => write!(f, "invalid href: {msg}"),
+            Issue::RequestFailed(msg) => write!(f, "request failed: {msg}"),
+        }
+    }
+}
+
+/// The results of checking a remote hierarchy for hygiene issues
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LintReport {
+    /// The number of collections whose listings were requested
+    pub(crate) collections: usize,
+    /// The total number of `<response>` elements checked
+    pub(crate) responses: usize,
+    pub(crate) findings: Vec<Finding>,
+}
+
+impl fmt::Display for LintReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{finding}")?;
+        }
+        writeln!(
+            f,
+            "Checked {} responses in {} collections; found {} issues",
+            self.responses,
+            self.collections,
+            self.findings.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(href: &str, is_collection: bool, statuses: &[u16]) -> ResponseSummary {
+        ResponseSummary {
+            href: href.into(),
+            is_collection,
+            propstat_statuses: statuses.to_vec(),
+            invalid_status_lines: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_responses() {
+        let base_url = Url::parse("https://example.com/dav/").unwrap();
+        let url = Url::parse("https://example.com/dav/data/").unwrap();
+        let mut bad_status = summary("/dav/data/odd.txt", false, &[200]);
+        bad_status.invalid_status_lines.push("200 OK".into());
+        bad_status.warnings.push(XmlWarning::StatusLine {
+            line: "HTTP/1.1 OK 404".into(),
+            code: 404,
+        });
+        let summaries = vec![
+            summary("/dav/data", true, &[200]),
+            summary("/dav/data/sub", true, &[200]),
+            summary("/dav/data/good/", true, &[200]),
+            summary("/dav/data/file.txt/", false, &[200]),
+            summary("/dav/data/plain.txt", false, &[200, 404]),
+            summary("/dav/data/good/", true, &[200]),
+            summary("/dav/data/good", true, &[200]),
+            bad_status,
+            summary("/dav/other/", true, &[200]),
+        ];
+        let (children, findings) = check_responses(&base_url, &url, &summaries, false);
+        assert_eq!(
+            children,
+            [
+                Url::parse("https://example.com/dav/data/sub/").unwrap(),
+                Url::parse("https://example.com/dav/data/good/").unwrap(),
+            ]
+        );
+        let finding = |href: &str, issue| Finding {
+            collection: url.clone(),
+            href: Some(href.into()),
+            issue,
+        };
+        assert_eq!(
+            findings,
+            [
+                finding("/dav/data/sub", Issue::CollectionWithoutSlash),
+                finding("/dav/data/file.txt/", Issue::FileWithSlash),
+                finding("/dav/data/plain.txt", Issue::PropstatStatus(404)),
+                finding(
+                    "/dav/data/odd.txt",
+                    Issue::InvalidStatusLine("200 OK".into())
+                ),
+                finding(
+                    "/dav/data/odd.txt",
+                    Issue::InvalidStatusLine("HTTP/1.1 OK 404".into())
+                ),
+                finding("/dav/data/good/", Issue::DuplicateHref(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_responses_root() {
+        let url = Url::parse("https://example.com/dav/").unwrap();
+        let summaries = vec![
+            summary("/dav", true, &[200, 403]),
+            summary("/dav/a.txt", false, &[200]),
+        ];
+        let (children, findings) = check_responses(&url, &url, &summaries, true);
+        assert!(children.is_empty());
+        assert_eq!(
+            findings,
+            [
+                Finding {
+                    collection: url.clone(),
+                    href: Some("/dav".into()),
+                    issue: Issue::CollectionWithoutSlash,
+                },
+                Finding {
+                    collection: url,
+                    href: Some("/dav".into()),
+                    issue: Issue::PropstatStatus(403),
+                },
+            ]
+        );
+    }
+}
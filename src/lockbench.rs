@@ -0,0 +1,243 @@
+use crate::client::{Client, LockResponse, RequestError};
+use crate::stats::LatencySummary;
+use rand::Rng;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// Maximum delay before retrying a `LOCK` request refused with 423 Locked
+/// for the first time
+const BASE_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Upper bound on the delay before retrying a refused `LOCK` request
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Have `workers` concurrent tasks each repeatedly LOCK & UNLOCK the resource
+/// at `url` until they have each completed `cycles` lock/unlock cycles
+pub(crate) async fn lock_bench(
+    client: Client,
+    url: Url,
+    workers: usize,
+    cycles: usize,
+) -> Result<LockBenchReport, RequestError> {
+    let start = Instant::now();
+    let token = CancellationToken::new();
+    let mut tasks = JoinSet::new();
+    for _ in 0..workers {
+        tasks.spawn(lock_worker(
+            client.clone(),
+            url.clone(),
+            cycles,
+            token.clone(),
+        ));
+    }
+    // Rather than aborting the other workers when one fails (which could
+    // leave the resource locked), tell them to stop after their current
+    // cycles and wait for them to release their locks
+    let mut all_stats = Vec::with_capacity(workers);
+    let mut error = None;
+    while let Some(r) = tasks.join_next().await {
+        match r {
+            Ok(Ok(stats)) => all_stats.push(stats),
+            Ok(Err(e)) => {
+                token.cancel();
+                error.get_or_insert(e);
+            }
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    Ok(LockBenchReport::new(workers, all_stats, start.elapsed()))
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct WorkerStats {
+    lock_times: Vec<Duration>,
+    unlock_times: Vec<Duration>,
+    contended: usize,
+}
+
+async fn lock_worker(
+    client: Client,
+    url: Url,
+    cycles: usize,
+    stop: CancellationToken,
+) -> Result<WorkerStats, RequestError> {
+    let mut stats = WorkerStats::default();
+    let mut refusals = 0u32;
+    while stats.unlock_times.len() < cycles && !stop.is_cancelled() {
+        match client.lock(url.clone()).await? {
+            LockResponse::Locked { token, elapsed } => {
+                refusals = 0;
+                stats.lock_times.push(elapsed);
+                // A failed UNLOCK is returned as an error without attempting
+                // any further LOCKs, so a lock is never left held by a
+                // worker that moves on
+                stats
+                    .unlock_times
+                    .push(client.unlock(url.clone(), token).await?);
+            }
+            // Another worker holds the lock; back off & try again
+            LockResponse::Contended { .. } => {
+                stats.contended = stats.contended.saturating_add(1);
+                let delay = backoff(refusals);
+                refusals = refusals.saturating_add(1);
+                tokio::select! {
+                    () = tokio::time::sleep(delay) => (),
+                    () = stop.cancelled() => break,
+                }
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Return a random delay (with "full jitter," so that contending workers
+/// don't retry in lockstep) before retrying a `LOCK` request that has been
+/// refused `refusals` times in a row already
+fn backoff(refusals: u32) -> Duration {
+    let cap = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(refusals))
+        .min(MAX_BACKOFF);
+    rand::thread_rng().gen_range(Duration::ZERO..=cap)
+}
+
+/// The results of a LOCK/UNLOCK benchmark
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct LockBenchReport {
+    pub(crate) workers: usize,
+    /// Number of successful lock/unlock cycles
+    pub(crate) cycles: usize,
+    /// Number of LOCK requests refused with 423 Locked
+    pub(crate) contended: usize,
+    /// Latencies of successful LOCK requests
+    pub(crate) lock_latency: Option<LatencySummary>,
+    pub(crate) unlock_latency: Option<LatencySummary>,
+    pub(crate) overall_time: Duration,
+}
+
+impl LockBenchReport {
+    /// Combine the statistics of the individual workers of a benchmark that
+    /// took `overall_time`
+    fn new<I: IntoIterator<Item = WorkerStats>>(
+        workers: usize,
+        stats: I,
+        overall_time: Duration,
+    ) -> LockBenchReport {
+        let mut lock_times = Vec::new();
+        let mut unlock_times = Vec::new();
+        let mut contended = 0usize;
+        for s in stats {
+            lock_times.extend(s.lock_times);
+            unlock_times.extend(s.unlock_times);
+            contended = contended.saturating_add(s.contended);
+        }
+        LockBenchReport {
+            workers,
+            cycles: unlock_times.len(),
+            contended,
+            lock_latency: LatencySummary::new(&lock_times),
+            unlock_latency: LatencySummary::new(&unlock_times),
+            overall_time,
+        }
+    }
+
+    /// The percentage of LOCK requests that were refused due to contention
+    #[allow(clippy::cast_precision_loss)]
+    fn contention_rate(&self) -> f64 {
+        let attempts = self.cycles.saturating_add(self.contended);
+        if attempts == 0 {
+            0.0
+        } else {
+            (self.contended as f64) * 100.0 / (attempts as f64)
+        }
+    }
+
+    /// Lock/unlock cycles completed per second
+    #[allow(clippy::cast_precision_loss)]
+    fn throughput(&self) -> f64 {
+        (self.cycles as f64) / self.overall_time.as_secs_f64()
+    }
+}
+
+impl fmt::Display for LockBenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Performed {} lock/unlock cycles with {} workers in {:?} ({:.2} cycles/s)",
+            self.cycles,
+            self.workers,
+            self.overall_time,
+            self.throughput()
+        )?;
+        if let Some(lat) = self.lock_latency {
+            writeln!(f, "LOCK latency: {lat}")?;
+        }
+        if let Some(lat) = self.unlock_latency {
+            writeln!(f, "UNLOCK latency: {lat}")?;
+        }
+        writeln!(
+            f,
+            "Contention: {} LOCK requests refused with 423 Locked ({:.1}% of attempts)",
+            self.contended,
+            self.contention_rate()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(locks: u64, contended: usize) -> WorkerStats {
+        let times = (1..=locks).map(Duration::from_millis).collect::<Vec<_>>();
+        WorkerStats {
+            lock_times: times.clone(),
+            unlock_times: times,
+            contended,
+        }
+    }
+
+    #[test]
+    fn report_contention() {
+        let report = LockBenchReport::new(
+            3,
+            [worker(4, 2), worker(3, 6), worker(3, 0)],
+            Duration::from_secs(2),
+        );
+        assert_eq!(report.workers, 3);
+        assert_eq!(report.cycles, 10);
+        assert_eq!(report.contended, 8);
+        // 8 refusals out of 18 LOCK requests
+        assert!((report.contention_rate() - 800.0 / 18.0).abs() < 1e-9);
+        assert!((report.throughput() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn report_no_attempts() {
+        let report = LockBenchReport::new(2, [], Duration::from_secs(1));
+        assert_eq!(report.cycles, 0);
+        assert_eq!(report.contended, 0);
+        assert_eq!(report.lock_latency, None);
+        assert!(report.contention_rate().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn backoff_bounds() {
+        for refusals in 0..20 {
+            let cap = BASE_BACKOFF
+                .saturating_mul(2u32.saturating_pow(refusals))
+                .min(MAX_BACKOFF);
+            let delay = backoff(refusals);
+            assert!(
+                delay <= cap,
+                "{delay:?} > {cap:?} after {refusals} refusals"
+            );
+        }
+        assert!(backoff(u32::MAX) <= MAX_BACKOFF);
+    }
+}
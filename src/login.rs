@@ -0,0 +1,357 @@
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, LOCATION, SET_COOKIE};
+use reqwest::{Method, StatusCode};
+use std::fmt;
+use std::sync::{PoisonError, RwLock};
+use thiserror::Error;
+use tokio::sync::Mutex;
+use url::Url;
+
+/// The default `Content-Type` of login request bodies
+pub(crate) const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+/// How to log in to a server that requires a session cookie, obtained by
+/// submitting credentials to a login endpoint, before it will answer any
+/// other requests
+#[derive(Clone, Eq, PartialEq)]
+pub(crate) struct LoginOptions {
+    pub(crate) url: Url,
+    pub(crate) method: Method,
+    /// The request body, in which `{username}` and `{password}` are replaced
+    /// by the credentials, encoded as appropriate for `content_type`
+    pub(crate) body: Option<String>,
+    pub(crate) content_type: String,
+    /// The status that a successful login responds with
+    pub(crate) expect_status: StatusCode,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<String>,
+}
+
+// Don't let secrets end up in debug output
+impl fmt::Debug for LoginOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoginOptions")
+            .field("url", &self.url)
+            .field("method", &self.method)
+            .field("body", &self.body)
+            .field("content_type", &self.content_type)
+            .field("expect_status", &self.expect_status)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<hidden>"))
+            .finish()
+    }
+}
+
+impl LoginOptions {
+    /// Return the login request body with the credentials filled in
+    fn render_body(&self) -> Option<String> {
+        let body = self.body.as_ref()?;
+        let encode = |s: &str| {
+            if self.content_type.starts_with(FORM_CONTENT_TYPE) {
+                url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>()
+            } else if self.content_type.contains("json") {
+                let quoted = serde_json::Value::from(s).to_string();
+                quoted[1..quoted.len() - 1].to_owned()
+            } else {
+                s.to_owned()
+            }
+        };
+        Some(
+            body.replace(
+                "{username}",
+                &encode(self.username.as_deref().unwrap_or_default()),
+            )
+            .replace(
+                "{password}",
+                &encode(self.password.as_deref().unwrap_or_default()),
+            ),
+        )
+    }
+
+    /// Return whether `resp` indicates that the session has expired: either
+    /// it is a 401, or it redirects to the login URL
+    pub(crate) fn is_expired(&self, resp: &reqwest::Response) -> bool {
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return true;
+        }
+        resp.status().is_redirection()
+            && resp
+                .headers()
+                .get(LOCATION)
+                .and_then(|loc| loc.to_str().ok())
+                .and_then(|loc| resp.url().join(loc).ok())
+                .is_some_and(|u| u.origin() == self.url.origin() && u.path() == self.url.path())
+    }
+}
+
+/// A login session: the `Cookie` header obtained by logging in, which is
+/// replaced by logging in again whenever the session expires
+#[derive(Debug)]
+pub(crate) struct LoginSession {
+    options: LoginOptions,
+    /// The current `Cookie` header value and the number of times the client
+    /// has logged in (`None` before the first login).  This is read by every
+    /// request, so it is only locked briefly.
+    current: RwLock<Option<(HeaderValue, u64)>>,
+    /// Held while logging in so that concurrent requests that find the same
+    /// session expired trigger only a single login
+    logging_in: Mutex<()>,
+}
+
+impl LoginSession {
+    pub(crate) fn new(options: LoginOptions) -> LoginSession {
+        LoginSession {
+            options,
+            current: RwLock::new(None),
+            logging_in: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &LoginOptions {
+        &self.options
+    }
+
+    fn get(&self) -> Option<(HeaderValue, u64)> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn set(&self, cookie: HeaderValue, generation: u64) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Some((cookie, generation));
+    }
+
+    /// Return the current `Cookie` header value along with its generation
+    /// number, logging in first if that has not been done yet
+    pub(crate) async fn current(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<(HeaderValue, u64), LoginError> {
+        if let Some(current) = self.get() {
+            return Ok(current);
+        }
+        let _guard = self.logging_in.lock().await;
+        // Another request may have logged in while we waited for the lock
+        if let Some(current) = self.get() {
+            return Ok(current);
+        }
+        let cookie = self.login(client).await?;
+        self.set(cookie.clone(), 1);
+        Ok((cookie, 1))
+    }
+
+    /// Number of times the client has logged in
+    pub(crate) fn logins(&self) -> u64 {
+        self.get().map_or(0, |(_, generation)| generation)
+    }
+
+    /// Replace the session of generation `stale`, which has expired, by
+    /// logging in again.  If the client has already logged in again since
+    /// generation `stale`, nothing is done.
+    pub(crate) async fn refresh(
+        &self,
+        client: &reqwest::Client,
+        stale: u64,
+    ) -> Result<(), LoginError> {
+        let _guard = self.logging_in.lock().await;
+        if self.logins() != stale {
+            return Ok(());
+        }
+        let cookie = self.login(client).await?;
+        self.set(cookie, stale.saturating_add(1));
+        Ok(())
+    }
+
+    /// Update the session of generation `generation` with any cookies set by
+    /// a later response from the server, so that servers that rotate the
+    /// session cookie keep receiving the latest one.  Cookies set after the
+    /// session has been replaced by a new login are ignored.
+    pub(crate) fn update(&self, generation: u64, headers: &HeaderMap) {
+        let set = session_cookies(headers.get_all(SET_COOKIE));
+        if set.is_empty() {
+            return;
+        }
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        let Some((ref mut cookie, g)) = *current else {
+            return;
+        };
+        if g != generation {
+            return;
+        }
+        let Ok(old) = cookie.to_str() else {
+            return;
+        };
+        if let Ok(mut value) = HeaderValue::from_str(&merge_cookies(old, &set)) {
+            value.set_sensitive(true);
+            *cookie = value;
+        }
+    }
+
+    /// Perform the login request and return a `Cookie` header value
+    /// containing the cookies that the response sets
+    async fn login(&self, client: &reqwest::Client) -> Result<HeaderValue, LoginError> {
+        let url = self.options.url.clone();
+        let mut req = client.request(self.options.method.clone(), url.clone());
+        if let Some(body) = self.options.render_body() {
+            req = req
+                .header(CONTENT_TYPE, &self.options.content_type)
+                .body(body);
+        }
+        let resp = req.send().await.map_err(|source| LoginError::Send {
+            url: url.clone(),
+            source,
+        })?;
+        let status = resp.status();
+        if status != self.options.expect_status {
+            return Err(LoginError::Status {
+                url,
+                status,
+                expected: self.options.expect_status,
+            });
+        }
+        let cookies = session_cookies(resp.headers().get_all(SET_COOKIE));
+        if cookies.is_empty() {
+            return Err(LoginError::NoCookies { url });
+        }
+        let mut value = HeaderValue::from_str(&cookies).map_err(|_| LoginError::Invalid { url })?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+/// Combine the `name=value` pairs of a response's `Set-Cookie` headers into a
+/// `Cookie` header value, ignoring the cookies' attributes
+fn session_cookies<'a, I: IntoIterator<Item = &'a HeaderValue>>(headers: I) -> String {
+    headers
+        .into_iter()
+        .filter_map(|h| h.to_str().ok())
+        .filter_map(|h| h.split(';').next())
+        .map(str::trim)
+        .filter(|pair| {
+            pair.split_once('=')
+                .is_some_and(|(name, _)| !name.is_empty())
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Replace the values of the cookies in the `Cookie` header value `old` with
+/// those in `new`, appending any cookies in `new` that `old` lacks
+fn merge_cookies(old: &str, new: &str) -> String {
+    let mut pairs = old
+        .split(';')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect::<Vec<_>>();
+    for (name, value) in new.split("; ").filter_map(|pair| pair.split_once('=')) {
+        match pairs.iter_mut().find(|(n, _)| *n == name) {
+            Some(pair) => pair.1 = value,
+            None => pairs.push((name, value)),
+        }
+    }
+    pairs
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum LoginError {
+    #[error("failed to send login request to {url}")]
+    Send { url: Url, source: reqwest::Error },
+    #[error("login request to {url} returned {status} instead of {expected}; check the credentials and --login-* options")]
+    Status {
+        url: Url,
+        status: StatusCode,
+        expected: StatusCode,
+    },
+    #[error("response to login request to {url} did not set any cookies")]
+    NoCookies { url: Url },
+    #[error("response to login request to {url} set cookies that are not a valid header value")]
+    Invalid { url: Url },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(body: &str, content_type: &str) -> LoginOptions {
+        LoginOptions {
+            url: Url::parse("https://dav.example.com/login").unwrap(),
+            method: Method::POST,
+            body: Some(body.into()),
+            content_type: content_type.into(),
+            expect_status: StatusCode::OK,
+            username: Some("jo bloggs".into()),
+            password: Some("p&ss\"word".into()),
+        }
+    }
+
+    #[test]
+    fn test_render_body() {
+        assert_eq!(
+            options("user={username}&pass={password}", FORM_CONTENT_TYPE)
+                .render_body()
+                .unwrap(),
+            "user=jo+bloggs&pass=p%26ss%22word"
+        );
+        assert_eq!(
+            options(
+                r#"{"user": "{username}", "pass": "{password}"}"#,
+                "application/json"
+            )
+            .render_body()
+            .unwrap(),
+            r#"{"user": "jo bloggs", "pass": "p&ss\"word"}"#
+        );
+    }
+
+    #[test]
+    fn test_session_cookies() {
+        let headers = [
+            HeaderValue::from_static("session=abc123; Path=/; HttpOnly"),
+            HeaderValue::from_static("csrf=xyz"),
+            HeaderValue::from_static("=bogus; Path=/"),
+        ];
+        assert_eq!(session_cookies(&headers), "session=abc123; csrf=xyz");
+    }
+
+    #[test]
+    fn test_merge_cookies() {
+        assert_eq!(
+            merge_cookies("session=abc123; csrf=xyz", "session=def456; lang=en"),
+            "session=def456; csrf=xyz; lang=en"
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        let session = LoginSession::new(options("", FORM_CONTENT_TYPE));
+        session.set(HeaderValue::from_static("session=abc123; csrf=xyz"), 2);
+        let mut headers = HeaderMap::new();
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static("session=def456; Path=/"),
+        );
+        session.update(1, &headers);
+        assert_eq!(
+            session.get().unwrap().0,
+            "session=abc123; csrf=xyz",
+            "cookies from a response to a replaced session should be ignored"
+        );
+        session.update(2, &headers);
+        assert_eq!(
+            session.get().unwrap(),
+            (HeaderValue::from_static("session=def456; csrf=xyz"), 2),
+            "rotated cookie should replace the old one"
+        );
+        session.update(2, &HeaderMap::new());
+        assert_eq!(
+            session.get().unwrap().0,
+            "session=def456; csrf=xyz",
+            "responses without cookies should leave the session alone"
+        );
+    }
+}
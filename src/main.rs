@@ -1,38 +1,259 @@
+mod adaptive;
+mod autoindex;
+mod backend;
+mod bandwidth;
+mod batch;
 mod btn;
+mod cache;
+mod checkpoint;
 mod client;
+mod clockskew;
+mod columnar;
+mod compress;
+mod conditional;
+mod conformance;
+mod consistency;
+mod dedup;
+mod diff;
+mod dryrun;
+mod errorrate;
+mod export;
+mod filter;
+mod hook;
+mod incremental;
+mod inventory;
+mod jitter;
+mod lint;
+mod lockbench;
+mod login;
+mod maintenance;
+mod metrics;
+mod notify;
+mod plot;
+mod preflight;
+mod progress;
+mod propnames;
+mod ramp;
+mod rangebench;
+mod replay;
+mod retry;
+mod runtime;
+mod s3;
 mod show_duration;
+mod sigv4;
+mod simulate;
+mod stats;
+mod strategy;
+mod table;
+mod throttle;
+mod token;
 mod traverse;
 mod types;
+mod usl;
+mod verify;
+mod warm;
+mod watch;
+mod writebench;
 mod xml;
-use crate::client::Client;
-use crate::show_duration::show_duration_as_seconds;
-use crate::traverse::{traverse, TraversalReport};
+mod zarr;
+use crate::adaptive::AdaptiveRange;
+use crate::autoindex::compare_discovery;
+use crate::batch::{
+    analyze, load_samples, merge_reports, JsonDetail, JsonMode, Output, SamplingPlan, StatManager,
+    SubtreePicker, SummaryFormat, Targets,
+};
+use crate::btn::{DispatchOrder, PanicPolicy};
+use crate::cache::CacheOptions;
+use crate::client::{take_userinfo, Auth, Client, ClientOptions, FileMethod, HostLimit};
+use crate::clockskew::ClockSkew;
+use crate::columnar::RequestParquet;
+use crate::conformance::check_conformance;
+use crate::consistency::{ConsistencyTracker, InventoryCheck, InventoryCounts};
+use crate::diff::ReportDiff;
+use crate::dryrun::{BatchPlan, Probe};
+use crate::errorrate::ErrorRateLimit;
+use crate::export::{
+    benchmark_entries, tidy_rows, write_benchmark_json, write_hgrm, write_tidy_file,
+    write_vega_lite,
+};
+use crate::filter::{normalize_extension, EntryFilter, FileSample, PathPattern};
+use crate::hook::CompletionHook;
+use crate::inventory::Inventory;
+use crate::jitter::{jitter, JitterOptions};
+use crate::lint::lint;
+use crate::lockbench::lock_bench;
+use crate::login::{LoginOptions, FORM_CONTENT_TYPE};
+use crate::maintenance::MaintenanceOptions;
+use crate::metrics::start_server;
+use crate::notify::{BatchNotice, NoticeEvent, Notifier};
+use crate::plot::{plot_svg, write_gnuplot};
+use crate::preflight::preflight;
+use crate::progress::{report_sample, report_scaling, BatchProgress, ProgressFormat, Quietness};
+use crate::propnames::discover_propnames;
+use crate::ramp::Ramp;
+use crate::rangebench::{range_bench, RangeBenchOptions};
+use crate::replay::{read_error_log, retry_failed};
+use crate::retry::{BreakerOptions, RetryConfig, RetryOptions, RetryRule};
+use crate::runtime::RuntimeConfig;
+use crate::sigv4::{SigV4Credentials, SigV4Options, SigV4Scope};
+use crate::simulate::SimulationSpec;
+use crate::stats::{Sample, WorkerSamples};
+use crate::strategy::{compare_strategies, print_comparison};
+use crate::token::run_token_command;
+use crate::traverse::{traverse, ErrorPolicy, TraversalOptions};
+use crate::types::{
+    parse_bounded_percent, parse_byte_rate, parse_interval, parse_label, parse_latency,
+    parse_percent, parse_positive_latency, parse_timestamp, parse_url_list, split_batch_args,
+    BatchArg,
+};
+use crate::verify::{remote_collections, verify};
+use crate::warm::{warm, WarmOrder};
+use crate::watch::{watch, RotatingLog, WatchOptions};
+use crate::writebench::{create_tree, join_url, print_summary, TreeSpec};
+use crate::xml::XmlOptions;
+use crate::zarr::ZarrBoundary;
 use anyhow::Context;
-use clap::{Parser, Subcommand};
-use serde::Serialize;
-use statrs::statistics::{Data, Distribution};
-use std::collections::BTreeMap;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::num::NonZeroUsize;
+use bytes::Bytes;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use reqwest::header::HeaderValue;
+use reqwest::{Method, StatusCode};
+use std::net::SocketAddr;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use time::OffsetDateTime;
 use url::Url;
 
+#[cfg(all(feature = "tokio-console", not(tokio_unstable)))]
+compile_error!(
+    "The tokio-console feature requires building with RUSTFLAGS=\"--cfg tokio_unstable\""
+);
+
 /// Traverse WebDAV hierarchies using concurrent tasks
-#[derive(Clone, Debug, Eq, Parser, PartialEq)]
+#[derive(Clone, Debug, Parser, PartialEq)]
 struct Arguments {
+    /// Serve instrumentation of the runtime's tasks for inspection with
+    /// tokio-console
+    #[arg(long, global = true)]
+    tokio_console: bool,
+
+    /// Run all tasks on a single thread instead of a multi-threaded runtime
+    #[arg(long, global = true, conflicts_with = "runtime_threads")]
+    current_thread: bool,
+
+    /// Number of worker threads for the multi-threaded runtime [default: one
+    /// per CPU]
+    #[arg(long, global = true, value_name = "INT")]
+    runtime_threads: Option<NonZeroUsize>,
+
     #[command(subcommand)]
     command: Command,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Subcommand)]
+#[derive(Clone, Debug, PartialEq, Subcommand)]
 enum Command {
     /// Traverse a hierarchy once
     Run {
-        /// Do not print details on each request as it's completed
-        #[arg(short, long)]
-        quiet: bool,
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        #[command(flatten)]
+        traversal_args: TraversalArgs,
+
+        /// Periodically save the traversal's progress to the given file.  If
+        /// the file already exists, resume the traversal recorded in it.
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+
+        /// Once the traversal completes, write an inventory of the resources
+        /// found to the given file for use with the `warm` command
+        #[arg(long, value_name = "FILE", conflicts_with = "checkpoint")]
+        tree_file: Option<PathBuf>,
+
+        /// Do not print details on each request as it's completed.  Can be
+        /// given multiple times for less output.
+        #[arg(short, long, action = ArgAction::Count)]
+        quiet: u8,
+
+        /// Write the distribution of request latencies to the given file in
+        /// HdrHistogram's `.hgrm` percentile format
+        #[arg(long, value_name = "FILE", conflicts_with = "low_memory")]
+        hgrm: Option<PathBuf>,
+
+        /// Write a JSON object giving the start & end times of each request
+        /// to the given file as each request completes
+        #[arg(long, value_name = "FILE")]
+        request_log: Option<PathBuf>,
+
+        /// Write a JSON object describing each failed request, including an
+        /// equivalent `curl` command, to the given file as each request fails
+        #[arg(long, value_name = "FILE")]
+        error_log: Option<PathBuf>,
+
+        /// Write a JSON object giving the path, size, and ETag of each file
+        /// found inside a Zarr store to the given file.  Implies `--etags`
+        /// and `--sizes`.
+        #[arg(long, value_name = "FILE")]
+        zarr_manifest: Option<PathBuf>,
+
+        /// After traversing, make an `If-None-Match` request for each file
+        /// with the ETag from its collection listing and check that the
+        /// server responds with 304 Not Modified.  Implies `--etags`.
+        #[arg(long, conflicts_with = "simulate")]
+        check_conditional: bool,
+
+        /// Write the URL of each file found to the given file, one per line
+        #[arg(long, value_name = "FILE")]
+        urls_out: Option<PathBuf>,
+
+        /// Load the ETags of the collections found by the previous traversal
+        /// from the given file, if it exists, and save those found by this
+        /// traversal to it.  Implies `--etags`.
+        #[arg(long, value_name = "FILE", conflicts_with = "simulate")]
+        etag_state: Option<PathBuf>,
+
+        /// Do not list collections whose ETags are unchanged from those in
+        /// the `--etag-state` file
+        #[arg(long, requires = "etag_state")]
+        skip_unchanged: bool,
+
+        /// Follow each URL written to the `--urls-out` file with a tab and
+        /// the URL that the file ultimately redirects to, if any
+        #[arg(long, requires = "urls_out")]
+        urls_out_targets: bool,
+
+        /// Show the URLs in the details on each request relative to the base
+        /// URL
+        #[arg(long)]
+        relative: bool,
+
+        /// Print the details on each request (and write the `--tree-file`
+        /// inventory) in path order once the traversal completes rather than
+        /// in order of completion
+        #[arg(long)]
+        sort: bool,
+
+        /// Prefix the details on each request with the time (in RFC 3339
+        /// format) at which the request completed
+        #[arg(long)]
+        timestamps: bool,
+
+        /// Also traverse the collection at the given URL, using the same pool
+        /// of workers.  Can be given multiple times.
+        #[arg(long = "root", value_name = "URL", conflicts_with = "checkpoint")]
+        roots: Vec<Url>,
+
+        /// Also traverse the collections at the URLs listed in the given
+        /// file, one per line
+        #[arg(long, value_name = "FILE", conflicts_with = "checkpoint")]
+        roots_file: Option<PathBuf>,
+
+        /// Run the given shell command after each traversal completes, with
+        /// details of the traversal passed in `BATCHDAV_*` environment
+        /// variables
+        #[arg(long, value_name = "COMMAND")]
+        on_complete: Option<String>,
 
         /// The root URL of the hierarchy
         base_url: Url,
@@ -41,190 +262,2286 @@ enum Command {
         workers: usize,
     },
 
+    /// Replay the requests recorded in an inventory from `run --tree-file`
+    /// without rediscovering the hierarchy
+    Warm {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The inventory file to replay
+        #[arg(long, value_name = "FILE")]
+        tree: PathBuf,
+
+        /// The order in which to issue the requests
+        #[arg(long, default_value = "listed")]
+        order: WarmOrder,
+
+        /// Do not print details on each request as it's completed
+        #[arg(short, long, action = ArgAction::Count)]
+        quiet: u8,
+
+        /// Maximum number of requests to have active at once
+        workers: usize,
+    },
+
     /// Traverse a hierarchy multiple times and summarize the results
     Batch {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        #[command(flatten)]
+        traversal_args: TraversalArgs,
+
+        /// Format in which to output the per-workers summary
+        #[arg(
+            short = 'F',
+            long,
+            default_value = "csv",
+            conflicts_with_all = ["json_file", "per_traversal_stats"]
+        )]
+        format: SummaryFormat,
+
         #[arg(short = 'J', long, conflicts_with = "per_traversal_stats")]
         json_file: Option<PathBuf>,
 
+        /// If the `--json-file` file already exists, append the new
+        /// traversals to the report in it instead of overwriting it
+        #[arg(long, requires = "json_file")]
+        append: bool,
+
+        /// Include the start & end times of every request in each traversal
+        /// report in the `--json-file` file.  Equivalent to `--detail full`.
+        #[arg(long, requires = "json_file", conflicts_with = "low_memory")]
+        request_timestamps: bool,
+
+        /// How much detail on each traversal to include in the
+        /// `--json-file` file: only aggregates ("summary"), also the
+        /// individual request latencies ("timings"), or also a complete
+        /// record of each request ("full")
+        #[arg(
+            long,
+            default_value = "timings",
+            value_name = "LEVEL",
+            requires = "json_file",
+            conflicts_with = "request_timestamps"
+        )]
+        detail: JsonDetail,
+
+        /// Continue an interrupted batch whose report was written to the
+        /// `--json-file` file, running only the traversals not already
+        /// recorded in it
+        #[arg(long, requires = "json_file", conflicts_with = "append")]
+        resume: bool,
+
+        /// Write the per-traversal or per-workers output to the given file
+        /// instead of standard output
+        #[arg(long, value_name = "FILE", conflicts_with = "json_file")]
+        csv_file: Option<PathBuf>,
+
+        /// Comma-separated list of the columns to include in the
+        /// per-traversal or per-workers CSV output, in order
+        #[arg(
+            long,
+            value_name = "FIELD,...",
+            value_delimiter = ',',
+            conflicts_with = "json_file"
+        )]
+        fields: Vec<String>,
+
+        /// Format in which to report progress on standard error
+        #[arg(long, default_value = "text")]
+        progress_format: ProgressFormat,
+
+        /// Prefix each "Finished" message with the time (in RFC 3339 format)
+        /// at which the traversal completed
+        #[arg(long)]
+        timestamps: bool,
+
+        /// Suppress progress messages: given twice, suppress the
+        /// "Finished" messages; given three times, suppress all progress
+        /// reports, including JSON progress events
+        #[arg(short, long, action = ArgAction::Count)]
+        quiet: u8,
+
+        /// Test whether the traversal times for each number of workers differ
+        /// significantly from those for the next smaller number of workers,
+        /// and include the results in the per-workers summary
+        #[arg(long, conflicts_with_all = ["json_file", "per_traversal_stats"])]
+        significance: bool,
+
+        /// Also render charts of time & throughput versus number of workers
+        /// to the given SVG file
+        #[arg(long, value_name = "FILE")]
+        plot: Option<PathBuf>,
+
+        /// Also write the per-workers means & standard deviations to
+        /// `<PREFIX>.dat` along with a gnuplot script `<PREFIX>.gp` that
+        /// charts them
+        #[arg(long, value_name = "PREFIX")]
+        gnuplot: Option<PathBuf>,
+
+        /// Also write the results of each traversal to the given file in long
+        /// format, one record per traversal.  The output is CSV if the
+        /// filename ends in `.csv` and JSON otherwise.
+        #[arg(long, value_name = "FILE")]
+        tidy_file: Option<PathBuf>,
+
+        /// Also write the start & end times of every request made in each
+        /// traversal to the given file in Parquet format, one row per request
+        #[arg(long, value_name = "FILE", conflicts_with = "low_memory")]
+        parquet: Option<PathBuf>,
+
+        /// Also write a Vega-Lite chart specification (with the data
+        /// embedded) to the given file
+        #[arg(long, value_name = "FILE")]
+        vega_lite: Option<PathBuf>,
+
+        /// Also write the mean traversal time for each number of workers to
+        /// the given file as a JSON array of `{name, unit, value}` objects,
+        /// as consumed by github-action-benchmark
+        #[arg(long, value_name = "FILE")]
+        benchmark_json: Option<PathBuf>,
+
         /// Emit a CSV line for each traversal rather than for each set of
         /// traversals per worker quantity
         #[arg(short = 'T', long)]
         per_traversal_stats: bool,
 
+        /// Instead of running the batch, perform a single probe traversal with
+        /// the largest number of workers and print the planned traversals
+        /// along with estimates of how many requests they will make and how
+        /// long they will take
+        #[arg(long, conflicts_with_all = ["resume", "targets"])]
+        dry_run: bool,
+
+        /// Do not check that each base URL is a WebDAV collection that can be
+        /// listed before starting its traversals
+        #[arg(long)]
+        no_preflight: bool,
+
+        /// With `--dry-run`, take the number of requests per traversal from
+        /// the given inventory written by `run --tree-file` instead of
+        /// performing a probe traversal.  Times are then not estimated.
+        #[arg(long, value_name = "FILE", requires = "dry_run")]
+        tree_file: Option<PathBuf>,
+
         /// Number of traversals to make for each number of workers
         #[arg(short, long, default_value = "10")]
         samples: NonZeroUsize,
 
+        /// Instead of performing a fixed number of traversals for each
+        /// number of workers, keep traversing until the 95% confidence
+        /// interval for the mean time is within the given percentage of the
+        /// mean.  `--samples` then gives the minimum number of traversals.
+        #[arg(long, value_name = "PCT", value_parser = parse_percent)]
+        until_ci: Option<f64>,
+
+        /// Report the number of workers after which the step to the next
+        /// larger number of workers yields less than the given percentage of
+        /// a linear throughput gain
+        #[arg(long, default_value = "25", value_name = "PCT", value_parser = parse_percent)]
+        knee_threshold: f64,
+
+        /// Maximum number of traversals to perform for each number of workers
+        /// with `--until-ci`
+        #[arg(long, default_value = "100", requires = "until_ci")]
+        max_samples: NonZeroUsize,
+
+        /// Start each traversal at a randomly chosen collection in the
+        /// hierarchy rather than at the base URL.  The same sequence of
+        /// starting points is used for every number of workers.
+        #[arg(long)]
+        random_subtree: bool,
+
+        /// Seed for the random number generator used by `--random-subtree`
+        #[arg(long, requires = "random_subtree")]
+        seed: Option<u64>,
+
+        /// Compare the sets of resources found by the traversals and report
+        /// any that were found by some traversals but not others
+        #[arg(long, conflicts_with = "random_subtree")]
+        check_consistency: bool,
+
+        /// Check that every traversal found the same numbers of collections &
+        /// files as the first one, warning about any that did not and exiting
+        /// with a nonzero status once the batch completes
+        #[arg(long, conflicts_with = "random_subtree")]
+        verify_inventory: bool,
+
+        /// With `--verify-inventory`, also check that every traversal found
+        /// the same set of URLs
+        #[arg(long, requires = "verify_inventory")]
+        verify_urls: bool,
+
+        /// Serve Prometheus metrics at `/metrics` and the current traversal's
+        /// progress at `/status` over HTTP on the given address
+        #[arg(long, value_name = "ADDR")]
+        metrics_addr: Option<SocketAddr>,
+
+        /// Run the given shell command after each traversal completes and
+        /// again after the whole batch completes, with details passed in
+        /// `BATCHDAV_*` environment variables
+        #[arg(long, value_name = "COMMAND")]
+        on_complete: Option<String>,
+
+        /// POST a JSON summary of the batch to the given URL when it
+        /// completes (or, with `--targets`, when each target's batch
+        /// completes)
+        #[arg(long, value_name = "URL")]
+        notify_url: Option<Url>,
+
+        /// Compare the traversal times for each number of workers against
+        /// those recorded in the given `batch --json-file` report and report
+        /// any that became significantly slower
+        #[arg(long, value_name = "FILE", conflicts_with = "targets")]
+        baseline: Option<PathBuf>,
+
+        /// With `--baseline`, the smallest percentage increase in mean
+        /// traversal time that counts as a regression
+        #[arg(
+            long,
+            default_value = "10",
+            value_name = "PCT",
+            value_parser = parse_percent,
+            requires = "baseline"
+        )]
+        regression_threshold: f64,
+
+        /// Run the batch for each of the base URLs listed in the given file
+        /// (`-` for standard input, which is read as the batch progresses),
+        /// one per line, each optionally followed by whitespace and a label
+        /// for its results.  The base URL argument is then omitted.
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = [
+                "json_file",
+                "plot",
+                "gnuplot",
+                "tidy_file",
+                "vega_lite",
+                "benchmark_json",
+                "random_subtree",
+                "check_consistency",
+            ]
+        )]
+        targets: Option<PathBuf>,
+
+        /// The root URL of the hierarchy (omitted when `--targets` is given),
+        /// followed by the varying worker amounts to run the traversal with
+        #[arg(value_name = "BASE_URL> <WORKERS", required = true)]
+        args: Vec<BatchArg>,
+    },
+
+    /// Recompute the per-workers summary statistics for the traversals
+    /// recorded in a `batch --json-file` report
+    Analyze {
+        /// Format in which to output the summary
+        #[arg(short = 'F', long, default_value = "csv")]
+        format: SummaryFormat,
+
+        /// Comma-separated list of the columns to include in the CSV output,
+        /// in order
+        #[arg(long, value_name = "FIELD,...", value_delimiter = ',')]
+        fields: Vec<String>,
+
+        /// Test whether the traversal times for each number of workers differ
+        /// significantly from those for the next smaller number of workers
+        #[arg(long)]
+        significance: bool,
+
+        /// Write the summary to the given file instead of standard output
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Report the number of workers after which the step to the next
+        /// larger number of workers yields less than the given percentage of
+        /// a linear throughput gain
+        #[arg(long, default_value = "25", value_name = "PCT", value_parser = parse_percent)]
+        knee_threshold: f64,
+
+        /// The JSON report to analyze
+        report: PathBuf,
+    },
+
+    /// Combine the traversals recorded in multiple `batch --json-file`
+    /// reports into a single report
+    Merge {
+        /// The file to write the combined report to
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// The JSON reports to combine
+        #[arg(required = true, num_args = 2..)]
+        reports: Vec<PathBuf>,
+    },
+
+    /// Compare the traversal times recorded in two `batch --json-file`
+    /// reports for each number of workers
+    Diff {
+        /// Also write the comparison as JSON to the given file
+        #[arg(short = 'J', long, value_name = "FILE")]
+        json_file: Option<PathBuf>,
+
+        /// The report to compare against
+        old: PathBuf,
+
+        /// The report to compare
+        new: PathBuf,
+    },
+
+    /// Compare traversing a hierarchy recursively with `Depth: 1` requests
+    /// against listing it with a single `Depth: infinity` request
+    StrategyCompare {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// Format in which to output the per-workers summary
+        #[arg(short = 'F', long, default_value = "csv")]
+        format: SummaryFormat,
+
+        /// Number of traversals to make with each strategy for each number of
+        /// workers
+        #[arg(short, long, default_value = "10")]
+        samples: NonZeroUsize,
+
         /// The root URL of the hierarchy
         base_url: Url,
 
-        /// Varying worker amounts to run the traversal with
+        /// Varying worker amounts to run the traversals with
+        workers_list: Vec<usize>,
+    },
+
+    /// Compare discovering a hierarchy via PROPFIND against discovering it
+    /// via the HTML index pages returned for GET requests on collections
+    DiscoveryCompare {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The root URL of the hierarchy
+        base_url: Url,
+
+        /// Maximum number of requests to have active at once
+        workers: usize,
+    },
+
+    /// Report which properties are defined on the resources in a hierarchy
+    Propnames {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The root URL of the hierarchy
+        base_url: Url,
+
+        /// Maximum number of tasks to have active at once
+        workers: usize,
+    },
+
+    /// Report the storage quota of a collection
+    Quota {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The URL of the collection
+        url: Url,
+    },
+
+    /// Benchmark repeatedly locking & unlocking a resource
+    LockBench {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// Number of lock/unlock cycles for each worker to perform
+        #[arg(short = 'n', long, default_value = "100")]
+        cycles: usize,
+
+        /// The URL of the resource to lock
+        url: Url,
+
+        /// Number of workers concurrently locking the resource
+        workers: usize,
+    },
+
+    /// Benchmark downloading a sample of the files in a hierarchy with
+    /// parallel ranged GET requests
+    RangeBench {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// Number of files to sample from the hierarchy
+        #[arg(short = 'n', long, default_value = "10")]
+        files: usize,
+
+        /// Size in bytes of each ranged request
+        #[arg(long, default_value = "8388608", value_name = "BYTES")]
+        chunk_size: NonZeroU64,
+
+        /// Number of ranged requests to have in flight for each file at once
+        #[arg(short = 'P', long, default_value = "4")]
+        parallel: NonZeroUsize,
+
+        /// Maximum number of redirects to follow when resolving each file
+        #[arg(long, default_value_t = 10, value_name = "INT")]
+        max_redirects: usize,
+
+        /// The URL of the hierarchy from which to sample files
+        base_url: Url,
+
+        /// Number of workers to use when traversing the hierarchy
+        workers: usize,
+    },
+
+    /// Measure the latency jitter of repeatedly probing a fixed sample of the
+    /// files in a hierarchy
+    Jitter {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// Number of files to sample from the hierarchy
+        #[arg(short = 'n', long, default_value = "100")]
+        files: usize,
+
+        /// Number of times to probe each sampled file for each number of
+        /// workers
+        #[arg(short, long, default_value = "50")]
+        rounds: NonZeroUsize,
+
+        /// Also write the per-URL latencies & statistics as JSON to the given
+        /// file
+        #[arg(short = 'J', long, value_name = "FILE")]
+        json_file: Option<PathBuf>,
+
+        /// The URL of the hierarchy from which to sample files
+        base_url: Url,
+
+        /// Varying worker amounts to probe the sample with
+        #[arg(required = true)]
+        workers_list: Vec<usize>,
+    },
+
+    /// Benchmark creating synthetic hierarchies with MKCOL & PUT requests
+    WriteBench {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// Format in which to output the per-workers summary
+        #[arg(short = 'F', long, default_value = "csv")]
+        format: SummaryFormat,
+
+        /// Number of levels of subcollections to create below each tree's
+        /// root
+        #[arg(long, default_value = "2")]
+        depth: usize,
+
+        /// Number of subcollections to create in each collection
+        #[arg(long, default_value = "3")]
+        fanout: usize,
+
+        /// Number of files to create in each collection
+        #[arg(long, default_value = "5")]
+        files: usize,
+
+        /// Size in bytes of each file
+        #[arg(long, default_value = "1024", value_name = "BYTES")]
+        file_size: usize,
+
+        /// Delete each tree after creating it
+        #[arg(long)]
+        cleanup: bool,
+
+        /// Number of trees to create for each number of workers
+        #[arg(short, long, default_value = "1")]
+        samples: NonZeroUsize,
+
+        /// The collection in which to create the trees
+        base_url: Url,
+
+        /// Varying worker amounts to create the trees with
         workers_list: Vec<usize>,
     },
+
+    /// Compare a remote hierarchy against a local directory
+    Verify {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The root URL of the hierarchy
+        base_url: Url,
+
+        /// The local directory to compare against
+        local_path: PathBuf,
+
+        /// Maximum number of requests to have active at once
+        workers: usize,
+    },
+
+    /// Traverse a hierarchy and report WebDAV hygiene issues in the server's
+    /// multistatus responses
+    Lint {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The root URL of the hierarchy
+        base_url: Url,
+
+        /// Maximum number of requests to have active at once
+        #[arg(default_value = "1")]
+        workers: usize,
+    },
+
+    /// Re-issue the failed requests recorded in an error log written by `run
+    /// --error-log` and report whether they now succeed
+    RetryFailed {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The error log to read failed requests from
+        error_log: PathBuf,
+
+        /// Maximum number of requests to have active at once
+        #[arg(default_value = "1")]
+        workers: usize,
+    },
+
+    /// Traverse a hierarchy repeatedly at a fixed interval and emit a summary
+    /// of each traversal
+    Watch {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        #[command(flatten)]
+        traversal_args: TraversalArgs,
+
+        /// Time between the starts of consecutive traversals, given as an
+        /// integer followed by an optional unit of s, m, h, or d
+        #[arg(long, default_value = "10m", value_parser = parse_interval)]
+        interval: Duration,
+
+        /// Stop after the given number of traversals instead of running
+        /// forever
+        #[arg(long, value_name = "INT")]
+        count: Option<NonZeroUsize>,
+
+        /// Also append each traversal's summary to the given file
+        #[arg(long, value_name = "FILE")]
+        log_file: Option<PathBuf>,
+
+        /// Rotate the log file once writing to it would make it larger than
+        /// the given number of bytes
+        #[arg(
+            long,
+            default_value = "10485760",
+            value_name = "BYTES",
+            requires = "log_file"
+        )]
+        log_max_bytes: u64,
+
+        /// Number of rotated log files to keep
+        #[arg(long, default_value = "5", value_name = "INT", requires = "log_file")]
+        log_keep: usize,
+
+        /// Serve Prometheus metrics at `/metrics` and the current traversal's
+        /// progress at `/status` over HTTP on the given address
+        #[arg(long, value_name = "ADDR")]
+        metrics_addr: Option<SocketAddr>,
+
+        /// Run the given shell command after each traversal (including
+        /// failed ones), with details passed in `BATCHDAV_*` environment
+        /// variables
+        #[arg(long, value_name = "COMMAND")]
+        on_complete: Option<String>,
+
+        /// The root URL of the hierarchy
+        base_url: Url,
+
+        /// Maximum number of requests to have active at once
+        workers: usize,
+    },
+
+    /// Check a server's handling of a battery of PROPFIND requests against
+    /// RFC 4918
+    Conformance {
+        #[command(flatten)]
+        client_args: ClientArgs,
+
+        /// The URL of a collection to make the requests against
+        url: Url,
+    },
+}
+
+/// Options controlling the requests made during traversal
+#[derive(Clone, Debug, Eq, PartialEq, Args)]
+struct ClientArgs {
+    /// Also request the sizes of files and report the total number of bytes
+    #[arg(long)]
+    sizes: bool,
+
+    /// Also request the display names of resources and show them in the
+    /// per-request output
+    #[arg(long)]
+    display_names: bool,
+
+    /// Also request the ETags of resources and report groups of files that
+    /// share an ETag
+    #[arg(long)]
+    etags: bool,
+
+    /// Also request the modification times of resources and report
+    /// statistics on them
+    #[arg(long)]
+    mtimes: bool,
+
+    /// Also request the MIME types of resources and report file counts &
+    /// sizes per type
+    #[arg(long)]
+    content_types: bool,
+
+    /// Authenticate with HTTP Basic authentication as the given user.  The
+    /// password is taken from the `BATCHDAV_PASSWORD` environment variable
+    /// unless `--password-prompt` is given.
+    #[arg(long, value_name = "NAME")]
+    user: Option<String>,
+
+    /// Prompt for the Basic authentication password on the terminal
+    #[arg(long, requires = "user")]
+    password_prompt: bool,
+
+    /// Obtain a bearer token by running the given shell command and reading
+    /// the token from its standard output.  The command is run again to get
+    /// a fresh token whenever a request is rejected with a 401, after which
+    /// the request is retried.
+    #[arg(long, value_name = "COMMAND", conflicts_with = "user")]
+    token_command: Option<String>,
+
+    /// Sign requests with AWS Signature Version 4 for the given region and
+    /// service, using the credentials in the `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY`, and `AWS_SESSION_TOKEN` environment
+    /// variables
+    #[arg(
+        long,
+        value_name = "REGION:SERVICE",
+        conflicts_with_all = ["user", "token_command"]
+    )]
+    sigv4: Option<SigV4Scope>,
+
+    /// Before making any other requests, log in by sending a request to the
+    /// given URL, and send the cookies set by its response with all requests
+    /// to the base URL's origin.  The credentials given with `--user` or in
+    /// the base URL are used only for the login.  The login is repeated
+    /// whenever a request is rejected with a 401 or redirected to the login
+    /// URL, after which the request is retried.
+    #[arg(
+        long,
+        value_name = "URL",
+        requires = "login_body",
+        conflicts_with_all = ["token_command", "sigv4"]
+    )]
+    login_url: Option<Url>,
+
+    /// The HTTP method of the login request
+    #[arg(
+        long,
+        default_value = "POST",
+        value_name = "METHOD",
+        requires = "login_url"
+    )]
+    login_method: Method,
+
+    /// The body of the login request, in which `{username}` and `{password}`
+    /// are replaced by the credentials, encoded to suit `--login-content-type`
+    #[arg(long, value_name = "TEMPLATE", requires = "login_url")]
+    login_body: Option<String>,
+
+    /// The `Content-Type` of the login request body
+    #[arg(
+        long,
+        default_value = FORM_CONTENT_TYPE,
+        value_name = "TYPE",
+        requires = "login_url"
+    )]
+    login_content_type: String,
+
+    /// The status with which the server responds to a successful login
+    #[arg(
+        long,
+        default_value = "200",
+        value_name = "CODE",
+        requires = "login_url"
+    )]
+    login_status: StatusCode,
+
+    /// Send the contents of the given file as the body of PROPFIND requests.
+    /// The body must request the `resourcetype` property.
+    #[arg(long, value_name = "FILE")]
+    propfind_body: Option<PathBuf>,
+
+    /// Store successful PROPFIND responses in the given directory and answer
+    /// repeated PROPFIND requests from it instead of the server while the
+    /// stored responses are fresher than `--cache-ttl`
+    #[arg(long, value_name = "DIR")]
+    propfind_cache: Option<PathBuf>,
+
+    /// How long PROPFIND responses stored with `--propfind-cache` remain
+    /// usable, given as an integer followed by an optional unit of s, m, h,
+    /// or d
+    #[arg(
+        long,
+        default_value = "1h",
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        requires = "propfind_cache"
+    )]
+    cache_ttl: Duration,
+
+    /// Retry each request up to the given number of times if it fails with a
+    /// connection error, a 429 response, or a 5xx response [default: 0]
+    #[arg(long, value_name = "INT")]
+    retries: Option<u32>,
+
+    /// Override the number of retries for one class of failure: "connection",
+    /// "timeout", "5xx", 429, or a specific 5xx status code.  Can be given
+    /// multiple times.
+    #[arg(long, value_name = "CLASS=INT")]
+    retry_policy: Vec<RetryRule>,
+
+    /// Read the retry count, retry budget, & per-class retry policy from the
+    /// given TOML file.  Options given on the command line take precedence.
+    #[arg(long, value_name = "FILE")]
+    retry_config: Option<PathBuf>,
+
+    /// Perform at most the given number of retries in total across all
+    /// requests
+    #[arg(long, value_name = "INT")]
+    retry_budget: Option<usize>,
+
+    /// Stop sending requests after the given number of consecutive transient
+    /// failures, resuming with a single probe request after the cooldown
+    #[arg(long, value_name = "INT")]
+    breaker_threshold: Option<NonZeroUsize>,
+
+    /// Number of seconds to wait after the circuit breaker opens before
+    /// probing the server
+    #[arg(long, default_value_t = 5, value_name = "SECS")]
+    breaker_cooldown: u64,
+
+    /// When the server responds with a 503 without a `Retry-After` header (or
+    /// a `--maintenance-status`), pause all requests, probe the base URL
+    /// periodically, and resume once the server has recovered
+    #[arg(long)]
+    pause_on_maintenance: bool,
+
+    /// Also treat responses with the given status as meaning that the server
+    /// is down for maintenance.  Can be given multiple times.
+    #[arg(long, value_name = "CODE", requires = "pause_on_maintenance")]
+    maintenance_status: Vec<StatusCode>,
+
+    /// How often to probe the base URL while paused for maintenance, given
+    /// as an integer followed by an optional unit of s, m, h, or d
+    #[arg(
+        long,
+        default_value = "10s",
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        requires = "pause_on_maintenance"
+    )]
+    maintenance_probe_interval: Duration,
+
+    /// Stop pausing for maintenance once an outage has lasted the given
+    /// interval (an integer followed by an optional unit of s, m, h, or d),
+    /// treating maintenance responses as ordinary failures from then on
+    #[arg(
+        long,
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        requires = "pause_on_maintenance"
+    )]
+    max_outage: Option<Duration>,
+
+    /// Make at most the given number of concurrent requests to the given
+    /// host.  Can be given multiple times.
+    #[arg(long, value_name = "HOST=INT")]
+    per_host_limit: Vec<HostLimit>,
+
+    /// Reject XML responses with elements nested more than the given number
+    /// of levels deep
+    #[arg(long, default_value_t = XmlOptions::DEFAULT_MAX_DEPTH, value_name = "INT")]
+    max_xml_depth: usize,
+
+    /// Reject XML responses containing more than the given number of tokens
+    /// (start tags, end tags, and text nodes)
+    #[arg(long, default_value_t = XmlOptions::DEFAULT_MAX_TOKENS, value_name = "INT")]
+    max_xml_tokens: usize,
+
+    /// Decode XML responses using the encoding named in their XML
+    /// declarations in preference to the charset in the `Content-Type` header
+    #[arg(long)]
+    prefer_xml_encoding: bool,
+
+    /// Treat PROPFIND responses whose `Content-Type` is not an XML type as
+    /// errors instead of warning about them
+    #[arg(long)]
+    strict_content_type: bool,
+
+    /// Open a new connection for every request instead of reusing
+    /// connections
+    #[arg(long)]
+    no_keepalive: bool,
+
+    /// Abandon each request attempt that has not completed within the given
+    /// duration (a positive integer followed by a unit of us, ms, or s),
+    /// treating it as a timeout failure
+    #[arg(long, value_name = "DURATION", value_parser = parse_positive_latency)]
+    timeout: Option<Duration>,
+
+    /// Send a duplicate of each GET, HEAD, OPTIONS, or PROPFIND request
+    /// attempt that has not received a response within the given duration
+    /// (a positive integer followed by a unit of us, ms, or s) and use
+    /// whichever finishes first, cancelling the other
+    #[arg(long, value_name = "DURATION", value_parser = parse_positive_latency)]
+    hedge_after: Option<Duration>,
+
+    /// Read the bodies of GET responses for files (e.g., with `--file-method
+    /// get`, `range-bench`, or `--measure-bandwidth`) at no more than the
+    /// given number of bytes per second in total across all requests.  The
+    /// rate may be followed by a unit of k, m, or g (powers of 1024).
+    #[arg(long, value_name = "BYTES/S", value_parser = parse_byte_rate)]
+    limit_rate: Option<NonZeroU64>,
+
+    /// Send the given string as the `User-Agent` header instead of the
+    /// default
+    #[arg(long, value_name = "STRING", value_parser = HeaderValue::from_str)]
+    user_agent: Option<HeaderValue>,
+
+    /// Send a unique `X-Request-Id` header with each request and, during
+    /// traversals, an `X-Traversal-Id` header identifying the traversal
+    #[arg(long)]
+    request_ids: bool,
+
+    /// Send a W3C Trace Context `traceparent` header with each request, with
+    /// all requests in a traversal belonging to the same trace
+    #[arg(long)]
+    trace_context: bool,
+
+    /// Tolerate recoverable deviations from the expected structure of XML
+    /// responses, reporting them as warnings instead of failing
+    #[arg(long)]
+    lenient_xml: bool,
+
+    /// The HTTP method(s) with which to probe non-collection resources
+    #[arg(long, value_enum, default_value = "head", value_name = "METHOD")]
+    file_method: FileMethod,
+}
+
+impl ClientArgs {
+    /// Construct the client options, moving any credentials embedded in
+    /// `base_url` into the options' authentication settings
+    fn into_options(self, base_url: &mut Url) -> anyhow::Result<ClientOptions> {
+        let url_auth = take_userinfo(base_url);
+        self.into_options_with(url_auth)
+    }
+
+    /// Construct the client options, using `url_auth` as the credentials
+    /// embedded in the base URL
+    fn into_options_with(self, url_auth: Option<Auth>) -> anyhow::Result<ClientOptions> {
+        let propfind_body = match self.propfind_body {
+            Some(ref path) => Some(Bytes::from(std::fs::read(path).with_context(|| {
+                format!("failed to read PROPFIND body from {}", path.display())
+            })?)),
+            None => None,
+        };
+        let token = std::env::var("BATCHDAV_TOKEN")
+            .ok()
+            .filter(|s| !s.is_empty());
+        if url_auth.is_some() && self.token_command.is_some() {
+            anyhow::bail!("credentials in the base URL cannot be combined with --token-command");
+        }
+        if self.sigv4.is_some() && (url_auth.is_some() || token.is_some()) {
+            anyhow::bail!(
+                "--sigv4 cannot be combined with credentials in the base URL or BATCHDAV_TOKEN"
+            );
+        }
+        let sigv4 = match self.sigv4 {
+            Some(scope) => Some(SigV4Options {
+                scope,
+                credentials: SigV4Credentials::from_env()?,
+            }),
+            None => None,
+        };
+        let auth = match (url_auth, self.user, token) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => anyhow::bail!(
+                "credentials in the base URL cannot be combined with --user or BATCHDAV_TOKEN"
+            ),
+            (Some(auth), None, None) => Some(auth),
+            (None, Some(_), Some(_)) => {
+                anyhow::bail!("--user cannot be used when BATCHDAV_TOKEN is set")
+            }
+            (None, Some(username), None) => {
+                let password = if self.password_prompt {
+                    Some(
+                        rpassword::prompt_password(format!("Password for {username}: "))
+                            .context("failed to read password")?,
+                    )
+                } else {
+                    std::env::var("BATCHDAV_PASSWORD").ok()
+                };
+                Some(Auth::Basic { username, password })
+            }
+            (None, None, Some(token)) => Some(Auth::Bearer(token)),
+            (None, None, None) => match self.token_command {
+                Some(ref cmd) => Some(Auth::Bearer(run_token_command(cmd)?)),
+                None => None,
+            },
+        };
+        let login = |url, username, password| LoginOptions {
+            url,
+            method: self.login_method,
+            body: self.login_body,
+            content_type: self.login_content_type,
+            expect_status: self.login_status,
+            username,
+            password,
+        };
+        // Basic credentials are submitted to the login endpoint instead of
+        // being sent with every request
+        let (auth, login) = match (self.login_url, auth) {
+            (Some(url), Some(Auth::Basic { username, password })) => {
+                (None, Some(login(url, Some(username), password)))
+            }
+            (Some(url), auth) => (auth, Some(login(url, None, None))),
+            (None, auth) => (auth, None),
+        };
+        let retry_config = match self.retry_config {
+            Some(ref path) => RetryConfig::load(path)?,
+            None => RetryConfig::default(),
+        };
+        let mut per_class = retry_config.policy;
+        per_class.extend(
+            self.retry_policy
+                .into_iter()
+                .map(|rule| (rule.class, rule.retries)),
+        );
+        let retry = RetryOptions {
+            retries: self.retries.or(retry_config.retries).unwrap_or(0),
+            per_class,
+            budget: self.retry_budget.or(retry_config.budget),
+            breaker: self.breaker_threshold.map(|threshold| BreakerOptions {
+                threshold: threshold.get(),
+                cooldown: Duration::from_secs(self.breaker_cooldown),
+            }),
+        };
+        Ok(ClientOptions {
+            sizes: self.sizes,
+            display_names: self.display_names,
+            etags: self.etags,
+            mtimes: self.mtimes,
+            content_types: self.content_types,
+            auth,
+            propfind_body,
+            retry,
+            host_limits: self
+                .per_host_limit
+                .into_iter()
+                .map(|hl| (hl.host, hl.limit))
+                .collect(),
+            xml_options: XmlOptions {
+                max_depth: self.max_xml_depth,
+                max_tokens: self.max_xml_tokens,
+                prefer_declared_encoding: self.prefer_xml_encoding,
+                lenient: self.lenient_xml,
+            },
+            strict_content_type: self.strict_content_type,
+            no_keepalive: self.no_keepalive,
+            timeout: self.timeout,
+            hedge_after: self.hedge_after,
+            limit_rate: self.limit_rate,
+            user_agent: self.user_agent,
+            request_ids: self.request_ids,
+            trace_context: self.trace_context,
+            file_method: self.file_method,
+            token_command: self.token_command,
+            sigv4,
+            login,
+            propfind_cache: self.propfind_cache.map(|dir| CacheOptions {
+                dir,
+                ttl: self.cache_ttl,
+            }),
+            maintenance: self.pause_on_maintenance.then_some(MaintenanceOptions {
+                statuses: self.maintenance_status,
+                probe_interval: self.maintenance_probe_interval,
+                max_outage: self.max_outage,
+            }),
+        })
+    }
+}
+
+/// Options controlling how a traversal proceeds
+#[derive(Clone, Debug, PartialEq, Args)]
+struct TraversalArgs {
+    /// What to do when a request fails
+    #[arg(long, default_value = "abort", value_name = "POLICY")]
+    on_error: ErrorPolicy,
+
+    /// What to do when a request task panics
+    #[arg(long, value_enum, default_value = "propagate")]
+    on_panic: PanicPolicy,
+
+    /// Follow the full chain of redirects for each non-collection resource
+    /// and report chain-length statistics
+    #[arg(long)]
+    follow_redirects: bool,
+
+    /// Maximum number of redirects to follow per resource when
+    /// `--follow-redirects` is given
+    #[arg(
+        long,
+        default_value_t = 10,
+        value_name = "INT",
+        requires = "follow_redirects"
+    )]
+    max_redirects: usize,
+
+    /// Only probe files modified at or after the given time (given as
+    /// YYYY-MM-DD or an RFC 3339 date-time); requires `--mtimes`
+    #[arg(
+        long,
+        value_name = "DATE",
+        value_parser = parse_timestamp,
+        requires = "mtimes"
+    )]
+    modified_since: Option<OffsetDateTime>,
+
+    /// Bound memory use on very large hierarchies by aggregating request
+    /// latencies into histograms instead of retaining each one (making
+    /// latency percentiles approximate) and, for `batch --json-file`, by
+    /// spooling traversal reports to a temporary file
+    #[arg(long)]
+    low_memory: bool,
+
+    /// Coalesce concurrent identical requests (e.g., for a URL reached via
+    /// overlapping roots) into one, with every task that made them sharing
+    /// its result
+    #[arg(long)]
+    coalesce_requests: bool,
+
+    /// Record the given `KEY=VALUE` label in the report (e.g.,
+    /// `server=prod-eu`).  Can be given multiple times.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_label)]
+    label: Vec<(String, String)>,
+
+    /// Skip collections and files whose names begin with a period
+    #[arg(long)]
+    skip_hidden: bool,
+
+    /// Only probe files whose names end in one of the given comma-separated
+    /// extensions (e.g., `.nc,.h5`)
+    #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+    only_ext: Vec<String>,
+
+    /// Do not probe files whose names end in any of the given
+    /// comma-separated extensions
+    #[arg(long, value_name = "EXTS", value_delimiter = ',')]
+    skip_ext: Vec<String>,
+
+    /// Only probe files whose percent-decoded paths match the given regular
+    /// expression
+    #[arg(long, value_name = "REGEX")]
+    filter_regex: Option<PathPattern>,
+
+    /// Neither descend into collections nor probe files whose
+    /// percent-decoded paths match the given regular expression
+    #[arg(long, value_name = "REGEX")]
+    prune_regex: Option<PathPattern>,
+
+    /// Probe at most the given number of files from each collection
+    #[arg(long, value_name = "INT")]
+    max_files_per_dir: Option<usize>,
+
+    /// How to choose the files to probe from collections with more than
+    /// `--max-files-per-dir` files
+    #[arg(long, default_value = "first", requires = "max_files_per_dir")]
+    file_sample: FileSample,
+
+    /// Traverse a synthetic hierarchy with simulated latencies instead of
+    /// making any requests, e.g., `fanout=10,depth=4,latency=30ms±10ms`
+    #[arg(long, value_name = "SPEC")]
+    simulate: Option<SimulationSpec>,
+
+    /// List collections directly from the S3 bucket at the given URL via
+    /// `ListObjectsV2` requests instead of via PROPFIND, treating the
+    /// traversal root as a key prefix within the bucket
+    #[arg(long, value_name = "BUCKET-URL", conflicts_with = "simulate")]
+    s3: Option<Url>,
+
+    /// How to treat Zarr stores (collections whose names end in `.zarr`)
+    #[arg(long, value_enum, default_value = "descend")]
+    zarr_boundary: ZarrBoundary,
+
+    /// The order in which to start requests that are waiting for a free
+    /// worker
+    #[arg(long, value_enum, default_value = "bfs")]
+    order: DispatchOrder,
+
+    /// Make PROPFIND requests using a separate pool of the given number of
+    /// workers from that used to probe non-collection resources (by
+    /// default, the workers not given to --file-workers)
+    #[arg(long, value_name = "N")]
+    dir_workers: Option<NonZeroUsize>,
+
+    /// Probe non-collection resources using a separate pool of the given
+    /// number of workers from that used to make PROPFIND requests (by
+    /// default, the workers not given to --dir-workers)
+    #[arg(long, value_name = "N")]
+    file_workers: Option<NonZeroUsize>,
+
+    /// Adjust the number of workers automatically within the given range,
+    /// increasing it while requests complete promptly & cleanly and halving
+    /// it upon failures, retries, or rising latency
+    #[arg(
+        long,
+        value_name = "MIN..MAX",
+        conflicts_with_all = ["dir_workers", "file_workers"]
+    )]
+    adaptive_workers: Option<AdaptiveRange>,
+
+    /// Start each traversal with one worker and raise the number of workers
+    /// to the full number over the given time interval, given as an integer
+    /// followed by an optional unit of s, m, h, or d
+    #[arg(
+        long,
+        value_name = "INTERVAL",
+        value_parser = parse_interval,
+        conflicts_with = "adaptive_workers"
+    )]
+    ramp: Option<Duration>,
+
+    /// Raise the number of workers during `--ramp` in the given number of
+    /// equal steps rather than continuously
+    #[arg(long, value_name = "N", requires = "ramp")]
+    ramp_steps: Option<NonZeroUsize>,
+
+    /// Log each request that takes longer than the given duration (an
+    /// integer followed by a unit of us, ms, or s) to stderr, even when
+    /// per-request output is suppressed, and count such requests
+    #[arg(long, value_name = "DURATION", value_parser = parse_latency)]
+    log_slower_than: Option<Duration>,
+
+    /// Stop the traversal once more than the given percentage (at most 100)
+    /// of the most recent `--error-window` requests have failed.  Requires
+    /// `--on-error skip`.
+    #[arg(long, value_name = "PCT", value_parser = parse_bounded_percent)]
+    max_error_rate: Option<f64>,
+
+    /// The number of most recent requests over which `--max-error-rate` is
+    /// measured
+    #[arg(
+        long,
+        default_value = "100",
+        value_name = "N",
+        requires = "max_error_rate"
+    )]
+    error_window: NonZeroUsize,
+
+    /// Stop the traversal once the given number of collections have been
+    /// listed (successfully or not), abandoning any outstanding requests,
+    /// and report on the requests completed so far
+    #[arg(long, value_name = "N")]
+    max_dirs: Option<NonZeroUsize>,
+
+    /// After traversing, fully download this many randomly sampled files
+    /// (discarding their contents) and report the download rate of each
+    #[arg(long, value_name = "N", conflicts_with = "simulate")]
+    measure_bandwidth: Option<NonZeroUsize>,
+
+    /// Repeat each PROPFIND request & file probe the given number of times
+    /// immediately after it first completes, and report the latencies of
+    /// the first & repeated requests separately
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    repeat_requests: usize,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    match Arguments::parse().command {
+impl TraversalArgs {
+    fn into_options(self, quiet: bool, runtime: RuntimeConfig) -> anyhow::Result<TraversalOptions> {
+        if self.max_error_rate.is_some() && self.on_error != ErrorPolicy::Skip {
+            anyhow::bail!("--max-error-rate requires --on-error skip");
+        }
+        Ok(TraversalOptions {
+            quiet,
+            on_error: self.on_error,
+            on_panic: self.on_panic,
+            checkpoint: None,
+            tree_file: None,
+            progress_events: false,
+            sort: false,
+            timestamps: false,
+            follow_redirects: self.follow_redirects.then_some(self.max_redirects),
+            modified_since: self.modified_since,
+            request_log: None,
+            error_log: None,
+            zarr_manifest: None,
+            urls_out: None,
+            urls_out_targets: false,
+            check_conditional: false,
+            relative: false,
+            request_timestamps: false,
+            low_memory: self.low_memory,
+            coalesce_requests: self.coalesce_requests,
+            labels: self.label.into_iter().collect(),
+            runtime: Some(runtime),
+            extra_roots: Vec::new(),
+            collect_urls: false,
+            status: None,
+            simulate: self.simulate,
+            s3: self.s3,
+            zarr_boundary: self.zarr_boundary,
+            order: self.order,
+            dir_workers: self.dir_workers.map(NonZeroUsize::get),
+            file_workers: self.file_workers.map(NonZeroUsize::get),
+            adaptive_workers: self.adaptive_workers,
+            ramp: self.ramp.map(|duration| Ramp {
+                duration,
+                steps: self.ramp_steps.map(NonZeroUsize::get),
+            }),
+            log_slower_than: self.log_slower_than,
+            max_error_rate: self.max_error_rate.map(|percent| ErrorRateLimit {
+                percent,
+                window: self.error_window.get(),
+            }),
+            max_dirs: self.max_dirs.map(NonZeroUsize::get),
+            etag_state: None,
+            skip_unchanged: false,
+            measure_bandwidth: self.measure_bandwidth.map(NonZeroUsize::get),
+            repeat_requests: self.repeat_requests,
+            filter: Arc::new(EntryFilter {
+                skip_hidden: self.skip_hidden,
+                only_ext: self
+                    .only_ext
+                    .iter()
+                    .map(|s| normalize_extension(s))
+                    .collect(),
+                skip_ext: self
+                    .skip_ext
+                    .iter()
+                    .map(|s| normalize_extension(s))
+                    .collect(),
+                filter_regex: self.filter_regex,
+                prune_regex: self.prune_regex,
+                max_files_per_dir: self.max_files_per_dir,
+                file_sample: self.file_sample,
+            }),
+        })
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Arguments::parse();
+    if args.tokio_console {
+        #[cfg(feature = "tokio-console")]
+        console_subscriber::init();
+        #[cfg(not(feature = "tokio-console"))]
+        anyhow::bail!(
+            "--tokio-console requires batchdav to be built with the tokio-console feature"
+        );
+    }
+    let runtime = RuntimeConfig::new(args.current_thread, args.runtime_threads)?;
+    runtime.build()?.block_on(run(args.command, runtime))
+}
+
+async fn run(command: Command, runtime: RuntimeConfig) -> anyhow::Result<()> {
+    match command {
         Command::Run {
+            client_args,
+            traversal_args,
+            checkpoint,
+            tree_file,
+            hgrm,
+            request_log,
+            error_log,
+            zarr_manifest,
+            check_conditional,
+            urls_out,
+            urls_out_targets,
+            etag_state,
+            skip_unchanged,
+            relative,
             quiet,
-            base_url,
+            sort,
+            timestamps,
+            mut roots,
+            roots_file,
+            on_complete,
+            mut base_url,
             workers,
         } => {
-            let client = Client::new(base_url.clone())?;
-            let report = traverse(client, base_url, workers, quiet).await?;
+            let mut client_options = client_args.into_options(&mut base_url)?;
+            if zarr_manifest.is_some() || check_conditional || etag_state.is_some() {
+                client_options.etags = true;
+            }
+            if zarr_manifest.is_some() {
+                client_options.sizes = true;
+            }
+            let client = Client::new(base_url.clone(), client_options)?;
+            if let Some(path) = roots_file {
+                let s = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                roots.extend(
+                    parse_url_list(&s)
+                        .with_context(|| format!("failed to parse {}", path.display()))?,
+                );
+            }
+            for url in &roots {
+                if url.origin() != base_url.origin() {
+                    anyhow::bail!("root {url} is not on the same server as the base URL");
+                }
+                if !url.username().is_empty() || url.password().is_some() {
+                    anyhow::bail!("credentials may only be given in the base URL");
+                }
+            }
+            let options = TraversalOptions {
+                checkpoint,
+                tree_file,
+                sort,
+                timestamps,
+                request_log,
+                error_log,
+                zarr_manifest,
+                check_conditional,
+                urls_out,
+                urls_out_targets,
+                etag_state,
+                skip_unchanged,
+                relative,
+                extra_roots: roots,
+                ..traversal_args.into_options(quiet > 0, runtime)?
+            };
+            let hook = on_complete.map(|cmd| CompletionHook::new(cmd, base_url.clone(), None));
+            let report = traverse(client.clone(), base_url, workers, &options).await?;
             println!(
                 "Performed {} requests with {} workers in {:?}",
                 report.requests(),
                 report.workers,
                 report.overall_time
             );
+            if report.stopped_early {
+                println!(
+                    "Stopped early after listing {} collections (--max-dirs)",
+                    report.directories
+                );
+            }
+            if let Some(abort) = report.error_rate_abort {
+                println!("Stopped early: {abort} (--max-error-rate)");
+            }
+            if let Some(panic) = report.panics.first() {
+                println!(
+                    "Skipped {} panicked request tasks (--on-panic skip); first: {panic}",
+                    report.panics.len()
+                );
+            }
+            if let (Some(peak), Some(mean)) = (report.peak_concurrency, report.mean_concurrency) {
+                println!(
+                    "Achieved concurrency: peak {peak}, mean {mean:.2} of {} workers",
+                    report.workers
+                );
+            }
+            if let Some(ref pools) = report.pools {
+                println!("PROPFIND pool: {}", pools.directories);
+                println!("File probe pool: {}", pools.files);
+            }
+            if let Some(ref adaptive) = report.adaptive {
+                println!("{adaptive}");
+            }
+            if let Some(slow) = report.slow_requests {
+                println!("{slow}");
+            }
+            if let Some(timeouts) = report.timeouts {
+                println!("{timeouts}");
+            }
+            if let Some(hedges) = report.hedges {
+                println!("{hedges}");
+            }
+            let retries = client.retries_used();
+            if retries > 0 {
+                let tally = client
+                    .retry_tally()
+                    .into_iter()
+                    .map(|(failure, n)| format!("{failure}: {n}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                println!("Retried failed requests {retries} times ({tally})");
+            }
+            if let Some((hits, misses)) = client.cache_stats() {
+                println!("PROPFIND cache: {hits} hits, {misses} misses");
+            }
+            print!(
+                "Found {} directories and {} files",
+                report.directories, report.files
+            );
+            if let Some(bytes) = report.total_bytes {
+                print!(" totalling {bytes} bytes");
+            }
+            println!();
+            if let Some(latency) = report.directory_latency {
+                println!("PROPFIND latency: {latency}");
+            }
+            // Distinguish file probes from collection listings
+            let file_method = match client.options().file_method {
+                FileMethod::Propfind0 => format!("Depth 0 {}", client.file_method()),
+                _ => client.file_method().to_owned(),
+            };
+            if let Some(latency) = report.file_latency {
+                println!("{file_method} latency: {latency}");
+            }
+            if let Some(ttfb) = report.file_ttfb {
+                println!("{file_method} time to first byte: {ttfb}");
+            }
+            if let Some(wait) = report.queue_wait {
+                println!("Queue wait: {wait}");
+            }
+            if let Some(ref pipeline) = report.pipeline {
+                print!("{pipeline}");
+            }
+            if let Some(ref comparison) = report.method_comparison {
+                print!("{comparison}");
+            }
+            if let Some(path) = hgrm {
+                let times = report
+                    .directory_request_times
+                    .iter()
+                    .chain(&report.file_request_times)
+                    .copied()
+                    .collect::<Vec<_>>();
+                write_hgrm(&path, &times).context("failed to write latency histogram")?;
+            }
+            if report.duplicate_hrefs > 0 {
+                println!(
+                    "Skipped {} duplicate hrefs after normalization",
+                    report.duplicate_hrefs
+                );
+            }
+            if report.excluded_entries > 0 {
+                println!("Excluded {} entries by filter", report.excluded_entries);
+            }
+            if let Some(coalesced) = report.coalesced_requests {
+                println!("Coalesced {coalesced} requests into identical requests in flight");
+            }
+            if let Some(relogins) = report.relogins.filter(|&n| n > 0) {
+                println!("Logged in again {relogins} times after the session expired");
+            }
+            if let Some(skew) = report.clock_skew.filter(ClockSkew::is_notable) {
+                println!("{skew}");
+            }
+            for outage in report.outages.iter().flatten() {
+                println!("Paused for {outage}");
+            }
+            if !report.response_descriptions.is_empty() {
+                println!("Response descriptions:");
+                for (text, count) in &report.response_descriptions {
+                    println!("  {count} × {text}");
+                }
+            }
+            print!("{}", report.concurrency);
+            if let Some(ref fairness) = report.worker_fairness {
+                print!("{fairness}");
+            }
+            if let Some(incremental) = report.incremental {
+                print!("{incremental}");
+            }
+            print!("{}", report.fanout);
+            print!("{}", report.listing_sizes);
+            if let Some(ref chains) = report.redirect_chains {
+                print!("{chains}");
+            }
+            if let Some(ref dups) = report.duplicate_files {
+                print!("{dups}");
+            }
+            if let Some(ref mtimes) = report.modification_times {
+                print!("{mtimes}");
+            }
+            if let Some(ref types) = report.content_types {
+                print!("{types}");
+            }
+            if let Some(ref zarr) = report.zarr {
+                print!("{zarr}");
+            }
+            if let Some(ref repeats) = report.repeats {
+                print!("{repeats}");
+            }
+            if let Some(ref bw) = report.bandwidth {
+                print!("{bw}");
+            }
+            if let Some(ref cond) = report.conditional {
+                print!("{cond}");
+            }
+            if !report.servers.is_empty() {
+                print!("{}", report.servers);
+            }
+            if !report.statuses.responses.is_empty() {
+                println!("HTTP statuses: {}", report.statuses.responses);
+            }
+            if !report.statuses.propstats.is_empty() {
+                println!("Propstat statuses: {}", report.statuses.propstats);
+            }
+            if !report.errors.is_empty() {
+                print!("{}", report.errors);
+            }
+            if !report.warnings.is_empty() {
+                print!("{}", report.warnings);
+            }
+            if report.hosts.len() > 1 {
+                println!("Per-host statistics:");
+                for (host, stats) in &report.hosts {
+                    match stats.latency {
+                        Some(latency) => println!("  {host}: {latency}"),
+                        None => println!("  {host}: no requests"),
+                    }
+                    if stats.redirect_targets > 0 {
+                        println!("    (target of {} redirects)", stats.redirect_targets);
+                    }
+                }
+            }
+            if !report.roots.is_empty() {
+                println!("Per-root statistics:");
+                for root in &report.roots {
+                    println!(
+                        "  {}: {} directories and {} files in {:?}",
+                        root.root, root.directories, root.files, root.overall_time
+                    );
+                    if let Some(latency) = root.directory_latency {
+                        println!("    PROPFIND latency: {latency}");
+                    }
+                    if let Some(latency) = root.file_latency {
+                        println!("    {file_method} latency: {latency}");
+                    }
+                    if !root.errors.is_empty() {
+                        println!("    {} failed requests", root.errors.total());
+                    }
+                }
+            }
+            if let Some(hook) = hook {
+                hook.traversal_complete(&report).await;
+            }
+            if let Some(abort) = report.error_rate_abort {
+                anyhow::bail!("traversal aborted: {abort}");
+            }
         }
         Command::Batch {
+            client_args,
+            traversal_args,
+            format,
             json_file,
+            append,
+            mut request_timestamps,
+            detail,
+            resume,
+            csv_file,
+            fields,
+            progress_format,
+            timestamps,
+            quiet,
+            significance,
+            plot,
+            gnuplot,
+            tidy_file,
+            parquet,
+            vega_lite,
+            benchmark_json,
             per_traversal_stats,
+            dry_run,
+            no_preflight,
+            tree_file,
             samples,
-            base_url,
-            workers_list,
+            until_ci,
+            knee_threshold,
+            max_samples,
+            random_subtree,
+            seed,
+            check_consistency,
+            verify_inventory,
+            verify_urls,
+            metrics_addr,
+            on_complete,
+            notify_url,
+            baseline,
+            regression_threshold,
+            targets,
+            args,
         } => {
-            let client = Client::new(base_url.clone())?;
-            let mut statter = if let Some(path) = json_file {
-                StatManager::json_file(path, base_url.clone())
-            } else if per_traversal_stats {
-                StatManager::per_traversal()
+            let low_memory = traversal_args.low_memory;
+            let plan = match until_ci {
+                Some(percent) => {
+                    // At least two samples are needed for a confidence
+                    // interval
+                    let max = max_samples.get().max(2);
+                    SamplingPlan::UntilCi {
+                        min: samples.get().clamp(2, max),
+                        max,
+                        percent,
+                    }
+                }
+                None => SamplingPlan::Fixed(samples.get()),
+            };
+            let sizes = client_args.sizes;
+            let (base_url, workers_list) = split_batch_args(args)?;
+            let (mut targets, client_options, single_url) = match (targets, base_url) {
+                (Some(path), None) => (
+                    Targets::open(&path)?,
+                    client_args.into_options_with(None)?,
+                    None,
+                ),
+                (None, Some(mut base_url)) => {
+                    let client_options = client_args.into_options(&mut base_url)?;
+                    let single_url = Some(base_url.clone());
+                    (Targets::Single(Some(base_url)), client_options, single_url)
+                }
+                (Some(_), Some(_)) => {
+                    anyhow::bail!("a base URL cannot be given along with --targets")
+                }
+                (None, None) => anyhow::bail!("a base URL is required unless --targets is given"),
+            };
+            let labelled = single_url.is_none();
+            let quietness = Quietness::from(quiet);
+            if detail == JsonDetail::Full {
+                if low_memory {
+                    anyhow::bail!("--detail full cannot be used with --low-memory");
+                }
+                request_timestamps = true;
+            }
+            let options = TraversalOptions {
+                progress_events: progress_format == ProgressFormat::Json
+                    && quietness < Quietness::DataOnly,
+                request_timestamps: request_timestamps || parquet.is_some(),
+                collect_urls: check_consistency || verify_urls,
+                status: start_server(metrics_addr).await?,
+                ..traversal_args.into_options(true, runtime)?
+            };
+            let baseline = baseline.as_deref().map(load_samples).transpose()?;
+            let check_targets = !no_preflight && options.simulate.is_none() && options.s3.is_none();
+            // Check a single base URL before anything is written so that an
+            // unusable URL doesn't clobber an existing report
+            if let Some(base_url) = single_url.as_ref().filter(|_| check_targets) {
+                let client = Client::new(base_url.clone(), client_options.clone())?;
+                let passed = preflight(&client, base_url).await?;
+                if quietness < Quietness::DataOnly {
+                    eprint!("{passed}");
+                }
+            }
+            if let Some(base_url) = single_url.as_ref().filter(|_| dry_run) {
+                let (requests, probe) = if let Some(path) = tree_file {
+                    (Inventory::load(&path)?.tasks.len(), None)
+                } else {
+                    let workers = workers_list.iter().copied().max().unwrap_or(1);
+                    if quietness < Quietness::DataOnly {
+                        eprintln!("Performing probe traversal with {workers} workers ...");
+                    }
+                    let client = Client::new(base_url.clone(), client_options)?;
+                    let report = traverse(client, base_url.clone(), workers, &options).await?;
+                    let probe = Probe {
+                        workers,
+                        elapsed: report.overall_time,
+                    };
+                    (report.requests(), Some(probe))
+                };
+                let plan = BatchPlan {
+                    workers: workers_list,
+                    traversals: (plan.min_samples(), plan.max_samples()),
+                    requests,
+                    probe,
+                };
+                print!("{plan}");
+                return Ok(());
+            }
+            let report_path = json_file.clone().or_else(|| csv_file.clone());
+            let mut statter = match (json_file, single_url) {
+                (Some(path), Some(base_url)) => {
+                    let mode = if resume {
+                        JsonMode::Resume
+                    } else if append {
+                        JsonMode::Append
+                    } else {
+                        JsonMode::Overwrite
+                    };
+                    StatManager::json_file(
+                        path,
+                        base_url,
+                        options.labels.clone(),
+                        mode,
+                        detail,
+                        low_memory,
+                    )?
+                }
+                _ if per_traversal_stats => StatManager::per_traversal(
+                    sizes,
+                    &fields,
+                    Output::open(csv_file.as_deref())?,
+                    labelled,
+                )?,
+                _ if format == SummaryFormat::Table && !fields.is_empty() => {
+                    anyhow::bail!("--fields cannot be used with --format table");
+                }
+                _ => StatManager::per_workers(
+                    format,
+                    &fields,
+                    Output::open(csv_file.as_deref())?,
+                    significance,
+                    labelled,
+                )?,
+            };
+            let mut all_samples = if resume {
+                statter.recorded_samples()?
             } else {
-                StatManager::per_workers()
+                WorkerSamples::new()
             };
-            statter.start();
-            for workers in workers_list {
-                for _ in 0..samples.get() {
-                    let report = traverse(client.clone(), base_url.clone(), workers, true).await?;
-                    statter.process(report);
+            let mut consistency = check_consistency.then(ConsistencyTracker::new);
+            // Number of traversals across all targets that found a different
+            // inventory than the first traversal of their target
+            let mut inventory_mismatches = 0usize;
+            let mut request_parquet = parquet.as_deref().map(RequestParquet::create).transpose()?;
+            // Number of already-recorded traversals for each number of
+            // workers that have yet to be counted against `samples`
+            let mut recorded = all_samples.counts();
+            // The hook for a single-target batch, which is run once the
+            // output has been finalized
+            let mut final_hook = None;
+            // The error rate that caused the batch to be cut short, if any
+            let mut aborted = None;
+            let notifier = notify_url.map(Notifier::new).transpose()?;
+            // The notification for a single-target batch, which is sent once
+            // the output has been finalized
+            let mut final_notice = None;
+            statter.start()?;
+            while let Some((base_url, label)) = targets.next().await? {
+                if !base_url.username().is_empty() || base_url.password().is_some() {
+                    anyhow::bail!("credentials may not be given in --targets URLs");
+                }
+                if let Some(ref label) = label {
+                    statter.start_target(label);
+                    all_samples = WorkerSamples::new();
+                    if quietness < Quietness::DataOnly {
+                        eprintln!("Starting target {label} ({base_url})");
+                    }
+                }
+                let client = Client::new(base_url.clone(), client_options.clone())?;
+                if labelled && check_targets {
+                    let passed = preflight(&client, &base_url).await?;
+                    if quietness < Quietness::DataOnly {
+                        eprint!("{passed}");
+                    }
+                }
+                let hook = on_complete
+                    .clone()
+                    .map(|cmd| CompletionHook::new(cmd, base_url.clone(), report_path.clone()));
+                let mut picker = if random_subtree {
+                    let seed = seed.unwrap_or_else(rand::random);
+                    let discovery_workers = workers_list.iter().copied().max().unwrap_or(1);
+                    let collections =
+                        remote_collections(client.clone(), base_url.clone(), discovery_workers)
+                            .await
+                            .context("failed to discover collections for --random-subtree")?;
+                    let picker = SubtreePicker::new(base_url.clone(), collections, seed);
+                    if quietness < Quietness::DataOnly {
+                        eprintln!(
+                            "Choosing traversal roots from {} collections with seed {seed}",
+                            picker.candidates()
+                        );
+                    }
+                    Some(picker)
+                } else {
+                    None
+                };
+                let mut inventory_check =
+                    verify_inventory.then(|| InventoryCheck::new(verify_urls));
+                let mut traversals = 0usize;
+                let already_taken = workers_list
+                    .iter()
+                    .map(|workers| {
+                        recorded.get_mut(workers).map_or(0, |n| {
+                            let done = (*n).min(plan.max_samples());
+                            *n = n.saturating_sub(done);
+                            done
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let mut progress = BatchProgress::new(
+                    workers_list
+                        .iter()
+                        .zip(&already_taken)
+                        .map(|(&w, &done)| (w, plan.max_samples().saturating_sub(done))),
+                );
+                'sweep: for (i, (&workers, &done)) in
+                    workers_list.iter().zip(&already_taken).enumerate()
+                {
+                    let mut taken = done;
+                    while plan.needs_more(taken, all_samples.time_ci_percent(workers)) {
+                        let root = match picker {
+                            Some(ref mut p) => p.root(taken),
+                            None => base_url.clone(),
+                        };
+                        taken = taken.saturating_add(1);
+                        let mut report = traverse(client.clone(), root, workers, &options).await?;
+                        let run = all_samples.push(report.workers, Sample::from(&report));
+                        if let Some(pq) = request_parquet.as_mut() {
+                            let records = if request_timestamps {
+                                report.requests.as_deref().unwrap_or_default()
+                            } else {
+                                &report.requests.take().unwrap_or_default()
+                            };
+                            pq.write_traversal(&report.root, report.workers, run, records)?;
+                        }
+                        progress.record(i, report.overall_time);
+                        report_sample(
+                            progress_format,
+                            quietness,
+                            run,
+                            &report,
+                            &progress,
+                            per_traversal_stats,
+                            timestamps,
+                        );
+                        if let Some((tracker, urls)) =
+                            consistency.as_mut().zip(report.urls.as_ref())
+                        {
+                            tracker.record(urls);
+                        }
+                        if let Some(check) = inventory_check.as_mut() {
+                            let counts = InventoryCounts::new(&report);
+                            if let Some(m) =
+                                check.record(report.workers, run, counts, report.urls.as_ref())
+                            {
+                                eprintln!("Warning: inventory mismatch: {m}");
+                                report.inventory_mismatch = true;
+                            }
+                        }
+                        traversals = traversals.saturating_add(1);
+                        // Run the hook after the report has been saved so
+                        // that the command sees it in the output file
+                        let finished = hook.as_ref().map(|_| report.clone());
+                        let error_rate_abort = report.error_rate_abort;
+                        statter.process(report)?;
+                        if let Some((hook, report)) = hook.as_ref().zip(finished) {
+                            hook.traversal_complete(&report).await;
+                        }
+                        if let Some(abort) = error_rate_abort {
+                            aborted = Some(abort);
+                            break 'sweep;
+                        }
+                    }
+                    progress.finish(i);
+                    if let SamplingPlan::UntilCi { percent, .. } = plan {
+                        let ci = all_samples.time_ci_percent(workers).unwrap_or(f64::NAN);
+                        let converged = ci <= percent;
+                        if !converged && quietness < Quietness::DataOnly {
+                            eprintln!(
+                                "Warning: workers = {workers}: confidence interval of ±{ci:.1}% still exceeds {percent}% after {taken} samples"
+                            );
+                        }
+                    }
+                }
+                statter.end_target()?;
+                if labelled && quietness < Quietness::DataOnly {
+                    report_scaling(&all_samples.summarize(), knee_threshold);
+                }
+                if let Some(check) = inventory_check {
+                    eprint!("{check}");
+                    inventory_mismatches =
+                        inventory_mismatches.saturating_add(check.mismatches().len());
+                }
+                match hook {
+                    Some(hook) if labelled => hook.batch_complete(traversals).await,
+                    hook => final_hook = hook.map(|h| (h, traversals)),
+                }
+                let regressions = baseline.as_ref().map_or_else(Vec::new, |old| {
+                    ReportDiff::new(old, &all_samples).regressions(regression_threshold)
+                });
+                for d in &regressions {
+                    eprintln!(
+                        "Regression against baseline: workers = {}: {:.3} s -> {:.3} s ({:+.1}%, p = {:.3})",
+                        d.workers,
+                        d.old_mean,
+                        d.new_mean,
+                        d.percent_change,
+                        d.p_value.unwrap_or(f64::NAN),
+                    );
+                }
+                if let Some(ref notifier) = notifier {
+                    let notice = BatchNotice {
+                        event: NoticeEvent::new(&regressions),
+                        base_url: base_url.clone(),
+                        label: label.clone(),
+                        hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+                        time: OffsetDateTime::now_utc(),
+                        traversals,
+                        report: report_path.clone(),
+                        aborted,
+                        summary: all_samples.summarize(),
+                        regressions,
+                    };
+                    if labelled {
+                        if let Err(e) = notifier.send(&notice).await {
+                            eprintln!("Warning: {e:#}");
+                        }
+                    } else {
+                        final_notice = Some(notice);
+                    }
+                }
+                if aborted.is_some() {
+                    break;
                 }
             }
             statter.end()?;
+            if !labelled && quietness < Quietness::DataOnly {
+                report_scaling(&all_samples.summarize(), knee_threshold);
+            }
+            if let Some(pq) = request_parquet {
+                pq.close()?;
+            }
+            if let Some((hook, traversals)) = final_hook {
+                hook.batch_complete(traversals).await;
+            }
+            if let Some((notifier, notice)) = notifier.as_ref().zip(final_notice) {
+                if let Err(e) = notifier.send(&notice).await {
+                    eprintln!("Warning: {e:#}");
+                }
+            }
+            if let Some(tracker) = consistency {
+                eprint!("{}", tracker.report());
+            }
+            if let Some(path) = plot {
+                plot_svg(&path, &all_samples.summarize()).context("failed to write plot")?;
+            }
+            if let Some(prefix) = gnuplot {
+                write_gnuplot(&prefix, &all_samples.summarize())
+                    .context("failed to write gnuplot files")?;
+            }
+            if let Some(path) = benchmark_json {
+                write_benchmark_json(&path, &benchmark_entries(&all_samples.summarize()))
+                    .context("failed to write benchmark results")?;
+            }
+            if tidy_file.is_some() || vega_lite.is_some() {
+                let rows = tidy_rows(&all_samples);
+                if let Some(path) = tidy_file {
+                    write_tidy_file(&path, &rows).context("failed to write tidy data")?;
+                }
+                if let Some(path) = vega_lite {
+                    write_vega_lite(&path, &rows).context("failed to write Vega-Lite spec")?;
+                }
+            }
+            if let Some(abort) = aborted {
+                anyhow::bail!("batch aborted: {abort}");
+            }
+            if inventory_mismatches == 1 {
+                anyhow::bail!("1 traversal found a different inventory than the first traversal");
+            } else if inventory_mismatches > 1 {
+                anyhow::bail!(
+                    "{inventory_mismatches} traversals found a different inventory than the first traversal"
+                );
+            }
         }
-    }
-    Ok(())
-}
-
-#[derive(Clone, Debug, PartialEq)]
-enum StatManager {
-    JsonFile {
-        outfile: PathBuf,
-        data: StatReport,
-    },
-    PerTraversal,
-    PerWorkers {
-        worker_runtimes: BTreeMap<usize, Vec<f64>>,
-    },
-}
-
-impl StatManager {
-    fn json_file(outfile: PathBuf, base_url: Url) -> Self {
-        StatManager::JsonFile {
-            outfile,
-            data: StatReport::new(base_url),
+        Command::Analyze {
+            format,
+            fields,
+            significance,
+            output,
+            knee_threshold,
+            report,
+        } => {
+            if format == SummaryFormat::Table && !fields.is_empty() {
+                anyhow::bail!("--fields cannot be used with --format table");
+            }
+            let samples = load_samples(&report)?;
+            analyze(
+                &samples,
+                format,
+                &fields,
+                significance,
+                Output::open(output.as_deref())?,
+            )?;
+            report_scaling(&samples.summarize(), knee_threshold);
         }
-    }
-
-    fn per_traversal() -> Self {
-        StatManager::PerTraversal
-    }
-
-    fn per_workers() -> Self {
-        StatManager::PerWorkers {
-            worker_runtimes: BTreeMap::new(),
+        Command::Merge { output, reports } => {
+            let traversals = merge_reports(&reports, &output)?;
+            println!(
+                "Merged {traversals} traversals from {} reports into {}",
+                reports.len(),
+                output.display()
+            );
         }
-    }
-
-    fn start(&mut self) {
-        match self {
-            StatManager::JsonFile { data, .. } => data.start_time = Some(OffsetDateTime::now_utc()),
-            StatManager::PerTraversal => println!("workers,requests,elapsed"),
-            StatManager::PerWorkers { .. } => (),
+        Command::Diff {
+            json_file,
+            old,
+            new,
+        } => {
+            let diff = ReportDiff::new(&load_samples(&old)?, &load_samples(&new)?);
+            print!("{diff}");
+            if let Some(path) = json_file {
+                diff.write_json(&path)?;
+            }
         }
-    }
-
-    fn process(&mut self, report: TraversalReport) {
-        match self {
-            StatManager::JsonFile { data, .. } => {
-                eprintln!(
-                    "Finished: workers = {}, requests = {}, elapsed = {:?}",
-                    report.workers,
-                    report.requests(),
-                    report.overall_time
-                );
-                data.traversals.push(report);
+        Command::StrategyCompare {
+            client_args,
+            format,
+            samples,
+            mut base_url,
+            workers_list,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let results =
+                compare_strategies(client, base_url, &workers_list, samples.get()).await?;
+            print_comparison(format, &results);
+        }
+        Command::DiscoveryCompare {
+            client_args,
+            mut base_url,
+            workers,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let report = compare_discovery(client, base_url, workers).await?;
+            print!("{report}");
+        }
+        Command::Propnames {
+            client_args,
+            mut base_url,
+            workers,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let report = discover_propnames(client, base_url, workers).await?;
+            print!("{report}");
+        }
+        Command::Quota {
+            client_args,
+            mut url,
+        } => {
+            let client_options = client_args.into_options(&mut url)?;
+            let client = Client::new(url.clone(), client_options)?;
+            let quota = client.get_quota(url).await?;
+            match quota.used_bytes {
+                Some(n) => println!("Used: {n} bytes"),
+                None => println!("Used: not reported"),
             }
-            StatManager::PerTraversal => {
-                println!(
-                    "{},{},{}",
-                    report.workers,
-                    report.requests(),
-                    show_duration_as_seconds(report.overall_time),
-                );
+            match quota.available_bytes {
+                Some(n) => println!("Available: {n} bytes"),
+                None => println!("Available: not reported"),
             }
-            StatManager::PerWorkers { worker_runtimes } => {
-                let workers = report.workers;
-                let elapsed = report.overall_time;
-                let requests = report.requests();
-                let timelist = worker_runtimes.entry(workers).or_default();
-                timelist.push(elapsed.as_secs_f64());
-                let i = timelist.len();
-                eprintln!("Finished: workers = {workers}, run = {i}, requests = {requests}, elapsed = {elapsed:?}");
+        }
+        Command::LockBench {
+            client_args,
+            cycles,
+            mut url,
+            workers,
+        } => {
+            let client_options = client_args.into_options(&mut url)?;
+            let client = Client::new(url.clone(), client_options)?;
+            let report = lock_bench(client, url, workers, cycles).await?;
+            print!("{report}");
+        }
+        Command::RangeBench {
+            client_args,
+            files,
+            chunk_size,
+            parallel,
+            max_redirects,
+            mut base_url,
+            workers,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let opts = RangeBenchOptions {
+                files,
+                chunk_size: chunk_size.get(),
+                parallel: parallel.get(),
+                max_redirects,
+            };
+            let report = range_bench(client, base_url, workers, opts).await?;
+            print!("{report}");
+            if report.failures() > 0 {
+                anyhow::bail!("{} files could not be downloaded", report.failures());
             }
         }
-    }
-
-    fn end(self) -> anyhow::Result<()> {
-        match self {
-            StatManager::JsonFile { outfile, mut data } => {
-                data.end_time = Some(OffsetDateTime::now_utc());
-                let mut fp =
-                    BufWriter::new(File::create(outfile).context("failed to open JSON outfile")?);
-                serde_json::to_writer_pretty(&mut fp, &data)
-                    .context("failed to dump JSON to file")?;
-                fp.write_all(b"\n")
-                    .context("failed to write final newline to JSON outfile")?;
-                fp.flush().context("failed to flush JSON outfile")?;
-            }
-            StatManager::PerTraversal => (),
-            StatManager::PerWorkers { worker_runtimes } => {
-                println!("workers,time_mean,time_stddev");
-                for (workers, runtimes) in worker_runtimes {
-                    let data = Data::new(runtimes);
-                    let mean = data
-                        .mean()
-                        .expect("mean should exist for nonzero number of samples");
-                    let stddev = data
-                        .std_dev()
-                        .expect("stddev should exist for nonzero number of samples");
-                    println!("{workers},{mean},{stddev}");
-                }
-            }
-        }
-        Ok(())
-    }
-}
-
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
-struct StatReport {
-    #[serde(with = "time::serde::rfc3339::option")]
-    start_time: Option<OffsetDateTime>,
-    #[serde(with = "time::serde::rfc3339::option")]
-    end_time: Option<OffsetDateTime>,
-    base_url: Url,
-    traversals: Vec<TraversalReport>,
-}
-
-impl StatReport {
-    fn new(base_url: Url) -> Self {
-        StatReport {
-            start_time: None,
-            end_time: None,
-            base_url,
-            traversals: Vec::new(),
+        Command::Jitter {
+            client_args,
+            files,
+            rounds,
+            json_file,
+            mut base_url,
+            workers_list,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let opts = JitterOptions {
+                files,
+                rounds: rounds.get(),
+            };
+            let report = jitter(client, base_url, &workers_list, opts).await?;
+            print!("{report}");
+            if let Some(path) = json_file {
+                report.write_json(&path)?;
+            }
+            if report.failures() > 0 {
+                anyhow::bail!("{} probes failed", report.failures());
+            }
+        }
+        Command::WriteBench {
+            client_args,
+            format,
+            depth,
+            fanout,
+            files,
+            file_size,
+            cleanup,
+            samples,
+            mut base_url,
+            workers_list,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            if !base_url.path().ends_with('/') {
+                base_url.set_path(&format!("{}/", base_url.path()));
+            }
+            let client = Client::new(base_url.clone(), client_options)?;
+            let spec = TreeSpec {
+                depth,
+                fanout,
+                files,
+                file_size,
+            };
+            eprintln!("Each tree requires {} requests to create", spec.requests());
+            let stamp = OffsetDateTime::now_utc().unix_timestamp();
+            let mut all_samples = WorkerSamples::new();
+            for workers in workers_list {
+                for run in 1..=samples.get() {
+                    let root = join_url(
+                        &base_url,
+                        &format!("batchdav-write-bench-{stamp}-{workers}-{run}/"),
+                    );
+                    let sample = create_tree(client.clone(), root.clone(), spec, workers).await?;
+                    eprintln!(
+                        "Finished: workers = {workers}, run = {run}, requests = {}, elapsed = {:?}",
+                        sample.requests, sample.elapsed
+                    );
+                    all_samples.push(workers, sample);
+                    if cleanup {
+                        client.delete(root).await?;
+                    }
+                }
+            }
+            print_summary(format, &all_samples);
+        }
+        Command::Warm {
+            client_args,
+            tree,
+            order,
+            quiet,
+            workers,
+        } => {
+            let Inventory {
+                mut base_url,
+                mut tasks,
+            } = Inventory::load(&tree)?;
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url, client_options)?;
+            order.arrange(&mut tasks);
+            let report = warm(client, tasks, workers, quiet > 0).await;
+            print!("{report}");
+        }
+        Command::Verify {
+            client_args,
+            mut base_url,
+            local_path,
+            workers,
+        } => {
+            let client_options = ClientOptions {
+                sizes: true,
+                ..client_args.into_options(&mut base_url)?
+            };
+            let client = Client::new(base_url.clone(), client_options)?;
+            let report = verify(client, base_url, local_path, workers).await?;
+            print!("{report}");
+            if report.discrepancies() > 0 {
+                anyhow::bail!("found {} discrepancies", report.discrepancies());
+            }
+        }
+        Command::Lint {
+            client_args,
+            mut base_url,
+            workers,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let report = lint(client, base_url, workers).await;
+            print!("{report}");
+            if !report.findings.is_empty() {
+                anyhow::bail!("found {} issues", report.findings.len());
+            }
+        }
+        Command::RetryFailed {
+            client_args,
+            error_log,
+            workers,
+        } => {
+            let requests = read_error_log(&error_log)?;
+            let Some(first) = requests.first() else {
+                println!("No failed requests to retry");
+                return Ok(());
+            };
+            let mut base_url = first.url.clone();
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url, client_options)?;
+            let report = retry_failed(client, requests, workers).await?;
+            print!("{report}");
+            if report.still_failing() > 0 {
+                anyhow::bail!("{} requests still fail", report.still_failing());
+            }
+        }
+        Command::Watch {
+            client_args,
+            traversal_args,
+            interval,
+            count,
+            log_file,
+            log_max_bytes,
+            log_keep,
+            metrics_addr,
+            on_complete,
+            mut base_url,
+            workers,
+        } => {
+            let client_options = client_args.into_options(&mut base_url)?;
+            let client = Client::new(base_url.clone(), client_options)?;
+            let hook =
+                on_complete.map(|cmd| CompletionHook::new(cmd, base_url.clone(), log_file.clone()));
+            let log = log_file
+                .map(|path| {
+                    RotatingLog::open(path.clone(), log_max_bytes, log_keep)
+                        .with_context(|| format!("failed to open {}", path.display()))
+                })
+                .transpose()?;
+            let options = TraversalOptions {
+                status: start_server(metrics_addr).await?,
+                ..traversal_args.into_options(true, runtime)?
+            };
+            let watch_options = WatchOptions {
+                interval,
+                count,
+                log,
+                hook,
+            };
+            watch(client, base_url, workers, &options, watch_options).await?;
+        }
+        Command::Conformance {
+            client_args,
+            mut url,
+        } => {
+            let client_options = client_args.into_options(&mut url)?;
+            if !url.path().ends_with('/') {
+                url.set_path(&format!("{}/", url.path()));
+            }
+            let client = Client::new(url.clone(), client_options)?;
+            let report = check_conformance(client, url).await;
+            print!("{report}");
+            if report.failures() > 0 {
+                anyhow::bail!("{} conformance checks failed", report.failures());
+            }
         }
     }
+    Ok(())
 }
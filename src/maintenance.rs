@@ -0,0 +1,348 @@
+use crate::export::serialize_seconds;
+use crate::show_duration::show_duration_as_seconds;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::fmt;
+use std::pin::pin;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::sync::Notify;
+
+/// Configuration for pausing all requests while the server is down for
+/// maintenance
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct MaintenanceOptions {
+    /// Statuses that indicate maintenance in addition to 503 responses
+    /// without a `Retry-After` header
+    pub(crate) statuses: Vec<StatusCode>,
+    /// How long to wait between probes of the base URL while paused
+    pub(crate) probe_interval: Duration,
+    /// Give up waiting for the server to recover after this long, treating
+    /// maintenance responses as ordinary failures from then on
+    pub(crate) max_outage: Option<Duration>,
+}
+
+impl MaintenanceOptions {
+    /// Return whether `resp` indicates that the server is down for
+    /// maintenance
+    pub(crate) fn is_maintenance(&self, resp: &reqwest::Response) -> bool {
+        let status = resp.status();
+        self.statuses.contains(&status)
+            || (status == StatusCode::SERVICE_UNAVAILABLE
+                && !resp.headers().contains_key(RETRY_AFTER))
+    }
+}
+
+/// Shared state for pausing requests during maintenance.  While paused,
+/// requests wait before being sent, and a single request at a time acts as
+/// the prober, checking the base URL every `probe_interval` until it gets a
+/// response that does not indicate maintenance.
+#[derive(Debug)]
+pub(crate) struct MaintenanceGate {
+    options: MaintenanceOptions,
+    state: Mutex<GateState>,
+    /// Notified when the pause ends
+    resumed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct GateState {
+    paused: Option<Pause>,
+    /// Set once the pause has lasted longer than `max_outage`, after which
+    /// requests are no longer paused
+    gave_up: bool,
+    outages: Vec<Outage>,
+}
+
+#[derive(Debug)]
+struct Pause {
+    start: Instant,
+    start_time: OffsetDateTime,
+    status: u16,
+    probes: usize,
+    /// Whether some request is currently probing the base URL
+    probing: bool,
+}
+
+impl MaintenanceGate {
+    pub(crate) fn new(options: MaintenanceOptions) -> MaintenanceGate {
+        MaintenanceGate {
+            options,
+            state: Mutex::new(GateState::default()),
+            resumed: Notify::new(),
+        }
+    }
+
+    pub(crate) fn options(&self) -> &MaintenanceOptions {
+        &self.options
+    }
+
+    /// Wait until requests may be sent.  Returns a `Probe` if, instead, the
+    /// caller has been made the prober and should now send a probe request
+    /// to the base URL and report the outcome with `Probe::probed()`.
+    pub(crate) async fn wait(&self) -> Option<Probe<'_>> {
+        loop {
+            let mut resumed = pin!(self.resumed.notified());
+            resumed.as_mut().enable();
+            let probe = {
+                let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+                match state.paused {
+                    None => return None,
+                    Some(ref mut pause) => !std::mem::replace(&mut pause.probing, true),
+                }
+            };
+            if probe {
+                // Create the guard before sleeping so that the probe is
+                // released even if this future is dropped while asleep
+                let probe = Probe {
+                    gate: self,
+                    reported: false,
+                };
+                tokio::time::sleep(self.options.probe_interval).await;
+                return Some(probe);
+            }
+            resumed.await;
+        }
+    }
+
+    /// Pause all requests after receiving a maintenance response with the
+    /// given status.  Returns `false` if requests are no longer being paused
+    /// because an earlier outage exceeded `max_outage`.
+    pub(crate) fn pause(&self, status: u16) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.gave_up {
+            return false;
+        }
+        if state.paused.is_none() {
+            eprintln!(
+                "Server responded with {status}; pausing requests until it recovers from maintenance"
+            );
+            state.paused = Some(Pause {
+                start: Instant::now(),
+                start_time: OffsetDateTime::now_utc(),
+                status,
+                probes: 0,
+                probing: false,
+            });
+        }
+        true
+    }
+
+    /// Report the outcome of a probe request: `recovered` is true if it got
+    /// a response that does not indicate maintenance
+    fn probed(&self, recovered: bool) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(mut pause) = state.paused.take() else {
+            return;
+        };
+        pause.probes = pause.probes.saturating_add(1);
+        pause.probing = false;
+        let duration = pause.start.elapsed();
+        let exceeded = self.options.max_outage.is_some_and(|max| duration >= max);
+        if !recovered && !exceeded {
+            state.paused = Some(pause);
+            return;
+        }
+        let outage = Outage {
+            start_time: pause.start_time,
+            end_time: pause.start_time + duration,
+            duration,
+            status: pause.status,
+            probes: pause.probes,
+            recovered,
+        };
+        if recovered {
+            eprintln!("Server recovered; resuming requests after {outage}");
+        } else {
+            eprintln!("Server has not recovered; no longer pausing requests after {outage}");
+            state.gave_up = true;
+        }
+        state.outages.push(outage);
+        drop(state);
+        self.resumed.notify_waiters();
+    }
+
+    /// Release the probe without an outcome, and wake the waiting requests
+    /// so that one of them can probe in its place
+    fn abandon_probe(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(ref mut pause) = state.paused {
+            pause.probing = false;
+        }
+        drop(state);
+        self.resumed.notify_waiters();
+    }
+
+    /// Return the outages that have ended so far
+    pub(crate) fn outages(&self) -> Vec<Outage> {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .outages
+            .clone()
+    }
+}
+
+/// The right to probe the base URL while requests are paused, returned by
+/// `MaintenanceGate::wait()`.  If it is dropped without reporting an
+/// outcome — e.g., because the probing request was cancelled or failed to be
+/// built — another request is made the prober in its place.
+#[derive(Debug)]
+pub(crate) struct Probe<'a> {
+    gate: &'a MaintenanceGate,
+    reported: bool,
+}
+
+impl Probe<'_> {
+    /// Report the outcome of the probe request: `recovered` is true if it
+    /// got a response that does not indicate maintenance
+    pub(crate) fn probed(mut self, recovered: bool) {
+        self.reported = true;
+        self.gate.probed(recovered);
+    }
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if !self.reported {
+            self.gate.abandon_probe();
+        }
+    }
+}
+
+/// A period during which requests were paused because the server was down
+/// for maintenance
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct Outage {
+    /// When the first maintenance response was received
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) start_time: OffsetDateTime,
+    /// When requests were resumed
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) end_time: OffsetDateTime,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) duration: Duration,
+    /// The status of the first maintenance response
+    pub(crate) status: u16,
+    /// Number of probe requests sent to the base URL
+    pub(crate) probes: usize,
+    /// False if requests were resumed because the outage exceeded
+    /// `max_outage`
+    pub(crate) recovered: bool,
+}
+
+impl fmt::Display for Outage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} s of maintenance (status {}, {} probes)",
+            show_duration_as_seconds(self.duration),
+            self.status,
+            self.probes
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let gate = Arc::new(MaintenanceGate::new(MaintenanceOptions {
+            statuses: Vec::new(),
+            probe_interval: Duration::from_millis(10),
+            max_outage: None,
+        }));
+        assert!(gate.wait().await.is_none());
+        assert!(gate.pause(503));
+        assert!(gate.pause(503));
+        let probe = gate.wait().await.unwrap();
+        let waiter = tokio::spawn({
+            let gate = Arc::clone(&gate);
+            async move { gate.wait().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+        probe.probed(false);
+        let probe = gate.wait().await.unwrap();
+        assert!(!waiter.is_finished());
+        probe.probed(true);
+        assert!(!waiter.await.unwrap());
+        let outages = gate.outages();
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].status, 503);
+        assert_eq!(outages[0].probes, 2);
+        assert!(outages[0].duration >= Duration::from_millis(20));
+        assert!(outages[0].recovered);
+        assert!(gate.wait().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_max_outage() {
+        let gate = MaintenanceGate::new(MaintenanceOptions {
+            statuses: vec![StatusCode::BAD_GATEWAY],
+            probe_interval: Duration::from_millis(10),
+            max_outage: Some(Duration::from_millis(15)),
+        });
+        assert!(gate.pause(502));
+        // How many probes fit in the outage depends on scheduling, so just
+        // keep failing them until the gate gives up
+        let mut probes = 0;
+        while let Some(probe) = gate.wait().await {
+            probe.probed(false);
+            probes += 1;
+            assert!(probes < 100, "gate should give up after max_outage");
+        }
+        assert!(!gate.pause(502));
+        let outages = gate.outages();
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].probes, probes);
+        assert!(outages[0].duration >= Duration::from_millis(15));
+        assert!(!outages[0].recovered);
+    }
+
+    #[tokio::test]
+    async fn test_probe_dropped() {
+        let gate = Arc::new(MaintenanceGate::new(MaintenanceOptions {
+            statuses: Vec::new(),
+            probe_interval: Duration::from_millis(10),
+            max_outage: None,
+        }));
+        assert!(gate.pause(503));
+        // Cancel the prober while it waits to send its probe
+        assert!(
+            tokio::time::timeout(Duration::from_millis(1), gate.wait())
+                .await
+                .is_err(),
+            "prober should still be waiting to probe"
+        );
+        let probe = tokio::time::timeout(Duration::from_secs(5), gate.wait())
+            .await
+            .expect("another request should take over probing")
+            .unwrap();
+        let waiter = tokio::spawn({
+            let gate = Arc::clone(&gate);
+            async move { gate.wait().await.map(|p| p.probed(true)).is_some() }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished(), "waiter should wait for the prober");
+        // Cancel the probe request mid-flight
+        drop(probe);
+        assert!(
+            tokio::time::timeout(Duration::from_secs(5), waiter)
+                .await
+                .expect("waiter should take over probing")
+                .unwrap(),
+            "waiter should have been made the prober"
+        );
+        assert!(gate.wait().await.is_none(), "requests should have resumed");
+        let outages = gate.outages();
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].probes, 1);
+        assert!(outages[0].recovered);
+    }
+}
@@ -0,0 +1,409 @@
+use crate::export::serialize_seconds;
+use crate::progress::ProgressEvent;
+use crate::stats::LatencySummary;
+use crate::traverse::TraversalReport;
+use anyhow::Context;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Maximum size of an HTTP request head that the metrics server will read
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// The state of the traversals made by a `batch` or `watch` run, shared
+/// between the traversals (which update it) and the metrics server (which
+/// reports it)
+#[derive(Debug, Default)]
+pub(crate) struct StatusBoard(Mutex<BoardState>);
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+struct BoardState {
+    /// Number of traversals that ran to completion
+    completed: u64,
+    /// Number of traversals aborted by an error
+    failed: u64,
+    /// Total `PROPFIND` requests made by completed traversals
+    directory_requests: u64,
+    /// Total `HEAD` requests made by completed traversals
+    file_requests: u64,
+    /// Total failed requests skipped by completed traversals
+    request_errors: u64,
+    /// The traversal in progress, if any
+    current: Option<CurrentTraversal>,
+    /// The most recently completed traversal, if any
+    last: Option<LastTraversal>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct CurrentTraversal {
+    workers: usize,
+    #[serde(with = "time::serde::rfc3339")]
+    start_time: OffsetDateTime,
+    /// Number of requests completed so far
+    requests: usize,
+    /// Number of failed requests so far
+    errors: usize,
+    #[serde(serialize_with = "serialize_seconds")]
+    elapsed: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct LastTraversal {
+    workers: usize,
+    #[serde(with = "time::serde::rfc3339")]
+    end_time: OffsetDateTime,
+    directories: usize,
+    files: usize,
+    errors: usize,
+    #[serde(serialize_with = "serialize_seconds")]
+    elapsed: Duration,
+    directory_latency: Option<LatencySummary>,
+    file_latency: Option<LatencySummary>,
+}
+
+// Boards are only equal to themselves
+impl PartialEq for StatusBoard {
+    fn eq(&self, other: &StatusBoard) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for StatusBoard {}
+
+impl StatusBoard {
+    pub(crate) fn new() -> Arc<StatusBoard> {
+        Arc::new(StatusBoard::default())
+    }
+
+    fn state(&self) -> MutexGuard<'_, BoardState> {
+        // A panic while holding the lock cannot leave the state
+        // inconsistent, so ignore poisoning
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Record that a traversal with the given number of workers has started
+    pub(crate) fn begin(&self, workers: usize) {
+        self.state().current = Some(CurrentTraversal {
+            workers,
+            start_time: OffsetDateTime::now_utc(),
+            requests: 0,
+            errors: 0,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    /// Record the progress of the traversal in progress
+    pub(crate) fn update(&self, event: &ProgressEvent) {
+        if let ProgressEvent::Progress {
+            requests,
+            errors,
+            elapsed,
+            ..
+        } = *event
+        {
+            if let Some(cur) = self.state().current.as_mut() {
+                cur.requests = requests;
+                cur.errors = errors;
+                cur.elapsed = elapsed;
+            }
+        }
+    }
+
+    /// Record that the traversal in progress has completed
+    pub(crate) fn finish(&self, report: &TraversalReport) {
+        let mut state = self.state();
+        state.current = None;
+        state.completed = state.completed.saturating_add(1);
+        state.directory_requests = state
+            .directory_requests
+            .saturating_add(u64::try_from(report.directories).unwrap_or(u64::MAX));
+        state.file_requests = state
+            .file_requests
+            .saturating_add(u64::try_from(report.files).unwrap_or(u64::MAX));
+        state.request_errors = state
+            .request_errors
+            .saturating_add(u64::try_from(report.errors.total()).unwrap_or(u64::MAX));
+        state.last = Some(LastTraversal {
+            workers: report.workers,
+            end_time: OffsetDateTime::now_utc(),
+            directories: report.directories,
+            files: report.files,
+            errors: report.errors.total(),
+            elapsed: report.overall_time,
+            directory_latency: report.directory_latency,
+            file_latency: report.file_latency,
+        });
+    }
+
+    /// Record that the traversal in progress was aborted by an error
+    pub(crate) fn fail(&self) {
+        let mut state = self.state();
+        state.current = None;
+        state.failed = state.failed.saturating_add(1);
+    }
+
+    /// Render the board's state in the Prometheus text exposition format
+    pub(crate) fn metrics(&self) -> String {
+        self.state().render_metrics()
+    }
+
+    /// Render the board's state as a JSON object
+    pub(crate) fn status(&self) -> String {
+        serde_json::to_string(&*self.state()).unwrap_or_else(|_| String::from("{}"))
+    }
+}
+
+impl BoardState {
+    fn render_metrics(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+            let _ = writeln!(out, "# HELP batchdav_{name} {help}");
+            let _ = writeln!(out, "# TYPE batchdav_{name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "batchdav_{name}{labels} {value}");
+            }
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let float = |n: u64| n as f64;
+        metric(
+            "traversals_total",
+            "counter",
+            "Number of traversals finished, by outcome",
+            &[
+                (r#"{result="success"}"#, float(self.completed)),
+                (r#"{result="failure"}"#, float(self.failed)),
+            ],
+        );
+        metric(
+            "requests_total",
+            "counter",
+            "Number of requests made by completed traversals, by type",
+            &[
+                (r#"{type="dir"}"#, float(self.directory_requests)),
+                (r#"{type="file"}"#, float(self.file_requests)),
+            ],
+        );
+        metric(
+            "request_errors_total",
+            "counter",
+            "Number of failed requests skipped by completed traversals",
+            &[("", float(self.request_errors))],
+        );
+        metric(
+            "traversal_in_progress",
+            "gauge",
+            "Whether a traversal is currently running",
+            &[("", if self.current.is_some() { 1.0 } else { 0.0 })],
+        );
+        if let Some(ref cur) = self.current {
+            #[allow(clippy::cast_precision_loss)]
+            metric(
+                "current_traversal_requests",
+                "gauge",
+                "Number of requests completed so far by the running traversal",
+                &[("", cur.requests as f64)],
+            );
+        }
+        if let Some(ref last) = self.last {
+            #[allow(clippy::cast_precision_loss)]
+            let end = last.end_time.unix_timestamp() as f64;
+            metric(
+                "last_traversal_timestamp_seconds",
+                "gauge",
+                "Time at which the most recent traversal completed",
+                &[("", end)],
+            );
+            metric(
+                "last_traversal_duration_seconds",
+                "gauge",
+                "Duration of the most recent traversal",
+                &[("", last.elapsed.as_secs_f64())],
+            );
+            #[allow(clippy::cast_precision_loss)]
+            metric(
+                "last_traversal_resources",
+                "gauge",
+                "Number of resources requested by the most recent traversal, by type",
+                &[
+                    (r#"{type="dir"}"#, last.directories as f64),
+                    (r#"{type="file"}"#, last.files as f64),
+                ],
+            );
+            let mut latencies = Vec::new();
+            for (kind, summary) in [("dir", last.directory_latency), ("file", last.file_latency)] {
+                if let Some(s) = summary {
+                    for (stat, d) in [
+                        ("mean", s.mean),
+                        ("median", s.median),
+                        ("p95", s.p95),
+                        ("max", s.max),
+                    ] {
+                        latencies.push((
+                            format!(r#"{{type="{kind}",stat="{stat}"}}"#),
+                            d.as_secs_f64(),
+                        ));
+                    }
+                }
+            }
+            if !latencies.is_empty() {
+                let samples = latencies
+                    .iter()
+                    .map(|(labels, v)| (labels.as_str(), *v))
+                    .collect::<Vec<_>>();
+                metric(
+                    "last_traversal_latency_seconds",
+                    "gauge",
+                    "Request latency statistics for the most recent traversal",
+                    &samples,
+                );
+            }
+        }
+        out
+    }
+}
+
+/// If `addr` is set, start serving `/metrics` and `/status` on it in the
+/// background, and return the board on which traversals should post their
+/// progress
+pub(crate) async fn start_server(
+    addr: Option<SocketAddr>,
+) -> anyhow::Result<Option<Arc<StatusBoard>>> {
+    let Some(addr) = addr else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind metrics server to {addr}"))?;
+    let board = StatusBoard::new();
+    tokio::spawn(serve(listener, Arc::clone(&board)));
+    Ok(Some(board))
+}
+
+/// Serve `/metrics` and `/status` for `board` on `listener` until the
+/// process exits or the listener fails
+async fn serve(listener: TcpListener, board: Arc<StatusBoard>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let board = Arc::clone(&board);
+        tokio::spawn(async move {
+            // Errors talking to a scraper are of no concern to the run
+            let _ = handle(stream, &board).await;
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, board: &StatusBoard) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_HEAD {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(chunk.get(..n).unwrap_or_default());
+    }
+    let request_line = String::from_utf8_lossy(&buf)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    let (status, content_type, body) = route(&request_line, board);
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Determine the status line, content type, and body of the response to a
+/// request with the given request line
+fn route(request_line: &str, board: &StatusBoard) -> (&'static str, &'static str, String) {
+    let mut words = request_line.split_ascii_whitespace();
+    let method = words.next().unwrap_or_default();
+    let path = words
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+    match (method, path) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            board.metrics(),
+        ),
+        ("GET", "/status") => ("200 OK", "application/json", board.status()),
+        (_, "/metrics" | "/status") => (
+            "405 Method Not Allowed",
+            "text/plain; charset=utf-8",
+            String::from("Method not allowed\n"),
+        ),
+        _ => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            String::from("Not found\n"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let state = BoardState {
+            completed: 2,
+            failed: 1,
+            directory_requests: 34,
+            file_requests: 70,
+            request_errors: 0,
+            current: Some(CurrentTraversal {
+                workers: 4,
+                start_time: OffsetDateTime::UNIX_EPOCH,
+                requests: 12,
+                errors: 0,
+                elapsed: Duration::from_millis(250),
+            }),
+            last: None,
+        };
+        let metrics = state.render_metrics();
+        assert!(metrics.contains(
+            "# TYPE batchdav_traversals_total counter\nbatchdav_traversals_total{result=\"success\"} 2\nbatchdav_traversals_total{result=\"failure\"} 1\n"
+        ));
+        assert!(metrics.contains("batchdav_requests_total{type=\"file\"} 70\n"));
+        assert!(metrics.contains("batchdav_traversal_in_progress 1\n"));
+        assert!(metrics.contains("batchdav_current_traversal_requests 12\n"));
+        assert!(!metrics.contains("last_traversal"));
+    }
+
+    #[test]
+    fn test_route() {
+        let board = StatusBoard::default();
+        board.begin(8);
+        let (status, _, body) = route("GET /status HTTP/1.1", &board);
+        assert_eq!(status, "200 OK");
+        let value = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(value["current"]["workers"], 8);
+        assert_eq!(value["completed"], 0);
+        let (status, content_type, _) = route("GET /metrics?x=1 HTTP/1.1", &board);
+        assert_eq!(status, "200 OK");
+        assert!(content_type.starts_with("text/plain; version=0.0.4"));
+        assert_eq!(
+            route("POST /metrics HTTP/1.1", &board).0,
+            "405 Method Not Allowed"
+        );
+        assert_eq!(route("GET / HTTP/1.1", &board).0, "404 Not Found");
+        board.fail();
+        assert!(board
+            .metrics()
+            .contains("batchdav_traversal_in_progress 0\n"));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::client::USER_AGENT;
+use crate::diff::WorkerDiff;
+use crate::errorrate::ErrorRateAbort;
+use crate::stats::WorkerSummary;
+use anyhow::Context;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use time::OffsetDateTime;
+use url::Url;
+
+/// How long to wait for a notification webhook to respond
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Posts summaries of completed batches as JSON to a webhook URL
+#[derive(Clone, Debug)]
+pub(crate) struct Notifier {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub(crate) fn new(url: Url) -> anyhow::Result<Notifier> {
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(NOTIFY_TIMEOUT)
+            .build()
+            .context("failed to construct notification client")?;
+        Ok(Notifier { url, client })
+    }
+
+    /// POST `notice` to the webhook URL.  Responses with error statuses are
+    /// treated as failures.
+    pub(crate) async fn send(&self, notice: &BatchNotice) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(notice).context("failed to serialize notification")?;
+        self.client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("failed to send notification to {}", self.url))?;
+        Ok(())
+    }
+}
+
+/// The kind of event reported by a `BatchNotice`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum NoticeEvent {
+    /// The batch finished without any regressions against the baseline
+    Complete,
+    /// The batch finished, and some numbers of workers regressed against the
+    /// baseline
+    Regression,
+}
+
+impl NoticeEvent {
+    pub(crate) fn new(regressions: &[WorkerDiff]) -> NoticeEvent {
+        if regressions.is_empty() {
+            NoticeEvent::Complete
+        } else {
+            NoticeEvent::Regression
+        }
+    }
+}
+
+/// The JSON document posted to the `--notify-url` webhook after a batch (or,
+/// with `--targets`, a target's batch) completes
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BatchNotice {
+    pub(crate) event: NoticeEvent,
+    pub(crate) base_url: Url,
+    /// The target's label, if running with `--targets`
+    pub(crate) label: Option<String>,
+    /// The name of the host on which batchdav was run
+    pub(crate) hostname: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) time: OffsetDateTime,
+    /// The number of traversals performed
+    pub(crate) traversals: usize,
+    /// The file to which results were written, if any
+    pub(crate) report: Option<PathBuf>,
+    /// The error rate that caused the batch to be cut short, if any
+    pub(crate) aborted: Option<ErrorRateAbort>,
+    /// The summary statistics for each number of workers
+    pub(crate) summary: Vec<WorkerSummary>,
+    /// The numbers of workers that regressed against the baseline, if one
+    /// was given
+    pub(crate) regressions: Vec<WorkerDiff>,
+}
@@ -0,0 +1,172 @@
+use crate::stats::WorkerSummary;
+use anyhow::Context;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Render charts of mean traversal time and mean throughput versus number of
+/// workers, with error bars spanning one standard deviation, as an SVG file
+pub(crate) fn plot_svg(path: &Path, summary: &[WorkerSummary]) -> anyhow::Result<()> {
+    let root = SVGBackend::new(path, (1280, 480)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let (left, right) = root.split_horizontally(640);
+    draw_panel(&left, "Traversal Time", "Time (s)", summary, |s| {
+        (s.time_mean, s.time_stddev)
+    })?;
+    draw_panel(&right, "Throughput", "Requests per second", summary, |s| {
+        (s.throughput_mean, s.throughput_stddev)
+    })?;
+    root.present()?;
+    Ok(())
+}
+
+fn draw_panel<F>(
+    area: &DrawingArea<SVGBackend<'_>, Shift>,
+    caption: &str,
+    y_desc: &str,
+    summary: &[WorkerSummary],
+    value: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(&WorkerSummary) -> (f64, f64),
+{
+    // Standard deviations are NaN when there's only one sample; draw no error
+    // bar in such cases.
+    let points = summary
+        .iter()
+        .map(|s| {
+            let (mean, stddev) = value(s);
+            let stddev = if stddev.is_nan() { 0.0 } else { stddev };
+            (s.workers, mean, stddev)
+        })
+        .collect::<Vec<_>>();
+    let max_workers = points.iter().map(|&(w, _, _)| w).max().unwrap_or(1);
+    let max_y = points
+        .iter()
+        .map(|&(_, mean, stddev)| mean + stddev)
+        .fold(0.0, f64::max);
+    let mut chart = ChartBuilder::on(area)
+        .caption(caption, ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0..max_workers.saturating_add(1), 0.0..(max_y * 1.1))?;
+    chart
+        .configure_mesh()
+        .x_desc("Workers")
+        .y_desc(y_desc)
+        .draw()?;
+    chart.draw_series(LineSeries::new(
+        points.iter().map(|&(w, mean, _)| (w, mean)),
+        &BLUE,
+    ))?;
+    chart.draw_series(points.iter().map(|&(w, mean, stddev)| {
+        ErrorBar::new_vertical(w, mean - stddev, mean, mean + stddev, BLUE.filled(), 8)
+    }))?;
+    Ok(())
+}
+
+/// Write the mean traversal time and mean throughput for each number of
+/// workers, with their standard deviations, to `{prefix}.dat`, along with a
+/// gnuplot script `{prefix}.gp` that plots them with error bars to
+/// `{prefix}.svg`.  The script refers to the other files by name only, so it
+/// must be run from the directory containing them.
+pub(crate) fn write_gnuplot(prefix: &Path, summary: &[WorkerSummary]) -> anyhow::Result<()> {
+    let name = prefix
+        .file_name()
+        .context("gnuplot prefix must end in a filename")?
+        .to_string_lossy();
+    let datapath = with_suffix(prefix, ".dat");
+    std::fs::write(&datapath, gnuplot_data(summary))
+        .with_context(|| format!("failed to write {}", datapath.display()))?;
+    let scriptpath = with_suffix(prefix, ".gp");
+    std::fs::write(&scriptpath, gnuplot_script(&name))
+        .with_context(|| format!("failed to write {}", scriptpath.display()))?;
+    Ok(())
+}
+
+fn with_suffix(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut s = prefix.as_os_str().to_os_string();
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+fn gnuplot_data(summary: &[WorkerSummary]) -> String {
+    // Standard deviations are NaN when there's only one sample; write them as
+    // zero so that the points are still plotted.
+    let finite = |x: f64| if x.is_nan() { 0.0 } else { x };
+    let mut s = String::from("# workers time_mean time_stddev throughput_mean throughput_stddev\n");
+    for ws in summary {
+        let _ = writeln!(
+            s,
+            "{} {} {} {} {}",
+            ws.workers,
+            ws.time_mean,
+            finite(ws.time_stddev),
+            ws.throughput_mean,
+            finite(ws.throughput_stddev),
+        );
+    }
+    s
+}
+
+fn gnuplot_script(name: &str) -> String {
+    let quote = |s: String| format!("'{}'", s.replace('\'', "''"));
+    let data = quote(format!("{name}.dat"));
+    let output = quote(format!("{name}.svg"));
+    format!(
+        "\
+set terminal svg size 1280,480
+set output {output}
+set multiplot layout 1,2
+set xlabel 'Workers'
+set xrange [0:*]
+set yrange [0:*]
+set key off
+set grid
+set title 'Traversal Time'
+set ylabel 'Time (s)'
+plot {data} using 1:2 with lines lc rgb 'blue', \\
+     {data} using 1:2:3 with yerrorbars lc rgb 'blue' pt 7
+set title 'Throughput'
+set ylabel 'Requests per second'
+plot {data} using 1:4 with lines lc rgb 'blue', \\
+     {data} using 1:4:5 with yerrorbars lc rgb 'blue' pt 7
+unset multiplot
+"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gnuplot_data() {
+        let summary = WorkerSummary {
+            workers: 2,
+            samples: 1,
+            time_mean: 1.5,
+            time_stddev: f64::NAN,
+            time_median: 1.5,
+            time_p95: 1.5,
+            time_ci: None,
+            throughput_mean: 40.0,
+            throughput_stddev: f64::NAN,
+            utilization_mean: None,
+            efficiency: Some(1.0),
+        };
+        assert_eq!(
+            gnuplot_data(&[summary]),
+            "# workers time_mean time_stddev throughput_mean throughput_stddev\n2 1.5 0 40 0\n"
+        );
+    }
+
+    #[test]
+    fn test_gnuplot_script_quoting() {
+        let script = gnuplot_script("bob's run");
+        assert!(script.contains("set output 'bob''s run.svg'\n"));
+        assert!(script.contains("plot 'bob''s run.dat' using 1:2 with lines"));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::client::{Client, RequestError};
+use crate::types::is_self_href;
+use crate::xml::{parse_response_summaries, FromXmlError, ResponseSummary};
+use std::fmt;
+use thiserror::Error;
+use url::Url;
+
+/// The outcome of a successful preflight check of a batch's base URL
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Preflight {
+    pub(crate) url: Url,
+    /// The `DAV` header of the `OPTIONS` response, if any
+    pub(crate) dav: Option<String>,
+    /// Problems that do not prevent the URL from being traversed
+    pub(crate) warnings: Vec<String>,
+}
+
+impl fmt::Display for Preflight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Preflight check of {} passed", self.url)?;
+        if let Some(ref dav) = self.dav {
+            write!(f, " (DAV: {dav})")?;
+        }
+        writeln!(f)?;
+        for w in &self.warnings {
+            writeln!(f, "Warning: {w}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that `url` is a WebDAV collection that `client` can list by making
+/// an `OPTIONS` request and a `Depth: 0` PROPFIND request (with the same body
+/// as traversals use) and parsing the latter's response
+pub(crate) async fn preflight(client: &Client, url: &Url) -> Result<Preflight, PreflightError> {
+    let request_error = |source| PreflightError::Request {
+        url: url.clone(),
+        source: Box::new(source),
+    };
+    let options = client.options_unchecked(url).await.map_err(request_error)?;
+    let mut warnings = Vec::new();
+    match options.status {
+        401 => {
+            return Err(PreflightError::Unauthorized {
+                url: url.clone(),
+                method: "OPTIONS",
+            })
+        }
+        200..=299 => {
+            if options.dav.is_none() {
+                warnings.push(String::from(
+                    "OPTIONS response has no DAV header; the server may not support WebDAV",
+                ));
+            }
+            if let Some(ref allow) = options.allow {
+                if !allow
+                    .split(',')
+                    .any(|m| m.trim().eq_ignore_ascii_case("PROPFIND"))
+                {
+                    warnings.push(format!(
+                        "OPTIONS response's Allow header ({allow}) does not include PROPFIND"
+                    ));
+                }
+            }
+        }
+        // Some servers refuse OPTIONS requests but support PROPFIND, so
+        // leave the verdict to the PROPFIND
+        status => warnings.push(format!("OPTIONS request failed with status {status}")),
+    }
+    let resp = client
+        .propfind_unchecked(url, Some("0"), Some(client.propfind_body()))
+        .await
+        .map_err(request_error)?;
+    check_propfind_status(url, resp.status)?;
+    let summaries = parse_response_summaries(resp.body, resp.charset, client.options().xml_options)
+        .map_err(|source| PreflightError::Xml {
+            url: url.clone(),
+            source: Box::new(source),
+        })?;
+    check_listing(url, &summaries)?;
+    Ok(Preflight {
+        url: url.clone(),
+        dav: options.dav,
+        warnings,
+    })
+}
+
+/// Check the status of the response to a `Depth: 0` PROPFIND of `url`
+fn check_propfind_status(url: &Url, status: u16) -> Result<(), PreflightError> {
+    let url = url.clone();
+    match status {
+        207 => Ok(()),
+        200..=299 => Err(PreflightError::NotMultistatus { url, status }),
+        300..=399 => Err(PreflightError::Redirect { url, status }),
+        401 => Err(PreflightError::Unauthorized {
+            url,
+            method: "PROPFIND",
+        }),
+        403 => Err(PreflightError::Forbidden { url }),
+        404 => Err(PreflightError::NotFound { url }),
+        405 | 501 => Err(PreflightError::NoPropfind { url, status }),
+        status => Err(PreflightError::Status { url, status }),
+    }
+}
+
+/// Check that a `Depth: 0` listing of `url` describes `url` as a collection
+fn check_listing(url: &Url, summaries: &[ResponseSummary]) -> Result<(), PreflightError> {
+    let this = summaries.iter().find(|r| is_self_href(url, &r.href));
+    match this {
+        Some(r) if r.is_collection => Ok(()),
+        Some(_) => Err(PreflightError::NotCollection { url: url.clone() }),
+        None => Err(PreflightError::SelfMissing { url: url.clone() }),
+    }
+}
+
+/// A problem found by a preflight check that makes a URL unusable as the
+/// base URL of a batch
+#[derive(Debug, Error)]
+pub(crate) enum PreflightError {
+    #[error("preflight request to {url} failed; check the URL and that the server is reachable")]
+    Request { url: Url, source: Box<RequestError> },
+    #[error("{method} request to {url} was rejected as unauthorized (401); check the credentials given in the URL or via --token-command")]
+    Unauthorized { url: Url, method: &'static str },
+    #[error("PROPFIND request to {url} was forbidden (403); check that the account may list the collection")]
+    Forbidden { url: Url },
+    #[error("{url} was not found (404); check the path of the base URL")]
+    NotFound { url: Url },
+    #[error("{url} does not support PROPFIND (status {status}); check that the URL points to a WebDAV endpoint")]
+    NoPropfind { url: Url, status: u16 },
+    #[error("PROPFIND request to {url} was redirected (status {status}); use the URL that it redirects to (e.g., with a trailing slash) instead")]
+    Redirect { url: Url, status: u16 },
+    #[error("PROPFIND request to {url} returned status {status} instead of 207 Multi-Status; check that the URL points to a WebDAV endpoint rather than a plain web server")]
+    NotMultistatus { url: Url, status: u16 },
+    #[error("PROPFIND request to {url} failed with status {status}")]
+    Status { url: Url, status: u16 },
+    #[error("PROPFIND response from {url} is not a valid multistatus document; check that the URL points to a WebDAV endpoint")]
+    Xml { url: Url, source: Box<FromXmlError> },
+    #[error("PROPFIND response from {url} does not describe the base URL itself")]
+    SelfMissing { url: Url },
+    #[error("{url} is not a collection; the base URL must be a WebDAV collection")]
+    NotCollection { url: Url },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(207, None)]
+    #[case(200, Some("instead of 207 Multi-Status"))]
+    #[case(301, Some("was redirected (status 301)"))]
+    #[case(401, Some("unauthorized (401)"))]
+    #[case(403, Some("forbidden (403)"))]
+    #[case(404, Some("not found (404)"))]
+    #[case(405, Some("does not support PROPFIND (status 405)"))]
+    #[case(503, Some("failed with status 503"))]
+    fn test_check_propfind_status(#[case] status: u16, #[case] message: Option<&str>) {
+        let url = Url::parse("https://dav.example.com/data/").unwrap();
+        match (check_propfind_status(&url, status), message) {
+            (Ok(()), None) => (),
+            (Err(e), Some(msg)) => assert!(e.to_string().contains(msg), "{e}"),
+            (r, _) => panic!("unexpected result for {status}: {r:?}"),
+        }
+    }
+
+    #[test]
+    fn test_check_listing() {
+        let url = Url::parse("https://dav.example.com/data/").unwrap();
+        let summary = |href: &str, is_collection| ResponseSummary {
+            href: href.into(),
+            is_collection,
+            propstat_statuses: vec![200],
+            invalid_status_lines: Vec::new(),
+            warnings: Vec::new(),
+        };
+        assert!(check_listing(&url, &[summary("/data/", true)]).is_ok());
+        assert!(check_listing(&url, &[summary("https://dav.example.com/data", true)]).is_ok());
+        assert!(matches!(
+            check_listing(&url, &[summary("/data/", false)]),
+            Err(PreflightError::NotCollection { .. })
+        ));
+        assert!(matches!(
+            check_listing(&url, &[summary("/other/", true)]),
+            Err(PreflightError::SelfMissing { .. })
+        ));
+        let tilde = Url::parse("https://dav.example.com/%7Euser/").unwrap();
+        assert!(check_listing(&tilde, &[summary("/~user/", true)]).is_ok());
+    }
+}
@@ -0,0 +1,325 @@
+use crate::export::{serialize_optional_seconds, serialize_seconds};
+use crate::show_duration::show_hms;
+use crate::stats::{rfc3339, Knee, WorkerSummary};
+use crate::traverse::TraversalReport;
+use crate::usl::UslFit;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+/// How to report the progress of a batch on standard error
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ProgressFormat {
+    /// Human-readable "Finished: …" lines
+    #[default]
+    Text,
+    /// One JSON object per line for each completed traversal plus periodic
+    /// updates during each traversal
+    Json,
+}
+
+/// How much incidental output to suppress, as set by the number of times the
+/// `-q`/`--quiet` option is given
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Quietness {
+    /// Print everything
+    #[default]
+    Normal,
+    /// Don't print details on each request as it's completed
+    Requests,
+    /// Also don't print human-readable progress messages
+    Progress,
+    /// Only output the requested data; no progress reports of any kind
+    DataOnly,
+}
+
+impl From<u8> for Quietness {
+    fn from(count: u8) -> Quietness {
+        match count {
+            0 => Quietness::Normal,
+            1 => Quietness::Requests,
+            2 => Quietness::Progress,
+            _ => Quietness::DataOnly,
+        }
+    }
+}
+
+/// How often to emit progress events during a traversal
+pub(crate) const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An event emitted on standard error under `--progress-format json`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum ProgressEvent {
+    /// A traversal is still in progress
+    Progress {
+        workers: usize,
+        /// Number of requests completed so far
+        requests: usize,
+        /// Number of failed requests so far
+        errors: usize,
+        #[serde(serialize_with = "serialize_seconds")]
+        elapsed: Duration,
+    },
+    /// A traversal has completed
+    Sample {
+        workers: usize,
+        /// The 1-based index of the traversal among those with the same
+        /// number of workers
+        run: usize,
+        requests: usize,
+        errors: usize,
+        #[serde(serialize_with = "serialize_seconds")]
+        elapsed: Duration,
+        /// The 1-based index of the traversal among all those in the batch
+        sample: usize,
+        /// The number of traversals that the batch is expected to perform
+        total_samples: usize,
+        /// The time since the start of the batch
+        #[serde(serialize_with = "serialize_seconds")]
+        batch_elapsed: Duration,
+        /// The estimated time until the batch completes
+        #[serde(serialize_with = "serialize_optional_seconds")]
+        remaining: Option<Duration>,
+    },
+}
+
+/// Tracks the traversals of a batch in order to report overall progress and
+/// estimate the time remaining
+#[derive(Clone, Debug)]
+pub(crate) struct BatchProgress {
+    start: Instant,
+    /// The numbers of workers of the batch in order, each paired with the
+    /// number of traversals still to be performed with it
+    pending: Vec<(usize, usize)>,
+    done: usize,
+    /// The total time & number of the completed traversals for each number
+    /// of workers
+    durations: BTreeMap<usize, (Duration, u32)>,
+    /// The number of workers of the most recently completed traversal
+    last_workers: Option<usize>,
+}
+
+impl BatchProgress {
+    /// Start tracking a batch that will perform at most the given numbers of
+    /// traversals for each number of workers, in order
+    pub(crate) fn new<I: IntoIterator<Item = (usize, usize)>>(schedule: I) -> BatchProgress {
+        BatchProgress {
+            start: Instant::now(),
+            pending: schedule.into_iter().collect(),
+            done: 0,
+            durations: BTreeMap::new(),
+            last_workers: None,
+        }
+    }
+
+    /// Record the completion of a traversal for the `index`-th number of
+    /// workers that took `elapsed`
+    pub(crate) fn record(&mut self, index: usize, elapsed: Duration) {
+        if let Some((workers, n)) = self.pending.get_mut(index) {
+            *n = n.saturating_sub(1);
+            let (total, count) = self.durations.entry(*workers).or_default();
+            *total = total.saturating_add(elapsed);
+            *count = count.saturating_add(1);
+            self.last_workers = Some(*workers);
+        }
+        self.done = self.done.saturating_add(1);
+    }
+
+    /// Record that no further traversals will be performed for the
+    /// `index`-th number of workers, e.g., because its confidence interval
+    /// converged early
+    pub(crate) fn finish(&mut self, index: usize) {
+        if let Some((_, n)) = self.pending.get_mut(index) {
+            *n = 0;
+        }
+    }
+
+    /// The number of traversals completed so far
+    pub(crate) fn done(&self) -> usize {
+        self.done
+    }
+
+    /// The number of traversals that the batch is expected to perform
+    pub(crate) fn total(&self) -> usize {
+        self.pending
+            .iter()
+            .fold(self.done, |acc, &(_, n)| acc.saturating_add(n))
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Estimate the time needed for the remaining traversals, taking each
+    /// one to last as long as the mean of the completed traversals with the
+    /// same number of workers or, if there are none, of those with the most
+    /// recently used number of workers.  Returns `None` if no traversals have
+    /// completed yet.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        let mean = |workers: usize| {
+            self.durations
+                .get(&workers)
+                .filter(|&&(_, count)| count > 0)
+                .map(|&(total, count)| total / count)
+        };
+        let fallback = mean(self.last_workers?)?;
+        Some(
+            self.pending
+                .iter()
+                .map(|&(workers, n)| {
+                    mean(workers)
+                        .unwrap_or(fallback)
+                        .saturating_mul(u32::try_from(n).unwrap_or(u32::MAX))
+                })
+                .fold(Duration::ZERO, Duration::saturating_add),
+        )
+    }
+}
+
+impl std::fmt::Display for BatchProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Progress: sample {}/{}, elapsed {}",
+            self.done,
+            self.total(),
+            show_hms(self.elapsed().as_secs_f64())
+        )?;
+        if let Some(remaining) = self.remaining() {
+            write!(f, ", est. remaining {}", show_hms(remaining.as_secs_f64()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Report the completion of the `run`-th traversal with `report.workers`
+/// workers along with the overall `progress` of the batch.  In text mode,
+/// nothing is reported if `per_traversal` is true, as the per-traversal CSV
+/// already serves as a progress report.
+pub(crate) fn report_sample(
+    format: ProgressFormat,
+    quietness: Quietness,
+    run: usize,
+    report: &TraversalReport,
+    progress: &BatchProgress,
+    per_traversal: bool,
+    timestamps: bool,
+) {
+    if quietness >= Quietness::DataOnly
+        || (format == ProgressFormat::Text && quietness >= Quietness::Progress)
+    {
+        return;
+    }
+    let workers = report.workers;
+    let requests = report.requests();
+    let elapsed = report.overall_time;
+    match format {
+        ProgressFormat::Text if !per_traversal => {
+            let lines = [
+                format!(
+                    "Finished: workers = {workers}, run = {run}, requests = {requests}, elapsed = {elapsed:?}"
+                ),
+                progress.to_string(),
+            ];
+            for line in lines {
+                if timestamps {
+                    eprintln!("{}", timestamped(&line));
+                } else {
+                    eprintln!("{line}");
+                }
+            }
+        }
+        ProgressFormat::Text => (),
+        ProgressFormat::Json => ProgressEvent::Sample {
+            workers,
+            run,
+            requests,
+            errors: report.errors.total(),
+            elapsed,
+            sample: progress.done(),
+            total_samples: progress.total(),
+            batch_elapsed: progress.elapsed(),
+            remaining: progress.remaining(),
+        }
+        .emit(),
+    }
+}
+
+/// Report the scalability knee (using a threshold of `knee_threshold`
+/// percent) and the Universal Scalability Law fit for the per-workers
+/// `summaries` of a batch on standard error
+pub(crate) fn report_scaling(summaries: &[WorkerSummary], knee_threshold: f64) {
+    if let Some(knee) = Knee::find(summaries, knee_threshold / 100.0) {
+        eprint!("{knee}");
+    }
+    if let Some(fit) = UslFit::fit(summaries) {
+        eprint!("{fit}");
+    }
+}
+
+/// Prefix `line` with the current time in RFC 3339 format
+pub(crate) fn timestamped(line: &str) -> String {
+    format!("{} {line}", rfc3339(OffsetDateTime::now_utc()))
+}
+
+impl ProgressEvent {
+    pub(crate) fn emit(&self) {
+        match serde_json::to_string(self) {
+            Ok(s) => eprintln!("{s}"),
+            Err(e) => eprintln!("failed to serialize progress event: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_sample() {
+        let event = ProgressEvent::Sample {
+            workers: 4,
+            run: 2,
+            requests: 39,
+            errors: 0,
+            elapsed: Duration::from_millis(1250),
+            sample: 7,
+            total_samples: 20,
+            batch_elapsed: Duration::from_secs(9),
+            remaining: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"sample","workers":4,"run":2,"requests":39,"errors":0,"elapsed":1.25,"sample":7,"total_samples":20,"batch_elapsed":9.0,"remaining":null}"#
+        );
+    }
+
+    #[test]
+    fn test_batch_progress() {
+        let mut progress = BatchProgress::new([(1, 3), (2, 3), (4, 3)]);
+        assert_eq!((progress.done(), progress.total()), (0, 9));
+        assert_eq!(progress.remaining(), None);
+        progress.record(0, Duration::from_secs(8));
+        progress.record(0, Duration::from_secs(10));
+        assert_eq!((progress.done(), progress.total()), (2, 9));
+        // One more at 9s, then six at the most recent mean of 9s
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(63)));
+        progress.finish(0);
+        progress.record(1, Duration::from_secs(5));
+        assert_eq!((progress.done(), progress.total()), (3, 8));
+        assert_eq!(progress.remaining(), Some(Duration::from_secs(25)));
+    }
+
+    #[test]
+    fn test_timestamped() {
+        let line = timestamped("DIR: https://example.com/");
+        let (stamp, rest) = line.split_once(' ').unwrap();
+        assert!(
+            OffsetDateTime::parse(stamp, &time::format_description::well_known::Rfc3339).is_ok()
+        );
+        assert_eq!(rest, "DIR: https://example.com/");
+    }
+}
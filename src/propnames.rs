@@ -0,0 +1,198 @@
+use crate::btn::{BoundedTreeNursery, Spawner};
+use crate::client::{Client, RequestError};
+use crate::table::{Align, Table};
+use crate::xml::PropName;
+use futures_util::{future::BoxFuture, FutureExt, TryStreamExt};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use url::Url;
+
+/// Traverse the hierarchy at `base_url`, making a `<propname/>` PROPFIND
+/// request to each collection, and tally how many resources define each
+/// property
+pub(crate) async fn discover_propnames(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+) -> Result<PropnameReport, RequestError> {
+    let root = base_url.clone();
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        [move |spawner| process_dir(spawner, client, root, true)],
+    );
+    let mut report = PropnameReport::default();
+    while let Some(resources) = stream.try_next().await? {
+        for (is_collection, names) in resources {
+            report.record(is_collection, names);
+        }
+    }
+    Ok(report)
+}
+
+/// A list of resources, each given as whether the resource is a collection
+/// paired with the names of its properties
+type Resources = Vec<(bool, Vec<PropName>)>;
+
+fn process_dir(
+    spawner: Spawner<Result<Resources, RequestError>>,
+    client: Client,
+    url: Url,
+    is_root: bool,
+) -> BoxFuture<'static, Result<Resources, RequestError>> {
+    async move {
+        // A `<propname/>` response doesn't tell us which resources are
+        // collections, so we also need to make a regular listing request.
+        let listing = client.list_directory(url.clone()).await?.listing;
+        let collections = listing
+            .directories
+            .iter()
+            .map(|d| d.href.as_str().trim_end_matches('/').to_owned())
+            .collect::<HashSet<_>>();
+        for d in listing.directories {
+            let cl2 = client.clone();
+            spawner.spawn(move |spawner| process_dir(spawner, cl2, d.href, false));
+        }
+        let self_key = url.as_str().trim_end_matches('/');
+        let mut resources = Vec::new();
+        for (href, names) in client.list_propnames(url.clone()).await? {
+            let key = href.as_str().trim_end_matches('/');
+            if key == self_key {
+                // Each collection other than the root is counted when listing
+                // its parent
+                if is_root {
+                    resources.push((true, names));
+                }
+            } else {
+                resources.push((collections.contains(key), names));
+            }
+        }
+        Ok(resources)
+    }
+    .boxed()
+}
+
+/// The number of resources on which a property is defined
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct PropCounts {
+    pub(crate) collections: usize,
+    pub(crate) files: usize,
+}
+
+impl PropCounts {
+    fn total(&self) -> usize {
+        self.collections.saturating_add(self.files)
+    }
+}
+
+/// The results of a property name discovery traversal
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct PropnameReport {
+    /// Total number of collections examined
+    pub(crate) collections: usize,
+    /// Total number of non-collection resources examined
+    pub(crate) files: usize,
+    pub(crate) properties: BTreeMap<PropName, PropCounts>,
+}
+
+impl PropnameReport {
+    fn record(&mut self, is_collection: bool, mut names: Vec<PropName>) {
+        if is_collection {
+            self.collections = self.collections.saturating_add(1);
+        } else {
+            self.files = self.files.saturating_add(1);
+        }
+        // Don't count a property twice if a server lists it twice
+        names.sort_unstable();
+        names.dedup();
+        for name in names {
+            let counts = self.properties.entry(name).or_default();
+            if is_collection {
+                counts.collections = counts.collections.saturating_add(1);
+            } else {
+                counts.files = counts.files.saturating_add(1);
+            }
+        }
+    }
+}
+
+impl fmt::Display for PropnameReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Examined {} collections and {} files",
+            self.collections, self.files
+        )?;
+        // Most common properties first
+        let mut props = self.properties.iter().collect::<Vec<_>>();
+        props.sort_by(|(n1, c1), (n2, c2)| c2.total().cmp(&c1.total()).then_with(|| n1.cmp(n2)));
+        let mut table = Table::new()
+            .column("Namespace", Align::Left)
+            .column("Property", Align::Left)
+            .column("Collections", Align::Right)
+            .column("Files", Align::Right);
+        for (name, counts) in props {
+            table.push_row(vec![
+                name.namespace.clone(),
+                name.name.clone(),
+                counts.collections.to_string(),
+                counts.files.to_string(),
+            ]);
+        }
+        write!(f, "{table}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dav(name: &str) -> PropName {
+        PropName {
+            namespace: "DAV:".into(),
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let mut report = PropnameReport::default();
+        report.record(true, vec![dav("resourcetype"), dav("displayname")]);
+        report.record(false, vec![dav("resourcetype"), dav("getcontentlength")]);
+        report.record(
+            false,
+            vec![
+                dav("resourcetype"),
+                dav("getcontentlength"),
+                dav("resourcetype"),
+            ],
+        );
+        assert_eq!(report.collections, 1);
+        assert_eq!(report.files, 2);
+        assert_eq!(
+            report.properties,
+            BTreeMap::from([
+                (
+                    dav("displayname"),
+                    PropCounts {
+                        collections: 1,
+                        files: 0
+                    }
+                ),
+                (
+                    dav("getcontentlength"),
+                    PropCounts {
+                        collections: 0,
+                        files: 2
+                    }
+                ),
+                (
+                    dav("resourcetype"),
+                    PropCounts {
+                        collections: 1,
+                        files: 2
+                    }
+                ),
+            ])
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+/// A schedule on which the number of workers of a traversal is raised from
+/// one to its full number over a period of time, either continuously or in a
+/// fixed number of equal steps
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Ramp {
+    pub(crate) duration: Duration,
+    /// If set, raise the number of workers in this many equal steps, the
+    /// last of which occurs at the end of the ramp
+    pub(crate) steps: Option<usize>,
+}
+
+impl Ramp {
+    /// The number of workers out of `workers` that may be active `elapsed`
+    /// after the start of the traversal
+    pub(crate) fn workers(&self, workers: usize, elapsed: Duration) -> usize {
+        let extra = workers.saturating_sub(1) as u128;
+        let total = self.duration.as_nanos().max(1);
+        let elapsed = elapsed.as_nanos().min(total);
+        let added = match self.steps {
+            Some(steps) => {
+                let steps = (steps as u128).max(1);
+                extra * (steps * elapsed / total) / steps
+            }
+            None => extra * elapsed / total,
+        };
+        usize::try_from(added)
+            .unwrap_or(usize::MAX)
+            .saturating_add(1)
+            .min(workers)
+    }
+
+    /// True if the ramp is over `elapsed` after the start of the traversal
+    pub(crate) fn finished(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, 0, 1)]
+    #[case(None, 1000, 2)]
+    #[case(None, 4999, 8)]
+    #[case(None, 5000, 9)]
+    #[case(None, 10000, 17)]
+    #[case(None, 20000, 17)]
+    #[case(Some(4), 2499, 1)]
+    #[case(Some(4), 2500, 5)]
+    #[case(Some(4), 7499, 9)]
+    #[case(Some(4), 9999, 13)]
+    #[case(Some(4), 10000, 17)]
+    fn test_ramp_workers(#[case] steps: Option<usize>, #[case] ms: u64, #[case] expected: usize) {
+        let ramp = Ramp {
+            duration: Duration::from_secs(10),
+            steps,
+        };
+        assert_eq!(ramp.workers(17, Duration::from_millis(ms)), expected);
+    }
+
+    #[test]
+    fn test_ramp_single_worker() {
+        let ramp = Ramp {
+            duration: Duration::from_secs(10),
+            steps: None,
+        };
+        assert_eq!(ramp.workers(1, Duration::ZERO), 1);
+        assert_eq!(ramp.workers(0, Duration::from_secs(5)), 0);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::client::{Client, RangeResponse, RequestError};
+use crate::stats::{mib_per_sec, LatencySummary};
+use crate::traverse::{traverse, ErrorPolicy, TraversalOptions};
+use futures_util::StreamExt;
+use rand::seq::SliceRandom;
+use std::fmt;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Settings for a ranged-download benchmark
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct RangeBenchOptions {
+    /// Number of files to sample from the hierarchy
+    pub(crate) files: usize,
+    /// Size in bytes of each ranged request
+    pub(crate) chunk_size: u64,
+    /// Number of ranged requests to have in flight for each file at once
+    pub(crate) parallel: usize,
+    /// Maximum number of redirects to follow when resolving each file
+    pub(crate) max_redirects: usize,
+}
+
+/// Traverse the hierarchy at `base_url` with `workers` workers, choose a
+/// random sample of the files found, and download each file in turn with
+/// parallel ranged `GET` requests to its final redirect target
+pub(crate) async fn range_bench(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+    opts: RangeBenchOptions,
+) -> anyhow::Result<RangeBenchReport> {
+    let options = TraversalOptions {
+        quiet: true,
+        on_error: ErrorPolicy::Skip,
+        collect_urls: true,
+        ..TraversalOptions::default()
+    };
+    let found = traverse(client.clone(), base_url, workers, &options).await?;
+    let files = found
+        .urls
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|u| !u.path().ends_with('/'))
+        .collect::<Vec<_>>();
+    let mut sample = files
+        .choose_multiple(&mut rand::thread_rng(), opts.files)
+        .cloned()
+        .collect::<Vec<_>>();
+    sample.sort();
+    let start = Instant::now();
+    let mut results = Vec::with_capacity(sample.len());
+    for url in sample {
+        let outcome = bench_file(&client, &url, opts)
+            .await
+            .map_err(|e| format!("{:#}", anyhow::Error::from(e)));
+        results.push(FileResult { url, outcome });
+    }
+    Ok(RangeBenchReport {
+        chunk_size: opts.chunk_size,
+        parallel: opts.parallel,
+        files_found: files.len(),
+        results,
+        overall_time: start.elapsed(),
+    })
+}
+
+/// Resolve the redirects for `url` and then download the final target in
+/// chunks.  The first chunk is requested on its own in order to learn the
+/// size of the resource; the remaining chunks are then requested with up to
+/// `opts.parallel` requests in flight at once.
+async fn bench_file(
+    client: &Client,
+    url: &Url,
+    opts: RangeBenchOptions,
+) -> Result<FileBench, RequestError> {
+    let target = client
+        .resolve_redirects(url.clone(), opts.max_redirects)
+        .await?;
+    let start = Instant::now();
+    let last = opts.chunk_size.saturating_sub(1);
+    let first = client.get_range(&target, 0, last).await?;
+    let mut chunk_times = vec![first.elapsed];
+    let mut bytes = first.received;
+    let mut size = bytes;
+    let mut mismatched = 0usize;
+    let ranges_honored = first.status == 206;
+    if ranges_honored {
+        size = first.total_size.unwrap_or(bytes);
+        let mut ranges = Vec::new();
+        let mut pos = opts.chunk_size;
+        while pos < size {
+            let end = pos.saturating_add(opts.chunk_size).min(size);
+            ranges.push((pos, end.saturating_sub(1)));
+            pos = end;
+        }
+        let mut stream = futures_util::stream::iter(ranges.into_iter().map(|(first, last)| {
+            let target = &target;
+            async move {
+                let r = client.get_range(target, first, last).await?;
+                Ok::<_, RequestError>((r, last.saturating_sub(first).saturating_add(1)))
+            }
+        }))
+        .buffer_unordered(opts.parallel);
+        while let Some(r) = stream.next().await {
+            let (
+                RangeResponse {
+                    status,
+                    received,
+                    elapsed,
+                    ..
+                },
+                expected,
+            ) = r?;
+            if status != 206 || received != expected {
+                mismatched = mismatched.saturating_add(1);
+            }
+            chunk_times.push(elapsed);
+            bytes = bytes.saturating_add(received);
+        }
+    }
+    Ok(FileBench {
+        target,
+        size,
+        bytes,
+        chunk_times,
+        ranges_honored,
+        mismatched,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// The outcome of downloading a single sampled file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct FileResult {
+    pub(crate) url: Url,
+    /// The download's statistics, or the error message if it failed
+    pub(crate) outcome: Result<FileBench, String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct FileBench {
+    /// The URL that was actually downloaded after following redirects
+    pub(crate) target: Url,
+    /// Size of the resource in bytes
+    pub(crate) size: u64,
+    /// Total number of bytes received
+    pub(crate) bytes: u64,
+    /// Latencies of the individual ranged requests
+    pub(crate) chunk_times: Vec<Duration>,
+    /// False if the server responded to the first ranged request with the
+    /// entire resource
+    pub(crate) ranges_honored: bool,
+    /// Number of chunk responses that were not 206 or did not contain the
+    /// requested number of bytes
+    pub(crate) mismatched: usize,
+    /// Time taken to download all chunks, not including resolving redirects
+    pub(crate) elapsed: Duration,
+}
+
+impl FileBench {
+    /// Effective download rate in MiB/s
+    fn throughput(&self) -> f64 {
+        mib_per_sec(self.bytes, self.elapsed)
+    }
+}
+
+/// The results of a ranged-download benchmark
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RangeBenchReport {
+    pub(crate) chunk_size: u64,
+    pub(crate) parallel: usize,
+    /// Number of files found in the hierarchy
+    pub(crate) files_found: usize,
+    /// The results for each sampled file, in URL order
+    pub(crate) results: Vec<FileResult>,
+    /// Time taken to download all of the sampled files, not including
+    /// discovering the hierarchy
+    pub(crate) overall_time: Duration,
+}
+
+impl RangeBenchReport {
+    /// The number of sampled files that could not be downloaded
+    pub(crate) fn failures(&self) -> usize {
+        self.results.iter().filter(|r| r.outcome.is_err()).count()
+    }
+
+    fn successes(&self) -> impl Iterator<Item = &FileBench> {
+        self.results.iter().filter_map(|r| r.outcome.as_ref().ok())
+    }
+}
+
+impl fmt::Display for RangeBenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for r in &self.results {
+            match r.outcome {
+                Ok(ref fb) => {
+                    write!(
+                        f,
+                        "OK      {}: {} bytes in {} chunks in {:?} ({:.2} MiB/s)",
+                        r.url,
+                        fb.bytes,
+                        fb.chunk_times.len(),
+                        fb.elapsed,
+                        fb.throughput()
+                    )?;
+                    if fb.target != r.url {
+                        write!(f, " via {}", fb.target)?;
+                    }
+                    if !fb.ranges_honored {
+                        write!(f, " [Range ignored]")?;
+                    }
+                    if fb.mismatched > 0 {
+                        write!(f, " [{} bad chunk responses]", fb.mismatched)?;
+                    }
+                    writeln!(f)?;
+                }
+                Err(ref msg) => writeln!(f, "FAILED  {}: {msg}", r.url)?,
+            }
+        }
+        let bytes = self
+            .successes()
+            .fold(0u64, |acc, fb| acc.saturating_add(fb.bytes));
+        writeln!(
+            f,
+            "Downloaded {bytes} bytes from {} of {} sampled files ({} found) in {:?} ({:.2} MiB/s) using {}-byte chunks with {} parallel requests per file",
+            self.results.len().saturating_sub(self.failures()),
+            self.results.len(),
+            self.files_found,
+            self.overall_time,
+            mib_per_sec(bytes, self.overall_time),
+            self.chunk_size,
+            self.parallel,
+        )?;
+        let chunk_times = self
+            .successes()
+            .flat_map(|fb| fb.chunk_times.iter().copied())
+            .collect::<Vec<_>>();
+        if let Some(lat) = LatencySummary::new(&chunk_times) {
+            writeln!(f, "Chunk latency: {lat}")?;
+        }
+        let mut rates = self
+            .successes()
+            .map(FileBench::throughput)
+            .collect::<Vec<_>>();
+        rates.sort_by(f64::total_cmp);
+        if let (Some(min), Some(max)) = (rates.first(), rates.last()) {
+            let median = rates.get(rates.len() / 2).unwrap_or(min);
+            writeln!(
+                f,
+                "Per-file throughput: min {min:.2} MiB/s, median {median:.2} MiB/s, max {max:.2} MiB/s"
+            )?;
+        }
+        let ignored = self.successes().filter(|fb| !fb.ranges_honored).count();
+        if ignored > 0 {
+            writeln!(f, "Range requests ignored for {ignored} files")?;
+        }
+        Ok(())
+    }
+}
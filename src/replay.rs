@@ -0,0 +1,200 @@
+use crate::btn::BoundedTreeNursery;
+use crate::client::{Client, RequestDescription};
+use crate::compress::InFile;
+use anyhow::Context;
+use futures_util::StreamExt;
+use reqwest::Method;
+use serde::Deserialize;
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use url::Url;
+
+/// A failed request read from an error log written by `run --error-log`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub(crate) struct FailedRequest {
+    pub(crate) url: Url,
+    #[serde(flatten)]
+    pub(crate) request: RequestDescription,
+}
+
+/// Read the failed requests from the error log at `path`, skipping blank
+/// lines
+pub(crate) fn read_error_log(path: &Path) -> anyhow::Result<Vec<FailedRequest>> {
+    let mut s = String::new();
+    InFile::open(path)
+        .and_then(|mut fp| fp.read_to_string(&mut s))
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse_error_log(&s).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+fn parse_error_log(s: &str) -> anyhow::Result<Vec<FailedRequest>> {
+    let mut requests = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let req = serde_json::from_str::<FailedRequest>(line)
+            .with_context(|| format!("invalid entry on line {}", i.saturating_add(1)))?;
+        requests.push(req);
+    }
+    Ok(requests)
+}
+
+/// Re-issue each of `requests` with its original method, headers, and body,
+/// with up to `workers` requests active at once, and report which now
+/// succeed
+pub(crate) async fn retry_failed(
+    client: Client,
+    requests: Vec<FailedRequest>,
+    workers: usize,
+) -> anyhow::Result<RetryReport> {
+    let mut tasks = Vec::with_capacity(requests.len());
+    for (i, fr) in requests.into_iter().enumerate() {
+        let method = Method::from_bytes(fr.request.method.as_bytes())
+            .with_context(|| format!("invalid HTTP method {:?}", fr.request.method))?;
+        let client = client.clone();
+        tasks.push(move |_spawner| async move {
+            let r = client
+                .replay(
+                    &fr.url,
+                    method,
+                    &fr.request.headers,
+                    fr.request.body.as_deref(),
+                )
+                .await
+                .map_err(|e| format!("{:#}", anyhow::Error::from(e)));
+            (
+                i,
+                RetryResult {
+                    url: fr.url,
+                    method: fr.request.method,
+                    outcome: r,
+                },
+            )
+        });
+    }
+    let mut stream = BoundedTreeNursery::new(workers, tasks);
+    let mut results = Vec::new();
+    while let Some(r) = stream.next().await {
+        results.push(r);
+    }
+    results.sort_by_key(|&(i, _)| i);
+    Ok(RetryReport {
+        results: results.into_iter().map(|(_, r)| r).collect(),
+    })
+}
+
+/// The outcome of re-issuing a single failed request
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RetryResult {
+    pub(crate) url: Url,
+    pub(crate) method: String,
+    /// The response's status code if the request succeeded, or the error
+    /// message if it failed again
+    pub(crate) outcome: Result<u16, String>,
+}
+
+/// The outcomes of re-issuing failed requests, in the order in which they
+/// were logged
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct RetryReport {
+    pub(crate) results: Vec<RetryResult>,
+}
+
+impl RetryReport {
+    /// The number of requests that failed again
+    pub(crate) fn still_failing(&self) -> usize {
+        self.results.iter().filter(|r| r.outcome.is_err()).count()
+    }
+}
+
+impl fmt::Display for RetryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for r in &self.results {
+            match r.outcome {
+                Ok(status) => writeln!(f, "OK      {} {} ({status})", r.method, r.url)?,
+                Err(ref msg) => writeln!(f, "FAILED  {} {}: {msg}", r.method, r.url)?,
+            }
+        }
+        let failing = self.still_failing();
+        writeln!(
+            f,
+            "Retried {} requests: {} now succeed, {failing} still fail",
+            self.results.len(),
+            self.results.len().saturating_sub(failing),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_error_log() {
+        let s = concat!(
+            r#"{"url":"https://example.com/dav/","kind":"http_status","status":500,"#,
+            r#""message":"request to https://example.com/dav/ returned 500","#,
+            r#""method":"PROPFIND","headers":{"Depth":"1"},"body":"<propfind/>","#,
+            r#""curl":"curl -i -X PROPFIND 'https://example.com/dav/'"}"#,
+            "\n\n",
+            r#"{"url":"https://example.com/dav/a.txt","kind":"timeout","status":null,"#,
+            r#""message":"timed out","method":"HEAD","headers":{},"body":null,"#,
+            r#""curl":"curl --head 'https://example.com/dav/a.txt'"}"#,
+            "\n",
+        );
+        let requests = parse_error_log(s).unwrap();
+        assert_eq!(
+            requests,
+            [
+                FailedRequest {
+                    url: Url::parse("https://example.com/dav/").unwrap(),
+                    request: RequestDescription {
+                        method: "PROPFIND".into(),
+                        headers: BTreeMap::from([("Depth".into(), "1".into())]),
+                        body: Some("<propfind/>".into()),
+                        curl: "curl -i -X PROPFIND 'https://example.com/dav/'".into(),
+                    },
+                },
+                FailedRequest {
+                    url: Url::parse("https://example.com/dav/a.txt").unwrap(),
+                    request: RequestDescription {
+                        method: "HEAD".into(),
+                        headers: BTreeMap::new(),
+                        body: None,
+                        curl: "curl --head 'https://example.com/dav/a.txt'".into(),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display_retry_report() {
+        let report = RetryReport {
+            results: vec![
+                RetryResult {
+                    url: Url::parse("https://example.com/dav/").unwrap(),
+                    method: "PROPFIND".into(),
+                    outcome: Ok(207),
+                },
+                RetryResult {
+                    url: Url::parse("https://example.com/dav/a.txt").unwrap(),
+                    method: "HEAD".into(),
+                    outcome: Err("request returned 500".into()),
+                },
+            ],
+        };
+        assert_eq!(report.still_failing(), 1);
+        assert_eq!(
+            report.to_string(),
+            concat!(
+                "OK      PROPFIND https://example.com/dav/ (207)\n",
+                "FAILED  HEAD https://example.com/dav/a.txt: request returned 500\n",
+                "Retried 2 requests: 1 now succeed, 1 still fail\n",
+            )
+        );
+    }
+}
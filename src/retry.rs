@@ -0,0 +1,558 @@
+use crate::export::serialize_seconds;
+use anyhow::Context;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Initial delay before retrying a failed request; doubled on each subsequent
+/// attempt
+const BASE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Maximum delay before retrying a failed request
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How long to wait between checks of a circuit breaker while another request
+/// is probing the server
+const PROBE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A transient request failure that may be resolved by retrying
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Failure {
+    /// The request could not be sent or the connection failed
+    Connection,
+    /// The request timed out
+    Timeout,
+    /// The server responded with a 429 or 5xx status code
+    Status(u16),
+}
+
+impl Failure {
+    /// Classify a response status code, returning `None` if it does not
+    /// indicate a transient failure
+    pub(crate) fn from_status(status: u16) -> Option<Failure> {
+        is_retryable_status(status).then_some(Failure::Status(status))
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Connection => write!(f, "connection"),
+            Failure::Timeout => write!(f, "timeout"),
+            Failure::Status(code) => write!(f, "{code}"),
+        }
+    }
+}
+
+impl Serialize for Failure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn is_retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// A class of failures to which a per-class retry limit applies
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "String")]
+pub(crate) enum FailureClass {
+    Connection,
+    Timeout,
+    /// A specific status code
+    Status(u16),
+    /// Any 5xx status code without a more specific limit
+    ServerError,
+}
+
+impl FromStr for FailureClass {
+    type Err = ParseRetryRuleError;
+
+    fn from_str(s: &str) -> Result<FailureClass, ParseRetryRuleError> {
+        match s {
+            "connection" => Ok(FailureClass::Connection),
+            "timeout" => Ok(FailureClass::Timeout),
+            "5xx" => Ok(FailureClass::ServerError),
+            _ => match s.parse::<u16>() {
+                Ok(code) if is_retryable_status(code) => Ok(FailureClass::Status(code)),
+                _ => Err(ParseRetryRuleError::Class(s.to_owned())),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for FailureClass {
+    type Error = ParseRetryRuleError;
+
+    fn try_from(s: String) -> Result<FailureClass, ParseRetryRuleError> {
+        s.parse()
+    }
+}
+
+/// A per-class retry limit as given on the command line in the form
+/// `CLASS=N`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct RetryRule {
+    pub(crate) class: FailureClass,
+    pub(crate) retries: u32,
+}
+
+impl FromStr for RetryRule {
+    type Err = ParseRetryRuleError;
+
+    fn from_str(s: &str) -> Result<RetryRule, ParseRetryRuleError> {
+        let (class, retries) = s.split_once('=').ok_or(ParseRetryRuleError::NoEquals)?;
+        Ok(RetryRule {
+            class: class.trim().parse()?,
+            retries: retries
+                .trim()
+                .parse()
+                .map_err(|_| ParseRetryRuleError::Retries(retries.to_owned()))?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ParseRetryRuleError {
+    #[error("expected retry policy of the form CLASS=N")]
+    NoEquals,
+    #[error("invalid failure class {0:?}; expected \"connection\", \"timeout\", \"5xx\", 429, or a 5xx status code")]
+    Class(String),
+    #[error("invalid retry count {0:?}")]
+    Retries(String),
+}
+
+/// Retry settings read from a TOML file given with `--retry-config`.
+/// Settings given on the command line take precedence over those in the
+/// file.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RetryConfig {
+    /// Default maximum number of times to retry a single request
+    pub(crate) retries: Option<u32>,
+    /// Maximum number of retries to perform in total across all requests
+    pub(crate) budget: Option<usize>,
+    /// Per-failure-class overrides of `retries`
+    #[serde(default)]
+    pub(crate) policy: BTreeMap<FailureClass, u32>,
+}
+
+impl RetryConfig {
+    pub(crate) fn load(path: &Path) -> anyhow::Result<RetryConfig> {
+        let s = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read retry config {}", path.display()))?;
+        toml::from_str(&s)
+            .with_context(|| format!("failed to parse retry config {}", path.display()))
+    }
+}
+
+/// Configuration for retrying requests that fail with transient errors
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct RetryOptions {
+    /// Maximum number of times to retry a single request, unless overridden
+    /// for the type of failure by `per_class`
+    pub(crate) retries: u32,
+    /// Per-failure-class overrides of `retries`
+    pub(crate) per_class: BTreeMap<FailureClass, u32>,
+    /// Maximum number of retries to perform in total across all requests, or
+    /// `None` for no limit
+    pub(crate) budget: Option<usize>,
+    /// Circuit breaker configuration, if the breaker is enabled
+    pub(crate) breaker: Option<BreakerOptions>,
+}
+
+impl RetryOptions {
+    /// Return the maximum number of times to retry a request that failed
+    /// with `failure`
+    pub(crate) fn retries_for(&self, failure: Failure) -> u32 {
+        let class = match failure {
+            Failure::Connection => FailureClass::Connection,
+            Failure::Timeout => FailureClass::Timeout,
+            Failure::Status(code) => FailureClass::Status(code),
+        };
+        self.per_class
+            .get(&class)
+            .or_else(|| match failure {
+                Failure::Status(code) if code >= 500 => {
+                    self.per_class.get(&FailureClass::ServerError)
+                }
+                _ => None,
+            })
+            .copied()
+            .unwrap_or(self.retries)
+    }
+}
+
+/// What was decided about retrying a request after one of its attempts
+/// failed, as recorded in the request log
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RetryDecision {
+    pub(crate) failure: Failure,
+    /// Number of times the request had already been retried
+    pub(crate) attempt: u32,
+    /// Maximum number of retries permitted for `failure`
+    pub(crate) limit: u32,
+    #[serde(flatten)]
+    pub(crate) outcome: RetryOutcome,
+}
+
+impl RetryDecision {
+    /// Returns the delay to wait before retrying, or `None` if the request
+    /// is not to be retried
+    pub(crate) fn delay(&self) -> Option<Duration> {
+        match self.outcome {
+            RetryOutcome::Retry { delay } => Some(delay),
+            RetryOutcome::Exhausted | RetryOutcome::OverBudget => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "decision")]
+pub(crate) enum RetryOutcome {
+    /// The request was retried after waiting for `delay`
+    Retry {
+        #[serde(serialize_with = "serialize_seconds")]
+        delay: Duration,
+    },
+    /// The request had already been retried as many times as permitted for
+    /// the failure
+    Exhausted,
+    /// The retry budget had been used up
+    OverBudget,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct BreakerOptions {
+    /// Number of consecutive transient failures after which to open the
+    /// breaker
+    pub(crate) threshold: usize,
+    /// How long to wait after opening the breaker before probing the server
+    pub(crate) cooldown: Duration,
+}
+
+/// Shared state for retrying requests & tripping the circuit breaker
+#[derive(Debug)]
+pub(crate) struct RetryState {
+    options: RetryOptions,
+    retries_used: AtomicUsize,
+    /// Number of retries performed for each type of failure
+    tally: Mutex<BTreeMap<Failure, usize>>,
+    breaker: Option<Mutex<Breaker>>,
+}
+
+impl RetryState {
+    pub(crate) fn new(options: RetryOptions) -> RetryState {
+        RetryState {
+            breaker: options.breaker.map(|opts| Mutex::new(Breaker::new(opts))),
+            options,
+            retries_used: AtomicUsize::new(0),
+            tally: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Total number of retries performed so far
+    pub(crate) fn retries_used(&self) -> usize {
+        self.retries_used.load(Ordering::Relaxed)
+    }
+
+    /// Number of retries performed so far for each type of failure
+    pub(crate) fn retry_tally(&self) -> BTreeMap<Failure, usize> {
+        self.tally
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Decide whether a request that has already been retried `attempt`
+    /// times and has now failed with `failure` should be retried again.  If
+    /// so, a retry is deducted from the budget, and the decision includes the
+    /// delay to wait before retrying.
+    pub(crate) fn next_retry(&self, failure: Failure, attempt: u32) -> RetryDecision {
+        let limit = self.options.retries_for(failure);
+        let decision = |outcome| RetryDecision {
+            failure,
+            attempt,
+            limit,
+            outcome,
+        };
+        if attempt >= limit {
+            return decision(RetryOutcome::Exhausted);
+        }
+        let budget = self.options.budget.unwrap_or(usize::MAX);
+        if self
+            .retries_used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                (used < budget).then(|| used.saturating_add(1))
+            })
+            .is_err()
+        {
+            return decision(RetryOutcome::OverBudget);
+        }
+        let mut tally = self
+            .tally
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let n = tally.entry(failure).or_insert(0);
+        *n = n.saturating_add(1);
+        decision(RetryOutcome::Retry {
+            delay: BASE_BACKOFF
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(MAX_BACKOFF),
+        })
+    }
+
+    /// Wait until the circuit breaker (if any) permits a request to be made
+    pub(crate) async fn acquire(&self) {
+        let Some(ref breaker) = self.breaker else {
+            return;
+        };
+        loop {
+            let wait = {
+                let mut b = breaker
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                b.try_acquire(Instant::now())
+            };
+            match wait {
+                None => return,
+                Some(d) => tokio::time::sleep(d).await,
+            }
+        }
+    }
+
+    /// Inform the circuit breaker (if any) of the outcome of a request
+    pub(crate) fn record(&self, transient_failure: bool) {
+        if let Some(ref breaker) = self.breaker {
+            let mut b = breaker
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            b.record(transient_failure, Instant::now());
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BreakerState {
+    /// Requests are permitted; tracks the number of consecutive transient
+    /// failures
+    Closed(usize),
+    /// Requests are refused until the given time
+    Open(Instant),
+    /// A single probe request is in flight; other requests wait for its
+    /// outcome
+    HalfOpen,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Breaker {
+    options: BreakerOptions,
+    state: BreakerState,
+}
+
+impl Breaker {
+    fn new(options: BreakerOptions) -> Breaker {
+        Breaker {
+            options,
+            state: BreakerState::Closed(0),
+        }
+    }
+
+    /// Returns `None` if a request may be made now, or else the amount of
+    /// time to wait before asking again
+    fn try_acquire(&mut self, now: Instant) -> Option<Duration> {
+        match self.state {
+            BreakerState::Closed(_) => None,
+            BreakerState::Open(until) if now >= until => {
+                self.state = BreakerState::HalfOpen;
+                None
+            }
+            BreakerState::Open(until) => Some(until.saturating_duration_since(now)),
+            BreakerState::HalfOpen => Some(PROBE_POLL_INTERVAL),
+        }
+    }
+
+    fn record(&mut self, transient_failure: bool, now: Instant) {
+        self.state = match (self.state, transient_failure) {
+            (BreakerState::Closed(_) | BreakerState::HalfOpen, false) => BreakerState::Closed(0),
+            (BreakerState::Closed(n), true) => {
+                let n = n.saturating_add(1);
+                if n >= self.options.threshold {
+                    BreakerState::Open(now + self.options.cooldown)
+                } else {
+                    BreakerState::Closed(n)
+                }
+            }
+            (BreakerState::HalfOpen, true) => BreakerState::Open(now + self.options.cooldown),
+            // Outcomes of requests issued before the breaker opened don't
+            // affect it
+            (st @ BreakerState::Open(_), _) => st,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[test]
+    fn test_next_retry_budget() {
+        let state = RetryState::new(RetryOptions {
+            retries: 3,
+            budget: Some(4),
+            ..RetryOptions::default()
+        });
+        let f = Failure::Connection;
+        let delay = |attempt| state.next_retry(f, attempt).delay();
+        assert_eq!(delay(0), Some(Duration::from_millis(100)));
+        assert_eq!(delay(1), Some(Duration::from_millis(200)));
+        assert_eq!(delay(2), Some(Duration::from_millis(400)));
+        assert_eq!(delay(3), None);
+        assert_eq!(delay(0), Some(Duration::from_millis(100)));
+        assert_eq!(delay(0), None);
+        assert_eq!(state.retries_used(), 4);
+        assert_eq!(state.retry_tally(), BTreeMap::from([(f, 4)]));
+    }
+
+    #[test]
+    fn test_retries_for() {
+        let rules = ["timeout=2", "5xx=1", "501=0"]
+            .into_iter()
+            .map(|s| s.parse::<RetryRule>().unwrap())
+            .map(|r| (r.class, r.retries))
+            .collect();
+        let opts = RetryOptions {
+            retries: 3,
+            per_class: rules,
+            ..RetryOptions::default()
+        };
+        assert_eq!(opts.retries_for(Failure::Connection), 3);
+        assert_eq!(opts.retries_for(Failure::Timeout), 2);
+        assert_eq!(opts.retries_for(Failure::Status(429)), 3);
+        assert_eq!(opts.retries_for(Failure::Status(503)), 1);
+        assert_eq!(opts.retries_for(Failure::Status(501)), 0);
+    }
+
+    #[test]
+    fn test_retry_decisions() {
+        let state = RetryState::new(RetryOptions {
+            retries: 2,
+            per_class: BTreeMap::from([(FailureClass::Status(501), 0)]),
+            budget: Some(1),
+            breaker: None,
+        });
+        assert_eq!(
+            state.next_retry(Failure::Status(501), 0),
+            RetryDecision {
+                failure: Failure::Status(501),
+                attempt: 0,
+                limit: 0,
+                outcome: RetryOutcome::Exhausted,
+            }
+        );
+        let d = state.next_retry(Failure::Timeout, 0);
+        assert_eq!(d.delay(), Some(BASE_BACKOFF));
+        assert_eq!(
+            serde_json::to_value(d).unwrap(),
+            serde_json::json!({
+                "failure": "timeout",
+                "attempt": 0,
+                "limit": 2,
+                "decision": "retry",
+                "delay": 0.1,
+            })
+        );
+        let d = state.next_retry(Failure::Timeout, 1);
+        assert_eq!(d.outcome, RetryOutcome::OverBudget);
+        assert_eq!(
+            serde_json::to_value(d).unwrap(),
+            serde_json::json!({
+                "failure": "timeout",
+                "attempt": 1,
+                "limit": 2,
+                "decision": "over_budget",
+            })
+        );
+    }
+
+    #[test]
+    fn test_retry_config() {
+        let cfg = toml::from_str::<RetryConfig>(indoc! {"
+            retries = 3
+
+            [policy]
+            timeout = 2
+            5xx = 1
+            501 = 0
+        "})
+        .unwrap();
+        assert_eq!(
+            cfg,
+            RetryConfig {
+                retries: Some(3),
+                budget: None,
+                policy: BTreeMap::from([
+                    (FailureClass::Timeout, 2),
+                    (FailureClass::ServerError, 1),
+                    (FailureClass::Status(501), 0),
+                ]),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case("[policy]\n404 = 1\n")]
+    #[case("retries = -1\n")]
+    #[case("retry = 3\n")]
+    fn test_bad_retry_config(#[case] s: &str) {
+        assert!(toml::from_str::<RetryConfig>(s).is_err());
+    }
+
+    #[rstest]
+    #[case("connection")]
+    #[case("timeout=")]
+    #[case("404=1")]
+    #[case("4xx=1")]
+    #[case("timeout=-1")]
+    fn test_bad_retry_rule(#[case] s: &str) {
+        assert!(s.parse::<RetryRule>().is_err());
+    }
+
+    #[test]
+    fn test_breaker() {
+        let cooldown = Duration::from_secs(5);
+        let mut b = Breaker::new(BreakerOptions {
+            threshold: 3,
+            cooldown,
+        });
+        let t0 = Instant::now();
+        b.record(true, t0);
+        b.record(true, t0);
+        b.record(false, t0);
+        b.record(true, t0);
+        b.record(true, t0);
+        assert_eq!(b.try_acquire(t0), None);
+        b.record(true, t0);
+        assert_eq!(b.try_acquire(t0), Some(cooldown));
+        // Probe fails
+        let t1 = t0 + cooldown;
+        assert_eq!(b.try_acquire(t1), None);
+        assert_eq!(b.try_acquire(t1), Some(PROBE_POLL_INTERVAL));
+        b.record(true, t1);
+        assert_eq!(b.try_acquire(t1), Some(cooldown));
+        // Probe succeeds
+        let t2 = t1 + cooldown;
+        assert_eq!(b.try_acquire(t2), None);
+        b.record(false, t2);
+        assert_eq!(b.try_acquire(t2), None);
+    }
+}
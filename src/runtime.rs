@@ -0,0 +1,74 @@
+use anyhow::Context;
+use serde::Serialize;
+use std::num::NonZeroUsize;
+use tokio::runtime::{Builder, Runtime};
+
+/// Environment variable that Tokio consults for the default number of worker
+/// threads, honored here so that it continues to work
+const WORKER_THREADS_VAR: &str = "TOKIO_WORKER_THREADS";
+
+/// The kind of Tokio runtime that batchdav runs on
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RuntimeFlavor {
+    /// All tasks run on the main thread
+    CurrentThread,
+    /// Tasks are spread across a pool of worker threads
+    MultiThread,
+}
+
+/// The configuration of the Tokio runtime, recorded in reports because the
+/// number of executor threads can affect results at high worker counts
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RuntimeConfig {
+    pub(crate) flavor: RuntimeFlavor,
+    /// Number of threads on which tasks are run
+    pub(crate) worker_threads: usize,
+}
+
+impl RuntimeConfig {
+    /// Determine the runtime configuration from the command-line options:
+    /// either a current-thread runtime, or a multi-thread runtime with the
+    /// given number of worker threads, defaulting to Tokio's default of
+    /// `$TOKIO_WORKER_THREADS` or else one per CPU
+    pub(crate) fn new(
+        current_thread: bool,
+        threads: Option<NonZeroUsize>,
+    ) -> anyhow::Result<RuntimeConfig> {
+        if current_thread {
+            return Ok(RuntimeConfig {
+                flavor: RuntimeFlavor::CurrentThread,
+                worker_threads: 1,
+            });
+        }
+        let threads = match threads {
+            Some(n) => n,
+            None => match std::env::var(WORKER_THREADS_VAR) {
+                Ok(s) => s.parse::<NonZeroUsize>().with_context(|| {
+                    format!("invalid {WORKER_THREADS_VAR} value {s:?}; expected a positive integer")
+                })?,
+                Err(_) => std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN),
+            },
+        };
+        Ok(RuntimeConfig {
+            flavor: RuntimeFlavor::MultiThread,
+            worker_threads: threads.get(),
+        })
+    }
+
+    /// Construct a runtime with this configuration
+    pub(crate) fn build(&self) -> anyhow::Result<Runtime> {
+        let mut builder = match self.flavor {
+            RuntimeFlavor::CurrentThread => Builder::new_current_thread(),
+            RuntimeFlavor::MultiThread => {
+                let mut builder = Builder::new_multi_thread();
+                builder.worker_threads(self.worker_threads);
+                builder
+            }
+        };
+        builder
+            .enable_all()
+            .build()
+            .context("failed to start async runtime")
+    }
+}
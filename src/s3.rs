@@ -0,0 +1,263 @@
+use crate::backend::TraversalBackend;
+use crate::client::{Client, DirectoryResponse, FileResponse, ObjectsResponse, RequestError};
+use crate::types::{normalize_url, DirectoryListing, Entry};
+use futures_util::{future::BoxFuture, FutureExt};
+use percent_encoding::percent_decode_str;
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+/// A traversal backend that lists "directories" directly from an S3 bucket
+/// via `ListObjectsV2` requests, treating each `/`-delimited key prefix as a
+/// collection, so that a hierarchy exposed both over WebDAV and as a bucket
+/// can be traversed the same way over both protocols.  Files are probed with
+/// `HEAD` requests for their object URLs.
+#[derive(Clone, Debug)]
+pub(crate) struct S3Backend {
+    client: Client,
+    /// The URL of the bucket, either virtual-hosted-style
+    /// (`https://{bucket}.s3.amazonaws.com/`) or path-style
+    /// (`https://{host}/{bucket}/`), always ending in a slash
+    bucket_url: Url,
+}
+
+impl S3Backend {
+    pub(crate) fn new(client: Client, mut bucket_url: Url) -> S3Backend {
+        if !bucket_url.path().ends_with('/') {
+            bucket_url.set_path(&format!("{}/", bucket_url.path()));
+        }
+        bucket_url.set_query(None);
+        bucket_url.set_fragment(None);
+        S3Backend { client, bucket_url }
+    }
+
+    /// Check that `url` lies within the bucket, so that it corresponds to a
+    /// key prefix
+    pub(crate) fn check_url(&self, url: &Url) -> Result<(), Box<NotInBucketError>> {
+        if self.key_for(url).is_some() {
+            Ok(())
+        } else {
+            Err(Box::new(NotInBucketError {
+                url: url.clone(),
+                bucket_url: self.bucket_url.clone(),
+            }))
+        }
+    }
+
+    /// Return the object key (or, for a collection URL, the key prefix)
+    /// corresponding to `url`
+    fn key_for(&self, url: &Url) -> Option<String> {
+        if url.origin() != self.bucket_url.origin() {
+            return None;
+        }
+        let rel = url.path().strip_prefix(self.bucket_url.path())?;
+        Some(percent_decode_str(rel).decode_utf8_lossy().into_owned())
+    }
+
+    /// Return the URL for the object key or key prefix `key`
+    fn url_for(&self, key: &str) -> Url {
+        let mut url = self.bucket_url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(key.split('/'));
+        }
+        normalize_url(url, key.ends_with('/'))
+    }
+
+    /// List the objects & common prefixes directly under the collection at
+    /// `url`, following continuation tokens until the listing is complete.
+    /// The elapsed time and body size of the returned response are the
+    /// totals over all pages.
+    async fn list(&self, url: Url) -> Result<DirectoryResponse, RequestError> {
+        let prefix = self.key_for(&url).unwrap_or_default();
+        let mut listing = DirectoryListing {
+            directories: Vec::new(),
+            files: Vec::new(),
+        };
+        let mut elapsed = Duration::ZERO;
+        let mut body_size = 0u64;
+        let mut token = None;
+        let mut first_sent = None;
+        loop {
+            let ObjectsResponse {
+                result,
+                status,
+                body_size: page_size,
+                server_headers,
+                elapsed: page_elapsed,
+                mut ids,
+            } = self
+                .client
+                .list_objects(&self.bucket_url, &prefix, token.as_deref())
+                .await?;
+            elapsed = elapsed.saturating_add(page_elapsed);
+            // The listing as a whole was sent when its first page was
+            first_sent = first_sent.or(ids.sent);
+            ids.sent = first_sent;
+            body_size = body_size.saturating_add(page_size);
+            for p in result.common_prefixes {
+                if p != prefix {
+                    listing.directories.push(Entry::new(self.url_for(&p)));
+                }
+            }
+            for obj in result.contents {
+                // Skip zero-byte "directory marker" objects
+                if obj.key == prefix || obj.key.ends_with('/') {
+                    continue;
+                }
+                listing.files.push(Entry {
+                    size: obj.size,
+                    etag: obj.etag,
+                    modified: obj.last_modified,
+                    ..Entry::new(self.url_for(&obj.key))
+                });
+            }
+            match result.next_continuation_token {
+                Some(t) if result.is_truncated => token = Some(t),
+                _ => {
+                    return Ok(DirectoryResponse {
+                        listing,
+                        duplicates: 0,
+                        descriptions: Vec::new(),
+                        warnings: Vec::new(),
+                        status,
+                        propstat_statuses: Vec::new(),
+                        body_size,
+                        server_headers,
+                        elapsed,
+                        ids,
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl TraversalBackend for S3Backend {
+    fn list_children(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        self.list(url).boxed()
+    }
+
+    fn probe_file(
+        &self,
+        url: Url,
+        follow_redirects: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileResponse, RequestError>> {
+        self.client.probe_file(url, follow_redirects)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("{url} is not within S3 bucket {bucket_url}")]
+pub(crate) struct NotInBucketError {
+    url: Url,
+    bucket_url: Url,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientOptions;
+    use crate::xml::{parse_list_bucket_result, XmlOptions};
+    use bytes::Bytes;
+    use indoc::indoc;
+    use rstest::rstest;
+    use time::macros::datetime;
+
+    fn backend(bucket_url: &str) -> S3Backend {
+        let bucket_url = Url::parse(bucket_url).unwrap();
+        let client = Client::new(bucket_url.clone(), ClientOptions::default()).unwrap();
+        S3Backend::new(client, bucket_url)
+    }
+
+    #[rstest]
+    #[case(
+        "https://bucket.s3.amazonaws.com/",
+        "https://bucket.s3.amazonaws.com/",
+        ""
+    )]
+    #[case(
+        "http://localhost:9000/bucket",
+        "http://localhost:9000/bucket/data/",
+        "data/"
+    )]
+    #[case(
+        "http://localhost:9000/bucket/",
+        "http://localhost:9000/bucket/my%20data/a+b%23c.nc",
+        "my data/a+b#c.nc"
+    )]
+    fn test_key_for(#[case] bucket_url: &str, #[case] url: &str, #[case] key: &str) {
+        let s3 = backend(bucket_url);
+        let url = Url::parse(url).unwrap();
+        assert_eq!(s3.key_for(&url).as_deref(), Some(key));
+        assert_eq!(s3.url_for(key), url);
+    }
+
+    #[rstest]
+    #[case("http://localhost:9000/other/data/")]
+    #[case("http://localhost:9001/bucket/data/")]
+    fn test_not_in_bucket(#[case] url: &str) {
+        let s3 = backend("http://localhost:9000/bucket/");
+        assert!(s3.check_url(&Url::parse(url).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_parse_list_bucket_result() {
+        let body = indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Name>bucket</Name>
+                <Prefix>data/</Prefix>
+                <KeyCount>3</KeyCount>
+                <MaxKeys>1000</MaxKeys>
+                <Delimiter>/</Delimiter>
+                <IsTruncated>true</IsTruncated>
+                <NextContinuationToken>1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=</NextContinuationToken>
+                <Contents>
+                    <Key>data/</Key>
+                    <LastModified>2024-01-31T12:00:00.000Z</LastModified>
+                    <ETag>"d41d8cd98f00b204e9800998ecf8427e"</ETag>
+                    <Size>0</Size>
+                    <StorageClass>STANDARD</StorageClass>
+                </Contents>
+                <Contents>
+                    <Key>data/a&amp;b.nc</Key>
+                    <LastModified>2024-02-29T08:15:30.000Z</LastModified>
+                    <ETag>"0123456789abcdef0123456789abcdef"</ETag>
+                    <Size>1024</Size>
+                    <StorageClass>STANDARD</StorageClass>
+                </Contents>
+                <CommonPrefixes>
+                    <Prefix>data/sub/</Prefix>
+                </CommonPrefixes>
+            </ListBucketResult>
+        "#};
+        let result =
+            parse_list_bucket_result(Bytes::from(body), None, XmlOptions::default()).unwrap();
+        assert!(result.is_truncated);
+        assert_eq!(
+            result.next_continuation_token.as_deref(),
+            Some("1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=")
+        );
+        assert_eq!(result.common_prefixes, ["data/sub/"]);
+        assert_eq!(result.contents.len(), 2);
+        assert_eq!(result.contents[1].key, "data/a&b.nc");
+        assert_eq!(result.contents[1].size, Some(1024));
+        assert_eq!(
+            result.contents[1].etag.as_deref(),
+            Some("\"0123456789abcdef0123456789abcdef\"")
+        );
+        assert_eq!(
+            result.contents[1].last_modified,
+            Some(datetime!(2024-02-29 08:15:30 UTC))
+        );
+    }
+
+    #[test]
+    fn test_parse_list_bucket_error() {
+        let body = indoc! {r#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <Error><Code>NoSuchBucket</Code></Error>
+        "#};
+        assert!(parse_list_bucket_result(Bytes::from(body), None, XmlOptions::default()).is_err());
+    }
+}
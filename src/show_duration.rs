@@ -27,11 +27,37 @@ impl fmt::Display for ShowDurationAsSeconds {
     }
 }
 
+/// Format a number of seconds as hours, minutes, & seconds, e.g.,
+/// "2h03m04s", or as fractional seconds if less than a minute
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn show_hms(secs: f64) -> String {
+    if secs < 60.0 {
+        return format!("{secs:.1}s");
+    }
+    let secs = secs.round() as u64;
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if h > 0 {
+        format!("{h}h{m:02}m{s:02}s")
+    } else {
+        format!("{m}m{s:02}s")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rstest::rstest;
 
+    #[rstest]
+    #[case(0.25, "0.2s")]
+    #[case(12.34, "12.3s")]
+    #[case(60.0, "1m00s")]
+    #[case(754.6, "12m35s")]
+    #[case(7384.0, "2h03m04s")]
+    fn test_show_hms(#[case] secs: f64, #[case] s: &str) {
+        assert_eq!(show_hms(secs), s);
+    }
+
     #[rstest]
     #[case(0, 0, "0")]
     #[case(0, 1, "0.000000001")]
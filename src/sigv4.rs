@@ -0,0 +1,471 @@
+use crate::client::RequestSigner;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::fmt::{self, Write};
+use thiserror::Error;
+use time::OffsetDateTime;
+use url::Url;
+
+/// Headers that are never signed: `Authorization` (which carries the
+/// signature itself) and the hop-by-hop headers of RFC 9110, which proxies
+/// are free to add, remove, or rewrite
+const UNSIGNED_HEADERS: [&str; 9] = [
+    "authorization",
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// The region & service for which AWS Signature Version 4 signatures are
+/// computed, as given on the command line in the form `REGION:SERVICE`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SigV4Scope {
+    pub(crate) region: String,
+    pub(crate) service: String,
+}
+
+impl std::str::FromStr for SigV4Scope {
+    type Err = ParseSigV4ScopeError;
+
+    fn from_str(s: &str) -> Result<SigV4Scope, ParseSigV4ScopeError> {
+        let (region, service) = s.split_once(':').ok_or(ParseSigV4ScopeError)?;
+        let region = region.trim();
+        let service = service.trim();
+        if region.is_empty() || service.is_empty() || service.contains(':') {
+            return Err(ParseSigV4ScopeError);
+        }
+        Ok(SigV4Scope {
+            region: region.to_owned(),
+            service: service.to_ascii_lowercase(),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("expected signing scope of the form REGION:SERVICE")]
+pub(crate) struct ParseSigV4ScopeError;
+
+/// AWS credentials with which to sign requests
+#[derive(Clone, Eq, PartialEq)]
+pub(crate) struct SigV4Credentials {
+    pub(crate) access_key_id: String,
+    pub(crate) secret_access_key: String,
+    pub(crate) session_token: Option<String>,
+}
+
+impl SigV4Credentials {
+    /// Read credentials from the standard `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY`, and `AWS_SESSION_TOKEN` environment variables
+    pub(crate) fn from_env() -> Result<SigV4Credentials, MissingCredentialsError> {
+        let var = |name| std::env::var(name).ok().filter(|s| !s.is_empty());
+        Ok(SigV4Credentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID").ok_or(MissingCredentialsError)?,
+            secret_access_key: var("AWS_SECRET_ACCESS_KEY").ok_or(MissingCredentialsError)?,
+            session_token: var("AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+// Don't let secrets end up in debug output
+impl fmt::Debug for SigV4Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigV4Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"<redacted>")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("--sigv4 requires the AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY environment variables to be set")]
+pub(crate) struct MissingCredentialsError;
+
+/// Settings for signing requests with AWS Signature Version 4
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SigV4Options {
+    pub(crate) scope: SigV4Scope,
+    pub(crate) credentials: SigV4Credentials,
+}
+
+/// A `RequestSigner` that signs requests with AWS Signature Version 4
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SigV4Signer {
+    options: SigV4Options,
+}
+
+impl SigV4Signer {
+    pub(crate) fn new(options: SigV4Options) -> SigV4Signer {
+        SigV4Signer { options }
+    }
+
+    /// Compute the headers to add to a request in order to sign it at time
+    /// `now`.  `headers` are the headers already present in the request, all
+    /// of which are signed except for those in `UNSIGNED_HEADERS`.
+    fn signature_headers(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &[(String, String)],
+        body: &[u8],
+        now: OffsetDateTime,
+    ) -> Vec<(&'static str, String)> {
+        let SigV4Options { scope, credentials } = &self.options;
+        let now = now.to_offset(time::UtcOffset::UTC);
+        let scope_date = format!(
+            "{:04}{:02}{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day()
+        );
+        let amz_date = format!(
+            "{scope_date}T{:02}{:02}{:02}Z",
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let payload_hash = hex(&sha256(body));
+        let mut added = vec![("x-amz-date", amz_date.clone())];
+        // S3 requires the payload hash to be sent as well as signed
+        if scope.service == "s3" {
+            added.push(("x-amz-content-sha256", payload_hash.clone()));
+        }
+        if let Some(ref token) = credentials.session_token {
+            added.push(("x-amz-security-token", token.clone()));
+        }
+        let mut signed = headers
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), canonical_value(value)))
+            .filter(|(name, _)| !UNSIGNED_HEADERS.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        signed.push((String::from("host"), host_header(url)));
+        signed.extend(added.iter().map(|(k, v)| ((*k).to_owned(), v.clone())));
+        // Sort stably so that the values of repeated headers stay in order
+        signed.sort_by(|a, b| a.0.cmp(&b.0));
+        // Multiple values of the same header are combined with commas
+        let mut canonical_headers = String::new();
+        let mut signed_headers = Vec::<&str>::new();
+        for (name, value) in &signed {
+            if signed_headers.last() == Some(&name.as_str()) {
+                canonical_headers.pop();
+                let _ = writeln!(canonical_headers, ",{value}");
+            } else {
+                let _ = writeln!(canonical_headers, "{name}:{value}");
+                signed_headers.push(name);
+            }
+        }
+        let signed_headers = signed_headers.join(";");
+        let canonical_request = format!(
+            "{method}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+            canonical_uri(url, &scope.service),
+            canonical_query(url),
+        );
+        let credential_scope = format!(
+            "{scope_date}/{}/{}/aws4_request",
+            scope.region, scope.service
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{credential_scope}\n{}",
+            hex(&sha256(canonical_request.as_bytes()))
+        );
+        let mut key = hmac_sha256(
+            format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+            scope_date.as_bytes(),
+        );
+        for part in [&scope.region, &scope.service, "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        added.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{credential_scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                credentials.access_key_id
+            ),
+        ));
+        added
+    }
+}
+
+impl RequestSigner for SigV4Signer {
+    fn sign(&self, req: &mut reqwest::Request) -> Result<(), SignError> {
+        let headers = req
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str().to_owned(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let body = req.body().map(|b| b.as_bytes().ok_or(SignError::Stream));
+        let body = body.transpose()?.unwrap_or_default();
+        let added = self.signature_headers(
+            req.method().as_str(),
+            req.url(),
+            &headers,
+            body,
+            OffsetDateTime::now_utc(),
+        );
+        for (name, value) in added {
+            let mut value = HeaderValue::from_str(&value).map_err(|_| SignError::Header(name))?;
+            if name == "authorization" || name == "x-amz-security-token" {
+                value.set_sensitive(true);
+            }
+            req.headers_mut()
+                .insert(HeaderName::from_static(name), value);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub(crate) enum SignError {
+    #[error("cannot sign request with a streaming body")]
+    Stream,
+    #[error("signature produced an invalid {0} header")]
+    Header(&'static str),
+}
+
+/// The value of the `Host` header that will be sent for `url`
+fn host_header(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_owned(),
+    }
+}
+
+/// The canonical form of a header value: trimmed, with each run of spaces
+/// collapsed to a single space
+fn canonical_value(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Percent-encode everything in `s` other than unreserved characters (and,
+/// if `keep_slash` is true, slashes) as specified for SigV4
+fn uri_encode(s: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) || (keep_slash && b == b'/') {
+            out.push(char::from(b));
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+    }
+    out
+}
+
+/// The canonical URI of `url`.  S3 expects each path segment to be encoded
+/// once; all other services expect the already-encoded path to be encoded
+/// again.
+fn canonical_uri(url: &Url, service: &str) -> String {
+    let path = url.path();
+    if path.is_empty() {
+        return String::from("/");
+    }
+    if service == "s3" {
+        path.split('/')
+            .map(|seg| {
+                uri_encode(
+                    &percent_encoding::percent_decode_str(seg).decode_utf8_lossy(),
+                    false,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    } else {
+        uri_encode(path, true)
+    }
+}
+
+/// The canonical query string of `url`: each name & value encoded, sorted by
+/// name and then value
+fn canonical_query(url: &Url) -> String {
+    let mut pairs = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k, false), uri_encode(&v, false)))
+        .collect::<Vec<_>>();
+    pairs.sort();
+    pairs
+        .into_iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(s, "{b:02x}");
+    }
+    s
+}
+
+/// Compute the SHA-256 digest of `data`
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+/// Compute the HMAC-SHA256 of `data` with `key`
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("HMAC should accept keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use time::macros::datetime;
+
+    #[rstest]
+    #[case("us-east-1:s3", Some(("us-east-1", "s3")))]
+    #[case(" eu-west-2 : Execute-API ", Some(("eu-west-2", "execute-api")))]
+    #[case("us-east-1", None)]
+    #[case(":s3", None)]
+    #[case("us-east-1:", None)]
+    fn test_parse_sigv4_scope(#[case] s: &str, #[case] expected: Option<(&str, &str)>) {
+        let expected = expected.map(|(region, service)| SigV4Scope {
+            region: region.to_owned(),
+            service: service.to_owned(),
+        });
+        assert_eq!(s.parse::<SigV4Scope>().ok(), expected);
+    }
+
+    #[test]
+    fn test_canonical_query() {
+        let url = Url::parse("https://example.com/?b=2&a=x%20y&a=1").unwrap();
+        assert_eq!(canonical_query(&url), "a=1&a=x%20y&b=2");
+    }
+
+    // Cases from the AWS SigV4 test suite
+    #[rstest]
+    #[case::get_vanilla(
+        "GET",
+        "https://example.amazonaws.com/",
+        &[],
+        None,
+        "host;x-amz-date",
+        "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+    )]
+    #[case::get_vanilla_query_order_key_case(
+        "GET",
+        "https://example.amazonaws.com/?Param2=value2&Param1=value1",
+        &[],
+        None,
+        "host;x-amz-date",
+        "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+    )]
+    #[case::get_unreserved(
+        "GET",
+        "https://example.amazonaws.com/-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+        &[],
+        None,
+        "host;x-amz-date",
+        "07ef7494c76fa4850883e2b006601f940f8a34d404d0cfa977f52a65bbf5f24f"
+    )]
+    #[case::get_header_value_trim(
+        "GET",
+        "https://example.amazonaws.com/",
+        &[("My-Header1", " value1"), ("My-Header2", " \"a   b   c\"")],
+        None,
+        "host;my-header1;my-header2;x-amz-date",
+        "acc3ed3afb60bb290fc8d2dd0098b9911fcaa05412b367055dee359757a9c736"
+    )]
+    #[case::get_header_key_duplicate(
+        "GET",
+        "https://example.amazonaws.com/",
+        &[("My-Header1", "value2"), ("My-Header1", "value2"), ("My-Header1", "value1")],
+        None,
+        "host;my-header1;x-amz-date",
+        "c9d5ea9f3f72853aea855b47ea873832890dbdd183b4468f858259531a5138ea"
+    )]
+    #[case::get_vanilla_with_session_token(
+        "GET",
+        "https://example.amazonaws.com/",
+        &[],
+        Some("6e86291e8372ff2a2260956d9b8aae1d763fbf315fa00fa31553b73ebf194267"),
+        "host;x-amz-date;x-amz-security-token",
+        "07ec1639c89043aa0e3e2de82b96708f198cceab042d4a97044c66dd9f74e7f8"
+    )]
+    #[case::post_vanilla(
+        "POST",
+        "https://example.amazonaws.com/",
+        &[],
+        None,
+        "host;x-amz-date",
+        "5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+    )]
+    #[case::post_header_key_sort(
+        "POST",
+        "https://example.amazonaws.com/",
+        &[("My-Header1", "value1")],
+        None,
+        "host;my-header1;x-amz-date",
+        "c5410059b04c1ee005303aed430f6e6645f61f4dc9e1461ec8f8916fdf18852c"
+    )]
+    // Hop-by-hop headers are left out of the signature, so this signs the
+    // same as "get-vanilla"
+    #[case::get_vanilla_hop_by_hop(
+        "GET",
+        "https://example.amazonaws.com/",
+        &[("Connection", "keep-alive"), ("TE", "trailers"), ("Authorization", "Basic Zm9vOmJhcg==")],
+        None,
+        "host;x-amz-date",
+        "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+    )]
+    fn test_signature(
+        #[case] method: &str,
+        #[case] url: &str,
+        #[case] headers: &[(&str, &str)],
+        #[case] session_token: Option<&str>,
+        #[case] signed_headers: &str,
+        #[case] signature: &str,
+    ) {
+        let signer = SigV4Signer::new(SigV4Options {
+            scope: SigV4Scope {
+                region: String::from("us-east-1"),
+                service: String::from("service"),
+            },
+            credentials: SigV4Credentials {
+                access_key_id: String::from("AKIDEXAMPLE"),
+                secret_access_key: String::from("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY"),
+                session_token: session_token.map(String::from),
+            },
+        });
+        let url = Url::parse(url).unwrap();
+        let headers = headers
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect::<Vec<_>>();
+        let added = signer.signature_headers(
+            method,
+            &url,
+            &headers,
+            b"",
+            datetime!(2015-08-30 12:36:00 UTC),
+        );
+        let mut expected = vec![("x-amz-date", String::from("20150830T123600Z"))];
+        if let Some(token) = session_token {
+            expected.push(("x-amz-security-token", token.to_owned()));
+        }
+        expected.push((
+            "authorization",
+            format!("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders={signed_headers}, Signature={signature}"),
+        ));
+        assert_eq!(added, expected);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::backend::TraversalBackend;
+use crate::client::{DirectoryResponse, FileResponse, RequestError, RequestIds};
+use crate::types::{parse_latency, DirectoryListing, Entry};
+use futures_util::{future::BoxFuture, FutureExt};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use url::Url;
+
+/// The shape of a synthetic hierarchy and the latency of requests made to it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct SimulationSpec {
+    /// Number of subcollections in each collection above the bottom level
+    pub(crate) fanout: usize,
+    /// Number of levels of collections below the root
+    pub(crate) depth: usize,
+    /// Number of non-collection resources in each collection
+    pub(crate) files: usize,
+    /// Mean latency of each request
+    pub(crate) latency: Duration,
+    /// Maximum deviation of each request's latency from the mean
+    pub(crate) jitter: Duration,
+    /// Seed from which each request's latency is derived
+    pub(crate) seed: u64,
+}
+
+impl Default for SimulationSpec {
+    fn default() -> SimulationSpec {
+        SimulationSpec {
+            fanout: 2,
+            depth: 3,
+            files: 2,
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+impl FromStr for SimulationSpec {
+    type Err = ParseSimulationError;
+
+    /// Parse a comma-separated list of `key=value` settings, e.g.,
+    /// `fanout=10,depth=4,latency=30ms±10ms`.  `files` defaults to the value
+    /// of `fanout`.
+    fn from_str(s: &str) -> Result<SimulationSpec, ParseSimulationError> {
+        let mut spec = SimulationSpec::default();
+        let mut files = None;
+        for item in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(ParseSimulationError::NoValue(item.to_owned()));
+            };
+            let invalid = || ParseSimulationError::InvalidValue {
+                key: key.to_owned(),
+                value: value.to_owned(),
+            };
+            match key.trim() {
+                "fanout" => spec.fanout = value.trim().parse().map_err(|_| invalid())?,
+                "depth" => spec.depth = value.trim().parse().map_err(|_| invalid())?,
+                "files" => files = Some(value.trim().parse().map_err(|_| invalid())?),
+                "seed" => spec.seed = value.trim().parse().map_err(|_| invalid())?,
+                "latency" => {
+                    let (mean, jitter) = match value.split_once('±') {
+                        Some((mean, jitter)) => (mean, Some(jitter)),
+                        None => match value.split_once("+-") {
+                            Some((mean, jitter)) => (mean, Some(jitter)),
+                            None => (value, None),
+                        },
+                    };
+                    spec.latency = parse_latency(mean).map_err(|_| invalid())?;
+                    spec.jitter = match jitter {
+                        Some(j) => parse_latency(j).map_err(|_| invalid())?,
+                        None => Duration::ZERO,
+                    };
+                    if spec.jitter > spec.latency {
+                        return Err(invalid());
+                    }
+                }
+                _ => return Err(ParseSimulationError::UnknownKey(key.to_owned())),
+            }
+        }
+        spec.files = files.unwrap_or(spec.fanout);
+        Ok(spec)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum ParseSimulationError {
+    #[error("simulation setting {0:?} lacks a value")]
+    NoValue(String),
+    #[error("unknown simulation setting {0:?}")]
+    UnknownKey(String),
+    #[error("invalid value for simulation setting {key:?}: {value:?}")]
+    InvalidValue { key: String, value: String },
+}
+
+/// A synthetic hierarchy rooted at a base URL, from which directory listings
+/// and file probes are fabricated without making any network requests.
+/// Collections are named `d{i}/` and non-collection resources `f{i}.dat`.
+/// The latency of each request is derived from its URL and the seed, so that
+/// it is the same on every traversal regardless of the order in which
+/// requests are made.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Simulation {
+    spec: SimulationSpec,
+    base_url: Url,
+    /// Give files sizes derived from their URLs
+    sizes: bool,
+}
+
+impl Simulation {
+    pub(crate) fn new(spec: SimulationSpec, base_url: Url, sizes: bool) -> Simulation {
+        Simulation {
+            spec,
+            base_url,
+            sizes,
+        }
+    }
+
+    /// "List" the collection at `url` after a simulated delay
+    async fn list(&self, url: Url) -> DirectoryResponse {
+        let start = Instant::now();
+        tokio::time::sleep(self.latency(&url)).await;
+        let listing = self.listing(&url);
+        let responses = listing
+            .directories
+            .len()
+            .saturating_add(listing.files.len())
+            .saturating_add(1);
+        DirectoryResponse {
+            listing,
+            duplicates: 0,
+            descriptions: Vec::new(),
+            warnings: Vec::new(),
+            status: 207,
+            propstat_statuses: vec![200; responses],
+            body_size: 0,
+            server_headers: Vec::new(),
+            elapsed: start.elapsed(),
+            ids: RequestIds::default(),
+        }
+    }
+
+    /// "Probe" the non-collection resource at `url` after a simulated delay
+    async fn probe(&self, url: Url) -> FileResponse {
+        let start = Instant::now();
+        tokio::time::sleep(self.latency(&url)).await;
+        let elapsed = start.elapsed();
+        FileResponse {
+            target: None,
+            status: 200,
+            ttfb: elapsed,
+            elapsed,
+            server_headers: Vec::new(),
+            chain: None,
+            get_timing: None,
+            ids: RequestIds::default(),
+        }
+    }
+
+    /// Return the children of the collection at `url`
+    fn listing(&self, url: &Url) -> DirectoryListing<Url> {
+        let level = url
+            .path()
+            .strip_prefix(self.base_url.path())
+            .map_or(0, |rel| rel.split('/').filter(|s| !s.is_empty()).count());
+        let mut directories = Vec::new();
+        if level < self.spec.depth {
+            for i in 0..self.spec.fanout {
+                if let Ok(href) = url.join(&format!("d{i}/")) {
+                    directories.push(Entry::new(href));
+                }
+            }
+        }
+        let mut files = Vec::new();
+        for i in 0..self.spec.files {
+            if let Ok(href) = url.join(&format!("f{i}.dat")) {
+                let size = self.sizes.then(|| self.hash(&href) % (1 << 20));
+                files.push(Entry {
+                    size,
+                    ..Entry::new(href)
+                });
+            }
+        }
+        DirectoryListing { directories, files }
+    }
+
+    /// Return the simulated latency of a request for `url`, uniformly
+    /// distributed within `jitter` of the mean
+    fn latency(&self, url: &Url) -> Duration {
+        let jitter = u64::try_from(self.spec.jitter.as_micros()).unwrap_or(u64::MAX);
+        if jitter == 0 {
+            return self.spec.latency;
+        }
+        let offset = self.hash(url) % jitter.saturating_mul(2).saturating_add(1);
+        self.spec
+            .latency
+            .saturating_sub(self.spec.jitter)
+            .saturating_add(Duration::from_micros(offset))
+    }
+
+    fn hash(&self, url: &Url) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.spec.seed.hash(&mut hasher);
+        url.as_str().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl TraversalBackend for Simulation {
+    fn list_children(&self, url: Url) -> BoxFuture<'_, Result<DirectoryResponse, RequestError>> {
+        self.list(url).map(Ok).boxed()
+    }
+
+    /// Simulated files never redirect
+    fn probe_file(
+        &self,
+        url: Url,
+        _follow_redirects: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileResponse, RequestError>> {
+        self.probe(url).map(Ok).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Client, ClientOptions};
+    use crate::traverse::{traverse, TraversalOptions};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", SimulationSpec::default())]
+    #[case(
+        "fanout=10,depth=4,latency=30ms±10ms",
+        SimulationSpec {
+            fanout: 10,
+            depth: 4,
+            files: 10,
+            latency: Duration::from_millis(30),
+            jitter: Duration::from_millis(10),
+            seed: 0,
+        }
+    )]
+    #[case(
+        "fanout=3, files=0, latency=500us+-250us, seed=42",
+        SimulationSpec {
+            fanout: 3,
+            depth: 3,
+            files: 0,
+            latency: Duration::from_micros(500),
+            jitter: Duration::from_micros(250),
+            seed: 42,
+        }
+    )]
+    #[case(
+        "latency=2s",
+        SimulationSpec {
+            latency: Duration::from_secs(2),
+            ..SimulationSpec::default()
+        }
+    )]
+    fn test_parse_spec(#[case] s: &str, #[case] spec: SimulationSpec) {
+        assert_eq!(s.parse::<SimulationSpec>(), Ok(spec));
+    }
+
+    #[rstest]
+    #[case("fanout")]
+    #[case("fanout=-1")]
+    #[case("breadth=2")]
+    #[case("latency=30")]
+    #[case("latency=10ms±20ms")]
+    fn test_parse_bad_spec(#[case] s: &str) {
+        assert!(s.parse::<SimulationSpec>().is_err());
+    }
+
+    #[test]
+    fn test_latency_is_deterministic() {
+        let spec = "latency=30ms±10ms".parse::<SimulationSpec>().unwrap();
+        let base_url = Url::parse("https://sim.invalid/root/").unwrap();
+        let sim = Simulation::new(spec, base_url.clone(), false);
+        for i in 0..20 {
+            let url = base_url.join(&format!("d{i}/")).unwrap();
+            let latency = sim.latency(&url);
+            assert!(Duration::from_millis(20) <= latency && latency <= Duration::from_millis(40));
+            assert_eq!(latency, sim.latency(&url));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulated_traversal() {
+        let base_url = Url::parse("https://sim.invalid/root/").unwrap();
+        let client = Client::new(base_url.clone(), ClientOptions::default()).unwrap();
+        let options = TraversalOptions {
+            quiet: true,
+            simulate: Some("fanout=3,depth=2,files=2".parse().unwrap()),
+            ..TraversalOptions::default()
+        };
+        let report = traverse(client, base_url, 4, &options).await.unwrap();
+        // 1 + 3 + 9 collections, each with 2 files
+        assert_eq!(report.directories, 13);
+        assert_eq!(report.files, 26);
+        assert!(report.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_simulated_traversal_max_dirs() {
+        let base_url = Url::parse("https://sim.invalid/root/").unwrap();
+        let client = Client::new(base_url.clone(), ClientOptions::default()).unwrap();
+        let options = TraversalOptions {
+            quiet: true,
+            simulate: Some("fanout=3,depth=2,files=2".parse().unwrap()),
+            max_dirs: Some(4),
+            ..TraversalOptions::default()
+        };
+        let report = traverse(client, base_url, 4, &options).await.unwrap();
+        assert_eq!(
+            report.directories, 4,
+            "traversal should stop after 4 collections"
+        );
+        assert!(
+            report.files < 26,
+            "traversal should not have probed every file"
+        );
+        assert!(
+            report.stopped_early,
+            "report should be marked as stopped early"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_simulated_traversal_pools() {
+        let base_url = Url::parse("https://sim.invalid/root/").unwrap();
+        let client = Client::new(base_url.clone(), ClientOptions::default()).unwrap();
+        let options = TraversalOptions {
+            quiet: true,
+            simulate: Some("fanout=3,depth=2,files=2".parse().unwrap()),
+            dir_workers: Some(1),
+            ..TraversalOptions::default()
+        };
+        let report = traverse(client, base_url, 4, &options).await.unwrap();
+        let pools = report.pools.expect("report should have pool stats");
+        // The file pool gets the workers not given to the directory pool
+        assert_eq!((pools.directories.workers, pools.files.workers), (1, 3));
+        assert_eq!((pools.directories.tasks, pools.files.tasks), (13, 26));
+        assert_eq!(pools.directories.peak_concurrency, 1);
+        assert!(pools.files.peak_concurrency <= 3);
+    }
+}
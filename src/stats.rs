@@ -0,0 +1,2741 @@
+use crate::client::{ErrorKind, ProbeTiming, ServerHeaders, WarningKind};
+use crate::export::{serialize_optional_seconds, serialize_seconds};
+use crate::show_duration::show_duration_as_seconds;
+use crate::traverse::TraversalReport;
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ContinuousCDF, Normal, StudentsT};
+use statrs::statistics::{Data, Distribution};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use url::Url;
+
+/// The overall outcome of a single traversal, as needed for summarizing a
+/// batch
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Sample {
+    pub(crate) requests: usize,
+    pub(crate) elapsed: Duration,
+    /// Mean fraction of the workers that had requests in flight during the
+    /// traversal, if measured
+    pub(crate) utilization: Option<f64>,
+}
+
+impl Sample {
+    /// Requests completed per second
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn throughput(&self) -> f64 {
+        (self.requests as f64) / self.elapsed.as_secs_f64()
+    }
+}
+
+impl From<&TraversalReport> for Sample {
+    fn from(report: &TraversalReport) -> Sample {
+        Sample {
+            requests: report.requests(),
+            elapsed: report.overall_time,
+            utilization: report.concurrency.utilization,
+        }
+    }
+}
+
+/// Traversal samples grouped by number of workers
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct WorkerSamples(BTreeMap<usize, Vec<Sample>>);
+
+impl WorkerSamples {
+    pub(crate) fn new() -> WorkerSamples {
+        WorkerSamples(BTreeMap::new())
+    }
+
+    /// Add a sample for the given number of workers and return the number of
+    /// samples now recorded for that number of workers
+    pub(crate) fn push(&mut self, workers: usize, sample: Sample) -> usize {
+        let samples = self.0.entry(workers).or_default();
+        samples.push(sample);
+        samples.len()
+    }
+
+    /// Return the number of samples recorded for each number of workers
+    pub(crate) fn counts(&self) -> BTreeMap<usize, usize> {
+        self.0
+            .iter()
+            .map(|(&workers, samples)| (workers, samples.len()))
+            .collect()
+    }
+
+    /// Return the traversal times in seconds of the samples for the given
+    /// number of workers
+    pub(crate) fn times(&self, workers: usize) -> Vec<f64> {
+        self.0.get(&workers).map_or_else(Vec::new, |samples| {
+            samples.iter().map(|s| s.elapsed.as_secs_f64()).collect()
+        })
+    }
+
+    /// Return the half-width of the 95% confidence interval for the mean
+    /// traversal time with the given number of workers as a percentage of
+    /// the mean, or `None` if there are fewer than two samples for that
+    /// number of workers
+    pub(crate) fn time_ci_percent(&self, workers: usize) -> Option<f64> {
+        let (mean, half_width) = time_ci(self.0.get(&workers)?)?;
+        Some(half_width / mean * 100.0)
+    }
+
+    /// For each number of workers after the smallest, compare its traversal
+    /// times with those for the next smaller number of workers using a
+    /// Mann-Whitney U test, and return a mapping from numbers of workers to
+    /// the resulting two-sided p-values
+    pub(crate) fn adjacent_time_tests(&self) -> BTreeMap<usize, f64> {
+        let times = |samples: &[Sample]| {
+            samples
+                .iter()
+                .map(|s| s.elapsed.as_secs_f64())
+                .collect::<Vec<_>>()
+        };
+        self.0
+            .iter()
+            .zip(self.0.iter().skip(1))
+            .filter_map(|((_, prev), (&workers, samples))| {
+                Some((workers, mann_whitney_u(&times(prev), &times(samples))?))
+            })
+            .collect()
+    }
+
+    /// Iterate over all samples as `(workers, run, sample)` triples, where
+    /// `run` is the 1-based index of the sample among those for the same
+    /// number of workers
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, usize, Sample)> + '_ {
+        self.0.iter().flat_map(|(&workers, samples)| {
+            samples
+                .iter()
+                .enumerate()
+                .map(move |(i, &s)| (workers, i.saturating_add(1), s))
+        })
+    }
+
+    /// Compute summary statistics for each number of workers, in ascending
+    /// order of number of workers.  Scaling efficiencies are computed
+    /// relative to the smallest number of workers.
+    pub(crate) fn summarize(&self) -> Vec<WorkerSummary> {
+        let mut summaries = self
+            .0
+            .iter()
+            .map(|(&workers, samples)| {
+                let times = Data::new(
+                    samples
+                        .iter()
+                        .map(|s| s.elapsed.as_secs_f64())
+                        .collect::<Vec<_>>(),
+                );
+                let throughputs =
+                    Data::new(samples.iter().map(Sample::throughput).collect::<Vec<_>>());
+                let utilizations = samples
+                    .iter()
+                    .filter_map(|s| s.utilization)
+                    .collect::<Vec<_>>();
+                let mut sorted_times = samples.iter().map(|s| s.elapsed).collect::<Vec<_>>();
+                sorted_times.sort_unstable();
+                WorkerSummary {
+                    workers,
+                    samples: samples.len(),
+                    time_mean: times
+                        .mean()
+                        .expect("mean should exist for nonzero number of samples"),
+                    time_stddev: times
+                        .std_dev()
+                        .expect("stddev should exist for nonzero number of samples"),
+                    time_median: percentile(&sorted_times, 50).as_secs_f64(),
+                    time_p95: percentile(&sorted_times, 95).as_secs_f64(),
+                    time_ci: time_ci(samples).map(|(_, half_width)| half_width),
+                    throughput_mean: throughputs
+                        .mean()
+                        .expect("mean should exist for nonzero number of samples"),
+                    throughput_stddev: throughputs
+                        .std_dev()
+                        .expect("stddev should exist for nonzero number of samples"),
+                    utilization_mean: if utilizations.is_empty() {
+                        None
+                    } else {
+                        Data::new(utilizations).mean()
+                    },
+                    efficiency: None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Some(&base) = summaries.first() {
+            for s in &mut summaries {
+                s.efficiency = scaling_efficiency(&base, s);
+            }
+        }
+        summaries
+    }
+}
+
+/// Return the mean traversal time of `samples` and the half-width of its 95%
+/// confidence interval, in seconds, or `None` if there are fewer than two
+/// samples
+#[allow(clippy::cast_precision_loss)]
+fn time_ci(samples: &[Sample]) -> Option<(f64, f64)> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let times = Data::new(
+        samples
+            .iter()
+            .map(|s| s.elapsed.as_secs_f64())
+            .collect::<Vec<_>>(),
+    );
+    let mean = times.mean()?;
+    let stddev = times.std_dev()?;
+    let n = samples.len() as f64;
+    let t = StudentsT::new(0.0, 1.0, n - 1.0).ok()?.inverse_cdf(0.975);
+    Some((mean, t * stddev / n.sqrt()))
+}
+
+/// Return the ratio of the speedup in mean throughput from `base` to
+/// `summary` to the corresponding increase in the number of workers, so that
+/// perfectly linear scaling has an efficiency of 1
+#[allow(clippy::cast_precision_loss)]
+fn scaling_efficiency(base: &WorkerSummary, summary: &WorkerSummary) -> Option<f64> {
+    if base.workers == 0 || summary.workers == 0 || base.throughput_mean <= 0.0 {
+        return None;
+    }
+    let speedup = summary.throughput_mean / base.throughput_mean;
+    let scale = summary.workers as f64 / base.workers as f64;
+    Some(speedup / scale).filter(|e| e.is_finite())
+}
+
+/// The number of workers beyond which adding more workers stops paying off
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Knee {
+    /// The number of workers at the knee or, if throughput never stopped
+    /// scaling, the largest number of workers measured
+    pub(crate) workers: usize,
+    /// The next larger number of workers measured after the knee along with
+    /// the marginal scaling of the step to it, if a knee was found
+    pub(crate) next: Option<(usize, f64)>,
+}
+
+impl Knee {
+    /// Find the smallest number of workers in `summaries` (which must be in
+    /// ascending order of number of workers) after which the step to the next
+    /// larger number of workers has a marginal scaling below `threshold`.
+    /// The marginal scaling of a step is the relative increase in mean
+    /// throughput divided by the relative increase in workers, so that
+    /// linear scaling gives 1 and no gain gives 0.  Returns `None` if fewer
+    /// than two numbers of workers were measured.
+    pub(crate) fn find(summaries: &[WorkerSummary], threshold: f64) -> Option<Knee> {
+        if summaries.len() < 2 {
+            return None;
+        }
+        for (prev, s) in summaries.iter().zip(summaries.iter().skip(1)) {
+            if let Some(scaling) = marginal_scaling(prev, s) {
+                if scaling < threshold {
+                    return Some(Knee {
+                        workers: prev.workers,
+                        next: Some((s.workers, scaling)),
+                    });
+                }
+            }
+        }
+        summaries.last().map(|s| Knee {
+            workers: s.workers,
+            next: None,
+        })
+    }
+}
+
+impl fmt::Display for Knee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.next {
+            Some((next, scaling)) => writeln!(
+                f,
+                "Scalability knee: {} workers (going to {next} workers yielded {:.1}% of a linear throughput gain)",
+                self.workers,
+                scaling * 100.0
+            ),
+            None => writeln!(
+                f,
+                "Scalability knee: not reached; throughput was still scaling at {} workers",
+                self.workers
+            ),
+        }
+    }
+}
+
+/// Return the relative increase in mean throughput from `prev` to `next`
+/// divided by the relative increase in the number of workers
+#[allow(clippy::cast_precision_loss)]
+fn marginal_scaling(prev: &WorkerSummary, next: &WorkerSummary) -> Option<f64> {
+    if prev.workers == 0 || next.workers <= prev.workers || prev.throughput_mean <= 0.0 {
+        return None;
+    }
+    let gain = next.throughput_mean / prev.throughput_mean - 1.0;
+    let growth = next.workers as f64 / prev.workers as f64 - 1.0;
+    Some(gain / growth).filter(|s| s.is_finite())
+}
+
+/// Perform a two-sided Mann-Whitney U test of whether the values in `xs` and
+/// `ys` come from the same distribution and return the p-value.  The p-value
+/// is computed using the normal approximation with corrections for ties &
+/// continuity.  Returns `None` if either input is empty.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn mann_whitney_u(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    if xs.is_empty() || ys.is_empty() {
+        return None;
+    }
+    let mut values = xs
+        .iter()
+        .map(|&x| (x, true))
+        .chain(ys.iter().map(|&y| (y, false)))
+        .collect::<Vec<_>>();
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // Sum of the ranks of the `xs`, with tied values given the mean of the
+    // ranks they span
+    let mut xs_rank_sum = 0.0;
+    // Sum of t³ - t over all groups of t tied values
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i.saturating_add(1);
+        while values
+            .get(j)
+            .is_some_and(|v| v.0.total_cmp(&values[i].0).is_eq())
+        {
+            j = j.saturating_add(1);
+        }
+        // Ranks are 1-based, so the group spans ranks i+1 through j
+        let rank = ((i as f64) + (j as f64) + 1.0) / 2.0;
+        let group = &values[i..j];
+        xs_rank_sum += rank * (group.iter().filter(|v| v.1).count() as f64);
+        let t = group.len() as f64;
+        tie_term += t.powi(3) - t;
+        i = j;
+    }
+    let n1 = xs.len() as f64;
+    let n2 = ys.len() as f64;
+    let n = n1 + n2;
+    let u = xs_rank_sum - n1 * (n1 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_term / (n * (n - 1.0)));
+    if variance <= 0.0 || !variance.is_finite() {
+        // All values are tied
+        return Some(1.0);
+    }
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    let normal = Normal::new(0.0, 1.0).ok()?;
+    Some((2.0 * normal.sf(z)).min(1.0))
+}
+
+/// Summary statistics for the traversals performed with a given number of
+/// workers.  Times are in seconds, and throughputs are in requests per second.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct WorkerSummary {
+    pub(crate) workers: usize,
+    pub(crate) samples: usize,
+    pub(crate) time_mean: f64,
+    pub(crate) time_stddev: f64,
+    pub(crate) time_median: f64,
+    pub(crate) time_p95: f64,
+    /// Half-width of the 95% confidence interval for the mean time, if there
+    /// are at least two samples
+    pub(crate) time_ci: Option<f64>,
+    pub(crate) throughput_mean: f64,
+    pub(crate) throughput_stddev: f64,
+    /// Mean worker utilization over the samples for which it was measured
+    pub(crate) utilization_mean: Option<f64>,
+    /// Scaling efficiency relative to the smallest number of workers in the
+    /// batch
+    pub(crate) efficiency: Option<f64>,
+}
+
+impl WorkerSummary {
+    /// The mean time plus & minus one standard deviation, if there are at
+    /// least two samples
+    pub(crate) fn time_stddev_bounds(&self) -> Option<(f64, f64)> {
+        bounds(self.time_mean, self.time_stddev)
+    }
+
+    /// The bounds of the 95% confidence interval for the mean time, if there
+    /// are at least two samples
+    pub(crate) fn time_ci_bounds(&self) -> Option<(f64, f64)> {
+        bounds(self.time_mean, self.time_ci?)
+    }
+
+    /// The mean throughput plus & minus one standard deviation, if there are
+    /// at least two samples
+    pub(crate) fn throughput_stddev_bounds(&self) -> Option<(f64, f64)> {
+        bounds(self.throughput_mean, self.throughput_stddev)
+    }
+}
+
+fn bounds(center: f64, delta: f64) -> Option<(f64, f64)> {
+    (center.is_finite() && delta.is_finite()).then_some((center - delta, center + delta))
+}
+
+/// The p-value below which the difference in traversal times between two
+/// numbers of workers is reported as significant
+pub(crate) const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// A row of the per-workers summary: the summary statistics for a number of
+/// workers along with the p-value comparing its traversal times to those for
+/// the next smaller number of workers, if computed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct WorkerRow {
+    pub(crate) summary: WorkerSummary,
+    pub(crate) p_value: Option<f64>,
+}
+
+/// A named column of CSV output whose value is computed from a row of type
+/// `T`
+#[derive(Debug)]
+pub(crate) struct CsvColumn<T> {
+    pub(crate) name: &'static str,
+    /// Alternative names by which the column can be selected
+    aliases: &'static [&'static str],
+    value: fn(&T) -> String,
+}
+
+impl<T> CsvColumn<T> {
+    const fn new(name: &'static str, value: fn(&T) -> String) -> CsvColumn<T> {
+        CsvColumn {
+            name,
+            aliases: &[],
+            value,
+        }
+    }
+
+    const fn aliased(
+        name: &'static str,
+        aliases: &'static [&'static str],
+        value: fn(&T) -> String,
+    ) -> CsvColumn<T> {
+        CsvColumn {
+            name,
+            aliases,
+            value,
+        }
+    }
+}
+
+/// A type of row whose columns can be selected for CSV output
+pub(crate) trait CsvRecord: Sized + 'static {
+    /// All of the columns available for the row type, in the order listed in
+    /// error messages
+    const COLUMNS: &'static [CsvColumn<Self>];
+}
+
+impl CsvRecord for TraversalReport {
+    const COLUMNS: &'static [CsvColumn<TraversalReport>] = &[
+        CsvColumn::new("workers", |r| r.workers.to_string()),
+        CsvColumn::new("requests", |r| r.requests().to_string()),
+        CsvColumn::new("elapsed", |r| {
+            show_duration_as_seconds(r.overall_time).to_string()
+        }),
+        CsvColumn::new("utilization", |r| optional(r.concurrency.utilization)),
+        CsvColumn::new("directories", |r| r.directories.to_string()),
+        CsvColumn::new("files", |r| r.files.to_string()),
+        CsvColumn::new("bytes", |r| optional(r.total_bytes)),
+        CsvColumn::new("errors", |r| r.errors.total().to_string()),
+        CsvColumn::new("throughput", |r| Sample::from(r).throughput().to_string()),
+        CsvColumn::new("peak_concurrency", |r| optional(r.peak_concurrency)),
+        CsvColumn::new("mean_concurrency", |r| optional(r.mean_concurrency)),
+        CsvColumn::new("dir_median", |r| {
+            optional_secs(r.directory_latency.map(|l| l.median))
+        }),
+        CsvColumn::new("dir_p95", |r| {
+            optional_secs(r.directory_latency.map(|l| l.p95))
+        }),
+        CsvColumn::new("file_median", |r| {
+            optional_secs(r.file_latency.map(|l| l.median))
+        }),
+        CsvColumn::new("file_p95", |r| optional_secs(r.file_latency.map(|l| l.p95))),
+        CsvColumn::new("start_time", |r| rfc3339(r.start_time)),
+        CsvColumn::new("end_time", |r| rfc3339(r.end_time)),
+    ];
+}
+
+impl CsvRecord for WorkerRow {
+    const COLUMNS: &'static [CsvColumn<WorkerRow>] = &[
+        CsvColumn::new("workers", |r| r.summary.workers.to_string()),
+        CsvColumn::new("samples", |r| r.summary.samples.to_string()),
+        CsvColumn::aliased("time_mean", &["mean"], |r| r.summary.time_mean.to_string()),
+        CsvColumn::aliased("time_stddev", &["stddev"], |r| {
+            r.summary.time_stddev.to_string()
+        }),
+        CsvColumn::aliased("time_median", &["median"], |r| {
+            r.summary.time_median.to_string()
+        }),
+        CsvColumn::aliased("time_p95", &["p95"], |r| r.summary.time_p95.to_string()),
+        CsvColumn::aliased("time_ci", &["ci"], |r| optional(r.summary.time_ci)),
+        CsvColumn::new("time_stddev_lower", |r| {
+            optional(r.summary.time_stddev_bounds().map(|(lo, _)| lo))
+        }),
+        CsvColumn::new("time_stddev_upper", |r| {
+            optional(r.summary.time_stddev_bounds().map(|(_, hi)| hi))
+        }),
+        CsvColumn::new("time_ci_lower", |r| {
+            optional(r.summary.time_ci_bounds().map(|(lo, _)| lo))
+        }),
+        CsvColumn::new("time_ci_upper", |r| {
+            optional(r.summary.time_ci_bounds().map(|(_, hi)| hi))
+        }),
+        CsvColumn::aliased("throughput_mean", &["throughput"], |r| {
+            r.summary.throughput_mean.to_string()
+        }),
+        CsvColumn::new("throughput_stddev", |r| {
+            r.summary.throughput_stddev.to_string()
+        }),
+        CsvColumn::new("throughput_stddev_lower", |r| {
+            optional(r.summary.throughput_stddev_bounds().map(|(lo, _)| lo))
+        }),
+        CsvColumn::new("throughput_stddev_upper", |r| {
+            optional(r.summary.throughput_stddev_bounds().map(|(_, hi)| hi))
+        }),
+        CsvColumn::aliased("utilization_mean", &["utilization"], |r| {
+            optional(r.summary.utilization_mean)
+        }),
+        CsvColumn::new("efficiency", |r| optional(r.summary.efficiency)),
+        CsvColumn::new("p_value", |r| optional(r.p_value)),
+        CsvColumn::new("significant", |r| {
+            optional(r.p_value.map(|p| p < SIGNIFICANCE_LEVEL))
+        }),
+    ];
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn optional_secs(value: Option<Duration>) -> String {
+    optional(value.map(show_duration_as_seconds))
+}
+
+/// A selection of the columns of `T` to include in CSV output, in order
+#[derive(Debug)]
+pub(crate) struct CsvFields<T: 'static>(Vec<&'static CsvColumn<T>>);
+
+impl<T: CsvRecord> CsvFields<T> {
+    /// Select the columns with the given names or aliases
+    pub(crate) fn select<S: AsRef<str>>(names: &[S]) -> Result<CsvFields<T>, UnknownFieldError> {
+        names
+            .iter()
+            .map(|name| {
+                let name = name.as_ref();
+                T::COLUMNS
+                    .iter()
+                    .find(|c| c.name == name || c.aliases.contains(&name))
+                    .ok_or_else(|| UnknownFieldError {
+                        name: name.to_owned(),
+                        available: T::COLUMNS
+                            .iter()
+                            .map(|c| c.name)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(CsvFields)
+    }
+
+    /// Returns true if the column with the given name is selected
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|c| c.name == name)
+    }
+
+    /// The CSV header line, without a trailing newline
+    pub(crate) fn header(&self) -> String {
+        self.0.iter().map(|c| c.name).collect::<Vec<_>>().join(",")
+    }
+
+    /// The CSV line for `record`, without a trailing newline
+    pub(crate) fn row(&self, record: &T) -> String {
+        self.0
+            .iter()
+            .map(|c| (c.value)(record))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("unknown field {name:?} for --fields; available fields: {available}")]
+pub(crate) struct UnknownFieldError {
+    name: String,
+    available: String,
+}
+
+/// Distribution of the number of children per collection encountered during a
+/// traversal
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct FanoutStats {
+    /// Number of collections listed
+    pub(crate) collections: usize,
+    /// Mean number of children per collection
+    pub(crate) mean: f64,
+    /// Greatest number of children of any one collection
+    pub(crate) max: usize,
+    /// Mapping from lower bounds of power-of-two buckets (0, 1, 2, 4, 8, …)
+    /// to the number of collections whose number of children falls in that
+    /// bucket
+    pub(crate) histogram: BTreeMap<usize, usize>,
+    /// Total number of children of all collections
+    #[serde(skip)]
+    children: usize,
+}
+
+impl FanoutStats {
+    /// Tally a collection with the given number of children
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn add(&mut self, children: usize) {
+        self.collections = self.collections.saturating_add(1);
+        self.children = self.children.saturating_add(children);
+        self.mean = (self.children as f64) / (self.collections as f64);
+        self.max = self.max.max(children);
+        let bucket = if children == 0 {
+            0
+        } else {
+            1 << (usize::BITS - 1 - children.leading_zeros())
+        };
+        *self.histogram.entry(bucket).or_insert(0usize) += 1;
+    }
+}
+
+impl fmt::Display for FanoutStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Directory fan-out: {} collections, mean {:.2} children, max {}",
+            self.collections, self.mean, self.max
+        )?;
+        for (&lower, &count) in &self.histogram {
+            let upper = lower.saturating_mul(2).saturating_sub(1);
+            if upper > lower {
+                writeln!(f, "  {lower}-{upper} children: {count}")?;
+            } else {
+                writeln!(f, "  {lower} children: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Distribution of the sizes of the bodies of the `PROPFIND` responses
+/// received during a traversal
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct ResponseSizeStats {
+    /// Number of responses received
+    pub(crate) responses: usize,
+    /// Total size of all response bodies in bytes
+    pub(crate) total_bytes: u64,
+    /// Mean size of a response body in bytes
+    pub(crate) mean: f64,
+    /// Median size of a response body in bytes (approximate to within about
+    /// 1.6%)
+    pub(crate) median: u64,
+    /// Size of the largest response body in bytes
+    pub(crate) max: u64,
+    /// Mapping from lower bounds of power-of-two buckets (0, 1, 2, 4, 8, …)
+    /// to the number of responses whose size falls in that bucket
+    pub(crate) histogram: BTreeMap<u64, usize>,
+}
+
+/// Accumulator for the sizes of `PROPFIND` response bodies from which a
+/// `ResponseSizeStats` is computed.  Rather than retaining every size, the
+/// sizes are aggregated into a histogram with the same precision as
+/// `LatencyHistogram`, so that the median is approximate.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ResponseSizes {
+    responses: usize,
+    total: u64,
+    max: u64,
+    /// Mapping from bucket lower bounds to the number of sizes in each bucket
+    buckets: BTreeMap<u64, usize>,
+}
+
+impl ResponseSizes {
+    pub(crate) fn add(&mut self, size: u64) {
+        self.responses = self.responses.saturating_add(1);
+        self.total = self.total.saturating_add(size);
+        self.max = self.max.max(size);
+        let shift = bucket_shift(size);
+        let counter = self.buckets.entry((size >> shift) << shift).or_default();
+        *counter = counter.saturating_add(1);
+    }
+
+    /// Summarize the recorded sizes.  The median is the midpoint of the
+    /// bucket containing it, while all other statistics are exact.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn stats(&self) -> ResponseSizeStats {
+        let mut histogram = BTreeMap::new();
+        let mut median = None;
+        let mut seen = 0usize;
+        for (&lower, &count) in &self.buckets {
+            seen = seen.saturating_add(count);
+            if median.is_none() && seen > self.responses / 2 {
+                let midpoint = lower.saturating_add((1u64 << bucket_shift(lower)) >> 1);
+                median = Some(midpoint.min(self.max));
+            }
+            let bucket = if lower == 0 {
+                0
+            } else {
+                1 << (u64::BITS - 1 - lower.leading_zeros())
+            };
+            *histogram.entry(bucket).or_insert(0usize) += count;
+        }
+        let mean = if self.responses == 0 {
+            0.0
+        } else {
+            (self.total as f64) / (self.responses as f64)
+        };
+        ResponseSizeStats {
+            responses: self.responses,
+            total_bytes: self.total,
+            mean,
+            median: median.unwrap_or_default(),
+            max: self.max,
+            histogram,
+        }
+    }
+}
+
+impl fmt::Display for ResponseSizeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "PROPFIND response sizes: {} responses totalling {} bytes, mean {:.1} bytes, median {} bytes, max {} bytes",
+            self.responses, self.total_bytes, self.mean, self.median, self.max
+        )?;
+        for (&lower, &count) in &self.histogram {
+            let upper = lower.saturating_mul(2).saturating_sub(1);
+            if upper > lower {
+                writeln!(f, "  {lower}-{upper} bytes: {count}")?;
+            } else {
+                writeln!(f, "  {lower} bytes: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Statistics on the lengths of the redirect chains followed for
+/// non-collection resources
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct RedirectChainStats {
+    /// Number of resources whose redirects were followed
+    pub(crate) files: usize,
+    /// Greatest number of redirects followed for any one resource
+    pub(crate) max_length: usize,
+    /// Mapping from chain lengths (number of redirects) to the number of
+    /// resources with chains of that length
+    pub(crate) histogram: BTreeMap<usize, usize>,
+    /// Number of chains abandoned upon reaching the maximum number of hops
+    pub(crate) truncated: usize,
+    /// Latencies of requests made to redirect targets
+    pub(crate) hop_latency: Option<LatencySummary>,
+}
+
+impl RedirectChainStats {
+    /// Tally a chain of `length` redirects, which was abandoned at the
+    /// maximum number of hops if `truncated` is true
+    pub(crate) fn add(&mut self, length: usize, truncated: bool) {
+        self.files = self.files.saturating_add(1);
+        self.max_length = self.max_length.max(length);
+        *self.histogram.entry(length).or_insert(0usize) += 1;
+        if truncated {
+            self.truncated = self.truncated.saturating_add(1);
+        }
+    }
+}
+
+impl fmt::Display for RedirectChainStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Redirect chains: {} files, max {} redirects, {} truncated",
+            self.files, self.max_length, self.truncated
+        )?;
+        for (&length, &count) in &self.histogram {
+            writeln!(f, "  {length} redirects: {count}")?;
+        }
+        if let Some(lat) = self.hop_latency {
+            writeln!(f, "  Hop latency: {lat}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A comparison of the latencies of `HEAD` and `GET` requests made for the
+/// same non-collection resources
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct MethodComparison {
+    /// Number of resources probed with both methods
+    pub(crate) files: usize,
+    pub(crate) head_ttfb: Option<LatencySummary>,
+    pub(crate) head_latency: Option<LatencySummary>,
+    pub(crate) get_ttfb: Option<LatencySummary>,
+    pub(crate) get_latency: Option<LatencySummary>,
+    /// Mean over all resources of the `GET` request's time to first byte
+    /// minus the `HEAD` request's, in milliseconds
+    pub(crate) mean_ttfb_difference_ms: Option<f64>,
+    /// Median over all resources of the `GET` request's time to first byte
+    /// minus the `HEAD` request's, in milliseconds
+    pub(crate) median_ttfb_difference_ms: Option<f64>,
+    /// Number of resources for which the `GET` request's time to first byte
+    /// exceeded the `HEAD` request's
+    pub(crate) get_slower: usize,
+}
+
+/// Accumulator for the timings of `(HEAD, GET)` pairs of requests from
+/// which a `MethodComparison` is computed
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct MethodPairs {
+    head_ttfb: Latencies,
+    head_latency: Latencies,
+    get_ttfb: Latencies,
+    get_latency: Latencies,
+    /// Amounts by which the `GET` request's time to first byte exceeded or
+    /// equalled the `HEAD` request's
+    get_slower_by: Latencies,
+    /// Amounts by which the `HEAD` request's time to first byte exceeded the
+    /// `GET` request's
+    head_slower_by: Latencies,
+    /// Number of pairs in which the `GET` request's time to first byte
+    /// exceeded the `HEAD` request's
+    get_slower: usize,
+}
+
+impl MethodPairs {
+    pub(crate) fn new(low_memory: bool) -> MethodPairs {
+        MethodPairs {
+            head_ttfb: Latencies::new(low_memory),
+            head_latency: Latencies::new(low_memory),
+            get_ttfb: Latencies::new(low_memory),
+            get_latency: Latencies::new(low_memory),
+            get_slower_by: Latencies::new(low_memory),
+            head_slower_by: Latencies::new(low_memory),
+            get_slower: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, head: ProbeTiming, get: ProbeTiming) {
+        self.head_ttfb.push(head.ttfb);
+        self.head_latency.push(head.elapsed);
+        self.get_ttfb.push(get.ttfb);
+        self.get_latency.push(get.elapsed);
+        if get.ttfb > head.ttfb {
+            self.get_slower = self.get_slower.saturating_add(1);
+        }
+        match get.ttfb.checked_sub(head.ttfb) {
+            Some(d) => self.get_slower_by.push(d),
+            None => self.head_slower_by.push(head.ttfb.saturating_sub(get.ttfb)),
+        }
+    }
+
+    /// Return the `GET` request's time to first byte minus the `HEAD`
+    /// request's, in milliseconds, for the pair at index `rank` when the
+    /// pairs are sorted by that difference
+    fn ttfb_difference_ms(&self, rank: usize) -> Option<f64> {
+        let faster = self.head_slower_by.len();
+        if rank < faster {
+            self.head_slower_by
+                .nth_smallest(faster - 1 - rank)
+                .map(|d| -d.as_secs_f64() * 1000.0)
+        } else {
+            self.get_slower_by
+                .nth_smallest(rank - faster)
+                .map(|d| d.as_secs_f64() * 1000.0)
+        }
+    }
+
+    /// Compare the recorded timings
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn comparison(&self) -> MethodComparison {
+        let files = self.head_ttfb.len();
+        let total_ms = (self.get_slower_by.total().as_secs_f64()
+            - self.head_slower_by.total().as_secs_f64())
+            * 1000.0;
+        let (mean, median) = if files == 0 {
+            (None, None)
+        } else {
+            (
+                Some(total_ms / (files as f64)),
+                self.ttfb_difference_ms(files / 2),
+            )
+        };
+        MethodComparison {
+            files,
+            head_ttfb: self.head_ttfb.summary(),
+            head_latency: self.head_latency.summary(),
+            get_ttfb: self.get_ttfb.summary(),
+            get_latency: self.get_latency.summary(),
+            mean_ttfb_difference_ms: mean,
+            median_ttfb_difference_ms: median,
+            get_slower: self.get_slower,
+        }
+    }
+}
+
+impl fmt::Display for MethodComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "HEAD vs. GET: {} files probed with both", self.files)?;
+        for (label, summary) in [
+            ("HEAD TTFB", self.head_ttfb),
+            ("HEAD latency", self.head_latency),
+            ("GET TTFB", self.get_ttfb),
+            ("GET latency", self.get_latency),
+        ] {
+            if let Some(lat) = summary {
+                writeln!(f, "  {label}: {lat}")?;
+            }
+        }
+        if let (Some(mean), Some(median)) =
+            (self.mean_ttfb_difference_ms, self.median_ttfb_difference_ms)
+        {
+            writeln!(
+                f,
+                "  GET - HEAD TTFB: mean {mean:+.3}ms, median {median:+.3}ms; GET slower for {} of {} files",
+                self.get_slower, self.files
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// How evenly the tasks of a traversal were distributed across its workers
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct FairnessStats {
+    /// The load of each worker, in worker order
+    pub(crate) workers: Vec<WorkerLoadStats>,
+    /// Fewest tasks run by any worker
+    pub(crate) min_tasks: usize,
+    /// Most tasks run by any worker
+    pub(crate) max_tasks: usize,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) min_busy: Duration,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) mean_busy: Duration,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) max_busy: Duration,
+    /// Jain's fairness index of the workers' busy times, ranging from `1/n`
+    /// (all work done by one of `n` workers) to 1 (perfectly even)
+    pub(crate) fairness_index: f64,
+}
+
+/// The work done by a single worker during a traversal
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct WorkerLoadStats {
+    /// Number of tasks (each making one request, plus any redirects followed
+    /// or repeats) run by the worker
+    pub(crate) tasks: usize,
+    /// Total time the worker spent running tasks
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) busy: Duration,
+}
+
+impl FairnessStats {
+    /// Summarize the loads of the workers, given as pairs of task counts &
+    /// busy times.  Returns `None` if there were no workers or no tasks were
+    /// run.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn new<I>(loads: I) -> Option<FairnessStats>
+    where
+        I: IntoIterator<Item = (usize, Duration)>,
+    {
+        let workers = loads
+            .into_iter()
+            .map(|(tasks, busy)| WorkerLoadStats { tasks, busy })
+            .collect::<Vec<_>>();
+        if workers.iter().all(|w| w.tasks == 0) {
+            return None;
+        }
+        let min_tasks = workers.iter().map(|w| w.tasks).min()?;
+        let max_tasks = workers.iter().map(|w| w.tasks).max()?;
+        let min_busy = workers.iter().map(|w| w.busy).min()?;
+        let max_busy = workers.iter().map(|w| w.busy).max()?;
+        let total = workers.iter().map(|w| w.busy).sum::<Duration>();
+        let n = workers.len() as f64;
+        let mean_busy = total.div_f64(n);
+        let secs = workers.iter().map(|w| w.busy.as_secs_f64());
+        let sum_sq = secs.clone().map(|s| s * s).sum::<f64>();
+        let sum = secs.sum::<f64>();
+        let fairness_index = if sum_sq > 0.0 {
+            sum * sum / (n * sum_sq)
+        } else {
+            1.0
+        };
+        Some(FairnessStats {
+            workers,
+            min_tasks,
+            max_tasks,
+            min_busy,
+            mean_busy,
+            max_busy,
+            fairness_index,
+        })
+    }
+}
+
+impl fmt::Display for FairnessStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Worker fairness: {} workers ran {}–{} tasks each; busy time min {:?}, mean {:?}, max {:?}; Jain's index {:.3}",
+            self.workers.len(),
+            self.min_tasks,
+            self.max_tasks,
+            self.min_busy,
+            self.mean_busy,
+            self.max_busy,
+            self.fairness_index,
+        )
+    }
+}
+
+/// The utilization of the separate worker pools for collection listings &
+/// file probes in a traversal run with `--dir-workers` or `--file-workers`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct WorkerPools {
+    pub(crate) directories: PoolStats,
+    pub(crate) files: PoolStats,
+}
+
+/// The utilization of a single worker pool during a traversal
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct PoolStats {
+    /// Number of worker slots in the pool
+    pub(crate) workers: usize,
+    /// Number of tasks run by the pool
+    pub(crate) tasks: usize,
+    /// Greatest number of the pool's slots occupied at once
+    pub(crate) peak_concurrency: usize,
+    /// Total time spent running tasks divided by the duration of the
+    /// traversal; `None` if the traversal took no time
+    pub(crate) mean_concurrency: Option<f64>,
+    /// `mean_concurrency` as a fraction of `workers`
+    pub(crate) utilization: Option<f64>,
+    /// How evenly the pool's tasks were distributed across its workers
+    pub(crate) fairness: Option<FairnessStats>,
+}
+
+impl PoolStats {
+    /// Summarize a pool of `workers` slots with a peak occupancy of `peak`
+    /// whose slots had the given loads (as pairs of task counts & busy
+    /// times) over a traversal lasting `elapsed`
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn new<I>(workers: usize, peak: usize, loads: I, elapsed: Duration) -> PoolStats
+    where
+        I: IntoIterator<Item = (usize, Duration)>,
+    {
+        let loads = loads.into_iter().collect::<Vec<_>>();
+        let tasks = loads.iter().map(|&(n, _)| n).sum();
+        let busy = loads.iter().map(|&(_, b)| b).sum::<Duration>();
+        let mean_concurrency =
+            (!elapsed.is_zero()).then(|| busy.as_secs_f64() / elapsed.as_secs_f64());
+        PoolStats {
+            workers,
+            tasks,
+            peak_concurrency: peak,
+            mean_concurrency,
+            utilization: mean_concurrency
+                .filter(|_| workers > 0)
+                .map(|m| m / workers as f64),
+            fairness: FairnessStats::new(loads),
+        }
+    }
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tasks; peak {}, mean {:.2} of {} workers",
+            self.tasks,
+            self.peak_concurrency,
+            self.mean_concurrency.unwrap_or(0.0),
+            self.workers
+        )?;
+        if let Some(u) = self.utilization {
+            write!(f, " ({:.1}% utilization)", u * 100.0)?;
+        }
+        if let Some(ref fairness) = self.fairness {
+            write!(f, "; Jain's index {:.3}", fairness.fairness_index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts of the requests in a traversal whose latencies exceeded the
+/// `--log-slower-than` threshold
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct SlowRequestStats {
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) threshold: Duration,
+    /// Number of slow `PROPFIND` requests, including listings of Zarr stores
+    pub(crate) directories: usize,
+    /// Number of slow file probes
+    pub(crate) files: usize,
+}
+
+impl SlowRequestStats {
+    pub(crate) fn new(threshold: Duration) -> SlowRequestStats {
+        SlowRequestStats {
+            threshold,
+            directories: 0,
+            files: 0,
+        }
+    }
+}
+
+impl fmt::Display for SlowRequestStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Requests slower than {} s: {} PROPFIND, {} file probes",
+            show_duration_as_seconds(self.threshold),
+            self.directories,
+            self.files
+        )
+    }
+}
+
+/// Counts of the request attempts in a traversal that timed out.  Timed-out
+/// attempts do not contribute to latency statistics, which only cover
+/// completed requests; a request that timed out and was then retried
+/// successfully contributes only the latency of the successful attempt.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct TimeoutStats {
+    /// The client-side timeout, if one was set
+    #[serde(serialize_with = "serialize_optional_seconds")]
+    pub(crate) timeout: Option<Duration>,
+    /// Number of requests that failed because their final attempts timed out
+    pub(crate) failed: usize,
+    /// Number of timed-out attempts that were retried
+    pub(crate) retried: usize,
+}
+
+impl fmt::Display for TimeoutStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Timeouts")?;
+        if let Some(timeout) = self.timeout {
+            write!(f, " (after {} s)", show_duration_as_seconds(timeout))?;
+        }
+        write!(
+            f,
+            ": {} requests failed, {} attempts retried; excluded from latencies",
+            self.failed, self.retried
+        )
+    }
+}
+
+/// Counts of the duplicate requests sent during a traversal for request
+/// attempts that had not received a response within the hedging delay.  The
+/// latency of a hedged request is the time until the first response to
+/// either of its two requests.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct HedgeStats {
+    /// The delay after which a duplicate request was sent
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) hedge_after: Duration,
+    /// Number of duplicate requests sent
+    pub(crate) hedged: usize,
+    /// Number of duplicate requests whose responses were used in place of
+    /// their originals
+    pub(crate) won: usize,
+}
+
+impl fmt::Display for HedgeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Hedged requests (after {} s): {} duplicates sent, {} used",
+            show_duration_as_seconds(self.hedge_after),
+            self.hedged,
+            self.won
+        )
+    }
+}
+
+/// The mean times that request tasks spent waiting for a worker & running
+/// during a traversal, together with the mean numbers of tasks queued & in
+/// service that they imply by Little's law
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct PipelineStats {
+    /// Number of tasks that completed successfully
+    pub(crate) tasks: usize,
+    /// Completed tasks per second over the whole traversal
+    pub(crate) throughput: f64,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) mean_queue_wait: Duration,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) mean_service_time: Duration,
+    /// Mean time from a task being scheduled until it finished
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) mean_sojourn_time: Duration,
+    /// Mean number of tasks waiting for a worker
+    pub(crate) mean_queued: f64,
+    /// Mean number of tasks running
+    pub(crate) mean_in_service: f64,
+    /// Mean number of tasks either waiting or running
+    pub(crate) mean_in_system: f64,
+}
+
+impl PipelineStats {
+    /// Summarize `tasks` tasks that spent a total of `queue_wait` waiting for
+    /// workers and `service_time` running over a traversal lasting `elapsed`.
+    /// Returns `None` if no tasks completed or no time elapsed.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn new(
+        tasks: usize,
+        queue_wait: Duration,
+        service_time: Duration,
+        elapsed: Duration,
+    ) -> Option<PipelineStats> {
+        let secs = elapsed.as_secs_f64();
+        if tasks == 0 || secs <= 0.0 {
+            return None;
+        }
+        let n = tasks as f64;
+        let throughput = n / secs;
+        let mean_queue_wait = queue_wait.div_f64(n);
+        let mean_service_time = service_time.div_f64(n);
+        let mean_sojourn_time = mean_queue_wait.saturating_add(mean_service_time);
+        Some(PipelineStats {
+            tasks,
+            throughput,
+            mean_queue_wait,
+            mean_service_time,
+            mean_sojourn_time,
+            mean_queued: queue_wait.as_secs_f64() / secs,
+            mean_in_service: service_time.as_secs_f64() / secs,
+            mean_in_system: queue_wait.saturating_add(service_time).as_secs_f64() / secs,
+        })
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Pipeline: {} tasks at {:.2} tasks/s; mean queue wait {:?} + mean service time {:?} = mean time in system {:?}",
+            self.tasks,
+            self.throughput,
+            self.mean_queue_wait,
+            self.mean_service_time,
+            self.mean_sojourn_time,
+        )?;
+        writeln!(
+            f,
+            "  Little's law: {:.2} queued + {:.2} in service = {:.2} tasks in system on average",
+            self.mean_queued, self.mean_in_service, self.mean_in_system,
+        )
+    }
+}
+
+/// Latencies of the first requests for each resource compared to those of
+/// the same requests when repeated immediately afterwards
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RepeatStats {
+    /// Number of times each request was repeated
+    pub(crate) repeats: usize,
+    pub(crate) directory_first: Option<LatencySummary>,
+    pub(crate) directory_repeat: Option<LatencySummary>,
+    pub(crate) file_first: Option<LatencySummary>,
+    pub(crate) file_repeat: Option<LatencySummary>,
+    /// Number of repetitions that failed
+    pub(crate) failures: usize,
+}
+
+impl fmt::Display for RepeatStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Repeated requests: each repeated {} times, {} repetitions failed",
+            self.repeats, self.failures
+        )?;
+        for (label, first, repeat) in [
+            ("PROPFIND", self.directory_first, self.directory_repeat),
+            ("File probe", self.file_first, self.file_repeat),
+        ] {
+            if let Some(lat) = first {
+                writeln!(f, "  {label} first hit: {lat}")?;
+            }
+            if let Some(lat) = repeat {
+                writeln!(f, "  {label} repeat hits: {lat}")?;
+            }
+            if let (Some(first), Some(repeat)) = (first, repeat) {
+                let repeat_median = repeat.median.as_secs_f64();
+                if repeat_median > 0.0 {
+                    writeln!(
+                        f,
+                        "  {label} median first/repeat ratio: {:.2}",
+                        first.median.as_secs_f64() / repeat_median
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Distribution of the modification times of files encountered during a
+/// traversal
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ModificationStats {
+    /// Number of files for which the server reported a modification time
+    pub(crate) files: usize,
+    /// The least recently modified file
+    pub(crate) oldest: Option<DatedFile>,
+    /// The most recently modified file
+    pub(crate) newest: Option<DatedFile>,
+    /// Mapping from lower bounds of power-of-two buckets (0, 1, 2, 4, 8, …)
+    /// of file ages in whole days to the number of files whose ages fall in
+    /// that bucket
+    pub(crate) age_histogram: BTreeMap<u64, usize>,
+    /// Number of files that were not probed because they had not been
+    /// modified since the `--modified-since` cutoff
+    pub(crate) skipped: usize,
+    /// The time relative to which file ages are computed
+    #[serde(skip)]
+    now: OffsetDateTime,
+}
+
+/// A file and its modification time
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct DatedFile {
+    pub(crate) url: Url,
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) modified: OffsetDateTime,
+}
+
+impl ModificationStats {
+    /// Create an empty summary in which ages are computed relative to `now`
+    pub(crate) fn new(now: OffsetDateTime) -> ModificationStats {
+        ModificationStats {
+            files: 0,
+            oldest: None,
+            newest: None,
+            age_histogram: BTreeMap::new(),
+            skipped: 0,
+            now,
+        }
+    }
+
+    /// Tally a file with the given modification time.  Files modified in
+    /// the future are treated as zero days old.
+    pub(crate) fn add(&mut self, url: &Url, modified: OffsetDateTime) {
+        self.files = self.files.saturating_add(1);
+        let days = u64::try_from((self.now - modified).whole_days()).unwrap_or_default();
+        let bucket = if days == 0 {
+            0
+        } else {
+            1 << (u64::BITS - 1 - days.leading_zeros())
+        };
+        *self.age_histogram.entry(bucket).or_insert(0usize) += 1;
+        let dated = || DatedFile {
+            url: url.clone(),
+            modified,
+        };
+        if self.oldest.as_ref().map_or(true, |f| modified < f.modified) {
+            self.oldest = Some(dated());
+        }
+        if self
+            .newest
+            .as_ref()
+            .map_or(true, |f| modified >= f.modified)
+        {
+            self.newest = Some(dated());
+        }
+    }
+}
+
+impl Default for ModificationStats {
+    fn default() -> ModificationStats {
+        ModificationStats::new(OffsetDateTime::now_utc())
+    }
+}
+
+impl fmt::Display for ModificationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Modification times: {} files, {} skipped as unmodified",
+            self.files, self.skipped
+        )?;
+        if let Some(ref oldest) = self.oldest {
+            writeln!(f, "  Oldest: {} ({})", oldest.url, rfc3339(oldest.modified))?;
+        }
+        if let Some(ref newest) = self.newest {
+            writeln!(f, "  Newest: {} ({})", newest.url, rfc3339(newest.modified))?;
+        }
+        for (&lower, &count) in &self.age_histogram {
+            let upper = lower.saturating_mul(2).saturating_sub(1);
+            if upper > lower {
+                writeln!(f, "  {lower}-{upper} days old: {count}")?;
+            } else {
+                writeln!(f, "  {lower} days old: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format a timestamp as an RFC 3339 string
+pub(crate) fn rfc3339(dt: OffsetDateTime) -> String {
+    dt.format(&Rfc3339).unwrap_or_else(|_| dt.to_string())
+}
+
+/// File counts & sizes broken down by MIME type
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ContentTypeStats {
+    /// Tallies for each MIME type, lowercased & with any parameters removed
+    pub(crate) types: BTreeMap<String, ContentTypeTally>,
+    /// Tally for files without a reported content type
+    pub(crate) unknown: ContentTypeTally,
+}
+
+/// The number & total size of the files of a given content type
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ContentTypeTally {
+    pub(crate) files: usize,
+    /// Total size of the files whose sizes are known, if sizes were requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bytes: Option<u64>,
+}
+
+impl ContentTypeTally {
+    fn add(&mut self, size: Option<u64>) {
+        self.files = self.files.saturating_add(1);
+        if let Some(ref mut bytes) = self.bytes {
+            *bytes = bytes.saturating_add(size.unwrap_or_default());
+        }
+    }
+}
+
+impl fmt::Display for ContentTypeTally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} files", self.files)?;
+        if let Some(bytes) = self.bytes {
+            write!(f, ", {bytes} bytes")?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for ContentTypeStats {
+    fn default() -> ContentTypeStats {
+        ContentTypeStats {
+            types: BTreeMap::new(),
+            unknown: ContentTypeTally {
+                files: 0,
+                bytes: Some(0),
+            },
+        }
+    }
+}
+
+impl ContentTypeStats {
+    /// Tally a file with the given content type & size
+    pub(crate) fn add(&mut self, content_type: Option<&str>, size: Option<u64>) {
+        let essence = content_type
+            .and_then(|ct| ct.split(';').next())
+            .map(|ct| ct.trim().to_ascii_lowercase())
+            .filter(|ct| !ct.is_empty());
+        let tally = match essence {
+            Some(ct) => self.types.entry(ct).or_insert(ContentTypeTally {
+                files: 0,
+                bytes: Some(0),
+            }),
+            None => &mut self.unknown,
+        };
+        tally.add(size);
+    }
+
+    /// Remove the byte totals, for use when sizes were not requested
+    pub(crate) fn omit_sizes(&mut self) {
+        for tally in self.types.values_mut() {
+            tally.bytes = None;
+        }
+        self.unknown.bytes = None;
+    }
+}
+
+impl fmt::Display for ContentTypeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let files = self
+            .types
+            .values()
+            .fold(self.unknown.files, |acc, t| acc.saturating_add(t.files));
+        writeln!(
+            f,
+            "Content types: {} types among {files} files",
+            self.types.len()
+        )?;
+        let mut types = self.types.iter().collect::<Vec<_>>();
+        types.sort_by(|(a_ct, a), (b_ct, b)| {
+            b.files
+                .cmp(&a.files)
+                .then(b.bytes.cmp(&a.bytes))
+                .then(a_ct.cmp(b_ct))
+        });
+        for (ct, tally) in types {
+            writeln!(f, "  {ct}: {tally}")?;
+        }
+        if self.unknown.files > 0 {
+            writeln!(f, "  (unknown): {}", self.unknown)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maximum number of duplicate groups to list in human-readable output
+const MAX_DUPLICATE_GROUPS_SHOWN: usize = 10;
+
+/// Groups of files that share an ETag and thus (presumably) have identical
+/// contents
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct DuplicateStats {
+    /// Number of files for which the server reported an ETag
+    pub(crate) files_with_etags: usize,
+    /// Groups of two or more files sharing an ETag, in descending order of
+    /// potential space savings
+    pub(crate) groups: Vec<DuplicateGroup>,
+    /// Total number of files in excess of one per group
+    pub(crate) redundant_files: usize,
+    /// Total size of the files in excess of one per group, for groups whose
+    /// sizes are known
+    pub(crate) reclaimable_bytes: u64,
+}
+
+/// A set of files sharing an ETag
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct DuplicateGroup {
+    pub(crate) etag: String,
+    /// The size of each file in the group, if reported
+    pub(crate) size: Option<u64>,
+    pub(crate) urls: Vec<Url>,
+}
+
+impl DuplicateGroup {
+    /// The number of bytes that would be saved by keeping only one file in
+    /// the group
+    pub(crate) fn savings(&self) -> u64 {
+        let copies = u64::try_from(self.urls.len().saturating_sub(1)).unwrap_or(u64::MAX);
+        self.size.unwrap_or_default().saturating_mul(copies)
+    }
+}
+
+impl DuplicateStats {
+    /// Construct from a mapping from ETags to the URLs & sizes of the files
+    /// with those ETags
+    pub(crate) fn new(index: BTreeMap<String, Vec<(Url, Option<u64>)>>) -> DuplicateStats {
+        let mut stats = DuplicateStats::default();
+        for (etag, files) in index {
+            stats.files_with_etags = stats.files_with_etags.saturating_add(files.len());
+            if files.len() < 2 {
+                continue;
+            }
+            let size = files.iter().find_map(|&(_, size)| size);
+            let mut urls = files.into_iter().map(|(url, _)| url).collect::<Vec<_>>();
+            urls.sort_unstable();
+            let group = DuplicateGroup { etag, size, urls };
+            stats.redundant_files = stats
+                .redundant_files
+                .saturating_add(group.urls.len().saturating_sub(1));
+            stats.reclaimable_bytes = stats.reclaimable_bytes.saturating_add(group.savings());
+            stats.groups.push(group);
+        }
+        stats
+            .groups
+            .sort_by(|a, b| b.savings().cmp(&a.savings()).then(a.etag.cmp(&b.etag)));
+        stats
+    }
+}
+
+impl fmt::Display for DuplicateStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Duplicate files: {} groups among {} files with ETags, {} redundant copies, {} bytes reclaimable",
+            self.groups.len(),
+            self.files_with_etags,
+            self.redundant_files,
+            self.reclaimable_bytes
+        )?;
+        for group in self.groups.iter().take(MAX_DUPLICATE_GROUPS_SHOWN) {
+            match group.size {
+                Some(size) => writeln!(
+                    f,
+                    "  {} ({} files of {size} bytes):",
+                    group.etag,
+                    group.urls.len()
+                )?,
+                None => writeln!(f, "  {} ({} files):", group.etag, group.urls.len())?,
+            }
+            for url in &group.urls {
+                writeln!(f, "    {url}")?;
+            }
+        }
+        if let Some(more) = self.groups.len().checked_sub(MAX_DUPLICATE_GROUPS_SHOWN) {
+            if more > 0 {
+                writeln!(f, "  ... and {more} more groups")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts of HTTP status codes, both by exact code and by class (`2xx`,
+/// `4xx`, etc.)
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct StatusCounts {
+    pub(crate) by_class: BTreeMap<String, usize>,
+    pub(crate) by_code: BTreeMap<u16, usize>,
+}
+
+impl StatusCounts {
+    pub(crate) fn add(&mut self, code: u16) {
+        let class = self
+            .by_class
+            .entry(format!("{}xx", code / 100))
+            .or_default();
+        *class = class.saturating_add(1);
+        let exact = self.by_code.entry(code).or_default();
+        *exact = exact.saturating_add(1);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.by_code.is_empty()
+    }
+}
+
+impl fmt::Display for StatusCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (class, qty) in &self.by_class {
+            if !std::mem::replace(&mut first, false) {
+                write!(f, ", ")?;
+            }
+            write!(f, "{class}: {qty}")?;
+        }
+        write!(f, " (")?;
+        first = true;
+        for (code, qty) in &self.by_code {
+            if !std::mem::replace(&mut first, false) {
+                write!(f, ", ")?;
+            }
+            write!(f, "{code}: {qty}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Distribution of the HTTP status codes seen during a traversal
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct StatusDistribution {
+    /// Status codes of HTTP responses
+    pub(crate) responses: StatusCounts,
+    /// Status codes of `<propstat>` elements within multistatus responses
+    pub(crate) propstats: StatusCounts,
+}
+
+/// Maximum number of example failures to record for each category of error
+const MAX_ERROR_EXAMPLES: usize = 5;
+
+/// Counts of request failures by category, along with a few examples of each
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct ErrorSummary(BTreeMap<ErrorKind, ErrorClassSummary>);
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct ErrorClassSummary {
+    pub(crate) count: usize,
+    pub(crate) examples: Vec<ErrorExample>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ErrorExample {
+    pub(crate) url: Url,
+    pub(crate) message: String,
+}
+
+impl ErrorSummary {
+    pub(crate) fn add(&mut self, kind: ErrorKind, url: &Url, message: String) {
+        let class = self.0.entry(kind).or_default();
+        class.count = class.count.saturating_add(1);
+        if class.examples.len() < MAX_ERROR_EXAMPLES {
+            class.examples.push(ErrorExample {
+                url: url.clone(),
+                message,
+            });
+        }
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.0
+            .values()
+            .fold(0usize, |acc, c| acc.saturating_add(c.count))
+    }
+
+    /// The number of failures in the given category
+    pub(crate) fn count(&self, kind: ErrorKind) -> usize {
+        self.0.get(&kind).map_or(0, |c| c.count)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for ErrorSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Errors: {}", self.total())?;
+        for (kind, class) in &self.0 {
+            writeln!(f, "  {kind:?}: {}", class.count)?;
+            for ex in &class.examples {
+                writeln!(f, "    {}: {}", ex.url, ex.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The distinct values of the server-identifying headers (`Server`, `Via`,
+/// and `X-…-Backend`) seen in responses, with the number of responses in which
+/// each value occurred, keyed by lowercased header name
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct ServerFingerprints(BTreeMap<String, BTreeMap<String, usize>>);
+
+impl ServerFingerprints {
+    pub(crate) fn add(&mut self, headers: &ServerHeaders) {
+        for (name, value) in headers {
+            let count = self
+                .0
+                .entry(name.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_default();
+            *count = count.saturating_add(1);
+        }
+    }
+
+    /// Add the counts in `other` to those in `self`
+    pub(crate) fn merge(&mut self, other: &ServerFingerprints) {
+        for (name, values) in &other.0 {
+            let ours = self.0.entry(name.clone()).or_default();
+            for (value, &n) in values {
+                let count = ours.entry(value.clone()).or_default();
+                *count = count.saturating_add(n);
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for ServerFingerprints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Server headers:")?;
+        for (name, values) in &self.0 {
+            for (value, count) in values {
+                writeln!(f, "  {name}: {value} ({count} responses)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts of response warnings by category, along with a few examples of each
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub(crate) struct WarningSummary(BTreeMap<WarningKind, ErrorClassSummary>);
+
+impl WarningSummary {
+    pub(crate) fn add(&mut self, kind: WarningKind, url: &Url, message: String) {
+        let class = self.0.entry(kind).or_default();
+        class.count = class.count.saturating_add(1);
+        if class.examples.len() < MAX_ERROR_EXAMPLES {
+            class.examples.push(ErrorExample {
+                url: url.clone(),
+                message,
+            });
+        }
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.0
+            .values()
+            .fold(0usize, |acc, c| acc.saturating_add(c.count))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for WarningSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Warnings: {}", self.total())?;
+        for (kind, class) in &self.0 {
+            writeln!(f, "  {kind:?}: {}", class.count)?;
+            for ex in &class.examples {
+                writeln!(f, "    {}: {}", ex.url, ex.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the rate at which `bytes` bytes were transferred in `elapsed`, in
+/// MiB/s
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn mib_per_sec(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        (bytes as f64) / secs / 1_048_576.0
+    } else {
+        0.0
+    }
+}
+
+/// Summary statistics for a collection of request latencies
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct LatencySummary {
+    pub(crate) count: usize,
+    pub(crate) mean: Duration,
+    pub(crate) median: Duration,
+    pub(crate) p95: Duration,
+    pub(crate) max: Duration,
+}
+
+impl LatencySummary {
+    /// Summarize the given latencies.  Returns `None` if `times` is empty.
+    pub(crate) fn new(times: &[Duration]) -> Option<LatencySummary> {
+        let mut sorted = times.to_vec();
+        sorted.sort_unstable();
+        let count = sorted.len();
+        let max = *sorted.last()?;
+        let total = sorted.iter().sum::<Duration>();
+        let mean = total / u32::try_from(count).unwrap_or(u32::MAX);
+        Some(LatencySummary {
+            count,
+            mean,
+            median: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            max,
+        })
+    }
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests, mean {:?}, median {:?}, p95 {:?}, max {:?}",
+            self.count, self.mean, self.median, self.p95, self.max
+        )
+    }
+}
+
+/// Periodic samples of the number of requests in flight during a traversal
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ConcurrencyTimeline {
+    /// The number of worker slots available to the traversal, which can
+    /// exceed the number of workers under `--adaptive-workers` or with
+    /// separate pools
+    pub(crate) workers: usize,
+    /// Mean number of requests in flight across all samples
+    pub(crate) mean_active: Option<f64>,
+    /// `mean_active` as a fraction of `workers`
+    pub(crate) utilization: Option<f64>,
+    /// Fraction of samples at which fewer than `workers` requests were in
+    /// flight
+    pub(crate) underutilized: Option<f64>,
+    pub(crate) samples: Vec<ConcurrencySample>,
+}
+
+impl ConcurrencyTimeline {
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn new(workers: usize, samples: Vec<ConcurrencySample>) -> ConcurrencyTimeline {
+        let (mean_active, utilization, underutilized) = if samples.is_empty() {
+            (None, None, None)
+        } else {
+            let n = samples.len() as f64;
+            let mean = samples.iter().map(|s| s.active as f64).sum::<f64>() / n;
+            let under = samples.iter().filter(|s| s.active < workers).count() as f64 / n;
+            let utilization = (workers > 0).then(|| mean / (workers as f64));
+            (Some(mean), utilization, Some(under))
+        };
+        ConcurrencyTimeline {
+            workers,
+            mean_active,
+            utilization,
+            underutilized,
+            samples,
+        }
+    }
+}
+
+impl fmt::Display for ConcurrencyTimeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.mean_active, self.utilization, self.underutilized) {
+            (Some(mean), Some(util), Some(under)) => writeln!(
+                f,
+                "Concurrency: mean {mean:.2} of {} requests in flight ({:.1}%); below {} in {:.1}% of samples",
+                self.workers,
+                util * 100.0,
+                self.workers,
+                under * 100.0
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The number of requests in flight at a point during a traversal
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ConcurrencySample {
+    /// Time since the start of the traversal
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) elapsed: Duration,
+    pub(crate) active: usize,
+}
+
+/// A collection of request latencies, either retained in full or, to bound
+/// memory use, aggregated into a histogram as they're added
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Latencies {
+    Exact(Vec<Duration>),
+    Approximate(LatencyHistogram),
+}
+
+impl Latencies {
+    pub(crate) fn new(low_memory: bool) -> Latencies {
+        if low_memory {
+            Latencies::Approximate(LatencyHistogram::default())
+        } else {
+            Latencies::Exact(Vec::new())
+        }
+    }
+
+    pub(crate) fn push(&mut self, d: Duration) {
+        match self {
+            Latencies::Exact(times) => times.push(d),
+            Latencies::Approximate(hist) => hist.record(d),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Latencies::Exact(times) => times.len(),
+            Latencies::Approximate(hist) => hist.count,
+        }
+    }
+
+    pub(crate) fn summary(&self) -> Option<LatencySummary> {
+        match self {
+            Latencies::Exact(times) => LatencySummary::new(times),
+            Latencies::Approximate(hist) => hist.summary(),
+        }
+    }
+
+    /// Return the sum of the latencies
+    pub(crate) fn total(&self) -> Duration {
+        match self {
+            Latencies::Exact(times) => times
+                .iter()
+                .fold(Duration::ZERO, |a, &d| a.saturating_add(d)),
+            Latencies::Approximate(hist) => hist.total,
+        }
+    }
+
+    /// Return the latency at index `n` when the latencies are sorted in
+    /// ascending order.  For aggregated latencies, this is the midpoint of the
+    /// histogram bucket containing it.
+    pub(crate) fn nth_smallest(&self, n: usize) -> Option<Duration> {
+        match self {
+            Latencies::Exact(times) => {
+                let mut sorted = times.clone();
+                sorted.sort_unstable();
+                sorted.get(n).copied()
+            }
+            Latencies::Approximate(hist) => hist.nth_smallest(n),
+        }
+    }
+
+    /// Return the individual latencies, or an empty list if they were not
+    /// retained
+    pub(crate) fn into_times(self) -> Vec<Duration> {
+        match self {
+            Latencies::Exact(times) => times,
+            Latencies::Approximate(_) => Vec::new(),
+        }
+    }
+}
+
+impl Default for Latencies {
+    fn default() -> Latencies {
+        Latencies::Exact(Vec::new())
+    }
+}
+
+/// Number of significant bits of a latency (in nanoseconds) retained by
+/// `LatencyHistogram`, giving a bucket width of at most 1/64 of the bucket's
+/// lower bound
+const HISTOGRAM_SIGNIFICANT_BITS: u32 = 7;
+
+/// A histogram of request latencies with logarithmically-sized buckets,
+/// allowing approximate percentiles to be computed in bounded memory
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct LatencyHistogram {
+    count: usize,
+    total: Duration,
+    max: Duration,
+    /// Mapping from bucket lower bounds (in nanoseconds) to the number of
+    /// latencies in each bucket
+    buckets: BTreeMap<u64, usize>,
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&mut self, d: Duration) {
+        self.count = self.count.saturating_add(1);
+        self.total = self.total.saturating_add(d);
+        self.max = self.max.max(d);
+        let nanos = u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+        let shift = bucket_shift(nanos);
+        let counter = self.buckets.entry((nanos >> shift) << shift).or_default();
+        *counter = counter.saturating_add(1);
+    }
+
+    /// Return the midpoint of the bucket containing the latency at index `n`
+    /// in ascending order
+    fn nth_smallest(&self, n: usize) -> Option<Duration> {
+        let mut seen = 0usize;
+        for (&lower, &count) in &self.buckets {
+            seen = seen.saturating_add(count);
+            if seen > n {
+                let midpoint = lower.saturating_add((1u64 << bucket_shift(lower)) >> 1);
+                return Some(Duration::from_nanos(midpoint).min(self.max));
+            }
+        }
+        None
+    }
+
+    /// Summarize the recorded latencies.  The mean & maximum are exact, while
+    /// the median & 95th percentile are the midpoints of the buckets
+    /// containing them.  Returns `None` if no latencies were recorded.
+    pub(crate) fn summary(&self) -> Option<LatencySummary> {
+        if self.count == 0 {
+            return None;
+        }
+        let percentile = |p: usize| {
+            let rank = (p.saturating_mul(self.count)).div_ceil(100).max(1);
+            self.nth_smallest(rank - 1).unwrap_or(self.max)
+        };
+        Some(LatencySummary {
+            count: self.count,
+            mean: self.total / u32::try_from(self.count).unwrap_or(u32::MAX),
+            median: percentile(50),
+            p95: percentile(95),
+            max: self.max,
+        })
+    }
+}
+
+/// Return the number of low bits of `nanos` to discard when bucketing it
+fn bucket_shift(nanos: u64) -> u32 {
+    (u64::BITS - nanos.leading_zeros()).saturating_sub(HISTOGRAM_SIGNIFICANT_BITS)
+}
+
+/// Return the `p`-th percentile of a nonempty sorted slice using the
+/// nearest-rank method
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (p.saturating_mul(sorted.len())).div_ceil(100).max(1);
+    sorted[rank.saturating_sub(1).min(sorted.len().saturating_sub(1))]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+    use time::macros::datetime;
+
+    #[test]
+    fn test_time_ci_percent() {
+        let mut samples = WorkerSamples::new();
+        for secs in [9, 10, 11] {
+            samples.push(
+                1,
+                Sample {
+                    requests: 100,
+                    elapsed: Duration::from_secs(secs),
+                    utilization: None,
+                },
+            );
+        }
+        samples.push(
+            2,
+            Sample {
+                requests: 100,
+                elapsed: Duration::from_secs(5),
+                utilization: None,
+            },
+        );
+        // t(0.975, 2) * 1 / sqrt(3) / 10 * 100
+        let ci = samples.time_ci_percent(1).unwrap();
+        assert!((ci - 24.841).abs() < 1e-3, "ci = {ci}");
+        assert_eq!(samples.time_ci_percent(2), None);
+        assert_eq!(samples.time_ci_percent(3), None);
+    }
+
+    #[rstest]
+    #[case(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0], 0.0809)]
+    #[case(&[1.0, 2.0, 3.0, 4.0, 5.0], &[6.0, 7.0, 8.0, 9.0, 10.0], 0.0122)]
+    #[case(&[1.0, 3.0, 5.0], &[2.0, 4.0, 6.0], 0.6625)]
+    #[case(&[2.0, 2.0], &[2.0, 2.0, 2.0], 1.0)]
+    #[case(&[1.0, 2.0, 2.0, 3.0], &[2.0, 4.0, 5.0, 5.0], 0.1016)]
+    fn test_mann_whitney_u(#[case] xs: &[f64], #[case] ys: &[f64], #[case] p: f64) {
+        let r = mann_whitney_u(xs, ys).unwrap();
+        assert!((r - p).abs() < 1e-3, "p = {r}");
+        let r = mann_whitney_u(ys, xs).unwrap();
+        assert!((r - p).abs() < 1e-3, "p = {r}");
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut samples = WorkerSamples::new();
+        assert_eq!(
+            samples.push(
+                5,
+                Sample {
+                    requests: 100,
+                    elapsed: Duration::from_secs(4),
+                    utilization: Some(0.5),
+                }
+            ),
+            1
+        );
+        assert_eq!(
+            samples.push(
+                1,
+                Sample {
+                    requests: 100,
+                    elapsed: Duration::from_secs(10),
+                    utilization: None,
+                }
+            ),
+            1
+        );
+        assert_eq!(
+            samples.push(
+                5,
+                Sample {
+                    requests: 100,
+                    elapsed: Duration::from_secs(2),
+                    utilization: Some(0.7),
+                }
+            ),
+            2
+        );
+        let summary = samples.summarize();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].workers, 1);
+        assert_eq!(summary[0].samples, 1);
+        assert!(summary[0].time_stddev.is_nan());
+        assert_eq!(summary[1].workers, 5);
+        assert_eq!(summary[1].samples, 2);
+        assert!((summary[1].time_mean - 3.0).abs() < 1e-9);
+        assert!((summary[1].time_median - 2.0).abs() < 1e-9);
+        assert!((summary[1].time_p95 - 4.0).abs() < 1e-9);
+        assert!((summary[1].throughput_mean - 37.5).abs() < 1e-9);
+        assert_eq!(summary[0].time_ci, None);
+        // t(0.975, 1) * sqrt(2) / sqrt(2)
+        assert!((summary[1].time_ci.unwrap() - 12.706).abs() < 1e-3);
+        assert_eq!(summary[0].utilization_mean, None);
+        assert!((summary[1].utilization_mean.unwrap() - 0.6).abs() < 1e-9);
+        assert_eq!(summary[0].efficiency, Some(1.0));
+        // 37.5 / 10 requests per second with 5 times as many workers
+        assert!((summary[1].efficiency.unwrap() - 0.75).abs() < 1e-9);
+    }
+
+    #[rstest]
+    #[case(&[(1, 10.0), (2, 19.0), (4, 36.0), (8, 40.0), (16, 80.0)], 0.25, Some(Knee { workers: 4, next: Some((8, 0.111_111)) }))]
+    #[case(&[(1, 10.0), (2, 20.0), (4, 40.0)], 0.25, Some(Knee { workers: 4, next: None }))]
+    #[case(&[(1, 10.0), (2, 8.0)], 0.25, Some(Knee { workers: 1, next: Some((2, -0.2)) }))]
+    #[case(&[(1, 10.0), (2, 14.0)], 0.5, Some(Knee { workers: 1, next: Some((2, 0.4)) }))]
+    #[case(&[(4, 10.0)], 0.25, None)]
+    fn test_find_knee(
+        #[case] points: &[(usize, f64)],
+        #[case] threshold: f64,
+        #[case] knee: Option<Knee>,
+    ) {
+        let summaries = points
+            .iter()
+            .map(|&(workers, throughput_mean)| WorkerSummary {
+                workers,
+                samples: 1,
+                time_mean: 1.0,
+                time_stddev: f64::NAN,
+                time_median: 1.0,
+                time_p95: 1.0,
+                time_ci: None,
+                throughput_mean,
+                throughput_stddev: f64::NAN,
+                utilization_mean: None,
+                efficiency: None,
+            })
+            .collect::<Vec<_>>();
+        let found = Knee::find(&summaries, threshold);
+        assert_eq!(found.map(|k| k.workers), knee.map(|k| k.workers));
+        let scaling = |k: Option<Knee>| k.and_then(|k| k.next);
+        match (scaling(found), scaling(knee)) {
+            (Some((w1, s1)), Some((w2, s2))) => {
+                assert_eq!(w1, w2);
+                assert!((s1 - s2).abs() < 1e-6, "scaling = {s1}");
+            }
+            (a, b) => assert_eq!(a, b),
+        }
+    }
+
+    #[test]
+    fn test_csv_fields() {
+        let row = WorkerRow {
+            summary: WorkerSummary {
+                workers: 4,
+                samples: 3,
+                time_mean: 2.5,
+                time_stddev: 0.5,
+                time_median: 2.0,
+                time_p95: 3.0,
+                time_ci: Some(1.25),
+                throughput_mean: 40.0,
+                throughput_stddev: 8.0,
+                utilization_mean: None,
+                efficiency: Some(0.8),
+            },
+            p_value: Some(0.01),
+        };
+        let fields =
+            CsvFields::<WorkerRow>::select(&["workers", "median", "p95", "throughput"]).unwrap();
+        assert_eq!(
+            fields.header(),
+            "workers,time_median,time_p95,throughput_mean"
+        );
+        assert_eq!(fields.row(&row), "4,2,3,40");
+        assert!(fields.contains("time_p95"));
+        assert!(!fields.contains("p_value"));
+        let fields =
+            CsvFields::<WorkerRow>::select(&["utilization", "p_value", "significant"]).unwrap();
+        assert_eq!(fields.row(&row), ",0.01,true");
+        let fields = CsvFields::<WorkerRow>::select(&["ci", "efficiency"]).unwrap();
+        assert_eq!(fields.header(), "time_ci,efficiency");
+        assert_eq!(fields.row(&row), "1.25,0.8");
+        let fields = CsvFields::<WorkerRow>::select(&[
+            "time_stddev_lower",
+            "time_stddev_upper",
+            "time_ci_lower",
+            "time_ci_upper",
+            "throughput_stddev_lower",
+            "throughput_stddev_upper",
+        ])
+        .unwrap();
+        assert_eq!(fields.row(&row), "2,3,1.25,3.75,32,48");
+        let single = WorkerRow {
+            summary: WorkerSummary {
+                samples: 1,
+                time_stddev: f64::NAN,
+                time_ci: None,
+                throughput_stddev: f64::NAN,
+                ..row.summary
+            },
+            p_value: None,
+        };
+        assert_eq!(fields.row(&single), ",,,,,");
+        assert!(CsvFields::<WorkerRow>::select(&["workers", "bytes"]).is_err());
+    }
+
+    #[test]
+    fn test_fanout_stats() {
+        let mut stats = FanoutStats::default();
+        for n in [0, 1, 2, 3, 4, 7, 8, 0] {
+            stats.add(n);
+        }
+        assert_eq!(stats.collections, 8);
+        assert_eq!(stats.max, 8);
+        assert!((stats.mean - 3.125).abs() < 1e-9);
+        assert_eq!(
+            stats.histogram,
+            BTreeMap::from([(0, 2), (1, 1), (2, 2), (4, 2), (8, 1)])
+        );
+        assert_eq!(
+            stats.to_string(),
+            indoc! {"
+                Directory fan-out: 8 collections, mean 3.12 children, max 8
+                  0 children: 2
+                  1 children: 1
+                  2-3 children: 2
+                  4-7 children: 2
+                  8-15 children: 1
+            "}
+        );
+    }
+
+    #[test]
+    fn test_response_size_stats() {
+        let mut sizes = ResponseSizes::default();
+        for n in [1500, 0, 700, 2048, 900] {
+            sizes.add(n);
+        }
+        let stats = sizes.stats();
+        assert_eq!(stats.responses, 5);
+        assert_eq!(stats.total_bytes, 5148);
+        assert_eq!(stats.median, 900);
+        assert_eq!(stats.max, 2048);
+        assert!((stats.mean - 1029.6).abs() < 1e-9);
+        assert_eq!(
+            stats.to_string(),
+            indoc! {"
+                PROPFIND response sizes: 5 responses totalling 5148 bytes, mean 1029.6 bytes, median 900 bytes, max 2048 bytes
+                  0 bytes: 1
+                  512-1023 bytes: 2
+                  1024-2047 bytes: 1
+                  2048-4095 bytes: 1
+            "}
+        );
+    }
+
+    #[test]
+    fn test_fairness_stats() {
+        let secs = Duration::from_secs;
+        let even = FairnessStats::new([(3, secs(2)), (3, secs(2))]).unwrap();
+        assert_eq!((even.min_tasks, even.max_tasks), (3, 3));
+        assert_eq!(even.mean_busy, secs(2));
+        assert!((even.fairness_index - 1.0).abs() < 1e-9);
+        let skewed = FairnessStats::new([(4, secs(3)), (1, secs(1)), (0, Duration::ZERO)]).unwrap();
+        assert_eq!((skewed.min_tasks, skewed.max_tasks), (0, 4));
+        assert_eq!(skewed.min_busy, Duration::ZERO);
+        assert_eq!(skewed.max_busy, secs(3));
+        // (3 + 1)^2 / (3 * (9 + 1))
+        assert!((skewed.fairness_index - 16.0 / 30.0).abs() < 1e-9);
+        assert_eq!(FairnessStats::new([(0, Duration::ZERO)]), None);
+        assert_eq!(FairnessStats::new([]), None);
+    }
+
+    #[test]
+    fn test_pool_stats() {
+        let secs = Duration::from_secs;
+        let pool = PoolStats::new(
+            4,
+            3,
+            [(5, secs(4)), (3, secs(2)), (0, secs(0)), (2, secs(2))],
+            secs(4),
+        );
+        assert_eq!(
+            (pool.workers, pool.tasks, pool.peak_concurrency),
+            (4, 10, 3)
+        );
+        assert_eq!(pool.mean_concurrency, Some(2.0));
+        assert_eq!(pool.utilization, Some(0.5));
+        assert_eq!(
+            pool.to_string(),
+            "10 tasks; peak 3, mean 2.00 of 4 workers (50.0% utilization); Jain's index 0.667"
+        );
+        let idle = PoolStats::new(2, 0, [(0, secs(0)), (0, secs(0))], Duration::ZERO);
+        assert_eq!((idle.mean_concurrency, idle.utilization), (None, None));
+        assert_eq!(idle.fairness, None);
+    }
+
+    #[test]
+    fn test_pipeline_stats() {
+        let secs = Duration::from_secs;
+        let stats = PipelineStats::new(20, secs(10), secs(30), secs(5)).unwrap();
+        assert!((stats.throughput - 4.0).abs() < 1e-9);
+        assert_eq!(stats.mean_queue_wait, Duration::from_millis(500));
+        assert_eq!(stats.mean_service_time, Duration::from_millis(1500));
+        assert_eq!(stats.mean_sojourn_time, secs(2));
+        // L = λW: 4 tasks/s × 2 s
+        assert!((stats.mean_in_system - 8.0).abs() < 1e-9);
+        assert!((stats.mean_queued - 2.0).abs() < 1e-9);
+        assert!((stats.mean_in_service - 6.0).abs() < 1e-9);
+        assert_eq!(PipelineStats::new(0, secs(1), secs(1), secs(1)), None);
+        assert_eq!(
+            PipelineStats::new(3, secs(1), secs(1), Duration::ZERO),
+            None
+        );
+    }
+
+    #[test]
+    fn test_method_comparison() {
+        let timing = |ttfb, elapsed| ProbeTiming {
+            status: 200,
+            ttfb: Duration::from_millis(ttfb),
+            elapsed: Duration::from_millis(elapsed),
+        };
+        let mut pairs = MethodPairs::new(false);
+        pairs.add(timing(10, 12), timing(15, 40));
+        pairs.add(timing(20, 21), timing(18, 30));
+        pairs.add(timing(10, 11), timing(30, 35));
+        let stats = pairs.comparison();
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.get_slower, 2);
+        assert_eq!(
+            stats.head_ttfb.map(|s| s.median),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(
+            stats.get_latency.map(|s| s.max),
+            Some(Duration::from_millis(40))
+        );
+        let mean = stats.mean_ttfb_difference_ms.unwrap();
+        assert!((mean - 23.0 / 3.0).abs() < 1e-9);
+        let median = stats.median_ttfb_difference_ms.unwrap();
+        assert!((median - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_method_comparison_low_memory() {
+        let timing = |ttfb| ProbeTiming {
+            status: 200,
+            ttfb: Duration::from_millis(ttfb),
+            elapsed: Duration::from_millis(ttfb),
+        };
+        let mut pairs = MethodPairs::new(true);
+        for (head, get) in [(10, 15), (20, 18), (10, 30), (40, 10), (25, 25)] {
+            pairs.add(timing(head), timing(get));
+        }
+        let stats = pairs.comparison();
+        assert_eq!(stats.files, 5);
+        assert_eq!(stats.get_slower, 2);
+        let mean = stats.mean_ttfb_difference_ms.unwrap();
+        assert!((mean - (-7.0 / 5.0)).abs() < 1e-9, "mean = {mean}");
+        // Differences are -30, -2, 0, 5, & 20 ms; the median is 0 ms
+        let median = stats.median_ttfb_difference_ms.unwrap();
+        assert!(median.abs() < 1e-9, "median = {median}");
+        let empty = MethodPairs::new(true).comparison();
+        assert_eq!(empty.files, 0);
+        assert_eq!(empty.mean_ttfb_difference_ms, None);
+        assert_eq!(empty.median_ttfb_difference_ms, None);
+    }
+
+    #[test]
+    fn test_response_sizes_aggregated() {
+        let mut sizes = ResponseSizes::default();
+        for n in 0..100_000 {
+            sizes.add(n);
+        }
+        assert!(
+            sizes.buckets.len() < 1000,
+            "{} buckets",
+            sizes.buckets.len()
+        );
+        let stats = sizes.stats();
+        assert_eq!(stats.responses, 100_000);
+        assert_eq!(stats.max, 99_999);
+        assert_eq!(stats.total_bytes, 4_999_950_000);
+        assert!(
+            stats.median.abs_diff(50_000) < 800,
+            "median = {}",
+            stats.median
+        );
+        assert_eq!(stats.histogram.values().sum::<usize>(), 100_000);
+        assert_eq!(stats.histogram.get(&65536), Some(&(100_000 - 65536)));
+    }
+
+    #[test]
+    fn test_redirect_chain_stats() {
+        let mut stats = RedirectChainStats::default();
+        for (length, truncated) in [(0, false), (1, false), (1, false), (2, false), (5, true)] {
+            stats.add(length, truncated);
+        }
+        stats.hop_latency =
+            LatencySummary::new(&[Duration::from_millis(10), Duration::from_millis(20)]);
+        assert_eq!(stats.files, 5);
+        assert_eq!(stats.max_length, 5);
+        assert_eq!(
+            stats.histogram,
+            BTreeMap::from([(0, 1), (1, 2), (2, 1), (5, 1)])
+        );
+        assert_eq!(
+            stats.to_string(),
+            indoc! {"
+                Redirect chains: 5 files, max 5 redirects, 1 truncated
+                  0 redirects: 1
+                  1 redirects: 2
+                  2 redirects: 1
+                  5 redirects: 1
+                  Hop latency: 2 requests, mean 15ms, median 10ms, p95 20ms, max 20ms
+            "}
+        );
+    }
+
+    #[test]
+    fn test_duplicate_stats() {
+        let url = |path: &str| Url::parse(&format!("https://example.com/{path}")).unwrap();
+        let index = BTreeMap::from([
+            (
+                "\"aaa\"".to_owned(),
+                vec![(url("x/a.txt"), Some(10)), (url("a.txt"), Some(10))],
+            ),
+            ("\"bbb\"".to_owned(), vec![(url("b.txt"), Some(99))]),
+            (
+                "\"ccc\"".to_owned(),
+                vec![(url("c1"), None), (url("c2"), Some(7)), (url("c3"), None)],
+            ),
+            (
+                "\"ddd\"".to_owned(),
+                vec![(url("d1"), None), (url("d2"), None)],
+            ),
+        ]);
+        let stats = DuplicateStats::new(index);
+        assert_eq!(stats.files_with_etags, 8);
+        assert_eq!(stats.redundant_files, 4);
+        assert_eq!(stats.reclaimable_bytes, 24);
+        assert_eq!(
+            stats.to_string(),
+            indoc! {r#"
+                Duplicate files: 3 groups among 8 files with ETags, 4 redundant copies, 24 bytes reclaimable
+                  "ccc" (3 files of 7 bytes):
+                    https://example.com/c1
+                    https://example.com/c2
+                    https://example.com/c3
+                  "aaa" (2 files of 10 bytes):
+                    https://example.com/a.txt
+                    https://example.com/x/a.txt
+                  "ddd" (2 files):
+                    https://example.com/d1
+                    https://example.com/d2
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_repeat_stats() {
+        let ms = Duration::from_millis;
+        let stats = RepeatStats {
+            repeats: 2,
+            directory_first: LatencySummary::new(&[ms(30), ms(50)]),
+            directory_repeat: LatencySummary::new(&[ms(10), ms(10), ms(20)]),
+            file_first: LatencySummary::new(&[ms(8)]),
+            file_repeat: None,
+            failures: 1,
+        };
+        assert_eq!(
+            stats.to_string(),
+            indoc! {"
+                Repeated requests: each repeated 2 times, 1 repetitions failed
+                  PROPFIND first hit: 2 requests, mean 40ms, median 30ms, p95 50ms, max 50ms
+                  PROPFIND repeat hits: 3 requests, mean 13.333333ms, median 10ms, p95 20ms, max 20ms
+                  PROPFIND median first/repeat ratio: 3.00
+                  File probe first hit: 1 requests, mean 8ms, median 8ms, p95 8ms, max 8ms
+            "}
+        );
+    }
+
+    #[test]
+    fn test_modification_stats() {
+        let url = |path: &str| Url::parse(&format!("https://example.com/{path}")).unwrap();
+        let mtimes = [
+            (url("a.txt"), datetime!(2024-01-10 12:00 UTC)),
+            (url("b.txt"), datetime!(2023-12-01 00:00 UTC)),
+            (url("c.txt"), datetime!(2024-01-08 00:00 UTC)),
+            (url("d.txt"), datetime!(2024-01-20 00:00 UTC)),
+        ];
+        let mut stats = ModificationStats::new(datetime!(2024-01-10 18:00 UTC));
+        for (url, modified) in &mtimes {
+            stats.add(url, *modified);
+        }
+        stats.skipped = 2;
+        assert_eq!(stats.files, 4);
+        assert_eq!(
+            stats.to_string(),
+            indoc! {"
+                Modification times: 4 files, 2 skipped as unmodified
+                  Oldest: https://example.com/b.txt (2023-12-01T00:00:00Z)
+                  Newest: https://example.com/d.txt (2024-01-20T00:00:00Z)
+                  0 days old: 2
+                  2-3 days old: 1
+                  32-63 days old: 1
+            "}
+        );
+    }
+
+    #[test]
+    fn test_content_type_stats() {
+        let files = [
+            (Some("text/plain; charset=utf-8"), Some(10)),
+            (Some("image/png"), Some(1000)),
+            (Some("Text/Plain"), None),
+            (None, Some(5)),
+            (Some("image/jpeg"), Some(200)),
+            (Some("image/jpeg"), Some(300)),
+        ];
+        let mut stats = ContentTypeStats::default();
+        for (ct, size) in files {
+            stats.add(ct, size);
+        }
+        assert_eq!(
+            stats.to_string(),
+            indoc! {"
+                Content types: 3 types among 6 files
+                  image/jpeg: 2 files, 500 bytes
+                  text/plain: 2 files, 10 bytes
+                  image/png: 1 files, 1000 bytes
+                  (unknown): 1 files, 5 bytes
+            "}
+        );
+        stats.omit_sizes();
+        assert_eq!(
+            stats.types.get("text/plain"),
+            Some(&ContentTypeTally {
+                files: 2,
+                bytes: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let times = (1..=200)
+            .map(|i| Duration::from_micros(i * 137))
+            .collect::<Vec<_>>();
+        let mut hist = LatencyHistogram::default();
+        for &d in &times {
+            hist.record(d);
+        }
+        let exact = LatencySummary::new(&times).unwrap();
+        let approx = hist.summary().unwrap();
+        assert_eq!(approx.count, exact.count);
+        assert_eq!(approx.mean, exact.mean);
+        assert_eq!(approx.max, exact.max);
+        for (a, e) in [(approx.median, exact.median), (approx.p95, exact.p95)] {
+            let error = a.as_secs_f64() / e.as_secs_f64() - 1.0;
+            assert!(error.abs() < 1.0 / 64.0, "{a:?} vs. {e:?}");
+        }
+        assert_eq!(LatencyHistogram::default().summary(), None);
+    }
+
+    #[test]
+    fn test_concurrency_timeline() {
+        let samples = [1, 4, 4, 3]
+            .into_iter()
+            .enumerate()
+            .map(|(i, active)| ConcurrencySample {
+                elapsed: Duration::from_millis(250 * u64::try_from(i).unwrap()),
+                active,
+            })
+            .collect();
+        let timeline = ConcurrencyTimeline::new(4, samples);
+        assert_eq!(timeline.mean_active, Some(3.0));
+        assert_eq!(timeline.utilization, Some(0.75));
+        assert_eq!(timeline.underutilized, Some(0.5));
+        assert_eq!(
+            timeline.to_string(),
+            "Concurrency: mean 3.00 of 4 requests in flight (75.0%); below 4 in 50.0% of samples\n"
+        );
+        let empty = ConcurrencyTimeline::new(4, Vec::new());
+        assert_eq!(empty.mean_active, None);
+        assert_eq!(empty.to_string(), "");
+    }
+
+    #[test]
+    fn test_latency_summary() {
+        let times = (1..=20)
+            .rev()
+            .map(Duration::from_millis)
+            .collect::<Vec<_>>();
+        let summary = LatencySummary::new(&times).unwrap();
+        assert_eq!(
+            summary,
+            LatencySummary {
+                count: 20,
+                mean: Duration::from_micros(10500),
+                median: Duration::from_millis(10),
+                p95: Duration::from_millis(19),
+                max: Duration::from_millis(20),
+            }
+        );
+        assert_eq!(LatencySummary::new(&[]), None);
+    }
+
+    #[test]
+    fn test_status_counts() {
+        let mut counts = StatusCounts::default();
+        for code in [207, 200, 404, 200, 302] {
+            counts.add(code);
+        }
+        assert_eq!(
+            counts.by_class,
+            BTreeMap::from([("2xx".into(), 3), ("3xx".into(), 1), ("4xx".into(), 1)])
+        );
+        assert_eq!(
+            counts.to_string(),
+            "2xx: 3, 3xx: 1, 4xx: 1 (200: 2, 207: 1, 302: 1, 404: 1)"
+        );
+    }
+
+    #[test]
+    fn test_error_summary() {
+        let url = Url::parse("https://dav.example.com/foo").unwrap();
+        let mut errors = ErrorSummary::default();
+        assert!(errors.is_empty());
+        for i in 0..7 {
+            errors.add(ErrorKind::Timeout, &url, format!("timeout {i}"));
+        }
+        errors.add(ErrorKind::XmlParse, &url, "bad XML".into());
+        assert_eq!(errors.total(), 8);
+        assert_eq!(errors.0[&ErrorKind::Timeout].count, 7);
+        assert_eq!(
+            errors.0[&ErrorKind::Timeout].examples.len(),
+            MAX_ERROR_EXAMPLES
+        );
+        assert_eq!(
+            serde_json::to_value(&errors).unwrap()["xml_parse"],
+            serde_json::json!({
+                "count": 1,
+                "examples": [{"url": "https://dav.example.com/foo", "message": "bad XML"}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_server_fingerprints() {
+        let mut servers = ServerFingerprints::default();
+        assert!(servers.is_empty());
+        let headers = vec![
+            ("server".to_owned(), "nginx/1.25.3".to_owned()),
+            ("via".to_owned(), "1.1 varnish".to_owned()),
+        ];
+        servers.add(&headers);
+        servers.add(&headers);
+        let mut other = ServerFingerprints::default();
+        other.add(&vec![
+            ("server".to_owned(), "Apache/2.4.58".to_owned()),
+            ("x-cache-backend".to_owned(), "dav-3".to_owned()),
+        ]);
+        servers.merge(&other);
+        assert_eq!(
+            serde_json::to_value(&servers).unwrap(),
+            serde_json::json!({
+                "server": {"Apache/2.4.58": 1, "nginx/1.25.3": 2},
+                "via": {"1.1 varnish": 2},
+                "x-cache-backend": {"dav-3": 1},
+            })
+        );
+        assert_eq!(
+            servers.to_string(),
+            indoc! {"
+                Server headers:
+                  server: Apache/2.4.58 (1 responses)
+                  server: nginx/1.25.3 (2 responses)
+                  via: 1.1 varnish (2 responses)
+                  x-cache-backend: dav-3 (1 responses)
+            "}
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::batch::SummaryFormat;
+use crate::btn::BoundedTreeNursery;
+use crate::client::{Client, DirectoryResponse, RequestError};
+use crate::stats::{Sample, WorkerSamples};
+use crate::table::{Align, Table};
+use crate::traverse::{traverse, TraversalOptions};
+use futures_util::TryStreamExt;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use url::Url;
+
+/// Traverse the hierarchy at `base_url` with a single `Depth: infinity`
+/// `PROPFIND` request followed by a `HEAD` request for each non-collection
+/// resource found, using at most `workers` concurrent `HEAD` requests.
+/// Returns the sample along with the size of the `PROPFIND` response body.
+pub(crate) async fn traverse_infinity(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+) -> Result<(Sample, u64), RequestError> {
+    let start = Instant::now();
+    let DirectoryResponse {
+        listing, body_size, ..
+    } = client.list_tree(base_url).await?;
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        listing.files.into_iter().map(|f| {
+            let client = client.clone();
+            move |_spawner| async move { client.get_file_redirect(f.href).await }
+        }),
+    );
+    let mut requests = 1usize;
+    while let Some(_resp) = stream.try_next().await? {
+        requests = requests.saturating_add(1);
+    }
+    let sample = Sample {
+        requests,
+        elapsed: start.elapsed(),
+        utilization: None,
+    };
+    Ok((sample, body_size))
+}
+
+/// Traversal results for both discovery strategies
+#[derive(Clone, Debug)]
+pub(crate) struct StrategySamples {
+    /// Samples from recursive traversals with `Depth: 1` requests
+    pub(crate) recursive: WorkerSamples,
+    /// Samples from traversals with a single `Depth: infinity` request
+    pub(crate) infinity: WorkerSamples,
+    /// Total size of the `PROPFIND` response bodies received by all
+    /// recursive traversals for each number of workers
+    pub(crate) recursive_bytes: BTreeMap<usize, u64>,
+    /// Total size of the `PROPFIND` response bodies received by all
+    /// `Depth: infinity` traversals for each number of workers
+    pub(crate) infinity_bytes: BTreeMap<usize, u64>,
+}
+
+/// Traverse the hierarchy at `base_url` `samples` times with each strategy
+/// for each number of workers in `workers_list`, alternating between the
+/// strategies so that neither consistently benefits from caching done for
+/// the other
+pub(crate) async fn compare_strategies(
+    client: Client,
+    base_url: Url,
+    workers_list: &[usize],
+    samples: usize,
+) -> anyhow::Result<StrategySamples> {
+    let options = TraversalOptions {
+        quiet: true,
+        ..TraversalOptions::default()
+    };
+    let mut recursive = WorkerSamples::new();
+    let mut infinity = WorkerSamples::new();
+    let mut recursive_bytes = BTreeMap::<usize, u64>::new();
+    let mut infinity_bytes = BTreeMap::<usize, u64>::new();
+    for &workers in workers_list {
+        for _ in 0..samples {
+            let report = traverse(client.clone(), base_url.clone(), workers, &options).await?;
+            recursive.push(workers, Sample::from(&report));
+            let total = recursive_bytes.entry(workers).or_default();
+            *total = total.saturating_add(report.listing_sizes.total_bytes);
+            let (sample, body_size) =
+                traverse_infinity(client.clone(), base_url.clone(), workers).await?;
+            infinity.push(workers, sample);
+            let total = infinity_bytes.entry(workers).or_default();
+            *total = total.saturating_add(body_size);
+        }
+    }
+    Ok(StrategySamples {
+        recursive,
+        infinity,
+        recursive_bytes,
+        infinity_bytes,
+    })
+}
+
+/// The comparison of the two strategies for one number of workers
+#[derive(Clone, Debug, PartialEq)]
+struct ComparisonRow {
+    workers: usize,
+    samples: usize,
+    recursive_mean: f64,
+    recursive_stddev: f64,
+    infinity_mean: f64,
+    infinity_stddev: f64,
+    /// Ratio of the recursive mean time to the `Depth: infinity` mean time
+    speedup: f64,
+    /// Mean number of bytes of `PROPFIND` responses received per recursive
+    /// traversal
+    recursive_bytes: f64,
+    /// Mean number of bytes of `PROPFIND` responses received per
+    /// `Depth: infinity` traversal
+    infinity_bytes: f64,
+}
+
+fn comparison_rows(results: &StrategySamples) -> Vec<ComparisonRow> {
+    results
+        .recursive
+        .summarize()
+        .into_iter()
+        .zip(results.infinity.summarize())
+        .map(|(rec, inf)| ComparisonRow {
+            workers: rec.workers,
+            samples: rec.samples,
+            recursive_mean: rec.time_mean,
+            recursive_stddev: rec.time_stddev,
+            infinity_mean: inf.time_mean,
+            infinity_stddev: inf.time_stddev,
+            speedup: rec.time_mean / inf.time_mean,
+            recursive_bytes: mean_bytes(&results.recursive_bytes, rec.workers, rec.samples),
+            infinity_bytes: mean_bytes(&results.infinity_bytes, rec.workers, rec.samples),
+        })
+        .collect()
+}
+
+/// Print the mean & standard deviation of the time taken by each strategy
+/// for each number of workers, along with the ratio of the recursive mean to
+/// the `Depth: infinity` mean and the mean number of bytes of `PROPFIND`
+/// responses received per traversal under each strategy
+pub(crate) fn print_comparison(format: SummaryFormat, results: &StrategySamples) {
+    let mut table = Table::new()
+        .column("Workers", Align::Right)
+        .column("Samples", Align::Right)
+        .column("Depth:1 Mean", Align::Right)
+        .column("Std Dev", Align::Right)
+        .column("Depth:∞ Mean", Align::Right)
+        .column("Std Dev", Align::Right)
+        .column("Speedup", Align::Right)
+        .column("Depth:1 Bytes", Align::Right)
+        .column("Depth:∞ Bytes", Align::Right);
+    if format == SummaryFormat::Csv {
+        println!("workers,recursive_time_mean,recursive_time_stddev,infinity_time_mean,infinity_time_stddev,speedup,recursive_listing_bytes,infinity_listing_bytes");
+    }
+    for row in comparison_rows(results) {
+        let ComparisonRow {
+            workers,
+            samples,
+            recursive_mean: rec_mean,
+            recursive_stddev: rec_stddev,
+            infinity_mean: inf_mean,
+            infinity_stddev: inf_stddev,
+            speedup,
+            recursive_bytes: rec_bytes,
+            infinity_bytes: inf_bytes,
+        } = row;
+        match format {
+            SummaryFormat::Csv => {
+                println!("{workers},{rec_mean},{rec_stddev},{inf_mean},{inf_stddev},{speedup},{rec_bytes},{inf_bytes}");
+            }
+            SummaryFormat::Table => table.push_row(vec![
+                workers.to_string(),
+                samples.to_string(),
+                format!("{rec_mean:.3} s"),
+                format!("{rec_stddev:.3} s"),
+                format!("{inf_mean:.3} s"),
+                format!("{inf_stddev:.3} s"),
+                format!("{speedup:.2}×"),
+                format!("{rec_bytes:.0}"),
+                format!("{inf_bytes:.0}"),
+            ]),
+        }
+    }
+    if format == SummaryFormat::Table {
+        print!("{table}");
+    }
+}
+
+/// Return the mean number of bytes per traversal for `workers` workers, given
+/// the totals across all `n` traversals for each number of workers
+#[allow(clippy::cast_precision_loss)]
+fn mean_bytes(totals: &BTreeMap<usize, u64>, workers: usize, n: usize) -> f64 {
+    let total = totals.get(&workers).copied().unwrap_or_default();
+    if n == 0 {
+        0.0
+    } else {
+        (total as f64) / (n as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn sample(millis: u64) -> Sample {
+        Sample {
+            requests: 10,
+            elapsed: Duration::from_millis(millis),
+            utilization: None,
+        }
+    }
+
+    #[test]
+    fn test_comparison_rows() {
+        let mut recursive = WorkerSamples::new();
+        let mut infinity = WorkerSamples::new();
+        for (workers, rec, inf) in [
+            (1, 4000, 1000),
+            (1, 2000, 1000),
+            (4, 1000, 500),
+            (4, 1000, 500),
+        ] {
+            recursive.push(workers, sample(rec));
+            infinity.push(workers, sample(inf));
+        }
+        let results = StrategySamples {
+            recursive,
+            infinity,
+            recursive_bytes: BTreeMap::from([(1, 3000), (4, 2000)]),
+            infinity_bytes: BTreeMap::from([(1, 5000)]),
+        };
+        let rows = comparison_rows(&results);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].workers, 1);
+        assert_eq!(rows[0].samples, 2);
+        assert!((rows[0].recursive_mean - 3.0).abs() < 1e-9, "{rows:?}");
+        assert!((rows[0].infinity_mean - 1.0).abs() < 1e-9, "{rows:?}");
+        assert!((rows[0].speedup - 3.0).abs() < 1e-9, "{rows:?}");
+        assert!((rows[0].recursive_bytes - 1500.0).abs() < 1e-9, "{rows:?}");
+        assert!((rows[0].infinity_bytes - 2500.0).abs() < 1e-9, "{rows:?}");
+        assert_eq!(rows[1].workers, 4);
+        assert!((rows[1].speedup - 2.0).abs() < 1e-9, "{rows:?}");
+        assert!((rows[1].recursive_bytes - 1000.0).abs() < 1e-9, "{rows:?}");
+        assert!(rows[1].infinity_bytes.abs() < 1e-9, "{rows:?}");
+    }
+
+    #[test]
+    fn test_mean_bytes_no_samples() {
+        let totals = BTreeMap::from([(2, 100)]);
+        assert!(
+            mean_bytes(&totals, 2, 0).abs() < 1e-9,
+            "mean of no samples should be 0"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt::{self, Write};
+
+/// A simple text table rendered with Unicode box-drawing characters
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Column {
+    header: String,
+    align: Align,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Align {
+    Left,
+    Right,
+}
+
+impl Table {
+    pub(crate) fn new() -> Table {
+        Table {
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub(crate) fn column<S: Into<String>>(mut self, header: S, align: Align) -> Table {
+        self.columns.push(Column {
+            header: header.into(),
+            align,
+        });
+        self
+    }
+
+    /// Append a row of cells.  Missing cells are rendered as empty, and extra
+    /// cells are ignored.
+    pub(crate) fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(i, col)| {
+                self.rows
+                    .iter()
+                    .filter_map(|r| r.get(i))
+                    .map(|s| s.chars().count())
+                    .chain(std::iter::once(col.header.chars().count()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths = self.widths();
+        let rule = |f: &mut fmt::Formatter<'_>, left, mid, right| {
+            f.write_char(left)?;
+            for (i, w) in widths.iter().enumerate() {
+                if i > 0 {
+                    f.write_char(mid)?;
+                }
+                for _ in 0..(w + 2) {
+                    f.write_char('─')?;
+                }
+            }
+            f.write_char(right)?;
+            f.write_char('\n')
+        };
+        let line = |f: &mut fmt::Formatter<'_>, cells: &mut dyn Iterator<Item = (&str, Align)>| {
+            f.write_char('│')?;
+            for (w, (s, align)) in widths.iter().zip(cells) {
+                match align {
+                    Align::Left => write!(f, " {s:<w$} │")?,
+                    Align::Right => write!(f, " {s:>w$} │")?,
+                }
+            }
+            f.write_char('\n')
+        };
+        rule(f, '┌', '┬', '┐')?;
+        line(
+            f,
+            &mut self
+                .columns
+                .iter()
+                .map(|c| (c.header.as_str(), Align::Left)),
+        )?;
+        rule(f, '├', '┼', '┤')?;
+        for row in &self.rows {
+            line(
+                f,
+                &mut self
+                    .columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (row.get(i).map_or("", String::as_str), c.align)),
+            )?;
+        }
+        rule(f, '└', '┴', '┘')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_render() {
+        let mut table = Table::new()
+            .column("Workers", Align::Right)
+            .column("Mean", Align::Right)
+            .column("Note", Align::Left);
+        table.push_row(vec!["1".into(), "8.399 s".into(), "slow".into()]);
+        table.push_row(vec!["50".into(), "0.713 s".into()]);
+        assert_eq!(
+            table.to_string(),
+            indoc! {"
+                ┌─────────┬─────────┬──────┐
+                │ Workers │ Mean    │ Note │
+                ├─────────┼─────────┼──────┤
+                │       1 │ 8.399 s │ slow │
+                │      50 │ 0.713 s │      │
+                └─────────┴─────────┴──────┘
+            "}
+        );
+    }
+}
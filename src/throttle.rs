@@ -0,0 +1,79 @@
+use std::num::NonZeroU64;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How far ahead of the limit transfers may get after a lull, so that a
+/// limiter that has been idle doesn't delay the first bytes it sees
+const BURST: Duration = Duration::from_secs(1);
+
+/// Limits the rate at which response bodies are read, shared by all of the
+/// requests made by a `Client` and its clones.  Readers are delayed so that,
+/// over time, no more than `rate` bytes per second are read in total, while
+/// up to a second's worth of bytes may be read at once after an idle period.
+#[derive(Debug)]
+pub(crate) struct ByteRateLimiter {
+    rate: NonZeroU64,
+    /// The time at which all of the bytes read so far will have been paid
+    /// for
+    paid_until: Mutex<Option<Instant>>,
+}
+
+impl ByteRateLimiter {
+    pub(crate) fn new(rate: NonZeroU64) -> ByteRateLimiter {
+        ByteRateLimiter {
+            rate,
+            paid_until: Mutex::new(None),
+        }
+    }
+
+    /// Account for `bytes` bytes having been read, waiting as long as needed
+    /// to keep the overall rate within the limit
+    pub(crate) async fn consume(&self, bytes: u64) {
+        let delay = self.reserve(bytes, Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Add the cost of `bytes` bytes at time `now` and return how long to
+    /// wait before reading more
+    fn reserve(&self, bytes: u64, now: Instant) -> Duration {
+        #[allow(clippy::cast_precision_loss)]
+        let cost = Duration::try_from_secs_f64(bytes as f64 / self.rate.get() as f64)
+            .unwrap_or(Duration::MAX);
+        let mut paid_until = self
+            .paid_until
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let earliest = now.checked_sub(BURST).unwrap_or(now);
+        let start = paid_until.map_or(earliest, |t| t.max(earliest));
+        let until = start.checked_add(cost).unwrap_or(start);
+        *paid_until = Some(until);
+        until.saturating_duration_since(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limiter = ByteRateLimiter::new(NonZeroU64::new(1000).unwrap());
+        let t0 = Instant::now();
+        // The first second's worth of bytes can be read without waiting
+        assert_eq!(limiter.reserve(600, t0), Duration::ZERO);
+        assert_eq!(limiter.reserve(400, t0), Duration::ZERO);
+        assert_eq!(limiter.reserve(500, t0), Duration::from_millis(500));
+        assert_eq!(
+            limiter.reserve(500, t0 + Duration::from_millis(500)),
+            Duration::from_millis(500)
+        );
+        // After an idle period, only a second's worth of bytes is allowed
+        // through at once
+        let t1 = t0 + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(1000, t1), Duration::ZERO);
+        assert_eq!(limiter.reserve(250, t1), Duration::from_millis(250));
+    }
+}
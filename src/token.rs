@@ -0,0 +1,118 @@
+use crate::hook::shell_command;
+use reqwest::header::HeaderValue;
+use std::process::{ExitStatus, Stdio};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+/// A bearer token obtained by running a user-supplied shell command, which is
+/// run again to obtain a fresh token whenever the current one is rejected
+#[derive(Debug)]
+pub(crate) struct TokenCommand {
+    command: String,
+    /// The current `Authorization` header value and the number of times the
+    /// token has been refreshed.  The lock is held while the command runs so
+    /// that concurrent requests rejected with the same token trigger only a
+    /// single refresh.
+    current: Mutex<(HeaderValue, u64)>,
+}
+
+impl TokenCommand {
+    pub(crate) fn new(command: String, token: &str) -> Result<TokenCommand, TokenCommandError> {
+        Ok(TokenCommand {
+            command,
+            current: Mutex::new((bearer_header(token)?, 0)),
+        })
+    }
+
+    /// Return the current `Authorization` header value along with its
+    /// generation number
+    pub(crate) async fn current(&self) -> (HeaderValue, u64) {
+        self.current.lock().await.clone()
+    }
+
+    /// Replace the token of generation `stale`, which the server rejected,
+    /// with a fresh one by running the command.  If the token has already
+    /// been refreshed since generation `stale`, the command is not run
+    /// again.
+    pub(crate) async fn refresh(&self, stale: u64) -> Result<(), TokenCommandError> {
+        let mut current = self.current.lock().await;
+        if current.1 != stale {
+            return Ok(());
+        }
+        let command = self.command.clone();
+        let token = tokio::task::spawn_blocking(move || run_token_command(&command))
+            .await
+            .map_err(|_| TokenCommandError::Panicked)??;
+        *current = (bearer_header(&token)?, stale.saturating_add(1));
+        Ok(())
+    }
+}
+
+/// Run `command` with the shell and return its standard output, with
+/// surrounding whitespace removed, as a bearer token
+pub(crate) fn run_token_command(command: &str) -> Result<String, TokenCommandError> {
+    let output = shell_command(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(TokenCommandError::Spawn)?;
+    if !output.status.success() {
+        return Err(TokenCommandError::Status(output.status));
+    }
+    let token = String::from_utf8(output.stdout).map_err(|_| TokenCommandError::Invalid)?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(TokenCommandError::Empty);
+    }
+    Ok(token.to_owned())
+}
+
+fn bearer_header(token: &str) -> Result<HeaderValue, TokenCommandError> {
+    let mut value = HeaderValue::from_str(&format!("Bearer {token}"))
+        .map_err(|_| TokenCommandError::Invalid)?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum TokenCommandError {
+    #[error("failed to run --token-command")]
+    Spawn(#[source] std::io::Error),
+    #[error("--token-command exited with {0}")]
+    Status(ExitStatus),
+    #[error("--token-command output an empty token")]
+    Empty,
+    #[error("--token-command output a token that is not a valid header value")]
+    Invalid,
+    #[error("--token-command task panicked")]
+    Panicked,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_refresh_once_per_generation() {
+        let dir = std::env::temp_dir().join(format!("batchdav-token-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let counter = dir.join("count");
+        let command = format!(
+            "echo x >> '{0}'; echo tok$(wc -l < '{0}' | tr -d ' ')",
+            counter.display()
+        );
+        let tc = TokenCommand::new(command, "initial").unwrap();
+        let (value, generation) = tc.current().await;
+        assert_eq!(value, "Bearer initial");
+        assert_eq!(generation, 0);
+        tc.refresh(0).await.unwrap();
+        // A second request rejected with the stale token must not cause
+        // another refresh
+        tc.refresh(0).await.unwrap();
+        let (value, generation) = tc.current().await;
+        assert_eq!(value, "Bearer tok1");
+        assert_eq!(generation, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
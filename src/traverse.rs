@@ -1,53 +1,1746 @@
-use crate::btn::{BoundedTreeNursery, Spawner};
-use crate::client::Client;
-use futures_util::{future::BoxFuture, FutureExt, TryStreamExt};
-use serde::Serialize;
+use crate::adaptive::{AdaptiveRange, AdaptiveStats, AimdController};
+use crate::backend::TraversalBackend;
+use crate::bandwidth::{measure_bandwidth, BandwidthStats, Reservoir};
+use crate::btn::{BoundedTreeNursery, DispatchOrder, PanicPolicy, Spawner};
+use crate::checkpoint::Checkpointer;
+use crate::client::{
+    new_request_id, Client, ClientOptions, DirectoryResponse, ErrorKind, FileMethod, FileResponse,
+    ProbeTiming, RedirectChain, RequestDescription, RequestError, RequestIds, ResponseWarning,
+    ServerHeaders,
+};
+use crate::clockskew::{ClockSkew, ClockTracker};
+use crate::compress::OutFile;
+use crate::conditional::{check_conditional, ConditionalStats};
+use crate::dedup::Deduplicator;
+use crate::errorrate::{ErrorRateAbort, ErrorRateLimit, ErrorRateWindow};
+use crate::export::serialize_seconds;
+use crate::filter::EntryFilter;
+use crate::incremental::{EtagState, IncrementalStats};
+use crate::inventory::Inventory;
+use crate::maintenance::Outage;
+use crate::metrics::StatusBoard;
+use crate::progress::{timestamped, ProgressEvent, PROGRESS_INTERVAL};
+use crate::ramp::Ramp;
+use crate::retry::RetryDecision;
+use crate::runtime::RuntimeConfig;
+use crate::s3::S3Backend;
+use crate::simulate::{Simulation, SimulationSpec};
+use crate::stats::{
+    ConcurrencySample, ConcurrencyTimeline, ContentTypeStats, DuplicateStats, ErrorSummary,
+    FairnessStats, FanoutStats, HedgeStats, Latencies, LatencySummary, MethodComparison,
+    MethodPairs, ModificationStats, PipelineStats, PoolStats, RedirectChainStats, RepeatStats,
+    ResponseSizeStats, ResponseSizes, ServerFingerprints, SlowRequestStats, StatusDistribution,
+    TimeoutStats, WarningSummary, WorkerPools,
+};
+use crate::types::{is_within, path_order_key, DirectoryListing, Entry};
+use crate::zarr::{containing_zarr_store, is_zarr_store, ManifestEntry, ZarrBoundary, ZarrStats};
+use anyhow::Context;
+use clap::ValueEnum;
+use futures_util::{future::BoxFuture, FutureExt, StreamExt, TryFutureExt};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::pin::pin;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::time::MissedTickBehavior;
 use url::Url;
 
+/// How often to sample the number of requests in flight during a traversal
+const CONCURRENCY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum number of concurrency samples to keep in low-memory mode
+const MAX_CONCURRENCY_SAMPLES: usize = 4096;
+
+/// How often to raise the number of workers when ramping up
+const RAMP_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Options controlling the behavior of a traversal
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct TraversalOptions {
+    /// Do not print details on each request as it's completed
+    pub(crate) quiet: bool,
+    /// What to do when a request fails
+    pub(crate) on_error: ErrorPolicy,
+    /// What to do when a request task panics
+    pub(crate) on_panic: PanicPolicy,
+    /// File in which to periodically save the traversal's progress and from
+    /// which to resume an interrupted traversal
+    pub(crate) checkpoint: Option<PathBuf>,
+    /// File to which to write an inventory of the resources found once the
+    /// traversal completes
+    pub(crate) tree_file: Option<PathBuf>,
+    /// Periodically emit JSON progress events on standard error
+    pub(crate) progress_events: bool,
+    /// Buffer the details printed for each request (and the inventory
+    /// written to `tree_file`) and emit them in canonical path order once the
+    /// traversal completes
+    pub(crate) sort: bool,
+    /// Prefix the details printed for each request with the time at which
+    /// the request completed
+    pub(crate) timestamps: bool,
+    /// If set, follow the full chain of redirects for each non-collection
+    /// resource, making at most this many requests to redirect targets per
+    /// resource
+    pub(crate) follow_redirects: Option<usize>,
+    /// If set, only probe non-collection resources that were modified at or
+    /// after this time (or whose modification times are unknown)
+    pub(crate) modified_since: Option<OffsetDateTime>,
+    /// File to which to write a JSON object for each completed request as it
+    /// completes
+    pub(crate) request_log: Option<PathBuf>,
+    /// File to which to write a JSON object describing each failed request,
+    /// including an equivalent `curl` command, as it fails
+    pub(crate) error_log: Option<PathBuf>,
+    /// File to which to write a JSON object for each file found inside a
+    /// Zarr store, giving its path, size, and ETag
+    pub(crate) zarr_manifest: Option<PathBuf>,
+    /// File to which to write the URL of each non-collection resource found,
+    /// one per line
+    pub(crate) urls_out: Option<PathBuf>,
+    /// Follow each URL written to `urls_out` with a tab and the URL that the
+    /// resource redirects to, if any
+    pub(crate) urls_out_targets: bool,
+    /// Include the start & end times of every request in the report
+    pub(crate) request_timestamps: bool,
+    /// Aggregate request latencies into histograms as they're recorded rather
+    /// than retaining every latency
+    pub(crate) low_memory: bool,
+    /// Coalesce concurrent identical requests into one, sharing its result
+    pub(crate) coalesce_requests: bool,
+    /// User-defined labels to record in the report
+    pub(crate) labels: BTreeMap<String, String>,
+    /// The configuration of the async runtime, to record in the report
+    pub(crate) runtime: Option<RuntimeConfig>,
+    /// Further collections to traverse alongside the base URL in the same
+    /// pool of workers, each of which is also reported on separately
+    pub(crate) extra_roots: Vec<Url>,
+    /// Criteria for excluding collections & files from the traversal
+    pub(crate) filter: Arc<EntryFilter>,
+    /// Include the URLs of all collections & files found in the report
+    pub(crate) collect_urls: bool,
+    /// Board on which to post the traversal's progress & results
+    pub(crate) status: Option<Arc<StatusBoard>>,
+    /// If set, traverse a synthetic hierarchy instead of the server
+    pub(crate) simulate: Option<SimulationSpec>,
+    /// If set, list collections via S3 `ListObjectsV2` requests to the
+    /// bucket at this URL instead of via PROPFIND
+    pub(crate) s3: Option<Url>,
+    /// How to treat Zarr stores encountered during the traversal
+    pub(crate) zarr_boundary: ZarrBoundary,
+    /// The order in which to start requests that are waiting for a free
+    /// worker
+    pub(crate) order: DispatchOrder,
+    /// If set (or if `file_workers` is set), list collections in a separate
+    /// pool of this many workers (or of the workers not given to
+    /// `file_workers`, if unset) from that used to probe non-collection
+    /// resources
+    pub(crate) dir_workers: Option<usize>,
+    /// If set (or if `dir_workers` is set), probe non-collection resources
+    /// in a separate pool of this many workers (or of the workers not given
+    /// to `dir_workers`, if unset) from that used to list collections
+    pub(crate) file_workers: Option<usize>,
+    /// If set, adjust the number of workers within this range based on the
+    /// observed latencies, failures, & retries, starting from the overall
+    /// number of workers
+    pub(crate) adaptive_workers: Option<AdaptiveRange>,
+    /// If set, start the traversal with one worker (per pool) and raise the
+    /// number of workers to the full number on this schedule
+    pub(crate) ramp: Option<Ramp>,
+    /// If set, log each request whose latency exceeds this threshold to
+    /// stderr, regardless of `quiet`, and count such requests in the report
+    pub(crate) log_slower_than: Option<Duration>,
+    /// If set, stop the traversal once the percentage of failed requests
+    /// among the most recent ones exceeds this limit, abandoning any requests
+    /// still in progress or queued
+    pub(crate) max_error_rate: Option<ErrorRateLimit>,
+    /// If set, stop the traversal once this many collection listings have
+    /// completed (successfully or not), abandoning any requests still in
+    /// progress or queued
+    pub(crate) max_dirs: Option<usize>,
+    /// File from which to load the collection ETags recorded by the previous
+    /// traversal and to which to save those found by this one
+    pub(crate) etag_state: Option<PathBuf>,
+    /// Do not list collections whose ETags are the same as those recorded in
+    /// `etag_state`
+    pub(crate) skip_unchanged: bool,
+    /// If set, fully download this many randomly sampled files once the
+    /// traversal completes and report their download rates
+    pub(crate) measure_bandwidth: Option<usize>,
+    /// Once the traversal completes, check that the server answers
+    /// `If-None-Match` requests for each file with the file's listed ETag
+    /// with 304 Not Modified
+    pub(crate) check_conditional: bool,
+    /// Number of additional times to repeat each `PROPFIND` & file probe
+    /// request immediately after it first completes
+    pub(crate) repeat_requests: usize,
+    /// Show URLs in per-request output relative to the base URL
+    pub(crate) relative: bool,
+}
+
+impl TraversalOptions {
+    fn task_options(&self) -> TaskOptions {
+        TaskOptions {
+            follow_redirects: self.follow_redirects,
+            modified_since: self.modified_since,
+            filter: Arc::clone(&self.filter),
+            zarr_boundary: self.zarr_boundary,
+            repeat_requests: self.repeat_requests,
+            unchanged: None,
+        }
+    }
+
+    /// Return the sizes of the pools of the traversal's `BoundedTreeNursery`
+    /// when running with `workers` workers: a single pool of `workers`
+    /// workers unless `dir_workers` or `file_workers` is set, in which case
+    /// there are pools for `DIR_POOL` and `FILE_POOL`, with an unset size
+    /// taken to be the rest of the workers (but at least one).  With
+    /// `adaptive_workers` and a single pool, the pool is sized to the top of
+    /// the range.
+    fn pool_sizes(&self, workers: usize) -> Vec<usize> {
+        // With a single pool, tasks spawned in `FILE_POOL` go to the last
+        // (i.e., only) pool
+        match (self.dir_workers, self.file_workers) {
+            (None, None) => vec![self.adaptive_workers.map_or(workers, |range| range.max)],
+            (Some(dirs), None) => vec![dirs, workers.saturating_sub(dirs).max(1)],
+            (None, Some(files)) => vec![workers.saturating_sub(files).max(1), files],
+            (Some(dirs), Some(files)) => vec![dirs, files],
+        }
+    }
+}
+
+/// The options that affect how individual tasks are processed
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct TaskOptions {
+    follow_redirects: Option<usize>,
+    modified_since: Option<OffsetDateTime>,
+    filter: Arc<EntryFilter>,
+    zarr_boundary: ZarrBoundary,
+    repeat_requests: usize,
+    /// If set, collections whose ETags are unchanged from those in this
+    /// state are not listed
+    unchanged: Option<Arc<EtagState>>,
+}
+
+/// What to do when a request fails during traversal
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ErrorPolicy {
+    /// Abort the traversal with an error
+    #[default]
+    Abort,
+    /// Record the error in the report and continue traversing
+    Skip,
+}
+
 pub(crate) async fn traverse(
     client: Client,
     base_url: Url,
     workers: usize,
-    quiet: bool,
+    options: &TraversalOptions,
+) -> anyhow::Result<TraversalReport> {
+    // The traversal's state is large enough that keeping it inline would
+    // bloat the futures of everything that awaits a traversal
+    let run = Box::pin(run_traversal(client, base_url, workers, options));
+    let Some(board) = options.status.as_deref() else {
+        return run.await;
+    };
+    board.begin(workers);
+    let r = run.await;
+    match r {
+        Ok(ref report) => board.finish(report),
+        Err(_) => board.fail(),
+    }
+    r
+}
+
+async fn run_traversal(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+    options: &TraversalOptions,
 ) -> anyhow::Result<TraversalReport> {
+    // Log in before the clock starts so that the initial login is not
+    // counted against the first request
+    client.log_in().await?;
+    let logins_before = client.logins();
+    let outages_before = client.outages().map_or(0, |o| o.len());
     let start = Instant::now();
-    let mut stream = BoundedTreeNursery::new(workers, move |spawner| {
-        process_dir(spawner, client, base_url)
+    let start_time = OffsetDateTime::now_utc();
+    let root = base_url.clone();
+    let client_options = client.options().clone();
+    let traversal_id =
+        (client_options.request_ids || client_options.trace_context).then(new_request_id);
+    let client = match traversal_id {
+        Some(ref id) => client.with_traversal_id(id.clone()),
+        None => client,
+    };
+    let clock = Arc::new(ClockTracker::default());
+    let client = client.with_clock_tracker(Arc::clone(&clock));
+    let backend: Arc<dyn TraversalBackend> = match (options.simulate, options.s3.as_ref()) {
+        (Some(spec), _) => Arc::new(Simulation::new(
+            spec,
+            base_url.clone(),
+            client_options.sizes,
+        )),
+        (None, Some(bucket_url)) => {
+            let s3 = S3Backend::new(client.clone(), bucket_url.clone());
+            for url in std::iter::once(&base_url).chain(&options.extra_roots) {
+                s3.check_url(url)?;
+            }
+            Arc::new(s3)
+        }
+        (None, None) => Arc::new(client.clone()),
+    };
+    // S3 buckets are listed with `GET` requests
+    let listing_method = if options.s3.is_some() {
+        "GET"
+    } else {
+        client.listing_method()
+    };
+    let dedup = options
+        .coalesce_requests
+        .then(|| Arc::new(Deduplicator::new(backend.clone())));
+    let backend: Arc<dyn TraversalBackend> = match dedup {
+        Some(ref d) => d.clone(),
+        None => backend,
+    };
+    let mut checkpoint = options
+        .checkpoint
+        .as_deref()
+        .map(|path| Checkpointer::open(path, &base_url))
+        .transpose()?;
+    let mut inventory = options.tree_file.is_some().then(|| Inventory {
+        base_url: base_url.clone(),
+        tasks: Vec::new(),
+    });
+    let mut per_root = (!options.extra_roots.is_empty()).then(|| {
+        let urls = std::iter::once(&base_url)
+            .chain(&options.extra_roots)
+            .cloned()
+            .collect();
+        RootCollectors::new(urls, options.low_memory)
     });
-    let mut directory_request_times = Vec::new();
-    let mut file_request_times = Vec::new();
-    while let Some(r) = stream.try_next().await? {
-        if !quiet {
-            println!("{r}");
+    let roots = match (checkpoint.as_ref(), per_root.as_ref()) {
+        (Some(c), _) => c.pending(),
+        (None, Some(rc)) => rc.tasks(),
+        (None, None) => vec![Task::Dir {
+            url: base_url,
+            display_name: None,
+        }],
+    };
+    let old_etags = options
+        .etag_state
+        .as_deref()
+        .map(EtagState::load)
+        .transpose()?
+        .map(Arc::new);
+    let mut new_etags = old_etags.is_some().then(EtagState::default);
+    let mut unchanged_dirs = Vec::new();
+    let topts = TaskOptions {
+        unchanged: old_etags.clone().filter(|_| options.skip_unchanged),
+        ..options.task_options()
+    };
+    let pool_sizes = options.pool_sizes(workers);
+    let mut stream = BoundedTreeNursery::with_pools(
+        pool_sizes.clone(),
+        options.order,
+        roots.into_iter().map(|task| {
+            let backend = Arc::clone(&backend);
+            let topts = topts.clone();
+            (task.pool(), move |spawner| {
+                process_task(spawner, backend, task, topts)
+            })
+        }),
+    );
+    stream.set_panic_policy(options.on_panic);
+    let mut adaptive = options
+        .adaptive_workers
+        .map(|range| AimdController::new(range, workers));
+    if let Some(ref aimd) = adaptive {
+        stream.set_pool_limit(0, aimd.workers());
+    }
+    let mut retries_seen = client.retries_used();
+    let timeout_retries = client.timeout_retries();
+    let hedges_before = client.hedge_counts();
+    let mut ramp = options.ramp;
+    if ramp.is_some() {
+        for (pool, &size) in pool_sizes.iter().enumerate() {
+            stream.set_pool_limit(pool, size.min(1));
+        }
+    }
+    let mut ramp_ticks = tokio::time::interval(RAMP_INTERVAL);
+    ramp_ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut request_log = options
+        .request_log
+        .as_deref()
+        .map(|path| {
+            OutFile::create(path)
+                .with_context(|| format!("failed to create request log {}", path.display()))
+        })
+        .transpose()?;
+    let mut error_log = options
+        .error_log
+        .as_deref()
+        .map(|path| {
+            OutFile::create(path)
+                .with_context(|| format!("failed to create error log {}", path.display()))
+        })
+        .transpose()?;
+    let mut zarr_manifest = options
+        .zarr_manifest
+        .as_deref()
+        .map(|path| {
+            OutFile::create(path)
+                .with_context(|| format!("failed to create Zarr manifest {}", path.display()))
+        })
+        .transpose()?;
+    let mut urls_out = options
+        .urls_out
+        .as_deref()
+        .map(|path| {
+            OutFile::create(path)
+                .with_context(|| format!("failed to create URL list {}", path.display()))
+        })
+        .transpose()?;
+    let mut collector = Collector {
+        request_records: options.request_timestamps.then(Vec::new),
+        ..Collector::new(options.low_memory)
+    };
+    let mut urls = options.collect_urls.then(BTreeSet::new);
+    let mut bandwidth_sample = options.measure_bandwidth.map(Reservoir::new);
+    let mut etagged_files = options.check_conditional.then(Vec::new);
+    let mut files_without_etags = 0usize;
+    let mut last_progress = Instant::now();
+    let mut concurrency_ticks = tokio::time::interval(CONCURRENCY_INTERVAL);
+    concurrency_ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut printer = RequestPrinter::new(options.quiet, options.sort, options.timestamps);
+    let display_base = options.relative.then(|| root.clone());
+    // Only listen for Ctrl-C when checkpointing, so that the default signal
+    // behavior is otherwise left in place
+    let mut interrupt = pin!(tokio::signal::ctrl_c());
+    let mut stopped_early = false;
+    // Number of collection listings completed, successfully or not, for
+    // `max_dirs`
+    let mut dirs_requested = 0usize;
+    let mut slow_requests = options.log_slower_than.map(SlowRequestStats::new);
+    let mut error_window = options.max_error_rate.map(ErrorRateWindow::new);
+    let mut error_rate_abort = None;
+    loop {
+        let r = tokio::select! {
+            r = stream.next() => r,
+            _ = concurrency_ticks.tick() => {
+                collector.concurrency.push(ConcurrencySample {
+                    elapsed: start.elapsed(),
+                    active: stream.active(),
+                });
+                continue;
+            }
+            _ = ramp_ticks.tick(), if ramp.is_some() => {
+                if let Some(schedule) = ramp {
+                    let elapsed = start.elapsed();
+                    for (pool, &size) in pool_sizes.iter().enumerate() {
+                        stream.set_pool_limit(pool, schedule.workers(size, elapsed));
+                    }
+                    if schedule.finished(elapsed) {
+                        ramp = None;
+                    }
+                }
+                continue;
+            }
+            _ = &mut interrupt, if checkpoint.is_some() => {
+                if let Some(c) = checkpoint.as_mut() {
+                    c.save()?;
+                }
+                anyhow::bail!("traversal interrupted; progress saved to checkpoint");
+            }
+        };
+        let Some(r) = r else {
+            if let Some(panic) = stream
+                .panics()
+                .first()
+                .filter(|_| options.on_panic == PanicPolicy::Error)
+            {
+                printer.finish();
+                if let Some(fp) = error_log {
+                    fp.finish().context("failed to finish error log")?;
+                }
+                if let Some(c) = checkpoint.as_mut() {
+                    c.save()?;
+                }
+                anyhow::bail!("a request task panicked: {panic}");
+            }
+            break;
+        };
+        if let Some(aimd) = adaptive.as_mut() {
+            let retries = client.retries_used();
+            let latency = r.as_ref().ok().map(|r| r.task_times().1);
+            if let Some(n) = aimd.record(latency, retries > retries_seen, start.elapsed()) {
+                stream.set_pool_limit(0, n);
+            }
+            retries_seen = retries;
+        }
+        if let Some(window) = error_window.as_mut() {
+            error_rate_abort = window.record(r.is_err());
         }
         match r {
-            Report::Dir { elapsed, .. } => directory_request_times.push(elapsed),
-            Report::File { elapsed, .. } => file_request_times.push(elapsed),
+            Ok(r) => {
+                printer.print(r.url(), || r.line(display_base.as_ref()).to_string());
+                if let Some(c) = checkpoint.as_mut() {
+                    c.complete(r.url(), r.child_tasks(options.zarr_boundary));
+                }
+                if let Some(inv) = inventory.as_mut() {
+                    inv.tasks.push(r.task());
+                }
+                if let Some(urls) = urls.as_mut() {
+                    urls.insert(r.url().clone());
+                }
+                if request_log.is_some() || collector.request_records.is_some() {
+                    let record = r.record(
+                        start,
+                        traversal_id.as_deref(),
+                        listing_method,
+                        client.file_method(),
+                    );
+                    if let Some(fp) = request_log.as_mut() {
+                        serde_json::to_writer(&mut *fp, &record)
+                            .context("failed to write to request log")?;
+                        writeln!(fp).context("failed to write to request log")?;
+                    }
+                    if let Some(records) = collector.request_records.as_mut() {
+                        records.push(record);
+                    }
+                }
+                if let Some(fp) = zarr_manifest.as_mut() {
+                    for entry in r.listed_files().filter_map(ManifestEntry::for_file) {
+                        serde_json::to_writer(&mut *fp, &entry)
+                            .context("failed to write to Zarr manifest")?;
+                        writeln!(fp).context("failed to write to Zarr manifest")?;
+                    }
+                }
+                if let Some(fp) = urls_out.as_mut() {
+                    for (url, target) in r.discovered_files() {
+                        match target {
+                            Some(t) if options.urls_out_targets => writeln!(fp, "{url}\t{t}"),
+                            _ => writeln!(fp, "{url}"),
+                        }
+                        .context("failed to write to URL list")?;
+                    }
+                }
+                if let Some(files) = etagged_files.as_mut() {
+                    for entry in r.listed_files() {
+                        match entry.etag {
+                            Some(ref etag) => files.push((entry.href.clone(), etag.clone())),
+                            None => files_without_etags = files_without_etags.saturating_add(1),
+                        }
+                    }
+                }
+                if let Some(sample) = bandwidth_sample.as_mut() {
+                    // Only sample files that were successfully probed,
+                    // leaving out listed files that were never requested
+                    // (such as stale files) and the contents of Zarr stores
+                    if let Report::File { ref url, .. } = r {
+                        if containing_zarr_store(url).is_none() {
+                            sample.offer(url.clone(), &mut rand::thread_rng());
+                        }
+                    }
+                }
+                if let Some(state) = new_etags.as_mut() {
+                    for d in r.record_etags(state) {
+                        unchanged_dirs.push(d.href.clone());
+                    }
+                }
+                if let Some(rc) = per_root.as_mut() {
+                    rc.record(r.url(), start.elapsed(), |c| c.record(r.clone()));
+                }
+                if let Some(slow) = slow_requests.as_mut().filter(|s| r.elapsed() > s.threshold) {
+                    let line = r.slow_line(display_base.as_ref());
+                    log_slow(slow, r.kind(), &line, options.timestamps);
+                }
+                if r.kind() == RequestKind::Dir {
+                    dirs_requested = dirs_requested.saturating_add(1);
+                }
+                collector.record(r);
+            }
+            Err(e) if options.on_error == ErrorPolicy::Skip => {
+                printer.print(e.source.url(), || {
+                    let url = ShownUrl {
+                        url: e.source.url(),
+                        base: display_base.as_ref(),
+                    };
+                    format!("ERROR: {url}")
+                });
+                if let Some(slow) = slow_requests.as_mut().filter(|s| e.elapsed > s.threshold) {
+                    let line = e.slow_line(display_base.as_ref());
+                    log_slow(slow, e.kind, &line, options.timestamps);
+                }
+                if e.kind == RequestKind::Dir {
+                    dirs_requested = dirs_requested.saturating_add(1);
+                }
+                let TaskError { source: e, .. } = e;
+                if let Some(fp) = error_log.as_mut() {
+                    log_error(fp, &client, &root, &e)?;
+                }
+                let failure = Failure::from(e);
+                if let Some(urls) = urls.as_mut() {
+                    urls.insert(failure.url.clone());
+                }
+                if let Some(rc) = per_root.as_mut() {
+                    rc.record(&failure.url, start.elapsed(), |c| {
+                        c.record_failure(failure.clone());
+                    });
+                }
+                collector.record_failure(failure);
+            }
+            Err(TaskError { source: e, .. }) => {
+                printer.finish();
+                if let Some(mut fp) = error_log {
+                    log_error(&mut fp, &client, &root, &e)?;
+                    fp.finish().context("failed to finish error log")?;
+                }
+                if let Some(c) = checkpoint.as_mut() {
+                    c.save()?;
+                }
+                return Err(e.into());
+            }
+        }
+        // Failed listings count toward the limit as well, so that it bounds
+        // the number of listing requests made
+        if options.max_dirs.is_some_and(|n| dirs_requested >= n) {
+            stopped_early = true;
+            break;
+        }
+        if error_rate_abort.is_some() {
+            break;
         }
+        if let Some(c) = checkpoint.as_mut() {
+            c.tick()?;
+        }
+        if let Some(board) = options.status.as_deref() {
+            board.update(&collector.progress(workers, start.elapsed()));
+        }
+        if options.progress_events && last_progress.elapsed() >= PROGRESS_INTERVAL {
+            collector.progress(workers, start.elapsed()).emit();
+            last_progress = Instant::now();
+        }
+    }
+    printer.finish();
+    if let Some(fp) = request_log {
+        fp.finish().context("failed to finish request log")?;
+    }
+    if let Some(fp) = error_log {
+        fp.finish().context("failed to finish error log")?;
+    }
+    if let Some(fp) = zarr_manifest {
+        fp.finish().context("failed to finish Zarr manifest")?;
+    }
+    if let Some(fp) = urls_out {
+        fp.finish().context("failed to finish URL list")?;
+    }
+    if let Some((mut inv, path)) = inventory.zip(options.tree_file.as_deref()) {
+        if options.sort {
+            inv.tasks.sort_by_cached_key(|t| path_order_key(t.url()));
+        }
+        inv.save(path)?;
+    }
+    if let Some(c) = checkpoint {
+        c.finish()?;
     }
-    Ok(TraversalReport {
+    let topts = options.task_options();
+    let concurrency = std::mem::take(&mut collector.concurrency).samples;
+    let mut report = collector.finish(
+        root,
         workers,
-        directory_request_times,
-        file_request_times,
-        overall_time: start.elapsed(),
-    })
+        &client_options,
+        &topts,
+        start_time,
+        start.elapsed(),
+    );
+    if let Some(rc) = per_root {
+        report.roots = rc.finish(workers, &client_options, &topts, start_time);
+        for r in &mut report.roots {
+            r.labels.clone_from(&options.labels);
+            r.runtime = options.runtime;
+        }
+    }
+    report.urls = urls;
+    report.stopped_early = stopped_early;
+    report.error_rate_abort = error_rate_abort;
+    report.panics = stream.panics().to_vec();
+    report.labels.clone_from(&options.labels);
+    report.runtime = options.runtime;
+    report.coalesced_requests = dedup.map(|d| d.coalesced());
+    if let (Some(before), Some(after)) = (logins_before, client.logins()) {
+        report.relogins = Some(after.saturating_sub(before));
+    }
+    report.clock_skew = clock.report();
+    report.outages = client
+        .outages()
+        .map(|mut o| o.split_off(outages_before.min(o.len())));
+    if let (Some(old), Some(mut new), Some(path)) =
+        (old_etags, new_etags, options.etag_state.as_deref())
+    {
+        for url in &unchanged_dirs {
+            new.carry_over(&old, url);
+        }
+        new.save(path)?;
+        if options.skip_unchanged {
+            report.incremental = Some(IncrementalStats::new(&old, &unchanged_dirs));
+        }
+    }
+    // With `adaptive_workers` or separate pools, the nursery can have more
+    // slots than `workers`, so measure the concurrency against all of them.
+    report.concurrency = ConcurrencyTimeline::new(pool_sizes.iter().sum(), concurrency);
+    report.peak_concurrency = Some(stream.peak_active());
+    report.mean_concurrency = report.concurrency.mean_active;
+    report.worker_fairness = FairnessStats::new(
+        stream
+            .worker_loads()
+            .into_iter()
+            .map(|load| (load.tasks, load.busy)),
+    );
+    if let [dir_workers, file_workers] = pool_sizes[..] {
+        let pool = |index, size| {
+            PoolStats::new(
+                size,
+                stream.pool_peak_active(index),
+                stream
+                    .pool_loads(index)
+                    .into_iter()
+                    .map(|load| (load.tasks, load.busy)),
+                report.overall_time,
+            )
+        };
+        report.pools = Some(WorkerPools {
+            directories: pool(DIR_POOL, dir_workers),
+            files: pool(FILE_POOL, file_workers),
+        });
+    }
+    report.slow_requests = slow_requests;
+    let failed = report.errors.count(ErrorKind::Timeout);
+    let retried = client.timeout_retries().saturating_sub(timeout_retries);
+    if client_options.timeout.is_some() || failed > 0 || retried > 0 {
+        report.timeouts = Some(TimeoutStats {
+            timeout: client_options.timeout,
+            failed,
+            retried,
+        });
+    }
+    if let Some(hedge_after) = client_options.hedge_after {
+        let (sent, won) = client.hedge_counts();
+        report.hedges = Some(HedgeStats {
+            hedge_after,
+            hedged: sent.saturating_sub(hedges_before.0),
+            won: won.saturating_sub(hedges_before.1),
+        });
+    }
+    report.adaptive = adaptive.map(|aimd| aimd.finish(report.overall_time));
+    if let Some(sample) = bandwidth_sample {
+        let found = sample.seen();
+        report.bandwidth =
+            Some(measure_bandwidth(&client, sample.into_items(), found, workers).await);
+    }
+    if let Some(files) = etagged_files {
+        report.conditional =
+            Some(check_conditional(&client, files, files_without_etags, workers).await);
+    }
+    Ok(report)
+}
+
+/// Separate statistics for each root of a traversal with multiple roots
+#[derive(Clone, Debug, PartialEq)]
+struct RootCollectors(Vec<RootCollector>);
+
+#[derive(Clone, Debug, PartialEq)]
+struct RootCollector {
+    url: Url,
+    collector: Collector,
+    /// Time from the start of the traversal to the most recent completion of
+    /// a request under this root
+    last_completion: Duration,
+}
+
+impl RootCollectors {
+    /// Create collectors for the given roots, discarding any root that is
+    /// equivalent to an earlier one
+    fn new(urls: Vec<Url>, low_memory: bool) -> RootCollectors {
+        let mut roots: Vec<RootCollector> = Vec::with_capacity(urls.len());
+        for url in urls {
+            if !roots
+                .iter()
+                .any(|rc| is_within(&rc.url, &url) && is_within(&url, &rc.url))
+            {
+                roots.push(RootCollector {
+                    url,
+                    collector: Collector::new(low_memory),
+                    last_completion: Duration::ZERO,
+                });
+            }
+        }
+        RootCollectors(roots)
+    }
+
+    /// The initial tasks for the traversal.  Roots nested inside other roots
+    /// are reached by traversing the outer roots, so no tasks are created
+    /// for them, lest their contents be requested & counted twice.
+    fn tasks(&self) -> Vec<Task> {
+        self.0
+            .iter()
+            .filter(|rc| {
+                !self
+                    .0
+                    .iter()
+                    .any(|outer| outer.url != rc.url && is_within(&outer.url, &rc.url))
+            })
+            .map(|rc| Task::Dir {
+                url: rc.url.clone(),
+                display_name: None,
+            })
+            .collect()
+    }
+
+    /// Record a completed request for `url` with the collector for the
+    /// innermost root containing it, if any
+    fn record<F: FnOnce(&mut Collector)>(&mut self, url: &Url, elapsed: Duration, f: F) {
+        if let Some(rc) = self
+            .0
+            .iter_mut()
+            .filter(|rc| is_within(&rc.url, url))
+            .max_by_key(|rc| rc.url.path().len())
+        {
+            f(&mut rc.collector);
+            rc.last_completion = elapsed;
+        }
+    }
+
+    fn finish(
+        self,
+        workers: usize,
+        client_options: &ClientOptions,
+        topts: &TaskOptions,
+        start_time: OffsetDateTime,
+    ) -> Vec<TraversalReport> {
+        self.0
+            .into_iter()
+            .map(|rc| {
+                rc.collector.finish(
+                    rc.url,
+                    workers,
+                    client_options,
+                    topts,
+                    start_time,
+                    rc.last_completion,
+                )
+            })
+            .collect()
+    }
 }
 
+/// The details of a failed request, as recorded by a `Collector`
+#[derive(Clone, Debug)]
+struct Failure {
+    url: Url,
+    status: Option<u16>,
+    kind: ErrorKind,
+    message: String,
+}
+
+impl From<RequestError> for Failure {
+    fn from(e: RequestError) -> Failure {
+        Failure {
+            url: e.url().clone(),
+            status: e.status(),
+            kind: e.kind(),
+            message: format!("{:#}", anyhow::Error::from(e)),
+        }
+    }
+}
+
+/// An entry in a traversal's error log
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct ErrorLogEntry {
+    url: Url,
+    kind: ErrorKind,
+    status: Option<u16>,
+    message: String,
+    #[serde(flatten)]
+    request: RequestDescription,
+}
+
+/// Write an entry describing the failed request for `e` to an error log.
+/// Requests for URLs ending in a slash (and for the traversal's root) are
+/// taken to be `PROPFIND` requests; all others are taken to be requests made
+/// with the file probing method.
+fn log_error<W: Write>(
+    fp: &mut W,
+    client: &Client,
+    root: &Url,
+    e: &RequestError,
+) -> anyhow::Result<()> {
+    let url = e.url();
+    let collection = url == root || url.path().ends_with('/');
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(s) = source {
+        message.push_str(": ");
+        message.push_str(&s.to_string());
+        source = s.source();
+    }
+    let entry = ErrorLogEntry {
+        url: url.clone(),
+        kind: e.kind(),
+        status: e.status(),
+        message,
+        request: client.describe_request(url, collection),
+    };
+    serde_json::to_writer(&mut *fp, &entry).context("failed to write to error log")?;
+    writeln!(fp).context("failed to write to error log")?;
+    Ok(())
+}
+
+/// Printer for the details of each request, either as each one completes or,
+/// when sorting, all at once in canonical path order
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct RequestPrinter {
+    mode: PrintMode,
+    /// Prefix each line with the time at which it was produced
+    timestamps: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum PrintMode {
+    Quiet,
+    Immediate,
+    Sorted(Vec<(Url, String)>),
+}
+
+impl RequestPrinter {
+    fn new(quiet: bool, sort: bool, timestamps: bool) -> RequestPrinter {
+        let mode = if quiet {
+            PrintMode::Quiet
+        } else if sort {
+            PrintMode::Sorted(Vec::new())
+        } else {
+            PrintMode::Immediate
+        };
+        RequestPrinter { mode, timestamps }
+    }
+
+    fn print<F: FnOnce() -> String>(&mut self, url: &Url, line: F) {
+        let timestamps = self.timestamps;
+        let line = || {
+            if timestamps {
+                timestamped(&line())
+            } else {
+                line()
+            }
+        };
+        match &mut self.mode {
+            PrintMode::Quiet => (),
+            PrintMode::Immediate => println!("{}", line()),
+            PrintMode::Sorted(lines) => lines.push((url.clone(), line())),
+        }
+    }
+
+    /// Print any buffered lines
+    fn finish(&mut self) {
+        if let PrintMode::Sorted(lines) = &mut self.mode {
+            let mut lines = std::mem::take(lines);
+            lines.sort_by_cached_key(|(url, _)| path_order_key(url));
+            for (_, line) in lines {
+                println!("{line}");
+            }
+        }
+    }
+}
+
+/// A request to be made during traversal
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Task {
+    /// List the collection at `url`
+    Dir {
+        url: Url,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+    },
+    /// Probe the non-collection resource at `url`
+    File {
+        url: Url,
+        size: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+    },
+    /// List the Zarr store at `url` and everything inside it with a single
+    /// request
+    Zarr {
+        url: Url,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+    },
+}
+
+impl Task {
+    pub(crate) fn url(&self) -> &Url {
+        match self {
+            Task::Dir { url, .. } => url,
+            Task::File { url, .. } => url,
+            Task::Zarr { url, .. } => url,
+        }
+    }
+
+    /// The pool of the traversal's `BoundedTreeNursery` in which to run the
+    /// task: `DIR_POOL` for collection listings and `FILE_POOL` for probes
+    fn pool(&self) -> usize {
+        match self {
+            Task::Dir { .. } | Task::Zarr { .. } => DIR_POOL,
+            Task::File { .. } => FILE_POOL,
+        }
+    }
+}
+
+/// The nursery pool for tasks that list collections when separate pools are
+/// in use
+const DIR_POOL: usize = 0;
+
+/// The nursery pool for tasks that probe non-collection resources when
+/// separate pools are in use
+const FILE_POOL: usize = 1;
+
+/// The samples of the number of requests in flight taken during a traversal.
+/// If `limit` is set, then whenever that many samples have been taken, every
+/// other one is discarded and samples are taken half as often from then on,
+/// so that long traversals keep an evenly spaced timeline of bounded size.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ConcurrencySamples {
+    samples: Vec<ConcurrencySample>,
+    limit: Option<usize>,
+    /// Only one out of every `stride` ticks is sampled
+    stride: usize,
+    /// Number of ticks since the last sample was taken
+    ticks: usize,
+}
+
+impl ConcurrencySamples {
+    fn new(limit: Option<usize>) -> ConcurrencySamples {
+        ConcurrencySamples {
+            samples: Vec::new(),
+            limit,
+            stride: 1,
+            ticks: 0,
+        }
+    }
+
+    /// Record the sample taken at a tick of the sampling interval
+    fn push(&mut self, sample: ConcurrencySample) {
+        self.ticks = self.ticks.saturating_add(1);
+        if self.ticks < self.stride {
+            return;
+        }
+        self.ticks = 0;
+        self.samples.push(sample);
+        if self.limit.is_some_and(|limit| self.samples.len() >= limit) {
+            // Keep the odd-numbered samples so that the most recent one is
+            // kept and the next one falls `stride * 2` ticks after it
+            let mut odd = false;
+            self.samples.retain(|_| {
+                odd = !odd;
+                !odd
+            });
+            self.stride = self.stride.saturating_mul(2);
+        }
+    }
+}
+
+/// Accumulator for the statistics reported in a `TraversalReport`
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Collector {
+    low_memory: bool,
+    directory_request_times: Latencies,
+    file_request_times: Latencies,
+    file_ttfb: Latencies,
+    queue_waits: Latencies,
+    total_queue_wait: Duration,
+    total_service_time: Duration,
+    directory_repeat_times: Latencies,
+    file_repeat_times: Latencies,
+    repeat_failures: usize,
+    /// Timings of `HEAD` & `GET` requests made for the same files
+    method_pairs: MethodPairs,
+    fanout: FanoutStats,
+    duplicate_hrefs: usize,
+    excluded_entries: usize,
+    listing_sizes: ResponseSizes,
+    servers: ServerFingerprints,
+    etag_index: BTreeMap<String, Vec<(Url, Option<u64>)>>,
+    mtimes: ModificationStats,
+    content_types: ContentTypeStats,
+    stale_files: usize,
+    response_descriptions: BTreeMap<String, usize>,
+    total_bytes: u64,
+    host_request_times: BTreeMap<String, Latencies>,
+    host_redirects: BTreeMap<String, usize>,
+    redirect_chains: RedirectChainStats,
+    hop_times: Latencies,
+    statuses: StatusDistribution,
+    errors: ErrorSummary,
+    warnings: WarningSummary,
+    request_records: Option<Vec<RequestRecord>>,
+    concurrency: ConcurrencySamples,
+    zarr_stores: usize,
+    zarr_request_times: Latencies,
+    zarr_directories: usize,
+    zarr_files: usize,
+    zarr_bytes: u64,
+}
+
+impl Collector {
+    fn new(low_memory: bool) -> Collector {
+        Collector {
+            low_memory,
+            directory_request_times: Latencies::new(low_memory),
+            file_request_times: Latencies::new(low_memory),
+            file_ttfb: Latencies::new(low_memory),
+            queue_waits: Latencies::new(low_memory),
+            directory_repeat_times: Latencies::new(low_memory),
+            file_repeat_times: Latencies::new(low_memory),
+            hop_times: Latencies::new(low_memory),
+            zarr_request_times: Latencies::new(low_memory),
+            method_pairs: MethodPairs::new(low_memory),
+            concurrency: ConcurrencySamples::new(low_memory.then_some(MAX_CONCURRENCY_SAMPLES)),
+            ..Collector::default()
+        }
+    }
+
+    /// Return the latencies recorded for requests to the given host
+    fn host_times(&mut self, url: &Url) -> &mut Latencies {
+        let low_memory = self.low_memory;
+        self.host_request_times
+            .entry(host_key(url))
+            .or_insert_with(|| Latencies::new(low_memory))
+    }
+
+    fn record(&mut self, r: Report) {
+        let (queue_wait, service_time) = r.task_times();
+        self.queue_waits.push(queue_wait);
+        self.total_queue_wait = self.total_queue_wait.saturating_add(queue_wait);
+        self.total_service_time = self.total_service_time.saturating_add(service_time);
+        match r {
+            Report::Dir {
+                url,
+                elapsed,
+                listing,
+                stale_files,
+                duplicates,
+                excluded,
+                descriptions,
+                warnings,
+                status,
+                propstat_statuses,
+                body_size,
+                server_headers,
+                zarr_stores,
+                repeat_times,
+                repeat_failures,
+                ..
+            } => {
+                for d in repeat_times {
+                    self.directory_repeat_times.push(d);
+                }
+                self.repeat_failures = self.repeat_failures.saturating_add(repeat_failures);
+                self.listing_sizes.add(body_size);
+                self.zarr_stores = self.zarr_stores.saturating_add(zarr_stores.len());
+                self.servers.add(&server_headers);
+                self.duplicate_hrefs = self.duplicate_hrefs.saturating_add(duplicates);
+                self.excluded_entries = self.excluded_entries.saturating_add(excluded);
+                for text in descriptions {
+                    let count = self.response_descriptions.entry(text).or_default();
+                    *count = count.saturating_add(1);
+                }
+                for w in warnings {
+                    self.warnings.add(w.kind(), &url, w.to_string());
+                }
+                for f in listing.files.iter().chain(&stale_files) {
+                    // Indexing every file by ETag would take memory
+                    // proportional to the size of the hierarchy
+                    if let Some(etag) = f.etag.as_ref().filter(|_| !self.low_memory) {
+                        self.etag_index
+                            .entry(etag.clone())
+                            .or_default()
+                            .push((f.href.clone(), f.size));
+                    }
+                    if let Some(modified) = f.modified {
+                        self.mtimes.add(&f.href, modified);
+                    }
+                    self.content_types.add(f.content_type.as_deref(), f.size);
+                }
+                self.stale_files = self.stale_files.saturating_add(stale_files.len());
+                let children = listing
+                    .directories
+                    .len()
+                    .saturating_add(listing.files.len())
+                    .saturating_add(stale_files.len())
+                    .saturating_add(zarr_stores.len());
+                self.statuses.responses.add(status);
+                for code in propstat_statuses {
+                    self.statuses.propstats.add(code);
+                }
+                self.directory_request_times.push(elapsed);
+                self.fanout.add(children);
+                self.host_times(&url).push(elapsed);
+            }
+            Report::File {
+                url,
+                ttfb,
+                elapsed,
+                target,
+                size,
+                status,
+                chain,
+                get_timing,
+                server_headers,
+                repeat_times,
+                repeat_failures,
+                ..
+            } => {
+                for d in repeat_times {
+                    self.file_repeat_times.push(d);
+                }
+                self.repeat_failures = self.repeat_failures.saturating_add(repeat_failures);
+                self.servers.add(&server_headers);
+                self.statuses.responses.add(status);
+                self.file_request_times.push(elapsed);
+                self.file_ttfb.push(ttfb);
+                if let Some(get) = get_timing {
+                    self.statuses.responses.add(get.status);
+                    let head = ProbeTiming {
+                        status,
+                        ttfb,
+                        elapsed,
+                    };
+                    self.method_pairs.add(head, get);
+                }
+                self.total_bytes = self.total_bytes.saturating_add(size.unwrap_or_default());
+                self.host_times(&url).push(elapsed);
+                if let Some(t) = target {
+                    let redirects = self.host_redirects.entry(host_key(&t)).or_default();
+                    *redirects = redirects.saturating_add(1);
+                }
+                if let Some(chain) = chain {
+                    self.redirect_chains.add(chain.hops.len(), chain.truncated);
+                    for (i, hop) in chain.hops.into_iter().enumerate() {
+                        self.statuses.responses.add(hop.status);
+                        self.hop_times.push(hop.elapsed);
+                        self.host_times(&hop.url).push(hop.elapsed);
+                        // The first hop's URL was already counted as the
+                        // initial request's target
+                        if i > 0 {
+                            let redirects =
+                                self.host_redirects.entry(host_key(&hop.url)).or_default();
+                            *redirects = redirects.saturating_add(1);
+                        }
+                    }
+                }
+            }
+            Report::Zarr {
+                url,
+                elapsed,
+                listing,
+                status,
+                server_headers,
+                ..
+            } => {
+                self.servers.add(&server_headers);
+                self.statuses.responses.add(status);
+                self.zarr_request_times.push(elapsed);
+                self.host_times(&url).push(elapsed);
+                self.zarr_directories = self
+                    .zarr_directories
+                    .saturating_add(listing.directories.len());
+                self.zarr_files = self.zarr_files.saturating_add(listing.files.len());
+                for f in &listing.files {
+                    self.zarr_bytes = self.zarr_bytes.saturating_add(f.size.unwrap_or_default());
+                }
+            }
+        }
+    }
+
+    fn record_failure(&mut self, failure: Failure) {
+        if let Some(status) = failure.status {
+            self.statuses.responses.add(status);
+        }
+        self.errors.add(failure.kind, &failure.url, failure.message);
+    }
+
+    fn progress(&self, workers: usize, elapsed: Duration) -> ProgressEvent {
+        ProgressEvent::Progress {
+            workers,
+            requests: self
+                .directory_request_times
+                .len()
+                .saturating_add(self.file_request_times.len())
+                .saturating_add(self.zarr_request_times.len()),
+            errors: self.errors.total(),
+            elapsed,
+        }
+    }
+
+    fn finish(
+        self,
+        root: Url,
+        workers: usize,
+        client_options: &ClientOptions,
+        topts: &TaskOptions,
+        start_time: OffsetDateTime,
+        overall_time: Duration,
+    ) -> TraversalReport {
+        let zarr_boundary = topts.zarr_boundary;
+        let directory_latency = self.directory_request_times.summary();
+        let file_latency = self.file_request_times.summary();
+        let mut hosts = BTreeMap::new();
+        for (host, times) in self.host_request_times {
+            hosts.insert(
+                host,
+                HostStats {
+                    latency: times.summary(),
+                    redirect_targets: 0,
+                },
+            );
+        }
+        for (host, qty) in self.host_redirects {
+            hosts
+                .entry(host)
+                .or_insert(HostStats {
+                    latency: None,
+                    redirect_targets: 0,
+                })
+                .redirect_targets = qty;
+        }
+        TraversalReport {
+            root,
+            workers,
+            labels: BTreeMap::new(),
+            runtime: None,
+            peak_concurrency: None,
+            mean_concurrency: None,
+            directories: self.directory_request_times.len(),
+            files: self.file_request_times.len(),
+            total_bytes: client_options.sizes.then_some(self.total_bytes),
+            directory_latency,
+            file_latency,
+            file_ttfb: self.file_ttfb.summary(),
+            queue_wait: self.queue_waits.summary(),
+            pipeline: PipelineStats::new(
+                self.queue_waits.len(),
+                self.total_queue_wait,
+                self.total_service_time,
+                overall_time,
+            ),
+            method_comparison: (client_options.file_method == FileMethod::Both)
+                .then(|| self.method_pairs.comparison()),
+            directory_request_times: self.directory_request_times.into_times(),
+            file_request_times: self.file_request_times.into_times(),
+            overall_time,
+            start_time,
+            end_time: start_time + overall_time,
+            fanout: self.fanout,
+            listing_sizes: self.listing_sizes.stats(),
+            servers: self.servers,
+            duplicate_hrefs: self.duplicate_hrefs,
+            excluded_entries: self.excluded_entries,
+            duplicate_files: (client_options.etags && !self.low_memory)
+                .then(|| DuplicateStats::new(self.etag_index)),
+            modification_times: client_options.mtimes.then(|| {
+                let mut stats = self.mtimes;
+                stats.skipped = self.stale_files;
+                stats
+            }),
+            content_types: client_options.content_types.then(|| {
+                let mut stats = self.content_types;
+                if !client_options.sizes {
+                    stats.omit_sizes();
+                }
+                stats
+            }),
+            response_descriptions: self.response_descriptions,
+            redirect_chains: topts.follow_redirects.is_some().then(|| {
+                let mut stats = self.redirect_chains;
+                stats.hop_latency = self.hop_times.summary();
+                stats
+            }),
+            hosts,
+            statuses: self.statuses,
+            errors: self.errors,
+            warnings: self.warnings,
+            requests: self.request_records,
+            concurrency: ConcurrencyTimeline::new(workers, self.concurrency.samples),
+            worker_fairness: None,
+            pools: None,
+            adaptive: None,
+            slow_requests: None,
+            timeouts: None,
+            hedges: None,
+            stopped_early: false,
+            inventory_mismatch: false,
+            error_rate_abort: None,
+            panics: Vec::new(),
+            coalesced_requests: None,
+            relogins: None,
+            clock_skew: None,
+            outages: None,
+            incremental: None,
+            zarr: (zarr_boundary != ZarrBoundary::Descend).then(|| ZarrStats {
+                boundary: zarr_boundary,
+                stores: self.zarr_stores,
+                summarized: self.zarr_request_times.len(),
+                directories: self.zarr_directories,
+                files: self.zarr_files,
+                total_bytes: client_options.sizes.then_some(self.zarr_bytes),
+                latency: self.zarr_request_times.summary(),
+            }),
+            repeats: (topts.repeat_requests > 0).then(|| RepeatStats {
+                repeats: topts.repeat_requests,
+                directory_first: directory_latency,
+                directory_repeat: self.directory_repeat_times.summary(),
+                file_first: file_latency,
+                file_repeat: self.file_repeat_times.summary(),
+                failures: self.repeat_failures,
+            }),
+            bandwidth: None,
+            conditional: None,
+            roots: Vec::new(),
+            urls: None,
+        }
+    }
+}
+
+/// Request statistics for a single host
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct HostStats {
+    /// Latencies of requests made to the host, if any
+    pub(crate) latency: Option<LatencySummary>,
+    /// Number of file requests that redirected to the host
+    pub(crate) redirect_targets: usize,
+}
+
+/// Return the host (and port, if not the default for the scheme) of a URL
+fn host_key(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_owned(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub(crate) struct TraversalReport {
+    /// The URL at which the traversal started
+    pub(crate) root: Url,
     pub(crate) workers: usize,
+    /// User-defined labels given with `--label`
+    pub(crate) labels: BTreeMap<String, String>,
+    /// The configuration of the async runtime on which the traversal ran, if
+    /// known
+    pub(crate) runtime: Option<RuntimeConfig>,
+    /// Greatest number of requests (or rather request tasks) in flight at
+    /// once; `None` for the per-root reports of a multi-root traversal
+    pub(crate) peak_concurrency: Option<usize>,
+    /// Time-averaged number of request tasks in flight, i.e., the mean of the
+    /// concurrency timeline; `None` for the per-root reports of a multi-root
+    /// traversal and for traversals too short to be sampled
+    pub(crate) mean_concurrency: Option<f64>,
+    pub(crate) directories: usize,
+    pub(crate) files: usize,
+    /// Total size of all files, if sizes were requested
+    pub(crate) total_bytes: Option<u64>,
+    /// Latencies of the `PROPFIND` requests; approximate in low-memory mode
+    pub(crate) directory_latency: Option<LatencySummary>,
+    /// Latencies of the requests made with the file probing method;
+    /// approximate in low-memory mode
+    pub(crate) file_latency: Option<LatencySummary>,
+    /// Times to first byte of the requests made with the file probing
+    /// method; approximate in low-memory mode
+    pub(crate) file_ttfb: Option<LatencySummary>,
+    /// Times that request tasks spent waiting for a free worker before their
+    /// requests were made; approximate in low-memory mode
+    pub(crate) queue_wait: Option<LatencySummary>,
+    /// Decomposition of the time request tasks spent in the traversal into
+    /// queueing & service times
+    pub(crate) pipeline: Option<PipelineStats>,
+    /// Comparison of `HEAD` & `GET` request latencies, if files were probed
+    /// with both methods
+    pub(crate) method_comparison: Option<MethodComparison>,
+    /// The individual `PROPFIND` latencies (empty in low-memory mode)
     pub(crate) directory_request_times: Vec<Duration>,
+    /// The individual latencies of the requests made with the file probing
+    /// method (empty in low-memory mode)
     pub(crate) file_request_times: Vec<Duration>,
     pub(crate) overall_time: Duration,
+    /// Wall-clock time at which the traversal started
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) start_time: OffsetDateTime,
+    /// Wall-clock time at which the traversal ended, i.e., `start_time` plus
+    /// `overall_time`
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) end_time: OffsetDateTime,
+    pub(crate) fanout: FanoutStats,
+    /// Distribution of the sizes of the `PROPFIND` response bodies
+    pub(crate) listing_sizes: ResponseSizeStats,
+    /// The distinct values of the server-identifying headers in responses
+    /// (not including responses to redirect hops)
+    pub(crate) servers: ServerFingerprints,
+    /// Number of hrefs in listings that were skipped as duplicates of other
+    /// hrefs in the same listing after normalization
+    pub(crate) duplicate_hrefs: usize,
+    /// Number of entries in listings that were neither listed nor probed
+    /// because they were excluded by the traversal's filters
+    pub(crate) excluded_entries: usize,
+    /// Number of requests that shared the result of an identical request in
+    /// flight, if requests were coalesced
+    pub(crate) coalesced_requests: Option<usize>,
+    /// Number of times the client logged in again after finding its session
+    /// expired, if a login flow was configured
+    pub(crate) relogins: Option<u64>,
+    /// The estimated offset of the server's clock from the local clock, if
+    /// any responses had `Date` headers
+    pub(crate) clock_skew: Option<ClockSkew>,
+    /// The periods during which requests were paused because the server was
+    /// down for maintenance, if pausing for maintenance was enabled
+    pub(crate) outages: Option<Vec<Outage>>,
+    /// Groups of files sharing ETags, if ETags were requested
+    pub(crate) duplicate_files: Option<DuplicateStats>,
+    /// Modification time statistics, if modification times were requested
+    pub(crate) modification_times: Option<ModificationStats>,
+    /// File counts & sizes by MIME type, if content types were requested
+    pub(crate) content_types: Option<ContentTypeStats>,
+    /// The distinct texts of `<responsedescription>` elements in listing
+    /// responses, with the number of times each occurred
+    pub(crate) response_descriptions: BTreeMap<String, usize>,
+    /// Redirect chain statistics, if redirect chains were followed
+    pub(crate) redirect_chains: Option<RedirectChainStats>,
+    /// Request statistics broken down by host
+    pub(crate) hosts: BTreeMap<String, HostStats>,
+    /// Distribution of HTTP & propstat status codes
+    pub(crate) statuses: StatusDistribution,
+    /// Failed requests, by category (only populated when errors are skipped)
+    pub(crate) errors: ErrorSummary,
+    /// Problems with listing responses that did not prevent them from being
+    /// processed, by category
+    pub(crate) warnings: WarningSummary,
+    /// The timing of each completed request, in order of completion, if
+    /// request timestamps were requested
+    pub(crate) requests: Option<Vec<RequestRecord>>,
+    /// The number of requests in flight, sampled every
+    /// `CONCURRENCY_INTERVAL` (less often on long traversals in low-memory
+    /// mode)
+    pub(crate) concurrency: ConcurrencyTimeline,
+    /// How evenly the requests were distributed across the workers
+    pub(crate) worker_fairness: Option<FairnessStats>,
+    /// The utilization of the separate pools for collection listings & file
+    /// probes, if separate pools were used
+    pub(crate) pools: Option<WorkerPools>,
+    /// The adjustments made to the number of workers, if `--adaptive-workers`
+    /// was given
+    pub(crate) adaptive: Option<AdaptiveStats>,
+    /// Counts of requests slower than the `--log-slower-than` threshold, if
+    /// given
+    pub(crate) slow_requests: Option<SlowRequestStats>,
+    /// Counts of timed-out request attempts, if a client-side timeout was
+    /// set or any attempts timed out
+    pub(crate) timeouts: Option<TimeoutStats>,
+    /// Counts of duplicate requests sent, if `--hedge-after` was given
+    pub(crate) hedges: Option<HedgeStats>,
+    /// True if the traversal was stopped before completion upon reaching the
+    /// `max_dirs` limit
+    pub(crate) stopped_early: bool,
+    /// The error rate that caused the traversal to be stopped before
+    /// completion, if the `max_error_rate` limit was exceeded
+    pub(crate) error_rate_abort: Option<ErrorRateAbort>,
+    /// True if `--verify-inventory` found that the traversal did not find the
+    /// same inventory as the first traversal of its batch, in which case the
+    /// traversal is left out when the report is analyzed or resumed
+    pub(crate) inventory_mismatch: bool,
+    /// The messages of the panics of request tasks whose results were
+    /// discarded under `PanicPolicy::Skip`
+    pub(crate) panics: Vec<String>,
+    /// Statistics on the collections skipped as unchanged since the previous
+    /// traversal, if skipping them was enabled
+    pub(crate) incremental: Option<IncrementalStats>,
+    /// Statistics on Zarr stores, if Zarr stores were not descended into
+    pub(crate) zarr: Option<ZarrStats>,
+    /// First-hit vs. repeated-request latencies, if requests were repeated
+    pub(crate) repeats: Option<RepeatStats>,
+    /// Download rates of a random sample of files, if requested
+    pub(crate) bandwidth: Option<BandwidthStats>,
+    /// Results of checking the server's handling of conditional requests, if
+    /// requested
+    pub(crate) conditional: Option<ConditionalStats>,
+    /// Separate reports for each root when multiple roots were traversed
+    /// together, each with an `overall_time` giving the time at which the
+    /// last request under that root completed
+    pub(crate) roots: Vec<TraversalReport>,
+    /// The URLs of all collections & files found, if requested
+    #[serde(skip)]
+    pub(crate) urls: Option<BTreeSet<Url>>,
+}
+
+/// The timing of a single completed request
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct RequestRecord {
+    pub(crate) url: Url,
+    #[serde(rename = "type")]
+    pub(crate) kind: RequestKind,
+    /// The HTTP method of the request
+    pub(crate) method: String,
+    pub(crate) status: u16,
+    /// Time at which the request (or its final attempt, if retried) was sent,
+    /// relative to the start of the traversal, as measured by a monotonic
+    /// clock
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) start: Duration,
+    /// Time at which the request completed, relative to the start of the
+    /// traversal, as measured by a monotonic clock
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) end: Duration,
+    /// Wall-clock time at which the request was sent
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) start_time: OffsetDateTime,
+    /// Wall-clock time at which the request completed
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) end_time: OffsetDateTime,
+    /// Time the request's task spent waiting for a worker before the request
+    /// was started
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) queue_wait: Duration,
+    /// Time from when the request's task began making the request until the
+    /// task finished, including any retries, redirects followed, and repeats
+    /// made
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) service_time: Duration,
+    /// Time from when the request's task was scheduled until it finished; the
+    /// sum of `queue_wait` & `service_time`
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) sojourn_time: Duration,
+    /// The `X-Request-Id` sent with the request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) request_id: Option<String>,
+    /// The ID of the traversal, if correlation IDs were enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) traversal_id: Option<String>,
+    /// The `traceparent` sent with the request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) traceparent: Option<String>,
+    /// Whether & how the request was retried after each failed attempt
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) retries: Vec<RetryDecision>,
+}
+
+/// The type of resource requested
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RequestKind {
+    Dir,
+    File,
+    Zarr,
+}
+
+impl RequestKind {
+    /// The label for requests of this kind in log lines
+    fn label(self) -> &'static str {
+        match self {
+            RequestKind::Dir => "DIR",
+            RequestKind::File => "FILE",
+            RequestKind::Zarr => "ZARR",
+        }
+    }
+}
+
+/// A failed request made by a traversal task, along with how long the
+/// request took to fail
+#[derive(Debug)]
+struct TaskError {
+    source: RequestError,
+    kind: RequestKind,
+    elapsed: Duration,
+}
+
+impl TaskError {
+    fn new(source: RequestError, kind: RequestKind, begun: &RequestStart) -> TaskError {
+        TaskError {
+            source,
+            kind,
+            elapsed: begun.instant.elapsed(),
+        }
+    }
+
+    /// Returns a line describing the failed request for logging slow
+    /// requests
+    fn slow_line<'a>(&'a self, base: Option<&'a Url>) -> SlowFailureLine<'a> {
+        SlowFailureLine { error: self, base }
+    }
+}
+
+/// The time at which a request was started, according to both the monotonic
+/// & wall clocks
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct RequestStart {
+    instant: Instant,
+    time: OffsetDateTime,
+}
+
+impl RequestStart {
+    fn now() -> RequestStart {
+        RequestStart {
+            instant: Instant::now(),
+            time: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Given the time at which a task began making a request, return the
+    /// time at which the request was actually sent, as recorded in `ids`.  If
+    /// the response did not come from the server, `self` is returned.
+    fn sent(self, ids: &RequestIds) -> RequestStart {
+        match ids.sent {
+            Some(instant) => RequestStart {
+                instant,
+                time: self.time.saturating_add(
+                    instant
+                        .saturating_duration_since(self.instant)
+                        .try_into()
+                        .unwrap_or(time::Duration::MAX),
+                ),
+            },
+            None => self,
+        }
+    }
 }
 
 impl TraversalReport {
     pub(crate) fn requests(&self) -> usize {
-        self.directory_request_times
-            .len()
-            .saturating_add(self.file_request_times.len())
+        self.directories
+            .saturating_add(self.files)
+            .saturating_add(self.zarr.as_ref().map_or(0, |z| z.summarized))
+            .saturating_add(self.repeats.map_or(0, |r| {
+                let count = |lat: Option<LatencySummary>| lat.map_or(0, |l| l.count);
+                count(r.directory_repeat)
+                    .saturating_add(count(r.file_repeat))
+                    .saturating_add(r.failures)
+            }))
+    }
+
+    /// Discard the individual request latencies & records from the report
+    /// and the reports for its roots, leaving only the aggregates
+    pub(crate) fn clear_request_times(&mut self) {
+        self.directory_request_times = Vec::new();
+        self.file_request_times = Vec::new();
+        self.requests = None;
+        for r in &mut self.roots {
+            r.clear_request_times();
+        }
     }
 }
 
@@ -55,60 +1748,896 @@ impl TraversalReport {
 enum Report {
     Dir {
         url: Url,
+        display_name: Option<String>,
+        started: RequestStart,
+        /// Time the task spent waiting for a free worker before the request
+        /// was made
+        queue_wait: Duration,
+        /// Time from when the task began making the request until it
+        /// finished, including any retries, redirects followed, and repeats
+        /// made
+        service_time: Duration,
         elapsed: Duration,
+        listing: DirectoryListing<Url>,
+        /// Files in the listing that will not be probed because they were
+        /// not modified since the `modified_since` cutoff
+        stale_files: Vec<Entry<Url>>,
+        duplicates: usize,
+        /// Number of entries dropped from the listing by the traversal's
+        /// filters
+        excluded: usize,
+        descriptions: Vec<String>,
+        warnings: Vec<ResponseWarning>,
+        status: u16,
+        propstat_statuses: Vec<u16>,
+        /// Size of the response body in bytes
+        body_size: u64,
+        server_headers: ServerHeaders,
+        ids: RequestIds,
+        /// Zarr stores in the listing that are not traversed like other
+        /// collections
+        zarr_stores: Vec<Entry<Url>>,
+        /// Collections in the listing that will not be listed because their
+        /// ETags are unchanged since the previous traversal
+        unchanged_dirs: Vec<Entry<Url>>,
+        /// Latencies of the repetitions of the request
+        repeat_times: Vec<Duration>,
+        /// Number of repetitions of the request that failed
+        repeat_failures: usize,
     },
     File {
         url: Url,
+        display_name: Option<String>,
+        started: RequestStart,
+        /// Time the task spent waiting for a free worker before the request
+        /// was made
+        queue_wait: Duration,
+        /// Time from when the task began making the request until it
+        /// finished, including any retries, redirects followed, and repeats
+        /// made
+        service_time: Duration,
+        /// Time until the response headers were received
+        ttfb: Duration,
         elapsed: Duration,
         target: Option<Url>,
+        size: Option<u64>,
+        status: u16,
+        chain: Option<RedirectChain>,
+        /// The timing of the follow-up `GET` request, if probing with both
+        /// methods
+        get_timing: Option<ProbeTiming>,
+        server_headers: ServerHeaders,
+        ids: RequestIds,
+        /// Latencies of the repetitions of the request
+        repeat_times: Vec<Duration>,
+        /// Number of repetitions of the request that failed
+        repeat_failures: usize,
+    },
+    Zarr {
+        url: Url,
+        display_name: Option<String>,
+        started: RequestStart,
+        /// Time the task spent waiting for a free worker before the request
+        /// was made
+        queue_wait: Duration,
+        /// Time from when the task began making the request until it
+        /// finished, including any retries, redirects followed, and repeats
+        /// made
+        service_time: Duration,
+        elapsed: Duration,
+        /// Everything inside the store
+        listing: DirectoryListing<Url>,
+        status: u16,
+        server_headers: ServerHeaders,
+        ids: RequestIds,
     },
 }
 
-impl fmt::Display for Report {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Report {
+    /// Returns a line describing the request for per-request output
+    fn line<'a>(&'a self, base: Option<&'a Url>) -> ReportLine<'a> {
+        ReportLine { report: self, base }
+    }
+
+    fn url(&self) -> &Url {
         match self {
-            Report::Dir { url, elapsed } => write!(f, "DIR: {url} ({elapsed:?})"),
+            Report::Dir { url, .. } => url,
+            Report::File { url, .. } => url,
+            Report::Zarr { url, .. } => url,
+        }
+    }
+
+    /// Returns the time the task spent waiting for a worker and the time it
+    /// then spent running
+    fn task_times(&self) -> (Duration, Duration) {
+        match self {
+            Report::Dir {
+                queue_wait,
+                service_time,
+                ..
+            }
+            | Report::File {
+                queue_wait,
+                service_time,
+                ..
+            }
+            | Report::Zarr {
+                queue_wait,
+                service_time,
+                ..
+            } => (*queue_wait, *service_time),
+        }
+    }
+
+    /// Returns the type of request made
+    fn kind(&self) -> RequestKind {
+        match self {
+            Report::Dir { .. } => RequestKind::Dir,
+            Report::File { .. } => RequestKind::File,
+            Report::Zarr { .. } => RequestKind::Zarr,
+        }
+    }
+
+    /// Returns the latency of the request
+    fn elapsed(&self) -> Duration {
+        match self {
+            Report::Dir { elapsed, .. }
+            | Report::File { elapsed, .. }
+            | Report::Zarr { elapsed, .. } => *elapsed,
+        }
+    }
+
+    /// Returns a line describing the request and the phases of its task for
+    /// logging slow requests
+    fn slow_line<'a>(&'a self, base: Option<&'a Url>) -> SlowLine<'a> {
+        SlowLine { report: self, base }
+    }
+
+    /// Returns the task that was processed to produce this report
+    fn task(&self) -> Task {
+        match self {
+            Report::Dir {
+                url, display_name, ..
+            } => Task::Dir {
+                url: url.clone(),
+                display_name: display_name.clone(),
+            },
+            Report::File {
+                url,
+                display_name,
+                size,
+                ..
+            } => Task::File {
+                url: url.clone(),
+                size: *size,
+                display_name: display_name.clone(),
+            },
+            Report::Zarr {
+                url, display_name, ..
+            } => Task::Zarr {
+                url: url.clone(),
+                display_name: display_name.clone(),
+            },
+        }
+    }
+
+    /// Returns the timing of the request, with monotonic times given relative
+    /// to `traversal_start`, labelled with the ID of the traversal, if any.
+    /// For files whose redirects were followed, only the initial request is
+    /// covered.  `listing_method` and `file_method` are the HTTP methods with
+    /// which collections and non-collection resources were requested.
+    fn record(
+        &self,
+        traversal_start: Instant,
+        traversal_id: Option<&str>,
+        listing_method: &str,
+        file_method: &str,
+    ) -> RequestRecord {
+        let (url, kind, started, elapsed, status, ids) = match self {
+            Report::Dir {
+                url,
+                started,
+                elapsed,
+                status,
+                ids,
+                ..
+            } => (url, RequestKind::Dir, started, elapsed, status, ids),
+            Report::File {
+                url,
+                started,
+                elapsed,
+                status,
+                ids,
+                ..
+            } => (url, RequestKind::File, started, elapsed, status, ids),
+            Report::Zarr {
+                url,
+                started,
+                elapsed,
+                status,
+                ids,
+                ..
+            } => (url, RequestKind::Zarr, started, elapsed, status, ids),
+        };
+        let start = started.instant.saturating_duration_since(traversal_start);
+        let (queue_wait, service_time) = self.task_times();
+        let method = match kind {
+            RequestKind::File => file_method,
+            RequestKind::Dir | RequestKind::Zarr => listing_method,
+        };
+        RequestRecord {
+            url: url.clone(),
+            kind,
+            method: method.to_owned(),
+            status: *status,
+            start,
+            end: start.saturating_add(*elapsed),
+            start_time: started.time,
+            end_time: started
+                .time
+                .saturating_add((*elapsed).try_into().unwrap_or(time::Duration::MAX)),
+            queue_wait,
+            service_time,
+            sojourn_time: queue_wait.saturating_add(service_time),
+            request_id: ids.request_id.clone(),
+            traversal_id: traversal_id.map(String::from),
+            traceparent: ids.traceparent.clone(),
+            retries: ids.retries.clone(),
+        }
+    }
+
+    /// Returns the non-collection resources listed in the response, including
+    /// those that will not be probed
+    fn listed_files(&self) -> impl Iterator<Item = &Entry<Url>> {
+        let (files, stale): (&[Entry<Url>], &[Entry<Url>]) = match self {
+            Report::Dir {
+                listing,
+                stale_files,
+                ..
+            } => (&listing.files, stale_files),
+            Report::Zarr { listing, .. } => (&listing.files, &[]),
+            Report::File { .. } => (&[], &[]),
+        };
+        files.iter().chain(stale)
+    }
+
+    /// Returns the non-collection resources whose discovery is complete as
+    /// of this request — the resource probed, if any, along with its final
+    /// redirect target, plus any resources listed that will not be probed
+    fn discovered_files(&self) -> Vec<(&Url, Option<&Url>)> {
+        match self {
+            Report::Dir { stale_files, .. } => {
+                stale_files.iter().map(|f| (&f.href, None)).collect()
+            }
+            Report::Zarr { listing, .. } => listing.files.iter().map(|f| (&f.href, None)).collect(),
+            Report::File {
+                url, target, chain, ..
+            } => {
+                let last_hop = chain.as_ref().and_then(|c| c.hops.last()).map(|h| &h.url);
+                vec![(url, last_hop.or(target.as_ref()))]
+            }
+        }
+    }
+
+    /// If this is a collection listing, record the collection and the ETags
+    /// of the collections in it in `state`, and return the collections that
+    /// were skipped as unchanged
+    fn record_etags(&self, state: &mut EtagState) -> &[Entry<Url>] {
+        match self {
+            Report::Dir {
+                url,
+                listing,
+                stale_files,
+                unchanged_dirs,
+                ..
+            } => {
+                let files = listing.files.len().saturating_add(stale_files.len());
+                state.record_listing(url, files, &listing.directories);
+                unchanged_dirs
+            }
+            Report::File { .. } | Report::Zarr { .. } => &[],
+        }
+    }
+
+    /// Returns the tasks spawned as a result of the request
+    fn child_tasks(&self, zarr_boundary: ZarrBoundary) -> Vec<Task> {
+        match self {
+            Report::Dir {
+                listing,
+                zarr_stores,
+                ..
+            } => {
+                let mut tasks = listing_tasks(listing);
+                tasks.extend(zarr_tasks(zarr_stores, zarr_boundary));
+                tasks
+            }
+            Report::File { .. } | Report::Zarr { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Returns the tasks for processing the Zarr stores in a directory listing
+fn zarr_tasks(stores: &[Entry<Url>], zarr_boundary: ZarrBoundary) -> Vec<Task> {
+    if zarr_boundary == ZarrBoundary::Summarize {
+        stores
+            .iter()
+            .map(|d| Task::Zarr {
+                url: d.href.clone(),
+                display_name: d.display_name.clone(),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Returns the tasks for processing the entries in a directory listing
+fn listing_tasks(listing: &DirectoryListing<Url>) -> Vec<Task> {
+    listing
+        .directories
+        .iter()
+        .map(|d| Task::Dir {
+            url: d.href.clone(),
+            display_name: d.display_name.clone(),
+        })
+        .chain(listing.files.iter().map(|f| Task::File {
+            url: f.href.clone(),
+            size: f.size,
+            display_name: f.display_name.clone(),
+        }))
+        .collect()
+}
+
+/// Formats a `Report` as a line of per-request output, showing URLs
+/// relative to `base` if set
+struct ReportLine<'a> {
+    report: &'a Report,
+    base: Option<&'a Url>,
+}
+
+impl fmt::Display for ReportLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = |url| ShownUrl {
+            url,
+            base: self.base,
+        };
+        match self.report {
+            Report::Dir {
+                url,
+                display_name,
+                elapsed,
+                warnings,
+                ..
+            } => {
+                write!(
+                    f,
+                    "DIR: {}{} ({elapsed:?})",
+                    shown(url),
+                    DisplayName(display_name)
+                )?;
+                for w in warnings {
+                    write!(f, " [WARNING: {w}]")?;
+                }
+                Ok(())
+            }
             Report::File {
                 url,
+                display_name,
                 elapsed,
                 target: None,
-            } => write!(f, "FILE: {url} => <NOT A REDIRECT> ({elapsed:?})"),
+                ..
+            } => write!(
+                f,
+                "FILE: {}{} => <NOT A REDIRECT> ({elapsed:?})",
+                shown(url),
+                DisplayName(display_name)
+            ),
             Report::File {
                 url,
+                display_name,
                 elapsed,
                 target: Some(t),
-            } => write!(f, "FILE: {url} => {t} ({elapsed:?})"),
+                chain: None,
+                ..
+            } => write!(
+                f,
+                "FILE: {}{} => {} ({elapsed:?})",
+                shown(url),
+                DisplayName(display_name),
+                shown(t)
+            ),
+            Report::File {
+                url,
+                display_name,
+                elapsed,
+                status,
+                target: Some(_),
+                chain: Some(chain),
+                ..
+            } => {
+                write!(
+                    f,
+                    "FILE: {}{} [{status}, {elapsed:?}]",
+                    shown(url),
+                    DisplayName(display_name)
+                )?;
+                for hop in &chain.hops {
+                    write!(
+                        f,
+                        " => {} [{}, {:?}]",
+                        shown(&hop.url),
+                        hop.status,
+                        hop.elapsed
+                    )?;
+                }
+                if chain.truncated {
+                    write!(f, " => <TOO MANY REDIRECTS>")?;
+                }
+                Ok(())
+            }
+            Report::Zarr {
+                url,
+                display_name,
+                elapsed,
+                listing,
+                ..
+            } => write!(
+                f,
+                "ZARR: {}{} => {} directories, {} files ({elapsed:?})",
+                shown(url),
+                DisplayName(display_name),
+                listing.directories.len(),
+                listing.files.len()
+            ),
+        }
+    }
+}
+
+/// Formats a `Report` as a line logging a slow request, showing URLs
+/// relative to `base` if set
+struct SlowLine<'a> {
+    report: &'a Report,
+    base: Option<&'a Url>,
+}
+
+impl fmt::Display for SlowLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (url, status) = match self.report {
+            Report::Dir { url, status, .. }
+            | Report::File { url, status, .. }
+            | Report::Zarr { url, status, .. } => (url, status),
+        };
+        let (queue_wait, service_time) = self.report.task_times();
+        let elapsed = self.report.elapsed();
+        write!(
+            f,
+            "SLOW: {}: {} ({elapsed:?}): status {status}; queue wait {queue_wait:?}",
+            self.report.kind().label(),
+            ShownUrl {
+                url,
+                base: self.base
+            }
+        )?;
+        if let Report::File { ttfb, .. } = self.report {
+            write!(
+                f,
+                ", first byte {ttfb:?}, body {:?}",
+                elapsed.saturating_sub(*ttfb)
+            )?;
+        }
+        write!(f, ", service time {service_time:?}")
+    }
+}
+
+/// Formats a `TaskError` as a line logging a slow failed request, showing
+/// URLs relative to `base` if set
+struct SlowFailureLine<'a> {
+    error: &'a TaskError,
+    base: Option<&'a Url>,
+}
+
+impl fmt::Display for SlowFailureLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let TaskError {
+            source,
+            kind,
+            elapsed,
+        } = self.error;
+        write!(
+            f,
+            "SLOW: {}: {} ({elapsed:?}): failed: ",
+            kind.label(),
+            ShownUrl {
+                url: source.url(),
+                base: self.base
+            }
+        )?;
+        match source.status() {
+            Some(status) => write!(f, "status {status}"),
+            None => write!(f, "{}", source.kind()),
+        }
+    }
+}
+
+/// Print a line logging a slow request of the given kind to stderr and count
+/// it in `slow`
+fn log_slow(
+    slow: &mut SlowRequestStats,
+    kind: RequestKind,
+    line: &dyn fmt::Display,
+    timestamps: bool,
+) {
+    let line = line.to_string();
+    if timestamps {
+        eprintln!("{}", timestamped(&line));
+    } else {
+        eprintln!("{line}");
+    }
+    match kind {
+        RequestKind::File => slow.files = slow.files.saturating_add(1),
+        RequestKind::Dir | RequestKind::Zarr => {
+            slow.directories = slow.directories.saturating_add(1);
+        }
+    }
+}
+
+/// Formats a URL relative to `base` if `base` is set and the URL lies
+/// beneath it, or in full otherwise.  Relative paths are percent-decoded, and
+/// `base` itself is shown as `.`.
+struct ShownUrl<'a> {
+    url: &'a Url,
+    base: Option<&'a Url>,
+}
+
+impl fmt::Display for ShownUrl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relpath = self
+            .base
+            .filter(|base| base.origin() == self.url.origin())
+            .and_then(|base| self.url.path().strip_prefix(base.path()));
+        match relpath {
+            Some("") => write!(f, "."),
+            Some(p) => write!(f, "{}", percent_decode_str(p).decode_utf8_lossy()),
+            None => write!(f, "{}", self.url),
+        }
+    }
+}
+
+/// Formats a resource's display name (if any) for appending to its URL in
+/// per-request output
+struct DisplayName<'a>(&'a Option<String>);
+
+impl fmt::Display for DisplayName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(name) => write!(f, " {name:?}"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn process_task(
+    spawner: Spawner<Result<Report, TaskError>>,
+    backend: Arc<dyn TraversalBackend>,
+    task: Task,
+    topts: TaskOptions,
+) -> BoxFuture<'static, Result<Report, TaskError>> {
+    match task {
+        Task::Dir { url, display_name } => process_dir(spawner, backend, url, display_name, topts),
+        Task::File {
+            url,
+            size,
+            display_name,
+        } => process_file(
+            backend,
+            url,
+            size,
+            display_name,
+            spawner.queue_wait(),
+            topts,
+        )
+        .boxed(),
+        Task::Zarr { url, display_name } => {
+            process_zarr(backend, url, display_name, spawner.queue_wait()).boxed()
         }
     }
 }
 
 fn process_dir(
-    spawner: Spawner<anyhow::Result<Report>>,
-    client: Client,
+    spawner: Spawner<Result<Report, TaskError>>,
+    backend: Arc<dyn TraversalBackend>,
     url: Url,
-) -> BoxFuture<'static, anyhow::Result<Report>> {
+    display_name: Option<String>,
+    topts: TaskOptions,
+) -> BoxFuture<'static, Result<Report, TaskError>> {
     // We need to return a boxed Future in order to be able to call
     // `process_dir()` inside itself.
     async move {
-        let (dl, elapsed) = client.list_directory(url.clone()).await?;
-        for d in dl.directories {
-            let cl2 = client.clone();
-            spawner.spawn(move |spawner| process_dir(spawner, cl2, d));
+        let queue_wait = spawner.queue_wait();
+        let begun = RequestStart::now();
+        let DirectoryResponse {
+            listing: mut dl,
+            duplicates,
+            descriptions,
+            warnings,
+            status,
+            propstat_statuses,
+            body_size,
+            server_headers,
+            elapsed,
+            ids,
+        } = backend
+            .list_children(url.clone())
+            .await
+            .map_err(|e| TaskError::new(e, RequestKind::Dir, &begun))?;
+        let started = begun.sent(&ids);
+        let excluded = topts.filter.apply(&mut dl);
+        let mut stale_files = Vec::new();
+        if let Some(since) = topts.modified_since {
+            let (fresh, stale) = dl
+                .files
+                .into_iter()
+                .partition(|f| f.modified.map_or(true, |m| m >= since));
+            dl.files = fresh;
+            stale_files = stale;
+        }
+        let mut zarr_stores = Vec::new();
+        if topts.zarr_boundary != ZarrBoundary::Descend {
+            let (stores, dirs) = dl
+                .directories
+                .into_iter()
+                .partition(|d| is_zarr_store(&d.href));
+            dl.directories = dirs;
+            zarr_stores = stores;
         }
-        for f in dl.files {
-            let cl2 = client.clone();
-            spawner.spawn(move |_spawner| process_file(cl2, f));
+        let mut unchanged_dirs = Vec::new();
+        if let Some(ref old) = topts.unchanged {
+            let (unchanged, changed) = dl
+                .directories
+                .into_iter()
+                .partition(|d| old.is_unchanged(d));
+            dl.directories = changed;
+            unchanged_dirs = unchanged;
         }
-        Ok(Report::Dir { url, elapsed })
+        let mut tasks = listing_tasks(&dl);
+        tasks.extend(zarr_tasks(&zarr_stores, topts.zarr_boundary));
+        for task in tasks {
+            let backend = Arc::clone(&backend);
+            let topts = topts.clone();
+            spawner.spawn_in(task.pool(), move |spawner| {
+                process_task(spawner, backend, task, topts)
+            });
+        }
+        let (repeat_times, repeat_failures) = repeat_request(topts.repeat_requests, || {
+            backend.list_children(url.clone()).map_ok(|r| r.elapsed)
+        })
+        .await;
+        Ok(Report::Dir {
+            url,
+            display_name,
+            started,
+            queue_wait,
+            service_time: begun.instant.elapsed(),
+            elapsed,
+            listing: dl,
+            stale_files,
+            duplicates,
+            excluded,
+            descriptions,
+            warnings,
+            status,
+            propstat_statuses,
+            body_size,
+            server_headers,
+            ids,
+            zarr_stores,
+            unchanged_dirs,
+            repeat_times,
+            repeat_failures,
+        })
     }
     .boxed()
 }
 
-async fn process_file(client: Client, url: Url) -> anyhow::Result<Report> {
-    let (target, elapsed) = client.get_file_redirect(url.clone()).await?;
+async fn process_file(
+    backend: Arc<dyn TraversalBackend>,
+    url: Url,
+    size: Option<u64>,
+    display_name: Option<String>,
+    queue_wait: Duration,
+    topts: TaskOptions,
+) -> Result<Report, TaskError> {
+    let follow = topts.follow_redirects;
+    let begun = RequestStart::now();
+    let FileResponse {
+        target,
+        status,
+        ttfb,
+        elapsed,
+        server_headers,
+        chain,
+        get_timing,
+        ids,
+    } = backend
+        .probe_file(url.clone(), follow)
+        .await
+        .map_err(|e| TaskError::new(e, RequestKind::File, &begun))?;
+    let started = begun.sent(&ids);
+    let (repeat_times, repeat_failures) = repeat_request(topts.repeat_requests, || {
+        backend
+            .probe_file(url.clone(), follow)
+            .map_ok(|r| r.elapsed)
+    })
+    .await;
     Ok(Report::File {
         url,
+        display_name,
+        started,
+        queue_wait,
+        service_time: begun.instant.elapsed(),
+        ttfb,
         elapsed,
         target,
+        size,
+        status,
+        chain,
+        get_timing,
+        server_headers,
+        ids,
+        repeat_times,
+        repeat_failures,
     })
 }
+
+/// Repeat a request `times` times in succession, returning the latencies of
+/// the repetitions that succeeded along with the number that failed.  Failed
+/// repetitions do not affect the outcome of the original request.
+async fn repeat_request<F, Fut>(times: usize, mut request: F) -> (Vec<Duration>, usize)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Duration, RequestError>>,
+{
+    let mut latencies = Vec::with_capacity(times);
+    let mut failures = 0usize;
+    for _ in 0..times {
+        match request().await {
+            Ok(elapsed) => latencies.push(elapsed),
+            Err(_) => failures = failures.saturating_add(1),
+        }
+    }
+    (latencies, failures)
+}
+
+async fn process_zarr(
+    backend: Arc<dyn TraversalBackend>,
+    url: Url,
+    display_name: Option<String>,
+    queue_wait: Duration,
+) -> Result<Report, TaskError> {
+    let begun = RequestStart::now();
+    let DirectoryResponse {
+        listing,
+        status,
+        server_headers,
+        elapsed,
+        ids,
+        ..
+    } = backend
+        .list_tree(url.clone())
+        .await
+        .map_err(|e| TaskError::new(e, RequestKind::Zarr, &begun))?;
+    let started = begun.sent(&ids);
+    Ok(Report::Zarr {
+        url,
+        display_name,
+        started,
+        queue_wait,
+        service_time: begun.instant.elapsed(),
+        elapsed,
+        listing,
+        status,
+        server_headers,
+        ids,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_concurrency_samples() {
+        let sample = |i: u64| ConcurrencySample {
+            elapsed: Duration::from_millis(i),
+            active: 1,
+        };
+        let elapsed = |samples: &ConcurrencySamples| {
+            samples
+                .samples
+                .iter()
+                .map(|s| s.elapsed.as_millis())
+                .collect::<Vec<_>>()
+        };
+        let mut samples = ConcurrencySamples::new(Some(4));
+        for i in 0..3 {
+            samples.push(sample(i));
+        }
+        assert_eq!(elapsed(&samples), [0, 1, 2]);
+        samples.push(sample(3));
+        assert_eq!(elapsed(&samples), [1, 3], "samples should be thinned out");
+        for i in 4..10 {
+            samples.push(sample(i));
+        }
+        assert_eq!(
+            elapsed(&samples),
+            [3, 7],
+            "samples should stay evenly spaced"
+        );
+        let mut unbounded = ConcurrencySamples::new(None);
+        for i in 0..10 {
+            unbounded.push(sample(i));
+        }
+        assert_eq!(unbounded.samples.len(), 10);
+    }
+
+    /// Serve every request on `listener` with a 503 response after `delay`
+    async fn serve_unavailable(listener: TcpListener, delay: Duration) {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                tokio::time::sleep(delay).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_failures() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(serve_unavailable(listener, Duration::from_millis(50)));
+        let client = Client::new(base_url.clone(), ClientOptions::default()).unwrap();
+        let options = TraversalOptions {
+            quiet: true,
+            on_error: ErrorPolicy::Skip,
+            log_slower_than: Some(Duration::from_millis(20)),
+            ..TraversalOptions::default()
+        };
+        let report = traverse(client, base_url, 1, &options).await.unwrap();
+        assert_eq!(report.errors.count(ErrorKind::HttpStatus), 1);
+        assert_eq!(
+            report.slow_requests,
+            Some(SlowRequestStats {
+                threshold: Duration::from_millis(20),
+                directories: 1,
+                files: 0,
+            }),
+            "failed listing should be counted as slow"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_dirs_counts_failures() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(serve_unavailable(listener, Duration::ZERO));
+        let client = Client::new(base_url.clone(), ClientOptions::default()).unwrap();
+        let options = TraversalOptions {
+            quiet: true,
+            on_error: ErrorPolicy::Skip,
+            max_dirs: Some(1),
+            ..TraversalOptions::default()
+        };
+        let report = traverse(client, base_url, 1, &options).await.unwrap();
+        assert_eq!(report.errors.count(ErrorKind::HttpStatus), 1);
+        assert!(
+            report.stopped_early,
+            "failed listing should count toward --max-dirs"
+        );
+    }
+}
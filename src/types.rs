@@ -1,38 +1,507 @@
+use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::collections::HashSet;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use time::{format_description::well_known::Rfc3339, Date, Month, OffsetDateTime};
 use url::Url;
 
+/// Characters to percent-encode in normalized URL path segments: everything
+/// other than unreserved characters, sub-delimiters, `:`, and `@`
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'!')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')')
+    .remove(b'*')
+    .remove(b'+')
+    .remove(b',')
+    .remove(b';')
+    .remove(b'=')
+    .remove(b':')
+    .remove(b'@');
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub(crate) struct DirectoryListing<T> {
-    pub(crate) directories: Vec<T>,
-    pub(crate) files: Vec<T>,
+    pub(crate) directories: Vec<Entry<T>>,
+    pub(crate) files: Vec<Entry<T>>,
 }
 
 impl DirectoryListing<String> {
-    pub(crate) fn paths_to_urls(self, base_url: &Url) -> DirectoryListing<Url> {
-        DirectoryListing {
-            directories: self
-                .directories
-                .into_iter()
-                .map(|p| url_plus_path(base_url, &p))
-                .collect(),
-            files: self
-                .files
-                .into_iter()
-                .map(|p| url_plus_path(base_url, &p))
-                .collect(),
+    /// Resolve the hrefs in the listing against `base_url` and normalize
+    /// them, discarding any entries that normalize to the same URL as an
+    /// earlier entry (with collections taking precedence over
+    /// non-collections).  Returns the new listing along with the number of
+    /// entries discarded.  `response_url` is the URL of the response from
+    /// which the listing was parsed and is used for error reporting.
+    pub(crate) fn paths_to_urls(
+        self,
+        base_url: &Url,
+        response_url: &Url,
+    ) -> Result<(DirectoryListing<Url>, usize), HrefError> {
+        let mut seen = HashSet::new();
+        let mut duplicates = 0usize;
+        let mut dedup = |entries: Vec<Entry<String>>, collection: bool| {
+            let mut kept = Vec::with_capacity(entries.len());
+            for e in entries {
+                let href = url_plus_path(base_url, &e.href).map_err(|source| HrefError {
+                    href: e.href.clone(),
+                    url: response_url.clone(),
+                    source,
+                })?;
+                let e = e.map_href(|_| normalize_url(href, collection));
+                if seen.insert(e.href.as_str().trim_end_matches('/').to_owned()) {
+                    kept.push(e);
+                } else {
+                    duplicates = duplicates.saturating_add(1);
+                }
+            }
+            Ok(kept)
+        };
+        let directories = dedup(self.directories, true)?;
+        let files = dedup(self.files, false)?;
+        Ok((DirectoryListing { directories, files }, duplicates))
+    }
+}
+
+/// A resource listed in a multistatus response, along with any of its
+/// properties that were requested & returned
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Entry<T> {
+    pub(crate) href: T,
+    /// The value of the `getcontentlength` property
+    pub(crate) size: Option<u64>,
+    /// The value of the `displayname` property
+    pub(crate) display_name: Option<String>,
+    /// The value of the `getetag` property
+    pub(crate) etag: Option<String>,
+    /// The value of the `getlastmodified` property
+    pub(crate) modified: Option<OffsetDateTime>,
+    /// The value of the `getcontenttype` property
+    pub(crate) content_type: Option<String>,
+}
+
+impl<T> Entry<T> {
+    pub(crate) fn new(href: T) -> Entry<T> {
+        Entry {
+            href,
+            size: None,
+            display_name: None,
+            etag: None,
+            modified: None,
+            content_type: None,
+        }
+    }
+
+    fn map_href<U, F: FnOnce(T) -> U>(self, f: F) -> Entry<U> {
+        Entry {
+            href: f(self.href),
+            size: self.size,
+            display_name: self.display_name,
+            etag: self.etag,
+            modified: self.modified,
+            content_type: self.content_type,
         }
     }
 }
 
-fn url_plus_path(url: &Url, path: &str) -> Url {
-    // TODO: Better error handling:
+impl From<&str> for Entry<String> {
+    fn from(href: &str) -> Entry<String> {
+        Entry::new(href.to_owned())
+    }
+}
+
+pub(crate) fn url_plus_path(url: &Url, path: &str) -> Result<Url, url::ParseError> {
     url.join(path)
-        .expect("href returned from server should be a valid URL path")
+}
+
+/// Error returned when an href in a server response cannot be resolved to a
+/// URL
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("response from {url} contains invalid href {href:?}")]
+pub(crate) struct HrefError {
+    /// The offending href
+    pub(crate) href: String,
+    /// The URL of the response containing the href
+    pub(crate) url: Url,
+    pub(crate) source: url::ParseError,
+}
+
+/// Normalize the path of `url` so that equivalent hrefs compare equal:
+/// percent-encoding is made consistent (see [`normalize_segment()`]), and
+/// collection URLs are given a trailing slash.  Dot segments are already
+/// removed by the `url` crate when parsing.
+pub(crate) fn normalize_url(mut url: Url, collection: bool) -> Url {
+    let mut path = url
+        .path()
+        .split('/')
+        .map(normalize_segment)
+        .collect::<Vec<_>>()
+        .join("/");
+    if collection && !path.ends_with('/') {
+        path.push('/');
+    }
+    url.set_path(&path);
+    url
+}
+
+/// Test whether `href`, taken from a multistatus response to a request for
+/// `url`, refers to `url` itself.  Both sides are normalized with
+/// [`normalize_url()`] and any trailing slash is ignored, so that, e.g., an
+/// href of `/~user` matches a URL spelled with `%7E`.
+pub(crate) fn is_self_href(url: &Url, href: &str) -> bool {
+    let key = |u: Url| {
+        normalize_url(u, false)
+            .as_str()
+            .trim_end_matches('/')
+            .to_owned()
+    };
+    url_plus_path(url, href).is_ok_and(|u| key(u) == key(url.clone()))
+}
+
+/// Normalize the percent-encoding of a path segment as described in RFC 3986,
+/// section 6.2.2: percent-encoded unreserved characters are decoded, the hex
+/// digits of all other percent-encoded octets are uppercased, and characters
+/// that cannot appear literally in a segment are percent-encoded.  Reserved
+/// characters are never decoded, as doing so could change the meaning of the
+/// path.
+fn normalize_segment(seg: &str) -> String {
+    let mut out = String::with_capacity(seg.len());
+    let mut rest = seg.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if let (b'%', &[hi, lo, ref tail @ ..]) = (b, rest) {
+            if let (Some(hi), Some(lo)) = (hex_digit(hi), hex_digit(lo)) {
+                rest = tail;
+                let octet = (hi << 4) | lo;
+                if octet.is_ascii_alphanumeric() || b"-._~".contains(&octet) {
+                    out.push(char::from(octet));
+                } else {
+                    out.extend(percent_encode(&[octet], NON_ALPHANUMERIC));
+                }
+                continue;
+            }
+        }
+        out.extend(percent_encode(&[b], SEGMENT));
+    }
+    out
+}
+
+fn hex_digit(b: u8) -> Option<u8> {
+    char::from(b)
+        .to_digit(16)
+        .and_then(|d| u8::try_from(d).ok())
+}
+
+/// Return a key for sorting URLs into canonical path order, in which URLs are
+/// grouped by origin and each collection is immediately followed by its
+/// contents
+pub(crate) fn path_order_key(url: &Url) -> (String, Vec<String>) {
+    let segments = url
+        .path()
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect();
+    (url.origin().ascii_serialization(), segments)
+}
+
+/// Return whether `url` is `root` or lies beneath it
+pub(crate) fn is_within(root: &Url, url: &Url) -> bool {
+    if root.origin() != url.origin() {
+        return false;
+    }
+    let base = root.path().trim_end_matches('/');
+    url.path()
+        .strip_prefix(base)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Parse a timestamp given on the command line, either as an RFC 3339
+/// date-time (e.g., `2024-01-31T12:00:00Z`) or as a date (`2024-01-31`),
+/// which is taken to mean midnight UTC
+pub(crate) fn parse_timestamp(s: &str) -> Result<OffsetDateTime, ParseTimestampError> {
+    if let Ok(dt) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(dt);
+    }
+    let date = s
+        .split_once('-')
+        .and_then(|(year, rest)| {
+            let (month, day) = rest.split_once('-')?;
+            let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+            Date::from_calendar_date(year.parse().ok()?, month, day.parse().ok()?).ok()
+        })
+        .ok_or(ParseTimestampError)?;
+    Ok(date.midnight().assume_utc())
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid timestamp; expected YYYY-MM-DD or an RFC 3339 date-time")]
+pub(crate) struct ParseTimestampError;
+
+/// Parse a positive percentage given on the command line, with or without a
+/// trailing `%`
+pub(crate) fn parse_percent(s: &str) -> Result<f64, ParsePercentError> {
+    match s.strip_suffix('%').unwrap_or(s).parse::<f64>() {
+        Ok(pct) if pct.is_finite() && pct > 0.0 => Ok(pct),
+        _ => Err(ParsePercentError),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid percentage; expected a positive number")]
+pub(crate) struct ParsePercentError;
+
+/// Parse a percentage greater than 0 and at most 100 given on the command
+/// line, with or without a trailing `%`
+pub(crate) fn parse_bounded_percent(s: &str) -> Result<f64, ParseBoundedPercentError> {
+    match parse_percent(s) {
+        Ok(pct) if pct <= 100.0 => Ok(pct),
+        _ => Err(ParseBoundedPercentError),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid percentage; expected a number greater than 0 and at most 100")]
+pub(crate) struct ParseBoundedPercentError;
+
+/// Parse a positive time interval given on the command line as an integer
+/// followed by an optional unit of `s` (seconds, the default), `m` (minutes),
+/// `h` (hours), or `d` (days)
+pub(crate) fn parse_interval(s: &str) -> Result<Duration, ParseIntervalError> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 3600),
+        Some((i, 'd')) => (&s[..i], 86400),
+        _ => (s, 1),
+    };
+    match digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+    {
+        Some(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(ParseIntervalError),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid interval; expected a positive integer with an optional unit of s, m, h, or d")]
+pub(crate) struct ParseIntervalError;
+
+/// Parse a duration given on the command line as an integer followed by a
+/// unit of `us`, `ms`, or `s`
+pub(crate) fn parse_latency(s: &str) -> Result<Duration, ParseLatencyError> {
+    let s = s.trim();
+    let (digits, unit): (&str, fn(u64) -> Duration) = if let Some(d) = s.strip_suffix("us") {
+        (d, Duration::from_micros)
+    } else if let Some(d) = s.strip_suffix("ms") {
+        (d, Duration::from_millis)
+    } else if let Some(d) = s.strip_suffix('s') {
+        (d, Duration::from_secs)
+    } else {
+        return Err(ParseLatencyError);
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .map(unit)
+        .map_err(|_| ParseLatencyError)
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid duration; expected an integer with a unit of us, ms, or s")]
+pub(crate) struct ParseLatencyError;
+
+/// Parse a positive duration given on the command line as an integer
+/// followed by a unit of `us`, `ms`, or `s`
+pub(crate) fn parse_positive_latency(s: &str) -> Result<Duration, ParsePositiveLatencyError> {
+    match parse_latency(s) {
+        Ok(d) if !d.is_zero() => Ok(d),
+        _ => Err(ParsePositiveLatencyError),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid duration; expected a positive integer with a unit of us, ms, or s")]
+pub(crate) struct ParsePositiveLatencyError;
+
+/// Parse a positive byte rate given on the command line as an integer number
+/// of bytes per second followed by an optional unit of `k`, `m`, or `g`
+/// (case-insensitive, in powers of 1024)
+pub(crate) fn parse_byte_rate(s: &str) -> Result<NonZeroU64, ParseByteRateError> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .and_then(NonZeroU64::new)
+        .ok_or(ParseByteRateError)
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid byte rate; expected a positive integer with an optional unit of k, m, or g")]
+pub(crate) struct ParseByteRateError;
+
+/// Parse a user-defined label given on the command line in the form
+/// `KEY=VALUE`
+pub(crate) fn parse_label(s: &str) -> Result<(String, String), ParseLabelError> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_owned(), value.to_owned()))
+        }
+        _ => Err(ParseLabelError),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid label; expected KEY=VALUE with a nonempty key")]
+pub(crate) struct ParseLabelError;
+
+/// Parse a list of URLs, one per line.  Blank lines and lines starting with
+/// `#` are ignored.
+pub(crate) fn parse_url_list(s: &str) -> Result<Vec<Url>, ParseUrlListError> {
+    let mut urls = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let url = Url::parse(line).map_err(|source| ParseUrlListError {
+            line: i.saturating_add(1),
+            source,
+        })?;
+        urls.push(url);
+    }
+    Ok(urls)
+}
+
+/// A base URL to traverse in a batch run over multiple targets, along with
+/// the label identifying its results in the output
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct BatchTarget {
+    pub(crate) url: Url,
+    pub(crate) label: String,
+}
+
+/// Parse a line of a batch targets list, consisting of a URL optionally
+/// followed by whitespace and a label.  If no label is given, the URL itself
+/// is used as the label.  Returns `None` for blank lines and lines starting
+/// with `#`.
+pub(crate) fn parse_batch_target(line: &str) -> Result<Option<BatchTarget>, url::ParseError> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (url, label) = match line.split_once(char::is_whitespace) {
+        Some((url, label)) => (url, label.trim_start()),
+        None => (line, line),
+    };
+    Ok(Some(BatchTarget {
+        url: Url::parse(url)?,
+        label: label.to_owned(),
+    }))
+}
+
+/// Parse a list of batch targets, one per line, in the format accepted by
+/// [`parse_batch_target()`]
+pub(crate) fn parse_target_list(s: &str) -> Result<Vec<BatchTarget>, ParseUrlListError> {
+    let mut targets = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let target = parse_batch_target(line).map_err(|source| ParseUrlListError {
+            line: i.saturating_add(1),
+            source,
+        })?;
+        targets.extend(target);
+    }
+    Ok(targets)
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("invalid URL on line {line}")]
+pub(crate) struct ParseUrlListError {
+    line: usize,
+    source: url::ParseError,
+}
+
+/// A positional argument to the `batch` command: either the base URL of the
+/// hierarchy or a number of workers to run the traversal with
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum BatchArg {
+    BaseUrl(Url),
+    Workers(usize),
+}
+
+impl FromStr for BatchArg {
+    type Err = ParseBatchArgError;
+
+    /// Parse an integer as a number of workers and anything else as a URL
+    fn from_str(s: &str) -> Result<BatchArg, ParseBatchArgError> {
+        if let Ok(workers) = s.parse::<usize>() {
+            Ok(BatchArg::Workers(workers))
+        } else {
+            Url::parse(s)
+                .map(BatchArg::BaseUrl)
+                .map_err(|_| ParseBatchArgError)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+#[error("expected a base URL or a number of workers")]
+pub(crate) struct ParseBatchArgError;
+
+/// Split the positional arguments of the `batch` command into the base URL,
+/// if given (in which case it must come first), and the list of worker
+/// amounts
+pub(crate) fn split_batch_args(
+    args: Vec<BatchArg>,
+) -> Result<(Option<Url>, Vec<usize>), BatchArgsError> {
+    let mut args = args.into_iter().peekable();
+    let base_url = match args.next_if(|arg| matches!(arg, BatchArg::BaseUrl(_))) {
+        Some(BatchArg::BaseUrl(url)) => Some(url),
+        _ => None,
+    };
+    let workers_list = args
+        .map(|arg| match arg {
+            BatchArg::Workers(workers) => Ok(workers),
+            BatchArg::BaseUrl(url) => Err(BatchArgsError::InvalidWorkers(url.into())),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if workers_list.is_empty() {
+        return Err(BatchArgsError::NoWorkers);
+    }
+    Ok((base_url, workers_list))
+}
+
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub(crate) enum BatchArgsError {
+    #[error("invalid number of workers {0:?}")]
+    InvalidWorkers(String),
+    #[error("at least one number of workers is required")]
+    NoWorkers,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use rstest::rstest;
+    use time::macros::datetime;
 
     #[rstest]
     #[case(
@@ -51,6 +520,292 @@ mod tests {
         "https://www.example.com/foo/bar/baz"
     )]
     fn test_url_plus_path(#[case] url: Url, #[case] path: &str, #[case] r: Url) {
-        assert_eq!(url_plus_path(&url, path), r);
+        assert_eq!(url_plus_path(&url, path).unwrap(), r);
+    }
+
+    #[rstest]
+    #[case("https://example.com/foo/bar", false, "https://example.com/foo/bar")]
+    #[case("https://example.com/foo/bar", true, "https://example.com/foo/bar/")]
+    #[case("https://example.com/foo/bar/", true, "https://example.com/foo/bar/")]
+    #[case(
+        "https://example.com/%7Efoo/b%61r",
+        false,
+        "https://example.com/~foo/bar"
+    )]
+    #[case(
+        "https://example.com/foo bar/caf\u{e9}",
+        false,
+        "https://example.com/foo%20bar/caf%C3%A9"
+    )]
+    #[case(
+        "https://example.com/foo%2fbar",
+        false,
+        "https://example.com/foo%2Fbar"
+    )]
+    #[case("https://example.com/a/./b/../c", false, "https://example.com/a/c")]
+    #[case(
+        "https://example.com/a%3Ab?q=1",
+        false,
+        "https://example.com/a%3Ab?q=1"
+    )]
+    #[case("https://example.com/a%3ab", false, "https://example.com/a%3Ab")]
+    #[case("https://example.com/a%2Bb%21", false, "https://example.com/a%2Bb%21")]
+    #[case(
+        "https://example.com/100%25/50%",
+        false,
+        "https://example.com/100%25/50%25"
+    )]
+    fn test_normalize_url(#[case] url: Url, #[case] collection: bool, #[case] r: &str) {
+        assert_eq!(normalize_url(url, collection).as_str(), r);
+    }
+
+    #[rstest]
+    #[case("https://example.com/dav/", "/dav/", true)]
+    #[case("https://example.com/dav/", "/dav", true)]
+    #[case("https://example.com/dav", "https://example.com/dav/", true)]
+    #[case("https://example.com/%7Euser/", "/~user/", true)]
+    #[case("https://example.com/~user/", "/%7euser/", true)]
+    #[case("https://example.com/caf%c3%a9/", "/caf%C3%A9/", true)]
+    #[case("https://example.com/dav/", "/dav/a/", false)]
+    #[case("https://example.com/a%2Fb/", "/a/b/", false)]
+    fn test_is_self_href(#[case] url: Url, #[case] href: &str, #[case] r: bool) {
+        assert_eq!(is_self_href(&url, href), r);
+    }
+
+    #[rstest]
+    #[case("https://example.com/a/", "https://example.com/a/", true)]
+    #[case("https://example.com/a/", "https://example.com/a", true)]
+    #[case("https://example.com/a", "https://example.com/a/", true)]
+    #[case("https://example.com/a/", "https://example.com/a/b/c.txt", true)]
+    #[case("https://example.com/", "https://example.com/a/", true)]
+    #[case("https://example.com/a/b/", "https://example.com/a/", false)]
+    #[case("https://example.com/a/", "https://example.com/ab/", false)]
+    #[case("https://example.com/a/", "https://example.com:8443/a/b", false)]
+    #[case("https://example.com/a/", "http://example.com/a/b", false)]
+    fn test_is_within(#[case] root: Url, #[case] url: Url, #[case] r: bool) {
+        assert_eq!(is_within(&root, &url), r);
+    }
+
+    #[test]
+    fn test_paths_to_urls_dedup() {
+        let listing = DirectoryListing {
+            directories: vec![
+                Entry::from("/dav/sub%20dir"),
+                Entry::from("/dav/sub dir/"),
+                Entry::from("/dav/other/"),
+            ],
+            files: vec![
+                Entry::from("/dav/file.txt"),
+                Entry::from("/dav/./fil%65.txt"),
+                Entry::from("/dav/other"),
+            ],
+        };
+        let base = Url::parse("https://example.com/dav/").unwrap();
+        let (listing, duplicates) = listing.paths_to_urls(&base, &base).unwrap();
+        assert_eq!(duplicates, 3);
+        assert_eq!(
+            listing
+                .directories
+                .iter()
+                .map(|e| e.href.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://example.com/dav/sub%20dir/",
+                "https://example.com/dav/other/"
+            ]
+        );
+        assert_eq!(
+            listing
+                .files
+                .iter()
+                .map(|e| e.href.as_str())
+                .collect::<Vec<_>>(),
+            ["https://example.com/dav/file.txt"]
+        );
+    }
+
+    #[test]
+    fn test_paths_to_urls_bad_href() {
+        let listing = DirectoryListing {
+            directories: vec![Entry::from("/dav/ok/")],
+            files: vec![Entry::from("https://exa mple.com/dav/bad")],
+        };
+        let base = Url::parse("https://example.com/").unwrap();
+        let response_url = Url::parse("https://example.com/dav/").unwrap();
+        let e = listing.paths_to_urls(&base, &response_url).unwrap_err();
+        assert_eq!(e.href, "https://exa mple.com/dav/bad");
+        assert_eq!(e.url, response_url);
+        assert_eq!(e.source, url::ParseError::InvalidDomainCharacter);
+    }
+
+    #[rstest]
+    #[case("2024-01-31", Some(datetime!(2024-01-31 00:00 UTC)))]
+    #[case("2024-01-31T12:34:56Z", Some(datetime!(2024-01-31 12:34:56 UTC)))]
+    #[case(
+        "2024-01-31T12:34:56-05:00",
+        Some(datetime!(2024-01-31 12:34:56 -05:00))
+    )]
+    #[case("2024-02-30", None)]
+    #[case("2024-13-01", None)]
+    #[case("yesterday", None)]
+    fn test_parse_timestamp(#[case] s: &str, #[case] r: Option<OffsetDateTime>) {
+        assert_eq!(parse_timestamp(s).ok(), r);
+    }
+
+    #[rstest]
+    #[case("5", Some(5.0))]
+    #[case("2.5%", Some(2.5))]
+    #[case("0", None)]
+    #[case("-1", None)]
+    #[case("inf", None)]
+    #[case("five", None)]
+    fn test_parse_percent(#[case] s: &str, #[case] r: Option<f64>) {
+        assert_eq!(parse_percent(s).ok(), r);
+    }
+
+    #[rstest]
+    #[case("5", Some(5.0))]
+    #[case("100%", Some(100.0))]
+    #[case("0", None)]
+    #[case("100.5", None)]
+    #[case("150", None)]
+    fn test_parse_bounded_percent(#[case] s: &str, #[case] r: Option<f64>) {
+        assert_eq!(parse_bounded_percent(s).ok(), r);
+    }
+
+    #[rstest]
+    #[case("server=prod-eu", Some(("server", "prod-eu")))]
+    #[case(" cache =cold", Some(("cache", "cold")))]
+    #[case("query=a=b", Some(("query", "a=b")))]
+    #[case("empty=", Some(("empty", "")))]
+    #[case("=value", None)]
+    #[case("novalue", None)]
+    fn test_parse_label(#[case] s: &str, #[case] r: Option<(&str, &str)>) {
+        assert_eq!(
+            parse_label(s).ok(),
+            r.map(|(k, v)| (k.to_owned(), v.to_owned()))
+        );
+    }
+
+    #[rstest]
+    #[case("90", Some(90))]
+    #[case("45s", Some(45))]
+    #[case("10m", Some(600))]
+    #[case("2h", Some(7200))]
+    #[case("1d", Some(86400))]
+    #[case("0m", None)]
+    #[case("m", None)]
+    #[case("1.5h", None)]
+    #[case("-5s", None)]
+    #[case("10 minutes", None)]
+    fn test_parse_interval(#[case] s: &str, #[case] secs: Option<u64>) {
+        assert_eq!(parse_interval(s).ok(), secs.map(Duration::from_secs));
+    }
+
+    #[rstest]
+    #[case("500", Some(500))]
+    #[case("100k", Some(102_400))]
+    #[case("2M", Some(2_097_152))]
+    #[case("1g", Some(1_073_741_824))]
+    #[case("0", None)]
+    #[case("k", None)]
+    #[case("1.5m", None)]
+    #[case("10 MB/s", None)]
+    fn test_parse_byte_rate(#[case] s: &str, #[case] rate: Option<u64>) {
+        assert_eq!(parse_byte_rate(s).ok().map(NonZeroU64::get), rate);
+    }
+
+    #[rstest]
+    #[case("250us", Some(Duration::from_micros(250)))]
+    #[case("30ms", Some(Duration::from_millis(30)))]
+    #[case(" 2 s", Some(Duration::from_secs(2)))]
+    #[case("2", None)]
+    #[case("1.5s", None)]
+    #[case("ms", None)]
+    fn test_parse_latency(#[case] s: &str, #[case] d: Option<Duration>) {
+        assert_eq!(parse_latency(s).ok(), d);
+    }
+
+    #[rstest]
+    #[case("250us", Some(Duration::from_micros(250)))]
+    #[case("5s", Some(Duration::from_secs(5)))]
+    #[case("0s", None)]
+    #[case("0ms", None)]
+    #[case("0us", None)]
+    #[case("ms", None)]
+    fn test_parse_positive_latency(#[case] s: &str, #[case] d: Option<Duration>) {
+        assert_eq!(parse_positive_latency(s).ok(), d);
+    }
+
+    #[test]
+    fn test_parse_url_list() {
+        let urls = parse_url_list(
+            "# Datasets\nhttps://example.com/dav/a/\n\n  https://example.com/dav/b/  \n",
+        )
+        .unwrap();
+        assert_eq!(
+            urls,
+            [
+                Url::parse("https://example.com/dav/a/").unwrap(),
+                Url::parse("https://example.com/dav/b/").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_url_list_error() {
+        let e = parse_url_list("https://example.com/\nnot a url\n").unwrap_err();
+        assert_eq!(e.to_string(), "invalid URL on line 2");
+    }
+
+    #[rstest]
+    #[case("", None)]
+    #[case("  # comment", None)]
+    #[case(
+        "https://example.com/dav/a/",
+        Some(("https://example.com/dav/a/", "https://example.com/dav/a/"))
+    )]
+    #[case(
+        "  https://example.com/dav/a/\tdataset A  ",
+        Some(("https://example.com/dav/a/", "dataset A"))
+    )]
+    fn test_parse_batch_target(#[case] line: &str, #[case] expected: Option<(&str, &str)>) {
+        let expected = expected.map(|(url, label)| BatchTarget {
+            url: Url::parse(url).unwrap(),
+            label: label.to_owned(),
+        });
+        assert_eq!(parse_batch_target(line).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_target_list_error() {
+        let e = parse_target_list("https://example.com/ a\n\nnot-a-url b\n").unwrap_err();
+        assert_eq!(e.to_string(), "invalid URL on line 3");
+    }
+
+    #[rstest]
+    #[case(&["https://example.com/dav/", "1", "4"], Ok((Some("https://example.com/dav/"), vec![1, 4])))]
+    #[case(&["1", "4"], Ok((None, vec![1, 4])))]
+    #[case(
+        &["https://example.com/dav/", "1", "https://example.com/"],
+        Err(BatchArgsError::InvalidWorkers("https://example.com/".into()))
+    )]
+    #[case(&["https://example.com/dav/"], Err(BatchArgsError::NoWorkers))]
+    fn test_split_batch_args(
+        #[case] args: &[&str],
+        #[case] expected: Result<(Option<&str>, Vec<usize>), BatchArgsError>,
+    ) {
+        let args = args
+            .iter()
+            .map(|s| s.parse::<BatchArg>().unwrap())
+            .collect::<Vec<_>>();
+        let expected =
+            expected.map(|(url, workers)| (url.map(|u| Url::parse(u).unwrap()), workers));
+        assert_eq!(split_batch_args(args), expected);
+    }
+
+    #[test]
+    fn test_parse_batch_arg_error() {
+        assert_eq!("not-a-url".parse::<BatchArg>(), Err(ParseBatchArgError));
     }
 }
@@ -0,0 +1,272 @@
+use crate::stats::WorkerSummary;
+use std::fmt;
+
+/// Number of iterations of the golden-section search for the single-worker
+/// throughput
+const SEARCH_ITERATIONS: usize = 100;
+
+/// The ratio by which the upper end of the search range for the
+/// single-worker throughput exceeds the lower end
+const SEARCH_RANGE: f64 = 10.0;
+
+/// The parameters of the [Universal Scalability Law][usl] fitted to the mean
+/// throughput for each number of workers, modelling the throughput with `N`
+/// workers as `λN / (1 + σ(N − 1) + κN(N − 1))`
+///
+/// [usl]: http://www.perfdynamics.com/Manifesto/USLscalability.html
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct UslFit {
+    /// The throughput with a single worker, in requests per second
+    pub(crate) lambda: f64,
+    /// The contention coefficient
+    pub(crate) sigma: f64,
+    /// The coherency coefficient
+    pub(crate) kappa: f64,
+    /// The coefficient of determination of the fitted throughputs
+    pub(crate) r_squared: f64,
+}
+
+impl UslFit {
+    /// Fit the USL to the mean throughputs in `summaries` by least squares,
+    /// constraining the coefficients to be nonnegative.  Returns `None` if
+    /// fewer than three numbers of workers were measured.
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn fit(summaries: &[WorkerSummary]) -> Option<UslFit> {
+        let points = summaries
+            .iter()
+            .filter(|s| s.workers > 0 && s.throughput_mean.is_finite() && s.throughput_mean > 0.0)
+            .map(|s| (s.workers as f64, s.throughput_mean))
+            .collect::<Vec<_>>();
+        if points.len() < 3 {
+            return None;
+        }
+        // With nonnegative coefficients, the throughput never exceeds λN, so
+        // λ is at least the greatest per-worker throughput.
+        let mut lo = points.iter().map(|&(n, x)| x / n).fold(0.0, f64::max);
+        let mut hi = lo * SEARCH_RANGE;
+        let phi = (5f64.sqrt() - 1.0) / 2.0;
+        let sse = |lambda: f64| {
+            let (sigma, kappa) = fit_coefficients(&points, lambda);
+            sum_squared_error(&points, lambda, sigma, kappa)
+        };
+        for _ in 0..SEARCH_ITERATIONS {
+            let a = hi - phi * (hi - lo);
+            let b = lo + phi * (hi - lo);
+            if sse(a) <= sse(b) {
+                hi = b;
+            } else {
+                lo = a;
+            }
+        }
+        let lambda = (lo + hi) / 2.0;
+        let (sigma, kappa) = fit_coefficients(&points, lambda);
+        let mean = points.iter().map(|&(_, x)| x).sum::<f64>() / (points.len() as f64);
+        let total = points.iter().map(|&(_, x)| (x - mean).powi(2)).sum::<f64>();
+        let residual = sum_squared_error(&points, lambda, sigma, kappa);
+        let r_squared = if total > 0.0 {
+            1.0 - residual / total
+        } else {
+            1.0
+        };
+        Some(UslFit {
+            lambda,
+            sigma,
+            kappa,
+            r_squared,
+        })
+    }
+
+    /// The predicted throughput with `n` workers
+    pub(crate) fn throughput(&self, n: f64) -> f64 {
+        let denom = (self.kappa * n).mul_add(n - 1.0, self.sigma.mul_add(n - 1.0, 1.0));
+        self.lambda * n / denom
+    }
+
+    /// The number of workers at which the predicted throughput peaks, if the
+    /// coherency coefficient is positive
+    pub(crate) fn peak_workers(&self) -> Option<f64> {
+        (self.kappa > 0.0 && self.sigma < 1.0).then(|| ((1.0 - self.sigma) / self.kappa).sqrt())
+    }
+}
+
+impl fmt::Display for UslFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "USL fit: λ = {:.2} requests/s, σ = {:.4} (contention), κ = {:.6} (coherency), R² = {:.3}; ",
+            self.lambda, self.sigma, self.kappa, self.r_squared
+        )?;
+        match self.peak_workers() {
+            // With σ ≥ 1, each added worker costs at least as much in
+            // contention as it contributes, so the throughput never exceeds
+            // that of a single worker (and λ/σ would be a bound from above).
+            _ if self.sigma >= 1.0 => writeln!(
+                f,
+                "throughput predicted not to exceed the single-worker {:.1} requests/s",
+                self.lambda
+            ),
+            Some(peak) => writeln!(
+                f,
+                "predicted peak throughput of {:.1} requests/s at {:.0} workers",
+                self.throughput(peak),
+                peak
+            ),
+            None if self.sigma > 0.0 => writeln!(
+                f,
+                "throughput predicted to approach {:.1} requests/s without peaking",
+                self.lambda / self.sigma
+            ),
+            None => writeln!(f, "throughput predicted to scale linearly"),
+        }
+    }
+}
+
+/// For a given `lambda`, fit the nonnegative contention & coherency
+/// coefficients by linear least squares on the linearized model
+/// `λN/X − 1 = σ(N − 1) + κN(N − 1)`
+fn fit_coefficients(points: &[(f64, f64)], lambda: f64) -> (f64, f64) {
+    let (mut saa, mut sab, mut sbb, mut say, mut sby) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for &(n, x) in points {
+        let a = n - 1.0;
+        let b = n * (n - 1.0);
+        let y = lambda * n / x - 1.0;
+        saa += a * a;
+        sab += a * b;
+        sbb += b * b;
+        say += a * y;
+        sby += b * y;
+    }
+    let mut candidates = vec![(0.0, 0.0)];
+    if saa > 0.0 {
+        candidates.push(((say / saa).max(0.0), 0.0));
+    }
+    if sbb > 0.0 {
+        candidates.push((0.0, (sby / sbb).max(0.0)));
+    }
+    let det = saa.mul_add(sbb, -(sab * sab));
+    if det.abs() > f64::EPSILON * saa * sbb {
+        let sigma = say.mul_add(sbb, -(sby * sab)) / det;
+        let kappa = sby.mul_add(saa, -(say * sab)) / det;
+        if sigma >= 0.0 && kappa >= 0.0 {
+            candidates.push((sigma, kappa));
+        }
+    }
+    candidates
+        .into_iter()
+        .min_by(|&(s1, k1), &(s2, k2)| {
+            sum_squared_error(points, lambda, s1, k1)
+                .total_cmp(&sum_squared_error(points, lambda, s2, k2))
+        })
+        .unwrap_or((0.0, 0.0))
+}
+
+fn sum_squared_error(points: &[(f64, f64)], lambda: f64, sigma: f64, kappa: f64) -> f64 {
+    let fit = UslFit {
+        lambda,
+        sigma,
+        kappa,
+        r_squared: 0.0,
+    };
+    points
+        .iter()
+        .map(|&(n, x)| (x - fit.throughput(n)).powi(2))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summaries(fit: &UslFit, workers: &[usize]) -> Vec<WorkerSummary> {
+        workers
+            .iter()
+            .map(|&w| WorkerSummary {
+                workers: w,
+                samples: 1,
+                time_mean: 1.0,
+                time_stddev: f64::NAN,
+                time_median: 1.0,
+                time_p95: 1.0,
+                time_ci: None,
+                #[allow(clippy::cast_precision_loss)]
+                throughput_mean: fit.throughput(w as f64),
+                throughput_stddev: f64::NAN,
+                utilization_mean: None,
+                efficiency: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_exact() {
+        let truth = UslFit {
+            lambda: 100.0,
+            sigma: 0.05,
+            kappa: 0.001,
+            r_squared: 1.0,
+        };
+        for workers in [&[1, 2, 4, 8, 16, 32, 64][..], &[2, 4, 8, 16, 32, 64]] {
+            let fit = UslFit::fit(&summaries(&truth, workers)).unwrap();
+            assert!((fit.lambda - 100.0).abs() < 0.1, "{fit:?}");
+            assert!((fit.sigma - 0.05).abs() < 1e-3, "{fit:?}");
+            assert!((fit.kappa - 0.001).abs() < 1e-4, "{fit:?}");
+            assert!(fit.r_squared > 0.999, "{fit:?}");
+            let peak = fit.peak_workers().unwrap();
+            assert!((peak - 30.8).abs() < 1.0, "peak = {peak}");
+        }
+    }
+
+    #[test]
+    fn test_fit_linear() {
+        let truth = UslFit {
+            lambda: 50.0,
+            sigma: 0.0,
+            kappa: 0.0,
+            r_squared: 1.0,
+        };
+        let fit = UslFit::fit(&summaries(&truth, &[1, 2, 4])).unwrap();
+        assert!((fit.lambda - 50.0).abs() < 0.01, "{fit:?}");
+        assert!(fit.sigma < 1e-6, "{fit:?}");
+        assert!(fit.kappa < 1e-6, "{fit:?}");
+        assert_eq!(fit.peak_workers(), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let fit = UslFit {
+            lambda: 50.0,
+            sigma: 0.5,
+            kappa: 0.0,
+            r_squared: 1.0,
+        };
+        assert_eq!(
+            fit.to_string(),
+            "USL fit: λ = 50.00 requests/s, σ = 0.5000 (contention), κ = 0.000000 (coherency), R² = 1.000; throughput predicted to approach 100.0 requests/s without peaking\n"
+        );
+        for (sigma, kappa) in [(1.0, 0.0), (1.5, 0.0), (2.0, 0.01)] {
+            let fit = UslFit {
+                lambda: 50.0,
+                sigma,
+                kappa,
+                r_squared: 1.0,
+            };
+            assert!(
+                fit.to_string().ends_with(
+                    "; throughput predicted not to exceed the single-worker 50.0 requests/s\n"
+                ),
+                "{fit}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fit_too_few() {
+        let truth = UslFit {
+            lambda: 50.0,
+            sigma: 0.0,
+            kappa: 0.0,
+            r_squared: 1.0,
+        };
+        assert_eq!(UslFit::fit(&summaries(&truth, &[1, 2])), None);
+    }
+}
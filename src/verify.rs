@@ -0,0 +1,328 @@
+use crate::btn::{BoundedTreeNursery, Spawner};
+use crate::client::{Client, DirectoryResponse, RequestError};
+use anyhow::Context;
+use futures_util::{future::BoxFuture, FutureExt, TryStreamExt};
+use percent_encoding::percent_decode_str;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use url::Url;
+
+/// The type (and, for files, size) of an entry in a hierarchy
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum EntryKind {
+    Directory,
+    File { size: Option<u64> },
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryKind::Directory => write!(f, "directory"),
+            EntryKind::File { size: Some(size) } => write!(f, "file of {size} bytes"),
+            EntryKind::File { size: None } => write!(f, "file of unknown size"),
+        }
+    }
+}
+
+/// A mapping from paths relative to the root of a hierarchy (using forward
+/// slashes as separators) to the types of the entries at those paths
+pub(crate) type Listing = BTreeMap<String, EntryKind>;
+
+/// Traverse the remote hierarchy at `base_url` and the local directory
+/// `local` concurrently and compare their contents
+pub(crate) async fn verify(
+    client: Client,
+    base_url: Url,
+    local: PathBuf,
+    workers: usize,
+) -> anyhow::Result<VerifyReport> {
+    let local_task = tokio::task::spawn_blocking(move || walk_local(&local));
+    let (remote, _) = walk_remote(client, base_url, workers).await?;
+    let local = local_task
+        .await
+        .context("local directory walk panicked")?
+        .context("failed to walk local directory")?;
+    Ok(compare(&remote, &local))
+}
+
+/// Traverse the remote hierarchy at `base_url` and return its contents along
+/// with the time taken by each `PROPFIND` request
+pub(crate) async fn walk_remote(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+) -> Result<(Listing, Vec<Duration>), RequestError> {
+    let root = base_url.clone();
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        [move |spawner| list_remote_dir(spawner, client, root)],
+    );
+    let mut listing = Listing::new();
+    let mut times = Vec::new();
+    while let Some((elapsed, entries)) = stream.try_next().await? {
+        times.push(elapsed);
+        for (url, kind) in entries {
+            listing.insert(relative_path(&base_url, &url), kind);
+        }
+    }
+    Ok((listing, times))
+}
+
+/// Traverse the remote hierarchy at `base_url` and return the URLs of all
+/// collections beneath it (not including `base_url` itself)
+pub(crate) async fn remote_collections(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+) -> Result<Vec<Url>, RequestError> {
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        [move |spawner| list_remote_dir(spawner, client, base_url)],
+    );
+    let mut collections = Vec::new();
+    while let Some((_, entries)) = stream.try_next().await? {
+        collections.extend(
+            entries
+                .into_iter()
+                .filter(|(_, kind)| *kind == EntryKind::Directory)
+                .map(|(url, _)| url),
+        );
+    }
+    Ok(collections)
+}
+
+type RemoteEntries = (Duration, Vec<(Url, EntryKind)>);
+
+fn list_remote_dir(
+    spawner: Spawner<Result<RemoteEntries, RequestError>>,
+    client: Client,
+    url: Url,
+) -> BoxFuture<'static, Result<RemoteEntries, RequestError>> {
+    async move {
+        let DirectoryResponse {
+            listing, elapsed, ..
+        } = client.list_directory(url).await?;
+        let mut entries = Vec::new();
+        for d in listing.directories {
+            let cl2 = client.clone();
+            let url = d.href.clone();
+            spawner.spawn(move |spawner| list_remote_dir(spawner, cl2, url));
+            entries.push((d.href, EntryKind::Directory));
+        }
+        for f in listing.files {
+            entries.push((f.href, EntryKind::File { size: f.size }));
+        }
+        Ok((elapsed, entries))
+    }
+    .boxed()
+}
+
+/// Return the path of `url` relative to `base_url`, percent-decoded and with
+/// any trailing slash removed.  URLs not under `base_url` are returned in
+/// full.
+pub(crate) fn relative_path(base_url: &Url, url: &Url) -> String {
+    let relpath = if url.origin() == base_url.origin() {
+        url.path().strip_prefix(base_url.path())
+    } else {
+        None
+    };
+    match relpath {
+        Some(p) => percent_decode_str(p.trim_matches('/'))
+            .decode_utf8_lossy()
+            .into_owned(),
+        None => url.to_string(),
+    }
+}
+
+fn walk_local(root: &Path) -> std::io::Result<Listing> {
+    let mut listing = Listing::new();
+    let mut stack = vec![(root.to_owned(), String::new())];
+    while let Some((dirpath, relpath)) = stack.pop() {
+        for entry in std::fs::read_dir(&dirpath)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let entry_relpath = if relpath.is_empty() {
+                name
+            } else {
+                format!("{relpath}/{name}")
+            };
+            // Follow symlinks
+            let md = std::fs::metadata(entry.path())?;
+            if md.is_dir() {
+                listing.insert(entry_relpath.clone(), EntryKind::Directory);
+                stack.push((entry.path(), entry_relpath));
+            } else {
+                listing.insert(
+                    entry_relpath,
+                    EntryKind::File {
+                        size: Some(md.len()),
+                    },
+                );
+            }
+        }
+    }
+    Ok(listing)
+}
+
+pub(crate) fn compare(remote: &Listing, local: &Listing) -> VerifyReport {
+    let mut report = VerifyReport {
+        remote_entries: remote.len(),
+        local_entries: local.len(),
+        ..VerifyReport::default()
+    };
+    for (path, &rkind) in remote {
+        match local.get(path) {
+            None => report.missing.push(path.clone()),
+            Some(&lkind) => {
+                let matches = match (rkind, lkind) {
+                    (EntryKind::Directory, EntryKind::Directory) => true,
+                    // Sizes can only be compared if the server reported one
+                    (EntryKind::File { size: None }, EntryKind::File { .. }) => true,
+                    (EntryKind::File { size: rsize }, EntryKind::File { size: lsize }) => {
+                        rsize == lsize
+                    }
+                    _ => false,
+                };
+                if !matches {
+                    report.mismatched.push(Mismatch {
+                        path: path.clone(),
+                        remote: rkind,
+                        local: lkind,
+                    });
+                }
+            }
+        }
+    }
+    for path in local.keys() {
+        if !remote.contains_key(path) {
+            report.extra.push(path.clone());
+        }
+    }
+    report
+}
+
+/// An entry that exists on both sides but differs in type or size
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Mismatch {
+    pub(crate) path: String,
+    pub(crate) remote: EntryKind,
+    pub(crate) local: EntryKind,
+}
+
+/// The results of comparing a remote hierarchy with a local directory
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct VerifyReport {
+    pub(crate) remote_entries: usize,
+    pub(crate) local_entries: usize,
+    /// Paths that exist remotely but not locally
+    pub(crate) missing: Vec<String>,
+    /// Paths that exist locally but not remotely
+    pub(crate) extra: Vec<String>,
+    pub(crate) mismatched: Vec<Mismatch>,
+}
+
+impl VerifyReport {
+    /// The total number of discrepancies found
+    pub(crate) fn discrepancies(&self) -> usize {
+        self.missing
+            .len()
+            .saturating_add(self.extra.len())
+            .saturating_add(self.mismatched.len())
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Compared {} remote entries with {} local entries",
+            self.remote_entries, self.local_entries
+        )?;
+        for path in &self.missing {
+            writeln!(f, "MISSING: {path}")?;
+        }
+        for path in &self.extra {
+            writeln!(f, "EXTRA: {path}")?;
+        }
+        for m in &self.mismatched {
+            writeln!(
+                f,
+                "MISMATCH: {} (remote: {}, local: {})",
+                m.path, m.remote, m.local
+            )?;
+        }
+        writeln!(
+            f,
+            "{} missing, {} extra, {} mismatched",
+            self.missing.len(),
+            self.extra.len(),
+            self.mismatched.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://example.com/dav/", "https://example.com/dav/foo/", "foo")]
+    #[case(
+        "https://example.com/dav/",
+        "https://example.com/dav/foo/bar%20baz.txt",
+        "foo/bar baz.txt"
+    )]
+    #[case(
+        "https://example.com/dav/",
+        "https://elsewhere.org/dav/foo",
+        "https://elsewhere.org/dav/foo"
+    )]
+    fn test_relative_path(#[case] base: &str, #[case] url: &str, #[case] relpath: &str) {
+        let base = Url::parse(base).unwrap();
+        let url = Url::parse(url).unwrap();
+        assert_eq!(relative_path(&base, &url), relpath);
+    }
+
+    #[test]
+    fn test_compare() {
+        let file = |size| EntryKind::File { size };
+        let remote = Listing::from([
+            ("a".into(), EntryKind::Directory),
+            ("a/same.txt".into(), file(Some(5))),
+            ("a/unsized.txt".into(), file(None)),
+            ("a/bigger.txt".into(), file(Some(10))),
+            ("b".into(), EntryKind::Directory),
+            ("gone.txt".into(), file(Some(1))),
+        ]);
+        let local = Listing::from([
+            ("a".into(), EntryKind::Directory),
+            ("a/same.txt".into(), file(Some(5))),
+            ("a/unsized.txt".into(), file(Some(42))),
+            ("a/bigger.txt".into(), file(Some(7))),
+            ("b".into(), file(Some(0))),
+            ("new.txt".into(), file(Some(3))),
+        ]);
+        let report = compare(&remote, &local);
+        assert_eq!(report.missing, vec!["gone.txt".to_owned()]);
+        assert_eq!(report.extra, vec!["new.txt".to_owned()]);
+        assert_eq!(
+            report.mismatched,
+            vec![
+                Mismatch {
+                    path: "a/bigger.txt".into(),
+                    remote: file(Some(10)),
+                    local: file(Some(7)),
+                },
+                Mismatch {
+                    path: "b".into(),
+                    remote: EntryKind::Directory,
+                    local: file(Some(0)),
+                },
+            ]
+        );
+        assert_eq!(report.discrepancies(), 4);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::btn::BoundedTreeNursery;
+use crate::client::{Client, RequestError};
+use crate::stats::{ErrorSummary, LatencySummary};
+use crate::traverse::Task;
+use crate::types::path_order_key;
+use clap::ValueEnum;
+use futures_util::StreamExt;
+use std::fmt;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// The order in which to issue the requests of a warm-up run
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum WarmOrder {
+    /// The order in which the requests completed during the original
+    /// traversal
+    #[default]
+    Listed,
+    /// Shallower resources before deeper ones
+    BreadthFirst,
+    /// Each collection immediately followed by its contents
+    DepthFirst,
+    /// All non-collection resources before any collections
+    FilesFirst,
+}
+
+impl WarmOrder {
+    /// Sort `tasks` into this order.  Resources that compare equal keep their
+    /// original relative order.
+    pub(crate) fn arrange(self, tasks: &mut [Task]) {
+        match self {
+            WarmOrder::Listed => (),
+            WarmOrder::BreadthFirst => {
+                tasks.sort_by_cached_key(|t| path_order_key(t.url()).1.len());
+            }
+            WarmOrder::DepthFirst => tasks.sort_by_cached_key(|t| path_order_key(t.url())),
+            WarmOrder::FilesFirst => tasks.sort_by_key(|t| matches!(t, Task::Dir { .. })),
+        }
+    }
+}
+
+/// Issue the request for each of `tasks` (in order, subject to concurrency)
+/// using at most `workers` concurrent requests, without recursing into the
+/// collections listed
+pub(crate) async fn warm(
+    client: Client,
+    tasks: Vec<Task>,
+    workers: usize,
+    quiet: bool,
+) -> WarmReport {
+    let start = Instant::now();
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        tasks.into_iter().map(|task| {
+            let client = client.clone();
+            move |_spawner| warm_task(client, task)
+        }),
+    );
+    let mut report = WarmReport {
+        workers,
+        directory_request_times: Vec::new(),
+        file_request_times: Vec::new(),
+        errors: ErrorSummary::default(),
+        overall_time: Duration::ZERO,
+    };
+    while let Some(r) = stream.next().await {
+        match r {
+            Ok(WarmResult::Dir { url, elapsed }) => {
+                if !quiet {
+                    println!("DIR: {url} ({elapsed:?})");
+                }
+                report.directory_request_times.push(elapsed);
+            }
+            Ok(WarmResult::File { url, elapsed }) => {
+                if !quiet {
+                    println!("FILE: {url} ({elapsed:?})");
+                }
+                report.file_request_times.push(elapsed);
+            }
+            Err(e) => {
+                if !quiet {
+                    println!("ERROR: {}", e.url());
+                }
+                let kind = e.kind();
+                let url = e.url().clone();
+                report
+                    .errors
+                    .add(kind, &url, format!("{:#}", anyhow::Error::from(e)));
+            }
+        }
+    }
+    report.overall_time = start.elapsed();
+    report
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum WarmResult {
+    Dir { url: Url, elapsed: Duration },
+    File { url: Url, elapsed: Duration },
+}
+
+async fn warm_task(client: Client, task: Task) -> Result<WarmResult, RequestError> {
+    match task {
+        Task::Dir { url, .. } => {
+            let elapsed = client.list_directory(url.clone()).await?.elapsed;
+            Ok(WarmResult::Dir { url, elapsed })
+        }
+        Task::File { url, .. } => {
+            let elapsed = client.get_file_redirect(url.clone()).await?.elapsed;
+            Ok(WarmResult::File { url, elapsed })
+        }
+        Task::Zarr { url, .. } => {
+            let elapsed = client.list_tree(url.clone()).await?.elapsed;
+            Ok(WarmResult::Dir { url, elapsed })
+        }
+    }
+}
+
+/// The results of replaying an inventory
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct WarmReport {
+    pub(crate) workers: usize,
+    pub(crate) directory_request_times: Vec<Duration>,
+    pub(crate) file_request_times: Vec<Duration>,
+    pub(crate) errors: ErrorSummary,
+    pub(crate) overall_time: Duration,
+}
+
+impl fmt::Display for WarmReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Warmed {} directories and {} files with {} workers in {:?}",
+            self.directory_request_times.len(),
+            self.file_request_times.len(),
+            self.workers,
+            self.overall_time
+        )?;
+        if let Some(latency) = LatencySummary::new(&self.directory_request_times) {
+            writeln!(f, "  PROPFIND latency: {latency}")?;
+        }
+        if let Some(latency) = LatencySummary::new(&self.file_request_times) {
+            writeln!(f, "  HEAD latency: {latency}")?;
+        }
+        if !self.errors.is_empty() {
+            write!(f, "{}", self.errors)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(WarmOrder::Listed, &["/a/x.txt", "/", "/a/b/", "/b.txt", "/a/", "/a-b/"])]
+    #[case(WarmOrder::BreadthFirst, &["/", "/b.txt", "/a/", "/a-b/", "/a/x.txt", "/a/b/"])]
+    #[case(WarmOrder::DepthFirst, &["/", "/a/", "/a/b/", "/a/x.txt", "/a-b/", "/b.txt"])]
+    #[case(WarmOrder::FilesFirst, &["/a/x.txt", "/b.txt", "/", "/a/b/", "/a/", "/a-b/"])]
+    fn test_arrange(#[case] order: WarmOrder, #[case] expected: &[&str]) {
+        let url = |path: &str| Url::parse(&format!("https://example.com{path}")).unwrap();
+        let mut tasks = ["/a/x.txt", "/", "/a/b/", "/b.txt", "/a/", "/a-b/"]
+            .into_iter()
+            .map(|path| {
+                if path.ends_with('/') {
+                    Task::Dir {
+                        url: url(path),
+                        display_name: None,
+                    }
+                } else {
+                    Task::File {
+                        url: url(path),
+                        size: None,
+                        display_name: None,
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        order.arrange(&mut tasks);
+        let paths = tasks.iter().map(|t| t.url().path()).collect::<Vec<_>>();
+        assert_eq!(paths, expected);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::client::Client;
+use crate::export::serialize_seconds;
+use crate::hook::CompletionHook;
+use crate::stats::LatencySummary;
+use crate::traverse::{traverse, TraversalOptions, TraversalReport};
+use anyhow::Context;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::time::MissedTickBehavior;
+use url::Url;
+
+/// Options controlling how often `watch()` traverses and where it records
+/// the results
+#[derive(Debug)]
+pub(crate) struct WatchOptions {
+    /// Time between the starts of consecutive traversals
+    pub(crate) interval: Duration,
+    /// If set, stop after this many traversals
+    pub(crate) count: Option<NonZeroUsize>,
+    /// Log to which to append each traversal's summary
+    pub(crate) log: Option<RotatingLog>,
+    /// Command to run after each traversal
+    pub(crate) hook: Option<CompletionHook>,
+}
+
+/// Traverse the hierarchy at `base_url` every `options.interval` (forever, or
+/// until `options.count` traversals have been made), printing a JSON summary
+/// of each traversal to standard output and appending it to the log, if any.
+/// Failed traversals are summarized rather than ending the watch.
+pub(crate) async fn watch(
+    client: Client,
+    base_url: Url,
+    workers: usize,
+    traversal_options: &TraversalOptions,
+    mut options: WatchOptions,
+) -> anyhow::Result<()> {
+    let mut ticks = tokio::time::interval(options.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut iteration = 0usize;
+    while options.count.map_or(true, |count| iteration < count.get()) {
+        ticks.tick().await;
+        iteration = iteration.saturating_add(1);
+        let start_time = OffsetDateTime::now_utc();
+        let start = Instant::now();
+        let r = traverse(client.clone(), base_url.clone(), workers, traversal_options).await;
+        let elapsed = start.elapsed();
+        let summary = match r {
+            Ok(ref report) => IterationSummary::new(iteration, start_time, report),
+            Err(ref e) => {
+                IterationSummary::failed(iteration, start_time, workers, elapsed, format!("{e:#}"))
+            }
+        };
+        let line =
+            serde_json::to_string(&summary).context("failed to serialize traversal summary")?;
+        println!("{line}");
+        if let Some(log) = options.log.as_mut() {
+            log.write_line(&line)
+                .with_context(|| format!("failed to write to {}", log.path.display()))?;
+        }
+        // Run the hook only once the summary has been logged so that the
+        // command can read it from the log file
+        if let Some(ref hook) = options.hook {
+            match r {
+                Ok(report) => hook.traversal_complete(&report).await,
+                Err(_) => {
+                    hook.traversal_failed(
+                        workers,
+                        elapsed,
+                        summary.error.as_deref().unwrap_or_default(),
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A summary of a single traversal made by `watch()`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct IterationSummary {
+    /// The 1-based index of the traversal
+    pub(crate) iteration: usize,
+    /// Wall-clock time at which the traversal was started
+    #[serde(with = "time::serde::rfc3339")]
+    pub(crate) start_time: OffsetDateTime,
+    pub(crate) workers: usize,
+    /// Whether the traversal ran to completion
+    pub(crate) success: bool,
+    /// The error that aborted the traversal, if any
+    pub(crate) error: Option<String>,
+    pub(crate) directories: usize,
+    pub(crate) files: usize,
+    /// Number of failed requests that were skipped
+    pub(crate) errors: usize,
+    #[serde(serialize_with = "serialize_seconds")]
+    pub(crate) elapsed: Duration,
+    pub(crate) directory_latency: Option<LatencySummary>,
+    pub(crate) file_latency: Option<LatencySummary>,
+}
+
+impl IterationSummary {
+    fn new(iteration: usize, start_time: OffsetDateTime, report: &TraversalReport) -> Self {
+        IterationSummary {
+            iteration,
+            start_time,
+            workers: report.workers,
+            success: true,
+            error: None,
+            directories: report.directories,
+            files: report.files,
+            errors: report.errors.total(),
+            elapsed: report.overall_time,
+            directory_latency: report.directory_latency,
+            file_latency: report.file_latency,
+        }
+    }
+
+    fn failed(
+        iteration: usize,
+        start_time: OffsetDateTime,
+        workers: usize,
+        elapsed: Duration,
+        error: String,
+    ) -> Self {
+        IterationSummary {
+            iteration,
+            start_time,
+            workers,
+            success: false,
+            error: Some(error),
+            directories: 0,
+            files: 0,
+            errors: 0,
+            elapsed,
+            directory_latency: None,
+            file_latency: None,
+        }
+    }
+}
+
+/// A line-oriented log file that is rotated once it would exceed a maximum
+/// size: `FILE` is renamed to `FILE.1`, `FILE.1` to `FILE.2`, and so on, with
+/// the oldest file beyond the number to keep being overwritten
+#[derive(Debug)]
+pub(crate) struct RotatingLog {
+    path: PathBuf,
+    max_bytes: u64,
+    /// Number of rotated files to keep
+    keep: usize,
+    file: File,
+    /// Current size of `file`
+    size: u64,
+}
+
+impl RotatingLog {
+    pub(crate) fn open(path: PathBuf, max_bytes: u64, keep: usize) -> io::Result<RotatingLog> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingLog {
+            path,
+            max_bytes,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    /// Append `line` and a newline to the log, first rotating the log if
+    /// it's nonempty and would otherwise exceed its maximum size
+    pub(crate) fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = u64::try_from(line.len())
+            .unwrap_or(u64::MAX)
+            .saturating_add(1);
+        if self.size > 0 && self.size.saturating_add(len) > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.file.flush()?;
+        self.size = self.size.saturating_add(len);
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep > 0 {
+            for i in (1..self.keep).rev() {
+                match std::fs::rename(
+                    rotated_path(&self.path, i),
+                    rotated_path(&self.path, i.saturating_add(1)),
+                ) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => (),
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Return the path to which the log at `path` is moved on its `n`-th most
+/// recent rotation
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(format!(".{n}"));
+    PathBuf::from(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotated_path() {
+        assert_eq!(
+            rotated_path(Path::new("logs/watch.ndjson"), 2),
+            Path::new("logs/watch.ndjson.2")
+        );
+    }
+
+    /// Return a fresh directory in which to create log files
+    fn log_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("batchdav-watch-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_rotating_log() {
+        let dir = log_dir("rotate");
+        let path = dir.join("watch.ndjson");
+        let mut log = RotatingLog::open(path.clone(), 10, 2).unwrap();
+        log.write_line("aaaa").unwrap();
+        log.write_line("bbbb").unwrap();
+        assert_eq!(read(&path), "aaaa\nbbbb\n");
+        assert!(!rotated_path(&path, 1).exists(), "log rotated too soon");
+        log.write_line("cccc").unwrap();
+        assert_eq!(read(&path), "cccc\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "aaaa\nbbbb\n");
+        log.write_line("dddd").unwrap();
+        log.write_line("eeee").unwrap();
+        assert_eq!(read(&path), "eeee\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "cccc\ndddd\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "aaaa\nbbbb\n");
+        log.write_line("ffff").unwrap();
+        log.write_line("gggg").unwrap();
+        assert_eq!(read(&path), "gggg\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "eeee\nffff\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "cccc\ndddd\n");
+        assert!(!rotated_path(&path, 3).exists(), "too many logs kept");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotating_log_existing() {
+        let dir = log_dir("existing");
+        let path = dir.join("watch.ndjson");
+        std::fs::write(&path, "old line\n").unwrap();
+        let mut log = RotatingLog::open(path.clone(), 12, 1).unwrap();
+        log.write_line("new").unwrap();
+        assert_eq!(read(&path), "new\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "old line\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotating_log_keep_none() {
+        let dir = log_dir("keep-none");
+        let path = dir.join("watch.ndjson");
+        let mut log = RotatingLog::open(path.clone(), 5, 0).unwrap();
+        log.write_line("aaaa").unwrap();
+        log.write_line("bbbb").unwrap();
+        assert_eq!(read(&path), "bbbb\n");
+        assert!(!rotated_path(&path, 1).exists(), "rotated log was kept");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotating_log_oversized_line() {
+        let dir = log_dir("oversized");
+        let path = dir.join("watch.ndjson");
+        let mut log = RotatingLog::open(path.clone(), 4, 1).unwrap();
+        log.write_line("a long line").unwrap();
+        assert_eq!(read(&path), "a long line\n");
+        assert!(
+            !rotated_path(&path, 1).exists(),
+            "empty log should not be rotated"
+        );
+        log.write_line("next").unwrap();
+        assert_eq!(read(&path), "next\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "a long line\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_serialize_failed_summary() {
+        let summary = IterationSummary::failed(
+            3,
+            OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap(),
+            8,
+            Duration::from_millis(1500),
+            "connection refused".into(),
+        );
+        assert_eq!(
+            serde_json::to_string(&summary).unwrap(),
+            concat!(
+                r#"{"iteration":3,"start_time":"2023-11-14T22:13:20Z","workers":8,"#,
+                r#""success":false,"error":"connection refused","directories":0,"#,
+                r#""files":0,"errors":0,"elapsed":1.5,"directory_latency":null,"#,
+                r#""file_latency":null}"#,
+            )
+        );
+    }
+}
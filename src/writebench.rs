@@ -0,0 +1,166 @@
+use crate::batch::SummaryFormat;
+use crate::btn::{BoundedTreeNursery, Spawner};
+use crate::client::{Client, RequestError};
+use crate::stats::{Sample, WorkerSamples, WorkerSummary};
+use crate::table::{Align, Table};
+use bytes::Bytes;
+use futures_util::{future::BoxFuture, FutureExt, TryStreamExt};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// The shape of a synthetic hierarchy to create
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct TreeSpec {
+    /// Number of levels of subcollections below the root collection
+    pub(crate) depth: usize,
+    /// Number of subcollections in each collection above the lowest level
+    pub(crate) fanout: usize,
+    /// Number of files in each collection
+    pub(crate) files: usize,
+    /// Size of each file in bytes
+    pub(crate) file_size: usize,
+}
+
+impl TreeSpec {
+    /// The total number of requests needed to create the hierarchy
+    pub(crate) fn requests(&self) -> usize {
+        let mut collections = 1usize;
+        let mut level = 1usize;
+        for _ in 0..self.depth {
+            level = level.saturating_mul(self.fanout);
+            collections = collections.saturating_add(level);
+        }
+        collections.saturating_mul(self.files.saturating_add(1))
+    }
+}
+
+/// Create a hierarchy of the shape given by `spec` rooted at the (not yet
+/// existing) collection `root` using at most `workers` concurrent requests,
+/// and return the number of requests made & the time taken
+pub(crate) async fn create_tree(
+    client: Client,
+    root: Url,
+    spec: TreeSpec,
+    workers: usize,
+) -> Result<Sample, RequestError> {
+    let start = Instant::now();
+    let body = Bytes::from(vec![0u8; spec.file_size]);
+    let mut stream = BoundedTreeNursery::new(
+        workers,
+        [move |spawner| make_collection(spawner, client, root, spec, body, 0)],
+    );
+    let mut requests = 0usize;
+    while let Some(_elapsed) = stream.try_next().await? {
+        requests = requests.saturating_add(1);
+    }
+    Ok(Sample {
+        requests,
+        elapsed: start.elapsed(),
+        utilization: None,
+    })
+}
+
+fn make_collection(
+    spawner: Spawner<Result<Duration, RequestError>>,
+    client: Client,
+    url: Url,
+    spec: TreeSpec,
+    body: Bytes,
+    level: usize,
+) -> BoxFuture<'static, Result<Duration, RequestError>> {
+    async move {
+        let elapsed = client.mkcol(url.clone()).await?;
+        if level < spec.depth {
+            for i in 0..spec.fanout {
+                let cl2 = client.clone();
+                let subdir = join_url(&url, &format!("dir{i:04}/"));
+                let body = body.clone();
+                spawner.spawn(move |spawner| {
+                    make_collection(spawner, cl2, subdir, spec, body, level.saturating_add(1))
+                });
+            }
+        }
+        for i in 0..spec.files {
+            let cl2 = client.clone();
+            let file = join_url(&url, &format!("file{i:04}.dat"));
+            let body = body.clone();
+            spawner.spawn(move |_spawner| async move { cl2.put(file, body).await });
+        }
+        Ok(elapsed)
+    }
+    .boxed()
+}
+
+/// Print the mean & standard deviation of the time taken and of the write
+/// throughput (requests per second) for each number of workers
+pub(crate) fn print_summary(format: SummaryFormat, samples: &WorkerSamples) {
+    let mut table = Table::new()
+        .column("Workers", Align::Right)
+        .column("Samples", Align::Right)
+        .column("Mean Time", Align::Right)
+        .column("Std Dev", Align::Right)
+        .column("Mean Req/s", Align::Right)
+        .column("Std Dev", Align::Right);
+    if format == SummaryFormat::Csv {
+        println!("workers,time_mean,time_stddev,throughput_mean,throughput_stddev");
+    }
+    for ws in samples.summarize() {
+        let WorkerSummary {
+            workers,
+            samples,
+            time_mean,
+            time_stddev,
+            throughput_mean,
+            throughput_stddev,
+            ..
+        } = ws;
+        match format {
+            SummaryFormat::Csv => println!(
+                "{workers},{time_mean},{time_stddev},{throughput_mean},{throughput_stddev}"
+            ),
+            SummaryFormat::Table => table.push_row(vec![
+                workers.to_string(),
+                samples.to_string(),
+                format!("{time_mean:.3} s"),
+                format!("{time_stddev:.3} s"),
+                format!("{throughput_mean:.1}"),
+                format!("{throughput_stddev:.1}"),
+            ]),
+        }
+    }
+    if format == SummaryFormat::Table {
+        print!("{table}");
+    }
+}
+
+/// Return the URL for the given entry inside the collection at `url` (which
+/// must end in a slash)
+pub(crate) fn join_url(url: &Url, name: &str) -> Url {
+    url.join(name)
+        .expect("relative path with no special characters should be joinable to URL")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, 5, 3, 4)]
+    #[case(1, 2, 0, 3)]
+    #[case(2, 3, 2, 39)]
+    fn test_requests(
+        #[case] depth: usize,
+        #[case] fanout: usize,
+        #[case] files: usize,
+        #[case] requests: usize,
+    ) {
+        let spec = TreeSpec {
+            depth,
+            fanout,
+            files,
+            file_size: 0,
+        };
+        assert_eq!(spec.requests(), requests);
+    }
+}
@@ -1,6 +1,13 @@
-use crate::types::DirectoryListing;
-use bytes::{Buf, Bytes};
+use crate::types::{DirectoryListing, Entry};
+use bytes::Bytes;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+use std::fmt;
 use thiserror::Error;
+use time::{
+    format_description::well_known::{Rfc2822, Rfc3339},
+    OffsetDateTime,
+};
 use winnow::{
     combinator::{alt, delimited, opt, preceded, repeat, seq},
     error::{ContextError, ErrMode, ErrorKind, ParserError},
@@ -13,11 +20,246 @@ use xml::reader::{Error as XmlError, ParserConfig2, XmlEvent};
 /// The XML namespace for standard WebDAV elements
 static DAV_XMLNS: &str = "DAV:";
 
+/// Options for parsing XML responses.  The limits on the size of documents
+/// are enforced during tokenization so that pathological documents are
+/// rejected before they can consume unbounded memory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct XmlOptions {
+    /// Maximum depth to which elements may be nested
+    pub(crate) max_depth: usize,
+    /// Maximum total number of tokens (start tags, end tags, and text nodes)
+    pub(crate) max_tokens: usize,
+    /// Decode documents using the encoding named in the XML declaration, if
+    /// any, in preference to the charset given in the `Content-Type` header
+    pub(crate) prefer_declared_encoding: bool,
+    /// Tolerate recoverable deviations from the expected document structure,
+    /// reporting them as warnings instead of failing
+    pub(crate) lenient: bool,
+}
+
+impl XmlOptions {
+    pub(crate) const DEFAULT_MAX_DEPTH: usize = 64;
+    pub(crate) const DEFAULT_MAX_TOKENS: usize = 10_000_000;
+}
+
+impl Default for XmlOptions {
+    fn default() -> XmlOptions {
+        XmlOptions {
+            max_depth: XmlOptions::DEFAULT_MAX_DEPTH,
+            max_tokens: XmlOptions::DEFAULT_MAX_TOKENS,
+            prefer_declared_encoding: false,
+            lenient: false,
+        }
+    }
+}
+
 pub(crate) fn parse_multistatus(
     blob: Bytes,
     charset: Option<String>,
-) -> Result<DirectoryListing<String>, FromXmlError> {
-    parse(tokenize(blob, charset)?)
+    options: XmlOptions,
+) -> Result<Multistatus, FromXmlError> {
+    let (tokens, charset_fallback) = tokenize(blob, charset, options)?;
+    let mut ms = parse(tokens, options.lenient)?;
+    ms.charset_fallback = charset_fallback;
+    Ok(ms)
+}
+
+/// Parse the quota properties from a multistatus response to a `Depth: 0`
+/// PROPFIND request
+pub(crate) fn parse_quota(
+    blob: Bytes,
+    charset: Option<String>,
+    options: XmlOptions,
+) -> Result<Quota, FromXmlError> {
+    let Some(r) = parse_responses(tokenize(blob, charset, options)?.0, options.lenient)?
+        .responses
+        .into_iter()
+        .next()
+    else {
+        return Err(FromXmlError::Parse);
+    };
+    if is_ok(&r.status) {
+        Ok(r.quota)
+    } else {
+        Err(FromXmlError::BadStatus {
+            href: r.href,
+            status: r.status,
+        })
+    }
+}
+
+/// Parse the body of an S3 `ListObjectsV2` response.  Element namespaces are
+/// ignored, as S3-compatible servers differ in whether they declare one.
+pub(crate) fn parse_list_bucket_result(
+    blob: Bytes,
+    charset: Option<String>,
+    options: XmlOptions,
+) -> Result<ListBucketResult, FromXmlError> {
+    let mut result = ListBucketResult::default();
+    let mut path = Vec::new();
+    let mut object = None;
+    for token in tokenize(blob, charset, options)?.0 {
+        match token {
+            Token::OpenDav(name) | Token::OpenExt { name, .. } => {
+                if path.is_empty() && name != "ListBucketResult" {
+                    return Err(FromXmlError::Parse);
+                }
+                if path.len() == 1 && name == "Contents" {
+                    object = Some(S3Object::default());
+                }
+                path.push(name);
+            }
+            Token::CloseDav(name) | Token::CloseExt { name, .. } => {
+                path.pop();
+                if path.len() == 1 && name == "Contents" {
+                    match object.take() {
+                        Some(obj) if !obj.key.is_empty() => result.contents.push(obj),
+                        _ => return Err(FromXmlError::Parse),
+                    }
+                }
+            }
+            Token::Text(text) => {
+                let names = path.iter().map(String::as_str).collect::<Vec<_>>();
+                match names[..] {
+                    [_, "IsTruncated"] => result.is_truncated = text.trim() == "true",
+                    [_, "NextContinuationToken"] => result.next_continuation_token = Some(text),
+                    [_, "CommonPrefixes", "Prefix"] => result.common_prefixes.push(text),
+                    [_, "Contents", field] => {
+                        if let Some(obj) = object.as_mut() {
+                            match field {
+                                "Key" => obj.key.push_str(&text),
+                                "Size" => obj.size = text.trim().parse().ok(),
+                                "ETag" => obj.etag = Some(text),
+                                "LastModified" => {
+                                    obj.last_modified =
+                                        OffsetDateTime::parse(text.trim(), &Rfc3339).ok();
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    if path.is_empty() && object.is_none() {
+        Ok(result)
+    } else {
+        Err(FromXmlError::Parse)
+    }
+}
+
+/// Parse a multistatus response to a `<propname/>` PROPFIND request into a
+/// list of hrefs paired with the names of the properties defined on them
+pub(crate) fn parse_propnames(
+    blob: Bytes,
+    charset: Option<String>,
+    options: XmlOptions,
+) -> Result<Vec<(String, Vec<PropName>)>, FromXmlError> {
+    parse_responses(tokenize(blob, charset, options)?.0, options.lenient)?
+        .responses
+        .into_iter()
+        .map(|r| {
+            if is_ok(&r.status) {
+                Ok((r.href, r.prop_names))
+            } else {
+                Err(FromXmlError::BadStatus {
+                    href: r.href,
+                    status: r.status,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Parse a multistatus response into per-response summaries of the details
+/// relevant to checking the server's protocol hygiene.  Unlike
+/// `parse_multistatus()`, responses with non-OK statuses are included rather
+/// than treated as errors.
+pub(crate) fn parse_response_summaries(
+    blob: Bytes,
+    charset: Option<String>,
+    options: XmlOptions,
+) -> Result<Vec<ResponseSummary>, FromXmlError> {
+    Ok(
+        parse_responses(tokenize(blob, charset, options)?.0, options.lenient)?
+            .responses
+            .into_iter()
+            .map(|r| ResponseSummary {
+                href: r.href,
+                is_collection: r.is_collection,
+                propstat_statuses: r.propstat_statuses,
+                invalid_status_lines: r.invalid_status_lines,
+                warnings: r.warnings,
+            })
+            .collect(),
+    )
+}
+
+/// The details of a single `<response>` element used for checking protocol
+/// hygiene
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ResponseSummary {
+    /// The href exactly as given in the response
+    pub(crate) href: String,
+    pub(crate) is_collection: bool,
+    /// The status codes of the response's `<propstat>` elements
+    pub(crate) propstat_statuses: Vec<u16>,
+    /// The `<propstat>` status lines from which no status code could be
+    /// extracted
+    pub(crate) invalid_status_lines: Vec<String>,
+    /// Deviations tolerated in lenient mode
+    pub(crate) warnings: Vec<XmlWarning>,
+}
+
+/// The information extracted from a multistatus response
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Multistatus {
+    pub(crate) listing: DirectoryListing<String>,
+    /// The status codes of all `<propstat>` elements in the response, in
+    /// order of appearance
+    pub(crate) propstat_statuses: Vec<u16>,
+    /// Set if the response could not be decoded using its declared encoding
+    pub(crate) charset_fallback: Option<CharsetFallback>,
+    /// The nonempty texts of all `<responsedescription>` elements in the
+    /// response, in order of appearance
+    pub(crate) descriptions: Vec<String>,
+    /// Deviations from the expected structure that were tolerated in lenient
+    /// mode
+    pub(crate) warnings: Vec<XmlWarning>,
+}
+
+/// A recoverable deviation from the expected structure of a multistatus
+/// response, tolerated in lenient mode
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum XmlWarning {
+    /// A DAV element not permitted at its location was skipped
+    UnexpectedElement(String),
+    /// A repeated occurrence of an element that may only occur once was
+    /// skipped
+    DuplicateElement(String),
+    /// A malformed status line was interpreted as the given status code
+    StatusLine { line: String, code: u16 },
+    /// An element whose content could not be parsed was treated as empty
+    InvalidValue { element: String, value: String },
+}
+
+impl fmt::Display for XmlWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlWarning::UnexpectedElement(name) => {
+                write!(f, "ignored unexpected <{name}> element")
+            }
+            XmlWarning::DuplicateElement(name) => write!(f, "ignored duplicate <{name}> element"),
+            XmlWarning::StatusLine { line, code } => {
+                write!(f, "interpreted malformed status line {line:?} as {code}")
+            }
+            XmlWarning::InvalidValue { element, value } => {
+                write!(f, "ignored invalid value {value:?} of <{element}> element")
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -47,6 +289,55 @@ impl Token {
     }
 }
 
+/// A single page of the results of an S3 `ListObjectsV2` request
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ListBucketResult {
+    /// The keys of the objects listed on this page
+    pub(crate) contents: Vec<S3Object>,
+    /// The key prefixes, each ending in the delimiter, under which further
+    /// objects are grouped
+    pub(crate) common_prefixes: Vec<String>,
+    /// Whether there are further pages of results
+    pub(crate) is_truncated: bool,
+    /// The token with which to request the next page of results
+    pub(crate) next_continuation_token: Option<String>,
+}
+
+/// An object listed in an S3 `ListObjectsV2` response
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct S3Object {
+    pub(crate) key: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<OffsetDateTime>,
+}
+
+/// Quota information for a collection, as defined by RFC 4331.  Each field is
+/// `None` if the server did not report the corresponding property.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Quota {
+    /// The value of the `quota-available-bytes` property
+    pub(crate) available_bytes: Option<u64>,
+    /// The value of the `quota-used-bytes` property
+    pub(crate) used_bytes: Option<u64>,
+}
+
+/// The name of a WebDAV property
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) struct PropName {
+    pub(crate) namespace: String,
+    pub(crate) name: String,
+}
+
+impl PropName {
+    fn dav(name: &str) -> PropName {
+        PropName {
+            namespace: DAV_XMLNS.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+}
+
 impl Compare<Token> for &[Token] {
     #[inline]
     fn compare(&self, t: Token) -> CompareResult {
@@ -65,26 +356,191 @@ impl SliceLen for Token {
     }
 }
 
-fn tokenize(blob: Bytes, charset: Option<String>) -> Result<Vec<Token>, XmlTokenizeError> {
-    let encoding = charset.and_then(|cs| cs.parse::<xml::Encoding>().ok());
+/// Tokenize an XML document, returning the tokens along with a note if the
+/// document had to be decoded using a fallback encoding
+fn tokenize(
+    blob: Bytes,
+    charset: Option<String>,
+    options: XmlOptions,
+) -> Result<(Vec<Token>, Option<CharsetFallback>), XmlTokenizeError> {
+    let (text, charset_fallback) = decode(&blob, charset, options.prefer_declared_encoding);
+    // xml-rs expands entities declared in an internal DTD subset, so refuse
+    // to process documents with DTDs at all in order to rule out
+    // entity-expansion attacks from untrusted servers.  The check is made on
+    // the decoded text, as that is exactly what xml-rs will parse.
+    if has_doctype(&text) {
+        return Err(XmlTokenizeError::Doctype);
+    }
+    // The document has already been decoded, so make xml-rs ignore whatever
+    // encoding the XML declaration claims.
     let reader = ParserConfig2::new()
-        .ignore_invalid_encoding_declarations(false)
-        .override_encoding(encoding)
+        .ignore_invalid_encoding_declarations(true)
+        .override_encoding(Some(xml::Encoding::Utf8))
         .allow_multiple_root_elements(false)
         .trim_whitespace(true)
-        .create_reader(blob.reader());
+        .create_reader(text.as_bytes());
     let mut tokens = Vec::new();
+    let mut depth = 0usize;
     for event in reader {
         use XmlEvent::*;
         match event? {
-            StartElement { name, .. } => tokens.push(Token::open(name.local_name, name.namespace)),
-            EndElement { name, .. } => tokens.push(Token::close(name.local_name, name.namespace)),
+            StartElement { name, .. } => {
+                depth = depth.saturating_add(1);
+                if depth > options.max_depth {
+                    return Err(XmlTokenizeError::TooDeep {
+                        limit: options.max_depth,
+                    });
+                }
+                tokens.push(Token::open(name.local_name, name.namespace));
+            }
+            EndElement { name, .. } => {
+                depth = depth.saturating_sub(1);
+                tokens.push(Token::close(name.local_name, name.namespace));
+            }
             CData(s) | Characters(s) => tokens.push(Token::Text(s)),
             StartDocument { .. } | EndDocument | Comment(..) | Whitespace(..) => (),
             ProcessingInstruction { .. } => return Err(XmlTokenizeError::ProcessingInstruction),
         }
+        if tokens.len() > options.max_tokens {
+            return Err(XmlTokenizeError::TooManyTokens {
+                limit: options.max_tokens,
+            });
+        }
+    }
+    Ok((tokens, charset_fallback))
+}
+
+/// A note that a document could not be decoded using the encoding that it
+/// declared (or, if it declared none, using UTF-8)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct CharsetFallback {
+    /// The encoding declared for the document, if any
+    pub(crate) declared: Option<String>,
+    /// The name of the encoding actually used to decode the document
+    pub(crate) used: &'static str,
+}
+
+impl fmt::Display for CharsetFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.declared {
+            Some(ref declared) => write!(
+                f,
+                "could not decode response as {declared:?}; decoded as {} instead",
+                self.used
+            ),
+            None => write!(
+                f,
+                "response is not valid UTF-8; decoded as {} instead",
+                self.used
+            ),
+        }
+    }
+}
+
+/// Decode an XML document to a string.
+///
+/// A byte order mark, if present, determines the encoding.  Otherwise, the
+/// charset from the `Content-Type` header and the encoding named in the XML
+/// declaration are consulted in turn (in the opposite order if
+/// `prefer_declared` is true), with UTF-8 used if neither is present.  If the
+/// chosen encoding is unknown or the document is not valid in it, UTF-8 and
+/// then windows-1252 (a superset of Latin-1 that can decode any byte
+/// sequence) are tried, and a `CharsetFallback` is returned.
+fn decode(
+    blob: &[u8],
+    charset: Option<String>,
+    prefer_declared: bool,
+) -> (Cow<'_, str>, Option<CharsetFallback>) {
+    let (primary, body, declared) = if let Some((enc, bom_len)) = Encoding::for_bom(blob) {
+        (Some(enc), &blob[bom_len..], Some(enc.name().to_owned()))
+    } else {
+        let in_decl = declared_encoding(blob);
+        let declared = if prefer_declared {
+            in_decl.or(charset)
+        } else {
+            charset.or(in_decl)
+        };
+        let primary = match declared {
+            Some(ref label) => Encoding::for_label(label.trim().as_bytes()),
+            None => Some(UTF_8),
+        };
+        (primary, blob, declared)
+    };
+    if let Some(s) =
+        primary.and_then(|enc| enc.decode_without_bom_handling_and_without_replacement(body))
+    {
+        return (s, None);
+    }
+    if primary != Some(UTF_8) {
+        if let Some(s) = UTF_8.decode_without_bom_handling_and_without_replacement(body) {
+            return (
+                s,
+                Some(CharsetFallback {
+                    declared,
+                    used: UTF_8.name(),
+                }),
+            );
+        }
+    }
+    let (s, _) = WINDOWS_1252.decode_without_bom_handling(body);
+    (
+        s,
+        Some(CharsetFallback {
+            declared,
+            used: WINDOWS_1252.name(),
+        }),
+    )
+}
+
+/// Return the value of the `encoding` pseudo-attribute in the XML declaration
+/// at the start of `blob`, if any.  Documents in UTF-16 without a byte order
+/// mark are recognized by the declaration's byte pattern.
+fn declared_encoding(blob: &[u8]) -> Option<String> {
+    let ascii = match blob {
+        [b'<', 0, b'?', 0, ..] => Cow::Owned(blob.iter().step_by(2).copied().collect::<Vec<u8>>()),
+        [0, b'<', 0, b'?', ..] => Cow::Owned(blob.iter().skip(1).step_by(2).copied().collect()),
+        _ => Cow::Borrowed(blob),
+    };
+    let decl = ascii.strip_prefix(b"<?xml")?;
+    let decl = &decl[..decl.windows(2).position(|w| w == b"?>")?];
+    let i = decl.windows(8).position(|w| w == b"encoding")?;
+    let rest = trim_start(&decl[i.saturating_add(8)..]);
+    let rest = trim_start(rest.strip_prefix(b"=")?);
+    let (&quote, rest) = rest.split_first()?;
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+    let end = rest.iter().position(|&b| b == quote)?;
+    String::from_utf8(rest[..end].to_vec()).ok()
+}
+
+/// Strip leading XML whitespace from a byte string
+fn trim_start(bs: &[u8]) -> &[u8] {
+    let start = bs
+        .iter()
+        .position(|b| !matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
+        .unwrap_or(bs.len());
+    &bs[start..]
+}
+
+/// Returns true if the prolog of the decoded XML document `text` (i.e.,
+/// everything before the root element) contains a document type declaration
+fn has_doctype(text: &str) -> bool {
+    let mut s = text;
+    loop {
+        s = s.trim_start_matches([' ', '\t', '\r', '\n']);
+        let (rest, terminator) = if let Some(rest) = s.strip_prefix("<?") {
+            (rest, "?>")
+        } else if let Some(rest) = s.strip_prefix("<!--") {
+            (rest, "-->")
+        } else {
+            return s.starts_with("<!DOCTYPE");
+        };
+        match rest.split_once(terminator) {
+            Some((_, after)) => s = after,
+            None => return false,
+        }
     }
-    Ok(tokens)
 }
 
 /*
@@ -104,46 +560,121 @@ the actual text of the RFC states that the order of elements doesn't matter.
     <!ELEMENT location (href)>
 
 The responses that we'll be receiving match the following subset of the above
-(again, element order is irrelevant):
+(again, element order is irrelevant; <getcontentlength> and the quota
+properties are only present if requested by the client, and exactly one <prop> in each <response> must contain
+a <resourcetype>).  As the user may supply their own PROPFIND body, <prop> may
+also contain any number of other properties, which are ignored:
 
     <!ELEMENT multistatus (response*, responsedescription?)>
     <!ELEMENT response (href, propstat+, responsedescription?, location?)>
     <!ELEMENT href (#PCDATA)>
     <!ELEMENT propstat (prop, status, responsedescription?)>
-    <!ELEMENT prop (resourcetype)>
-    <!ELEMENT resourcetype (collection?)>
+    <!ELEMENT prop (resourcetype?, getcontentlength?, quota-available-bytes?,
+                    quota-used-bytes?, ANY)>
+    <!ELEMENT resourcetype (collection?, ANY)>
     <!ELEMENT collection EMPTY>
+    <!ELEMENT getcontentlength (#PCDATA)>
+    <!ELEMENT quota-available-bytes (#PCDATA)>
+    <!ELEMENT quota-used-bytes (#PCDATA)>
     <!ELEMENT status (#PCDATA)>
     <!ELEMENT responsedescription (#PCDATA)>
     <!ELEMENT location (href)>
 
 */
 
-fn parse(tokens: Vec<Token>) -> Result<DirectoryListing<String>, FromXmlError> {
-    let (responses,): (Vec<Option<Response>>,) = seq!(
+/// The contents of a multistatus document
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct MultistatusContents {
+    responses: Vec<Response>,
+    /// The nonempty texts of all `<responsedescription>` elements in the
+    /// document, at any level
+    descriptions: Vec<String>,
+    /// Deviations tolerated in lenient mode
+    warnings: Vec<XmlWarning>,
+}
+
+fn parse_responses(tokens: Vec<Token>, lenient: bool) -> Result<MultistatusContents, FromXmlError> {
+    let (children,): (Vec<MultistatusChild>,) = seq!(
         _: open("multistatus"),
-        repeat(0.., preceded(extensions, alt((response.map(Some), responsedescription.map(|()| None))))),
+        repeat(0.., preceded(extensions, alt((
+            response(lenient).map(|r| MultistatusChild::Response(Box::new(r))),
+            responsedescription.map(MultistatusChild::Description),
+            unexpected(lenient, &["response", "responsedescription"])
+                .map(MultistatusChild::Unexpected),
+        )))),
         _: extensions,
         _: close("multistatus"),
     )
     .parse(tokens.as_slice())
     .map_err(|_| FromXmlError::Parse)?;
+    let mut responses = Vec::new();
+    let mut descriptions = Vec::new();
+    let mut warnings = Vec::new();
+    for child in children {
+        match child {
+            MultistatusChild::Response(mut r) => {
+                descriptions.append(&mut r.descriptions);
+                warnings.extend(r.warnings.iter().cloned());
+                responses.push(*r);
+            }
+            MultistatusChild::Description(text) => descriptions.extend(text),
+            MultistatusChild::Unexpected(name) => {
+                warnings.push(XmlWarning::UnexpectedElement(name));
+            }
+        }
+    }
+    Ok(MultistatusContents {
+        responses,
+        descriptions,
+        warnings,
+    })
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum MultistatusChild {
+    Response(Box<Response>),
+    Description(Option<String>),
+    Unexpected(String),
+}
+
+fn parse(tokens: Vec<Token>, lenient: bool) -> Result<Multistatus, FromXmlError> {
     let mut directories = Vec::new();
     let mut files = Vec::new();
-    for r in responses.into_iter().flatten() {
+    let mut propstat_statuses = Vec::new();
+    let MultistatusContents {
+        responses,
+        descriptions,
+        warnings,
+    } = parse_responses(tokens, lenient)?;
+    for r in responses {
+        propstat_statuses.extend(r.propstat_statuses);
         if !is_ok(&r.status) {
             return Err(FromXmlError::BadStatus {
                 href: r.href,
                 status: r.status,
             });
         }
+        let entry = Entry {
+            href: r.href,
+            size: r.size,
+            display_name: r.display_name,
+            etag: r.etag,
+            modified: r.modified,
+            content_type: r.content_type,
+        };
         if r.is_collection {
-            directories.push(r.href);
+            directories.push(entry);
         } else {
-            files.push(r.href);
+            files.push(entry);
         }
     }
-    Ok(DirectoryListing { directories, files })
+    Ok(Multistatus {
+        listing: DirectoryListing { directories, files },
+        propstat_statuses,
+        charset_fallback: None,
+        descriptions,
+        warnings,
+    })
 }
 
 //type TokenStream<'a> = Located<&'a [Token]>;
@@ -166,130 +697,500 @@ struct Response {
     href: String,
     is_collection: bool,
     status: String,
+    size: Option<u64>,
+    display_name: Option<String>,
+    etag: Option<String>,
+    modified: Option<OffsetDateTime>,
+    content_type: Option<String>,
+    quota: Quota,
+    propstat_statuses: Vec<u16>,
+    /// Propstat status lines from which no status code could be extracted
+    invalid_status_lines: Vec<String>,
+    /// The names of all properties in propstats with OK statuses
+    prop_names: Vec<PropName>,
+    /// The texts of `<responsedescription>` elements in the response and its
+    /// propstats
+    descriptions: Vec<String>,
+    /// Deviations tolerated in lenient mode
+    warnings: Vec<XmlWarning>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum ResponseChild {
     Href(String),
-    Propstat(Propstat),
+    Propstat(Box<Propstat>),
+    Description(Option<String>),
+    Unexpected(String),
     Discard,
 }
 
-fn response(input: &mut TokenStream<'_>) -> PResult<Response> {
-    let (children,): (Vec<ResponseChild>,) = seq!(
-        _: open("response"),
-        repeat(0.., preceded(extensions, alt((
-            href_tag.map(ResponseChild::Href),
-            propstat.map(ResponseChild::Propstat),
-            responsedescription.map(|()| ResponseChild::Discard),
-            location.map(|()| ResponseChild::Discard),
-        )))),
-        _: extensions,
-        _: close("response"),
-    )
-    .parse_next(input)?;
-    let mut href = None;
-    let mut is_collection: Option<bool> = None;
-    let mut status = None;
-    for child in children {
-        match child {
-            ResponseChild::Href(value) => {
-                if href.replace(value).is_some() {
-                    return hard_fail(input);
+fn response<'a>(lenient: bool) -> impl Parser<TokenStream<'a>, Response, ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        let (children,): (Vec<ResponseChild>,) = seq!(
+            _: open("response"),
+            repeat(0.., preceded(extensions, alt((
+                href_tag.map(ResponseChild::Href),
+                propstat(lenient).map(|ps| ResponseChild::Propstat(Box::new(ps))),
+                responsedescription.map(ResponseChild::Description),
+                location.map(|()| ResponseChild::Discard),
+                unexpected(lenient, &["href", "propstat", "responsedescription", "location"])
+                    .map(ResponseChild::Unexpected),
+            )))),
+            _: extensions,
+            _: close("response"),
+        )
+        .parse_next(input)?;
+        let mut href = None;
+        let mut is_collection: Option<bool> = None;
+        let mut status = None;
+        let mut size = None;
+        let mut display_name = None;
+        let mut etag = None;
+        let mut modified = None;
+        let mut content_type = None;
+        let mut quota = Quota::default();
+        let mut propstat_statuses = Vec::new();
+        let mut invalid_status_lines = Vec::new();
+        let mut prop_names = Vec::new();
+        let mut descriptions = Vec::new();
+        let mut warnings = Vec::new();
+        for child in children {
+            match child {
+                ResponseChild::Href(value) => {
+                    set_once(input, &mut href, value, "href", lenient, &mut warnings)?;
                 }
-            }
-            ResponseChild::Propstat(ps) => {
-                if let Some(yesno) = ps.is_collection {
-                    if is_collection.replace(yesno).is_some() {
-                        return hard_fail(input);
+                ResponseChild::Propstat(mut ps) => {
+                    descriptions.append(&mut ps.descriptions);
+                    warnings.append(&mut ps.warnings);
+                    let ok = is_ok(&ps.status);
+                    match status_code(&ps.status) {
+                        Some(code) => propstat_statuses.push(code),
+                        None => invalid_status_lines.push(ps.status.trim().to_owned()),
                     }
-                    status = Some(ps.status);
+                    if let Some(yesno) = ps.prop.is_collection {
+                        if set_once(
+                            input,
+                            &mut is_collection,
+                            yesno,
+                            "resourcetype",
+                            lenient,
+                            &mut warnings,
+                        )? {
+                            status = Some(ps.status);
+                        }
+                    }
+                    // Properties other than <resourcetype> are only used if
+                    // their propstat is OK; servers commonly report
+                    // unsupported properties with a 404 propstat.
+                    if ok {
+                        if let Some(sz) = ps.prop.size.flatten() {
+                            set_once(
+                                input,
+                                &mut size,
+                                sz,
+                                "getcontentlength",
+                                lenient,
+                                &mut warnings,
+                            )?;
+                        }
+                        if let Some(name) = ps.prop.display_name.flatten() {
+                            set_once(
+                                input,
+                                &mut display_name,
+                                name,
+                                "displayname",
+                                lenient,
+                                &mut warnings,
+                            )?;
+                        }
+                        if let Some(tag) = ps.prop.etag.flatten() {
+                            set_once(input, &mut etag, tag, "getetag", lenient, &mut warnings)?;
+                        }
+                        if let Some(dt) = ps.prop.modified.flatten() {
+                            set_once(
+                                input,
+                                &mut modified,
+                                dt,
+                                "getlastmodified",
+                                lenient,
+                                &mut warnings,
+                            )?;
+                        }
+                        if let Some(ct) = ps.prop.content_type.flatten() {
+                            set_once(
+                                input,
+                                &mut content_type,
+                                ct,
+                                "getcontenttype",
+                                lenient,
+                                &mut warnings,
+                            )?;
+                        }
+                        if let Some(n) = ps.prop.quota_available.flatten() {
+                            set_once(
+                                input,
+                                &mut quota.available_bytes,
+                                n,
+                                "quota-available-bytes",
+                                lenient,
+                                &mut warnings,
+                            )?;
+                        }
+                        if let Some(n) = ps.prop.quota_used.flatten() {
+                            set_once(
+                                input,
+                                &mut quota.used_bytes,
+                                n,
+                                "quota-used-bytes",
+                                lenient,
+                                &mut warnings,
+                            )?;
+                        }
+                        prop_names.extend(ps.prop.names);
+                    }
+                }
+                ResponseChild::Description(text) => descriptions.extend(text),
+                ResponseChild::Unexpected(name) => {
+                    warnings.push(XmlWarning::UnexpectedElement(name));
                 }
+                ResponseChild::Discard => (),
             }
-            ResponseChild::Discard => (),
         }
+        let Some(((href, is_collection), status)) = href.zip(is_collection).zip(status) else {
+            return hard_fail(input);
+        };
+        Ok(Response {
+            href,
+            is_collection,
+            status,
+            size,
+            display_name,
+            etag,
+            modified,
+            content_type,
+            quota,
+            propstat_statuses,
+            invalid_status_lines,
+            prop_names,
+            descriptions,
+            warnings,
+        })
     }
-    let Some(((href, is_collection), status)) = href.zip(is_collection).zip(status) else {
-        return hard_fail(input);
-    };
-    Ok(Response {
-        href,
-        is_collection,
-        status,
-    })
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct Propstat {
-    is_collection: Option<bool>,
+    prop: Prop,
     status: String,
+    /// The texts of the propstat's `<responsedescription>` elements
+    descriptions: Vec<String>,
+    /// Deviations tolerated in lenient mode
+    warnings: Vec<XmlWarning>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum PropstatChild {
-    IsCollection(bool),
+    Prop(Prop),
     Status(String),
-    Discard,
+    Description(Option<String>),
+    Unexpected(String),
 }
 
-fn propstat(input: &mut TokenStream<'_>) -> PResult<Propstat> {
-    let (children,): (Vec<PropstatChild>,) = seq!(
-        _: open("propstat"),
-        repeat(0.., preceded(extensions, alt((
-            prop_tag.map(|Prop {is_collection}| PropstatChild::IsCollection(is_collection)),
-            status_tag.map(PropstatChild::Status),
-            responsedescription.map(|()| PropstatChild::Discard),
-        )))),
-        _: extensions,
-        _: close("propstat"),
-    )
-    .parse_next(input)?;
-    let mut is_collection = None;
-    let mut status = None;
-    for child in children {
-        match child {
-            PropstatChild::IsCollection(yesno) => {
-                if is_collection.replace(yesno).is_some() {
-                    return hard_fail(input);
+fn propstat<'a>(lenient: bool) -> impl Parser<TokenStream<'a>, Propstat, ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        let (children,): (Vec<PropstatChild>,) = seq!(
+            _: open("propstat"),
+            repeat(0.., preceded(extensions, alt((
+                prop_tag(lenient).map(PropstatChild::Prop),
+                status_tag.map(PropstatChild::Status),
+                responsedescription.map(PropstatChild::Description),
+                unexpected(lenient, &["prop", "status", "responsedescription"])
+                    .map(PropstatChild::Unexpected),
+            )))),
+            _: extensions,
+            _: close("propstat"),
+        )
+        .parse_next(input)?;
+        let mut prop = None;
+        let mut status = None;
+        let mut descriptions = Vec::new();
+        let mut warnings = Vec::new();
+        for child in children {
+            match child {
+                PropstatChild::Prop(mut p) => {
+                    warnings.append(&mut p.warnings);
+                    set_once(input, &mut prop, p, "prop", lenient, &mut warnings)?;
                 }
-            }
-            PropstatChild::Status(s) => {
-                if status.replace(s).is_some() {
-                    return hard_fail(input);
+                PropstatChild::Status(s) => {
+                    set_once(input, &mut status, s, "status", lenient, &mut warnings)?;
+                }
+                PropstatChild::Description(text) => descriptions.extend(text),
+                PropstatChild::Unexpected(name) => {
+                    warnings.push(XmlWarning::UnexpectedElement(name));
                 }
             }
-            PropstatChild::Discard => (),
         }
+        let Some((prop, mut status)) = prop.zip(status) else {
+            return hard_fail(input);
+        };
+        if lenient && status_code(&status).is_none() {
+            if let Some(code) = lenient_status_code(&status) {
+                warnings.push(XmlWarning::StatusLine {
+                    line: status.trim().to_owned(),
+                    code,
+                });
+                status = format!("HTTP/1.1 {code}");
+            }
+        }
+        Ok(Propstat {
+            prop,
+            status,
+            descriptions,
+            warnings,
+        })
     }
-    let Some((is_collection, status)) = is_collection.zip(status) else {
-        return hard_fail(input);
-    };
-    Ok(Propstat {
-        is_collection: Some(is_collection),
-        status,
-    })
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// The properties found in a `<prop>` element.  Each field is `None` if the
+/// corresponding property element was absent.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 struct Prop {
-    is_collection: bool,
+    is_collection: Option<bool>,
+    /// `Some(None)` if the element was present but empty
+    size: Option<Option<u64>>,
+    /// `Some(None)` if the element was present but empty
+    display_name: Option<Option<String>>,
+    etag: Option<Option<String>>,
+    modified: Option<Option<OffsetDateTime>>,
+    content_type: Option<Option<String>>,
+    quota_available: Option<Option<u64>>,
+    quota_used: Option<Option<u64>>,
+    /// The names of all properties present, in order of appearance
+    names: Vec<PropName>,
+    /// Deviations tolerated in lenient mode
+    warnings: Vec<XmlWarning>,
 }
 
-// Note: When parsing <prop>, we're assuming that the only property requested
-// by the client was <resourcetype>, so this part of the code expects <prop> to
-// contain that tag and only that tag.  (If the client is ever adjusted to
-// request more properties, this is where to start updating the XML-parsing
-// code.)
-fn prop_tag(input: &mut TokenStream<'_>) -> PResult<Prop> {
-    let (is_collection,) = seq!(
-        _: open("prop"),
-        _: open("resourcetype"),
-        opt((open("collection"), close("collection"))).map(|o| o.is_some()),
-        _: close("resourcetype"),
-        _: close("prop"),
-    )
-    .parse_next(input)?;
-    Ok(Prop { is_collection })
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum PropChild {
+    IsCollection(bool, Vec<XmlWarning>),
+    Size(Option<u64>),
+    DisplayName(Option<String>),
+    ETag(Option<String>),
+    Modified(Option<OffsetDateTime>, Option<XmlWarning>),
+    ContentType(Option<String>),
+    QuotaAvailable(Option<u64>),
+    QuotaUsed(Option<u64>),
+    Other(PropName),
+}
+
+fn prop_tag<'a>(lenient: bool) -> impl Parser<TokenStream<'a>, Prop, ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        let (children,): (Vec<PropChild>,) = seq!(
+            _: open("prop"),
+            repeat(0.., alt((
+                resourcetype_tag(lenient).map(|(yesno, w)| PropChild::IsCollection(yesno, w)),
+                u64_tag("getcontentlength").map(PropChild::Size),
+                string_tag("displayname").map(PropChild::DisplayName),
+                string_tag("getetag").map(PropChild::ETag),
+                date_tag("getlastmodified", lenient).map(|(dt, w)| PropChild::Modified(dt, w)),
+                string_tag("getcontenttype").map(PropChild::ContentType),
+                u64_tag("quota-available-bytes").map(PropChild::QuotaAvailable),
+                u64_tag("quota-used-bytes").map(PropChild::QuotaUsed),
+                any_element.map(PropChild::Other),
+            ))),
+            _: close("prop"),
+        )
+        .parse_next(input)?;
+        let mut prop = Prop::default();
+        let mut warnings = Vec::new();
+        for child in children {
+            let name = match child {
+                PropChild::IsCollection(yesno, mut w) => {
+                    warnings.append(&mut w);
+                    set_once(
+                        input,
+                        &mut prop.is_collection,
+                        yesno,
+                        "resourcetype",
+                        lenient,
+                        &mut warnings,
+                    )?
+                    .then(|| PropName::dav("resourcetype"))
+                }
+                PropChild::Size(sz) => set_once(
+                    input,
+                    &mut prop.size,
+                    sz,
+                    "getcontentlength",
+                    lenient,
+                    &mut warnings,
+                )?
+                .then(|| PropName::dav("getcontentlength")),
+                PropChild::QuotaAvailable(n) => set_once(
+                    input,
+                    &mut prop.quota_available,
+                    n,
+                    "quota-available-bytes",
+                    lenient,
+                    &mut warnings,
+                )?
+                .then(|| PropName::dav("quota-available-bytes")),
+                PropChild::QuotaUsed(n) => set_once(
+                    input,
+                    &mut prop.quota_used,
+                    n,
+                    "quota-used-bytes",
+                    lenient,
+                    &mut warnings,
+                )?
+                .then(|| PropName::dav("quota-used-bytes")),
+                PropChild::DisplayName(name) => set_once(
+                    input,
+                    &mut prop.display_name,
+                    name,
+                    "displayname",
+                    lenient,
+                    &mut warnings,
+                )?
+                .then(|| PropName::dav("displayname")),
+                PropChild::ETag(tag) => set_once(
+                    input,
+                    &mut prop.etag,
+                    tag,
+                    "getetag",
+                    lenient,
+                    &mut warnings,
+                )?
+                .then(|| PropName::dav("getetag")),
+                PropChild::Modified(dt, w) => {
+                    warnings.extend(w);
+                    set_once(
+                        input,
+                        &mut prop.modified,
+                        dt,
+                        "getlastmodified",
+                        lenient,
+                        &mut warnings,
+                    )?
+                    .then(|| PropName::dav("getlastmodified"))
+                }
+                PropChild::ContentType(ct) => set_once(
+                    input,
+                    &mut prop.content_type,
+                    ct,
+                    "getcontenttype",
+                    lenient,
+                    &mut warnings,
+                )?
+                .then(|| PropName::dav("getcontenttype")),
+                PropChild::Other(name) => Some(name),
+            };
+            prop.names.extend(name);
+        }
+        prop.warnings = warnings;
+        Ok(prop)
+    }
+}
+
+/// Parse a `<resourcetype>` element, returning whether it contains a
+/// `<collection>` element.  In lenient mode, other DAV elements inside it
+/// (such as `<principal>`) are skipped with a warning.
+fn resourcetype_tag<'a>(
+    lenient: bool,
+) -> impl Parser<TokenStream<'a>, (bool, Vec<XmlWarning>), ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        if !lenient {
+            let (is_collection,) = seq!(
+                _: open("resourcetype"),
+                _: extensions,
+                opt((open("collection"), close("collection"))).map(|o| o.is_some()),
+                _: extensions,
+                _: close("resourcetype"),
+            )
+            .parse_next(input)?;
+            return Ok((is_collection, Vec::new()));
+        }
+        let (children,): (Vec<Option<String>>,) = seq!(
+            _: open("resourcetype"),
+            repeat(0.., preceded(extensions, alt((
+                (open("collection"), close("collection")).map(|_| None),
+                unexpected(lenient, &["collection"]).map(Some),
+            )))),
+            _: extensions,
+            _: close("resourcetype"),
+        )
+        .parse_next(input)?;
+        let mut is_collection = false;
+        let mut warnings = Vec::new();
+        for child in children {
+            match child {
+                None if is_collection => {
+                    warnings.push(XmlWarning::DuplicateElement(String::from("collection")));
+                }
+                None => is_collection = true,
+                Some(name) => warnings.push(XmlWarning::UnexpectedElement(name)),
+            }
+        }
+        Ok((is_collection, warnings))
+    }
+}
+
+/// Parse an element named `name` in the DAV namespace whose content is a
+/// nonnegative integer, returning `None` if the element is empty
+fn u64_tag<'a>(name: &'static str) -> impl Parser<TokenStream<'a>, Option<u64>, ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        let s = delimited(open(name), text, close(name)).parse_next(input)?;
+        let s = s.trim();
+        if s.is_empty() {
+            Ok(None)
+        } else if let Ok(n) = s.parse::<u64>() {
+            Ok(Some(n))
+        } else {
+            hard_fail(input)
+        }
+    }
+}
+
+/// Parse an element named `name` in the DAV namespace whose content is an
+/// RFC 1123 date (as used in HTTP headers), returning `None` if the element is
+/// empty.  In lenient mode, an unparseable date is also treated as empty, with
+/// a warning.
+fn date_tag<'a>(
+    name: &'static str,
+    lenient: bool,
+) -> impl Parser<TokenStream<'a>, (Option<OffsetDateTime>, Option<XmlWarning>), ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        let s = delimited(open(name), text, close(name)).parse_next(input)?;
+        let s = s.trim();
+        if s.is_empty() {
+            Ok((None, None))
+        } else if let Ok(dt) = OffsetDateTime::parse(s, &Rfc2822) {
+            Ok((Some(dt), None))
+        } else if lenient {
+            Ok((
+                None,
+                Some(XmlWarning::InvalidValue {
+                    element: name.to_owned(),
+                    value: s.to_owned(),
+                }),
+            ))
+        } else {
+            hard_fail(input)
+        }
+    }
+}
+
+/// Parse an element named `name` in the DAV namespace whose content is text,
+/// returning the trimmed text if nonempty
+fn string_tag<'a>(
+    name: &'static str,
+) -> impl Parser<TokenStream<'a>, Option<String>, ContextError> {
+    move |input: &mut TokenStream<'a>| {
+        let s = delimited(open(name), text, close(name)).parse_next(input)?;
+        let s = s.trim();
+        Ok((!s.is_empty()).then(|| s.to_owned()))
+    }
 }
 
 fn href_tag(input: &mut TokenStream<'_>) -> PResult<String> {
@@ -300,14 +1201,17 @@ fn status_tag(input: &mut TokenStream<'_>) -> PResult<String> {
     delimited(open("status"), text, close("status")).parse_next(input)
 }
 
-fn responsedescription(input: &mut TokenStream<'_>) -> PResult<()> {
-    delimited(
+/// Parse a `<responsedescription>` element, returning its trimmed text if
+/// nonempty
+fn responsedescription(input: &mut TokenStream<'_>) -> PResult<Option<String>> {
+    let s = delimited(
         open("responsedescription"),
         text,
         close("responsedescription"),
     )
-    .void()
-    .parse_next(input)
+    .parse_next(input)?;
+    let s = s.trim();
+    Ok((!s.is_empty()).then(|| s.to_owned()))
 }
 
 fn location(input: &mut TokenStream<'_>) -> PResult<()> {
@@ -354,6 +1258,87 @@ fn extensions(input: &mut TokenStream<'_>) -> PResult<()> {
     Ok(())
 }
 
+/// In lenient mode, parse a single DAV element whose name is not one of
+/// `expected`, discarding its contents and returning its name.  Always
+/// backtracks in strict mode.
+fn unexpected<'a>(
+    lenient: bool,
+    expected: &'static [&'static str],
+) -> impl Parser<TokenStream<'a>, String, ContextError> {
+    move |input: &mut TokenStream<'a>| match input.first() {
+        Some(Token::OpenDav(name)) if lenient && !expected.contains(&name.as_str()) => {
+            any_element.map(|pn| pn.name).parse_next(input)
+        }
+        _ => Err(ErrMode::Backtrack(ContextError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        ))),
+    }
+}
+
+/// Store `value` in `slot` if it is empty and return `true`.  If `slot` is
+/// already filled, then in lenient mode the existing value is kept, a
+/// warning about a duplicate `name` element is recorded, and `false` is
+/// returned; in strict mode, parsing fails.
+fn set_once<T>(
+    input: &TokenStream<'_>,
+    slot: &mut Option<T>,
+    value: T,
+    name: &str,
+    lenient: bool,
+    warnings: &mut Vec<XmlWarning>,
+) -> PResult<bool> {
+    if slot.is_none() {
+        *slot = Some(value);
+        Ok(true)
+    } else if lenient {
+        warnings.push(XmlWarning::DuplicateElement(name.to_owned()));
+        Ok(false)
+    } else {
+        hard_fail(input)
+    }
+}
+
+/// Parse a single element of any name or namespace, discarding its contents
+/// and returning its name
+fn any_element(input: &mut TokenStream<'_>) -> PResult<PropName> {
+    let name = match input.first() {
+        Some(Token::OpenDav(name)) => PropName::dav(name),
+        Some(Token::OpenExt { name, namespace }) => PropName {
+            namespace: namespace.clone(),
+            name: name.clone(),
+        },
+        _ => {
+            return Err(ErrMode::Backtrack(ContextError::from_error_kind(
+                input,
+                ErrorKind::Tag,
+            )))
+        }
+    };
+    let mut depth = 0usize;
+    let mut end = None;
+    for (i, t) in input.iter().enumerate() {
+        match t {
+            Token::OpenDav(_) | Token::OpenExt { .. } => depth += 1,
+            Token::CloseDav(_) | Token::CloseExt { .. } => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    end = Some(i + 1);
+                    break;
+                }
+            }
+            Token::Text(_) => (),
+        }
+    }
+    // The tokenizer guarantees that tags are properly nested, so there's no
+    // need to check that the closing tag matches the opening tag.
+    let Some(end) = end else {
+        return hard_fail(input);
+    };
+    *input = &input[end..];
+    Ok(name)
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn text(input: &mut TokenStream<'_>) -> PResult<String> {
     let mut s = String::new();
@@ -378,17 +1363,30 @@ fn hard_fail<T>(input: TokenStream<'_>) -> PResult<T> {
 }
 
 fn is_ok(s: &str) -> bool {
+    status_code(s) == Some(200)
+}
+
+/// Extract the status code from a malformed status line (e.g., one lacking
+/// the HTTP version) by looking for the first three-digit word
+fn lenient_status_code(s: &str) -> Option<u16> {
+    s.split_ascii_whitespace()
+        .find(|w| w.len() == 3 && w.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|w| w.parse::<u16>().ok())
+}
+
+/// Extract the status code from an HTTP status line
+fn status_code(s: &str) -> Option<u16> {
     let mut words = s.split_ascii_whitespace();
-    let Some(http_version) = words.next() else {
-        return false;
-    };
+    let http_version = words.next()?;
     if !http_version.starts_with("HTTP/") {
-        return false;
+        return None;
+    }
+    let status = words.next()?;
+    if status.len() == 3 && status.bytes().all(|b| b.is_ascii_digit()) {
+        status.parse::<u16>().ok()
+    } else {
+        None
     }
-    let Some(status) = words.next() else {
-        return false;
-    };
-    status == "200"
 }
 
 #[derive(Clone, Debug, Error, Eq, PartialEq)]
@@ -407,17 +1405,26 @@ pub(crate) enum XmlTokenizeError {
     Xml(#[from] XmlError),
     #[error("unexpected XML processing instruction encountered")]
     ProcessingInstruction,
+    #[error("XML document type declarations are not permitted")]
+    Doctype,
+    #[error("XML elements are nested more than {limit} levels deep")]
+    TooDeep { limit: usize },
+    #[error("XML document contains more than {limit} tokens")]
+    TooManyTokens { limit: usize },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use indoc::indoc;
+    use rstest::rstest;
 
     #[test]
     fn test01() {
         let bs = include_bytes!("testdata/response.xml");
-        let dl = parse_multistatus(Bytes::from(bs.as_slice()), None).unwrap();
+        let dl = parse_multistatus(Bytes::from(bs.as_slice()), None, XmlOptions::default())
+            .unwrap()
+            .listing;
         assert_eq!(dl, DirectoryListing {
             directories: vec![
                 "/zarrs/0d5/b9b/0d5b9be5-e626-4f6a-96da-b6b602954899/0395d0a3767524377b58da3945b3c063-48379--27115470.zarr/".into(),
@@ -459,14 +1466,23 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        let dl = parse_multistatus(Bytes::from(src.as_bytes()), None).unwrap();
+        let ms =
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).unwrap();
         assert_eq!(
-            dl,
+            ms.listing,
             DirectoryListing {
                 directories: vec!["/foo/bar/".into()],
                 files: Vec::new(),
             }
         );
+        assert_eq!(
+            ms.descriptions,
+            vec![
+                "Your requested stats, sire.".to_owned(),
+                "These are the properties of /foo/bar/.".to_owned(),
+                "/foo/bar/ is a directory.".to_owned(),
+            ]
+        );
     }
 
     #[test]
@@ -487,7 +1503,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        let dl = parse_multistatus(Bytes::from(src.as_bytes()), None).unwrap();
+        let dl = parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default())
+            .unwrap()
+            .listing;
         assert_eq!(
             dl,
             DirectoryListing {
@@ -514,7 +1532,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -527,7 +1547,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -547,7 +1569,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -563,7 +1587,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -580,7 +1606,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -602,7 +1630,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -624,7 +1654,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -648,7 +1680,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -672,7 +1706,9 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
     }
 
     #[test]
@@ -694,6 +1730,559 @@ mod tests {
                 </response>
             </multistatus>
         "#};
-        assert!(parse_multistatus(Bytes::from(src.as_bytes()), None).is_err());
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_getcontentlength() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    <href>/foo/</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype><collection/></resourcetype>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                    <propstat>
+                        <prop>
+                            <getcontentlength/>
+                        </prop>
+                        <status>HTTP/1.1 404 Not Found</status>
+                    </propstat>
+                </response>
+                <response>
+                    <href>/foo/bar.txt</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype/>
+                            <getcontentlength>1234</getcontentlength>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        let dl = parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default())
+            .unwrap()
+            .listing;
+        assert_eq!(
+            dl,
+            DirectoryListing {
+                directories: vec!["/foo/".into()],
+                files: vec![Entry {
+                    href: "/foo/bar.txt".into(),
+                    size: Some(1234),
+                    display_name: None,
+                    etag: None,
+                    modified: None,
+                    content_type: None,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_displayname() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    <href>/foo/</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype><collection/></resourcetype>
+                            <displayname/>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+                <response>
+                    <href>/foo/6f1c2b0e/</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype><collection/></resourcetype>
+                            <displayname> Quarterly Reports </displayname>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+                <response>
+                    <href>/foo/9d3e.txt</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype/>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                    <propstat>
+                        <prop>
+                            <displayname>Ignored</displayname>
+                        </prop>
+                        <status>HTTP/1.1 403 Forbidden</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        let dl = parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default())
+            .unwrap()
+            .listing;
+        assert_eq!(
+            dl,
+            DirectoryListing {
+                directories: vec![
+                    "/foo/".into(),
+                    Entry {
+                        href: "/foo/6f1c2b0e/".into(),
+                        size: None,
+                        display_name: Some("Quarterly Reports".into()),
+                        etag: None,
+                        modified: None,
+                        content_type: None,
+                    }
+                ],
+                files: vec!["/foo/9d3e.txt".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_bad_getcontentlength() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    <href>/foo/bar.txt</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype/>
+                            <getcontentlength>lots</getcontentlength>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        assert!(
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_extra_props() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:" xmlns:x="http://example.com/ns">
+                <response>
+                    <href>/foo/</href>
+                    <propstat>
+                        <prop>
+                            <creationdate>1997-12-01T17:42:21-08:00</creationdate>
+                            <resourcetype><collection/><x:special/></resourcetype>
+                            <x:owner><x:name>Alice</x:name></x:owner>
+                            <lockdiscovery/>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+                <response>
+                    <href>/foo/bar.txt</href>
+                    <propstat>
+                        <prop>
+                            <getetag>"abc123"</getetag>
+                            <resourcetype/>
+                            <getcontentlength>42</getcontentlength>
+                            <getcontenttype>text/plain; charset=utf-8</getcontenttype>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        let dl = parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default())
+            .unwrap()
+            .listing;
+        assert_eq!(
+            dl,
+            DirectoryListing {
+                directories: vec!["/foo/".into()],
+                files: vec![Entry {
+                    href: "/foo/bar.txt".into(),
+                    size: Some(42),
+                    display_name: None,
+                    etag: Some("\"abc123\"".into()),
+                    modified: None,
+                    content_type: Some("text/plain; charset=utf-8".into()),
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_quota() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    <href>/foo/</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype><collection/></resourcetype>
+                            <quota-used-bytes>4096</quota-used-bytes>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                    <propstat>
+                        <prop>
+                            <quota-available-bytes/>
+                        </prop>
+                        <status>HTTP/1.1 404 Not Found</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        assert_eq!(
+            parse_quota(Bytes::from(src.as_bytes()), None, XmlOptions::default()).unwrap(),
+            Quota {
+                available_bytes: None,
+                used_bytes: Some(4096),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_propnames() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:" xmlns:x="http://example.com/ns">
+                <response>
+                    <href>/foo/</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype/>
+                            <displayname/>
+                            <x:owner/>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+                <response>
+                    <href>/foo/bar.txt</href>
+                    <propstat>
+                        <prop>
+                            <getcontentlength/>
+                            <resourcetype/>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        let dav = |name: &str| PropName::dav(name);
+        assert_eq!(
+            parse_propnames(Bytes::from(src.as_bytes()), None, XmlOptions::default()).unwrap(),
+            vec![
+                (
+                    "/foo/".to_owned(),
+                    vec![
+                        dav("resourcetype"),
+                        dav("displayname"),
+                        PropName {
+                            namespace: "http://example.com/ns".into(),
+                            name: "owner".into(),
+                        },
+                    ]
+                ),
+                (
+                    "/foo/bar.txt".to_owned(),
+                    vec![dav("getcontentlength"), dav("resourcetype")]
+                ),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("<?xml version=\"1.0\"?>\n<multistatus xmlns=\"DAV:\"/>", false)]
+    #[case(
+        "<?xml version=\"1.0\"?>\n<!-- <!DOCTYPE x> -->\n<multistatus xmlns=\"DAV:\"/>",
+        false
+    )]
+    #[case("<multistatus xmlns=\"DAV:\"><!DOCTYPE x></multistatus>", false)]
+    #[case(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE lolz [<!ENTITY lol \"lol\">]>\n<lolz>&lol;</lolz>",
+        true
+    )]
+    #[case("<!-- hi -->\n<!DOCTYPE multistatus><multistatus/>", true)]
+    #[case(" \r\n\t<!DOCTYPE multistatus><multistatus/>", true)]
+    fn test_has_doctype(#[case] text: &str, #[case] r: bool) {
+        assert_eq!(has_doctype(text), r);
+    }
+
+    fn utf16(s: &str, big_endian: bool) -> Bytes {
+        s.encode_utf16()
+            .flat_map(|u| {
+                if big_endian {
+                    u.to_be_bytes()
+                } else {
+                    u.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(
+        utf16(
+            "\u{FEFF}<!-- hi -->\n<!DOCTYPE x [<!ENTITY a \"b\">]><x>&a;</x>",
+            false
+        ),
+        None
+    )]
+    #[case(
+        utf16("\n<!DOCTYPE x [<!ENTITY a \"b\">]><x>&a;</x>", false),
+        Some("utf-16le")
+    )]
+    #[case(
+        utf16(
+            "<?xml version=\"1.0\" encoding=\"UTF-16BE\"?>\n  <!DOCTYPE x [<!ENTITY a \"b\">]><x>&a;</x>",
+            true
+        ),
+        None
+    )]
+    #[case(
+        Bytes::from_static(b"\n\n<!DOCTYPE x [<!ENTITY a \"b\">]><x>&a;</x>"),
+        None
+    )]
+    fn test_tokenize_doctype(#[case] blob: Bytes, #[case] charset: Option<&str>) {
+        assert_eq!(
+            tokenize(blob, charset.map(String::from), XmlOptions::default()),
+            Err(XmlTokenizeError::Doctype)
+        );
+    }
+
+    #[test]
+    fn test_tokenize_undeclared_utf16() {
+        // Without a BOM, charset, or XML declaration, the document is decoded
+        // as UTF-8, which xml-rs then rejects as malformed rather than
+        // parsing any DTD within
+        let blob = utf16("\n<!DOCTYPE x [<!ENTITY a \"b\">]><x>&a;</x>", false);
+        assert!(
+            tokenize(blob, None, XmlOptions::default()).is_err(),
+            "undeclared UTF-16 should not parse"
+        );
+    }
+
+    #[rstest]
+    #[case(
+        XmlOptions { max_depth: 3, ..XmlOptions::default() },
+        Err(XmlTokenizeError::TooDeep { limit: 3 })
+    )]
+    #[case(
+        XmlOptions { max_depth: 4, ..XmlOptions::default() },
+        Ok(())
+    )]
+    #[case(
+        XmlOptions { max_tokens: 10, ..XmlOptions::default() },
+        Err(XmlTokenizeError::TooManyTokens { limit: 10 })
+    )]
+    fn test_tokenize_limits(#[case] options: XmlOptions, #[case] r: Result<(), XmlTokenizeError>) {
+        let blob = b"<a><b><c><d>text</d></c></b><b/></a>";
+        assert_eq!(
+            tokenize(Bytes::from_static(blob), None, options).map(|_| ()),
+            r
+        );
+    }
+
+    #[rstest]
+    #[case(b"<a>\xC3\xA9</a>", None, false, "<a>\u{e9}</a>", None)]
+    #[case(b"<a>\xE9</a>", Some("ISO-8859-1"), false, "<a>\u{e9}</a>", None)]
+    #[case(b"<a>\xE9</a>", None, false, "<a>\u{e9}</a>", Some((None, "windows-1252")))]
+    #[case(
+        b"<a>\xC3\xA9</a>",
+        Some("bogus"),
+        false,
+        "<a>\u{e9}</a>",
+        Some((Some("bogus"), "UTF-8"))
+    )]
+    #[case(b"\xFF\xFE<\0a\0/\0>\0", Some("utf-8"), false, "<a/>", None)]
+    #[case(
+        b"<?xml version='1.0' encoding='ISO-8859-1'?><a>\xE9</a>",
+        Some("utf-8"),
+        false,
+        "<?xml version='1.0' encoding='ISO-8859-1'?><a>\u{e9}</a>",
+        Some((Some("utf-8"), "windows-1252"))
+    )]
+    #[case(
+        b"<?xml version='1.0' encoding='ISO-8859-1'?><a>\xE9</a>",
+        Some("utf-8"),
+        true,
+        "<?xml version='1.0' encoding='ISO-8859-1'?><a>\u{e9}</a>",
+        None
+    )]
+    fn test_decode(
+        #[case] blob: &[u8],
+        #[case] charset: Option<&str>,
+        #[case] prefer_declared: bool,
+        #[case] text: &str,
+        #[case] fallback: Option<(Option<&str>, &str)>,
+    ) {
+        let (s, fb) = decode(blob, charset.map(String::from), prefer_declared);
+        assert_eq!(s, text);
+        assert_eq!(
+            fb,
+            fallback.map(|(declared, used)| CharsetFallback {
+                declared: declared.map(String::from),
+                used: Encoding::for_label(used.as_bytes()).unwrap().name(),
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><a/>", Some("utf-8"))]
+    #[case(b"<?xml version='1.0' encoding = 'Latin1' ?><a/>", Some("Latin1"))]
+    #[case(b"<?xml version=\"1.0\"?><a encoding=\"utf-8\"/>", None)]
+    #[case(b"<a/>", None)]
+    #[case(
+        b"<\0?\0x\0m\0l\0 \0e\0n\0c\0o\0d\0i\0n\0g\0=\0'\0U\0T\0F\0-\x001\x006\0'\0?\0>\0",
+        Some("UTF-16")
+    )]
+    fn test_declared_encoding(#[case] blob: &[u8], #[case] encoding: Option<&str>) {
+        assert_eq!(declared_encoding(blob).as_deref(), encoding);
+    }
+
+    #[test]
+    fn test_reject_entity_expansion() {
+        let blob = indoc! {br#"
+            <?xml version="1.0"?>
+            <!DOCTYPE multistatus [
+                <!ENTITY a "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa">
+                <!ENTITY b "&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;">
+            ]>
+            <multistatus xmlns="DAV:"><response><href>&b;</href></response></multistatus>
+        "#};
+        assert_eq!(
+            parse_multistatus(Bytes::from_static(blob), None, XmlOptions::default()),
+            Err(FromXmlError::Tokenize(XmlTokenizeError::Doctype))
+        );
+    }
+
+    #[rstest]
+    #[case("HTTP/1.1 200 OK", Some(200))]
+    #[case("HTTP/1.1 404 Not Found", Some(404))]
+    #[case("HTTP/2 207", Some(207))]
+    #[case("HTTP/1.1 20 OK", None)]
+    #[case("HTTP/1.1 2000 OK", None)]
+    #[case("200 OK", None)]
+    #[case("", None)]
+    fn test_status_code(#[case] line: &str, #[case] code: Option<u16>) {
+        assert_eq!(status_code(line), code);
+    }
+
+    #[test]
+    fn test_propstat_statuses() {
+        let src = indoc! {r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    <href>/foo/</href>
+                    <propstat>
+                        <prop>
+                            <resourcetype><collection/></resourcetype>
+                        </prop>
+                        <status>HTTP/1.1 200 OK</status>
+                    </propstat>
+                    <propstat>
+                        <prop>
+                            <getcontentlength/>
+                        </prop>
+                        <status>HTTP/1.1 404 Not Found</status>
+                    </propstat>
+                </response>
+            </multistatus>
+        "#};
+        let ms =
+            parse_multistatus(Bytes::from(src.as_bytes()), None, XmlOptions::default()).unwrap();
+        assert_eq!(ms.propstat_statuses, vec![200, 404]);
+    }
+
+    #[rstest]
+    #[case(
+        "<href>/foo/</href><frobnicate>yes</frobnicate>",
+        "<resourcetype><collection/></resourcetype>",
+        "HTTP/1.1 200 OK",
+        vec![XmlWarning::UnexpectedElement("frobnicate".into())]
+    )]
+    #[case(
+        "<href>/foo/</href><href>/bar/</href>",
+        "<resourcetype><collection/></resourcetype>",
+        "HTTP/1.1 200 OK",
+        vec![XmlWarning::DuplicateElement("href".into())]
+    )]
+    #[case(
+        "<href>/foo/</href>",
+        "<resourcetype><collection/><principal/></resourcetype>",
+        "HTTP/1.1 200 OK",
+        vec![XmlWarning::UnexpectedElement("principal".into())]
+    )]
+    #[case(
+        "<href>/foo/</href>",
+        "<resourcetype><collection/></resourcetype><getcontentlength>1</getcontentlength><getcontentlength>2</getcontentlength>",
+        "HTTP/1.1 200 OK",
+        vec![XmlWarning::DuplicateElement("getcontentlength".into())]
+    )]
+    #[case(
+        "<href>/foo/</href>",
+        "<resourcetype><collection/></resourcetype>",
+        "200 OK",
+        vec![XmlWarning::StatusLine { line: "200 OK".into(), code: 200 }]
+    )]
+    #[case(
+        "<href>/foo/</href>",
+        "<resourcetype><collection/></resourcetype><getlastmodified>yesterday</getlastmodified>",
+        "HTTP/1.1 200 OK",
+        vec![XmlWarning::InvalidValue { element: "getlastmodified".into(), value: "yesterday".into() }]
+    )]
+    fn test_lenient(
+        #[case] response_children: &str,
+        #[case] props: &str,
+        #[case] status: &str,
+        #[case] warnings: Vec<XmlWarning>,
+    ) {
+        let src = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <multistatus xmlns="DAV:">
+                <response>
+                    {response_children}
+                    <propstat><prop>{props}</prop><status>{status}</status></propstat>
+                </response>
+            </multistatus>"#
+        );
+        assert!(parse_multistatus(Bytes::from(src.clone()), None, XmlOptions::default()).is_err());
+        let options = XmlOptions {
+            lenient: true,
+            ..XmlOptions::default()
+        };
+        let ms = parse_multistatus(Bytes::from(src), None, options).unwrap();
+        let hrefs = ms
+            .listing
+            .directories
+            .into_iter()
+            .map(|e| e.href)
+            .collect::<Vec<_>>();
+        assert_eq!(hrefs, vec!["/foo/".to_owned()]);
+        assert_eq!(ms.warnings, warnings);
+    }
+
+    #[rstest]
+    #[case("200 OK", Some(200))]
+    #[case("HTTP 404 Not Found", Some(404))]
+    #[case("OK", None)]
+    #[case("HTTP/1.1 2000", None)]
+    fn test_lenient_status_code(#[case] line: &str, #[case] code: Option<u16>) {
+        assert_eq!(lenient_status_code(line), code);
     }
 }
@@ -0,0 +1,208 @@
+use crate::stats::LatencySummary;
+use crate::types::Entry;
+use clap::ValueEnum;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use std::fmt;
+use url::Url;
+
+/// How to treat Zarr stores (collections whose names end in `.zarr`)
+/// encountered during a traversal
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ZarrBoundary {
+    /// Traverse Zarr stores like any other collection
+    #[default]
+    Descend,
+    /// Count Zarr stores but neither list nor probe anything inside them
+    Stop,
+    /// List each Zarr store in its entirety with a single `Depth: infinity`
+    /// PROPFIND request, without probing any files inside it
+    Summarize,
+}
+
+/// Returns true if `url` is the URL of a Zarr store, i.e., a collection whose
+/// final path component ends in `.zarr` (case-insensitive)
+pub(crate) fn is_zarr_store(url: &Url) -> bool {
+    url.path()
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .is_some_and(is_zarr_name)
+}
+
+fn is_zarr_name(name: &str) -> bool {
+    name.len() > ".zarr".len() && name.to_ascii_lowercase().ends_with(".zarr")
+}
+
+/// If `url` lies inside a Zarr store, return the URL of the outermost such
+/// store along with the percent-decoded path of `url` relative to it
+pub(crate) fn containing_zarr_store(url: &Url) -> Option<(Url, String)> {
+    let path = url.path();
+    let mut offset = 0usize;
+    for segment in path.split('/') {
+        let end = offset.saturating_add(segment.len()).saturating_add(1);
+        if end < path.len() && is_zarr_name(segment) {
+            let mut store = url.clone();
+            store.set_path(path.get(..end)?);
+            store.set_query(None);
+            store.set_fragment(None);
+            let rel = percent_decode_str(path.get(end..)?)
+                .decode_utf8_lossy()
+                .into_owned();
+            return Some((store, rel));
+        }
+        offset = end;
+    }
+    None
+}
+
+/// A line of a Zarr checksum manifest, giving the details of a single file
+/// inside a Zarr store.  Apart from `store`, the fields are those of
+/// `zarr_checksum`'s `ZarrArchiveFile`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ManifestEntry {
+    /// The URL of the Zarr store
+    pub(crate) store: Url,
+    /// The file's path relative to the store
+    pub(crate) path: String,
+    pub(crate) size: u64,
+    /// The file's ETag, with any weakness indicator & quotes removed, which
+    /// is the MD5 digest of the file's contents on S3-backed servers
+    pub(crate) digest: String,
+}
+
+impl ManifestEntry {
+    /// Construct a manifest entry for `file` if it lies inside a Zarr store
+    /// and its size & ETag are known
+    pub(crate) fn for_file(file: &Entry<Url>) -> Option<ManifestEntry> {
+        let (store, path) = containing_zarr_store(&file.href)?;
+        Some(ManifestEntry {
+            store,
+            path,
+            size: file.size?,
+            digest: etag_digest(file.etag.as_deref()?),
+        })
+    }
+}
+
+/// Strip the weakness indicator & quotes from an ETag
+fn etag_digest(etag: &str) -> String {
+    let etag = etag.strip_prefix("W/").unwrap_or(etag);
+    etag.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(etag)
+        .to_owned()
+}
+
+/// Statistics on the Zarr stores encountered during a traversal with a
+/// `ZarrBoundary` other than `Descend`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub(crate) struct ZarrStats {
+    pub(crate) boundary: ZarrBoundary,
+    /// Number of Zarr stores found
+    pub(crate) stores: usize,
+    /// Number of Zarr stores listed in their entirety
+    pub(crate) summarized: usize,
+    /// Total number of collections inside the summarized stores
+    pub(crate) directories: usize,
+    /// Total number of files inside the summarized stores
+    pub(crate) files: usize,
+    /// Total size of the files inside the summarized stores, if sizes were
+    /// requested
+    pub(crate) total_bytes: Option<u64>,
+    /// Latencies of the `Depth: infinity` PROPFIND requests
+    pub(crate) latency: Option<LatencySummary>,
+}
+
+impl fmt::Display for ZarrStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.boundary {
+            ZarrBoundary::Descend => Ok(()),
+            ZarrBoundary::Stop => writeln!(f, "Zarr stores: {} (not descended into)", self.stores),
+            ZarrBoundary::Summarize => {
+                write!(
+                    f,
+                    "Zarr stores: {} ({} summarized) containing {} directories and {} files",
+                    self.stores, self.summarized, self.directories, self.files
+                )?;
+                if let Some(bytes) = self.total_bytes {
+                    write!(f, " totalling {bytes} bytes")?;
+                }
+                writeln!(f)?;
+                if let Some(lat) = self.latency {
+                    writeln!(f, "  Depth: infinity PROPFIND latency: {lat}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://example.com/dav/data.zarr/", true)]
+    #[case("https://example.com/dav/DATA.ZARR/", true)]
+    #[case("https://example.com/dav/my%20data.zarr", true)]
+    #[case("https://example.com/dav/data.zarr/0/", false)]
+    #[case("https://example.com/dav/.zarr/", false)]
+    #[case("https://example.com/dav/zarr/", false)]
+    #[case("https://example.com/dav/data.zarray", false)]
+    fn test_is_zarr_store(#[case] url: &str, #[case] expected: bool) {
+        assert_eq!(is_zarr_store(&Url::parse(url).unwrap()), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "https://example.com/dav/data.zarr/0/1.2",
+        Some(("https://example.com/dav/data.zarr/", "0/1.2"))
+    )]
+    #[case(
+        "https://example.com/dav/a.zarr/b.zarr/.zattrs",
+        Some(("https://example.com/dav/a.zarr/", "b.zarr/.zattrs"))
+    )]
+    #[case(
+        "https://example.com/dav/my%20data.zarr/x%20y",
+        Some(("https://example.com/dav/my%20data.zarr/", "x y"))
+    )]
+    #[case("https://example.com/dav/data.zarr", None)]
+    #[case("https://example.com/dav/data/0/1.2", None)]
+    fn test_containing_zarr_store(#[case] url: &str, #[case] expected: Option<(&str, &str)>) {
+        let expected = expected.map(|(store, path)| (Url::parse(store).unwrap(), path.to_owned()));
+        assert_eq!(containing_zarr_store(&Url::parse(url).unwrap()), expected);
+    }
+
+    #[test]
+    fn test_manifest_entry() {
+        let mut file = Entry::new(Url::parse("https://example.com/dav/data.zarr/0/0").unwrap());
+        assert_eq!(ManifestEntry::for_file(&file), None);
+        file.size = Some(42);
+        assert_eq!(ManifestEntry::for_file(&file), None);
+        file.etag = Some(String::from("\"d41d8cd98f00b204e9800998ecf8427e\""));
+        let entry = ManifestEntry::for_file(&file).unwrap();
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            concat!(
+                r#"{"store":"https://example.com/dav/data.zarr/","path":"0/0","#,
+                r#""size":42,"digest":"d41d8cd98f00b204e9800998ecf8427e"}"#,
+            )
+        );
+        file.href = Url::parse("https://example.com/dav/data/0/0").unwrap();
+        assert_eq!(ManifestEntry::for_file(&file), None);
+    }
+
+    #[rstest]
+    #[case(
+        "\"d41d8cd98f00b204e9800998ecf8427e\"",
+        "d41d8cd98f00b204e9800998ecf8427e"
+    )]
+    #[case("W/\"abc\"", "abc")]
+    #[case("abc", "abc")]
+    fn test_etag_digest(#[case] etag: &str, #[case] digest: &str) {
+        assert_eq!(etag_digest(etag), digest);
+    }
+}